@@ -0,0 +1,335 @@
+// Attack maps and threats: the pieces attacking a square, the squares a side
+// attacks, pins against the king, checks, hanging pieces and the static
+// exchange evaluation of captures.
+//
+// A piece attacks a square whatever stands on it, so pieces defend their own
+// side too. En passant and castling are moves rather than attacks, and pins are
+// ignored by the exchange evaluation.
+
+use crate::eval::type_index;
+use crate::{Board, Color, Move, Piece, PieceType, Square};
+
+/// Piece values of the static exchange evaluation, in pawn, knight, bishop,
+/// rook, queen, king order
+pub const SEE_VALUES: [i32; 6] = [100, 300, 300, 500, 900, 20000];
+
+fn see_value(piece_type: PieceType) -> i32 {
+    SEE_VALUES[type_index(piece_type)]
+}
+
+/// A piece that cannot leave the line between its king and an enemy slider
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pin {
+    pub square: Square,
+    pub pinner: Square,
+    /// Squares the pinned piece may still move to, from next to the king up
+    /// to the pinner
+    pub line: Vec<Square>,
+}
+
+impl Board {
+    /// Squares of the pieces of `color` attacking `square`
+    pub fn attackers_of(&self, square: Square, color: Color) -> Vec<Square> {
+        self.get_all_color_squares(color)
+            .into_iter()
+            .filter(|&from| self.can_attack_square(from, square))
+            .collect()
+    }
+
+    /// Every square a piece of `color` attacks, from a1 to h8
+    pub fn attacked_squares(&self, color: Color) -> Vec<Square> {
+        let pieces = self.get_all_color_squares(color);
+        (0..8)
+            .flat_map(|rank| (0..8).map(move |file| Square::new(rank, file).expect("always valid")))
+            .filter(|&square| {
+                pieces
+                    .iter()
+                    .any(|&from| self.can_attack_square(from, square))
+            })
+            .collect()
+    }
+
+    /// Pieces of `color` pinned to their king
+    pub fn pinned_pieces(&self, color: Color) -> Vec<Pin> {
+        let Some(king) = self.find_king_square(color) else {
+            return Vec::new();
+        };
+
+        let mut pins = Vec::new();
+        for &(rank_offset, file_offset) in PieceType::Queen.get_offsets() {
+            let diagonal = rank_offset != 0 && file_offset != 0;
+            let mut line = Vec::new();
+            let mut pinned = None;
+
+            for distance in 1..8 {
+                let Ok(square) = king.offset(rank_offset * distance, file_offset * distance) else {
+                    break;
+                };
+                let Some(piece) = self.get_piece(square) else {
+                    line.push(square);
+                    continue;
+                };
+
+                if piece.color == color {
+                    if pinned.is_some() {
+                        break;
+                    }
+                    pinned = Some(square);
+                    continue;
+                }
+
+                let slides_here = match piece.piece_type {
+                    PieceType::Queen => true,
+                    PieceType::Bishop => diagonal,
+                    PieceType::Rook => !diagonal,
+                    _ => false,
+                };
+                if let (Some(pinned), true) = (pinned, slides_here) {
+                    line.push(square);
+                    pins.push(Pin {
+                        square: pinned,
+                        pinner: square,
+                        line,
+                    });
+                }
+                break;
+            }
+        }
+        pins
+    }
+
+    /// Squares of the pieces giving check to the side to move
+    pub fn checkers(&self) -> Vec<Square> {
+        let color = self.side_to_move();
+        match self.find_king_square(color) {
+            Some(king) => self.attackers_of(king, color.opposite()),
+            None => Vec::new(),
+        }
+    }
+
+    /// Pieces of `color` other than the king that the opponent wins material
+    /// by capturing
+    pub fn hanging_pieces(&self, color: Color) -> Vec<Square> {
+        self.get_all_color_squares(color)
+            .into_iter()
+            .filter(|&square| {
+                self.get_piece(square)
+                    .is_some_and(|piece| piece.piece_type != PieceType::King)
+            })
+            .filter(|&square| {
+                self.attackers_of(square, color.opposite())
+                    .into_iter()
+                    .any(|from| self.static_exchange(Move::new(from, square)) > 0)
+            })
+            .collect()
+    }
+
+    /// Material the side making `mv` wins when both sides keep recapturing on
+    /// its target square with their least valuable piece, and may stop at any
+    /// point. In centipawns of `SEE_VALUES`
+    pub fn static_exchange(&self, mv: Move) -> i32 {
+        let Some(mover) = self.get_piece(mv.from) else {
+            return 0;
+        };
+        let target = mv.to;
+        let mut board = self.clone();
+
+        let mut gain = match board.get_piece(target) {
+            Some(captured) => see_value(captured.piece_type),
+            None => {
+                // En passant takes the pawn standing next to the target square
+                let passed_pawn = target.offset(mv.from.rank() as i8 - target.rank() as i8, 0);
+                match passed_pawn {
+                    Ok(square)
+                        if mover.piece_type == PieceType::Pawn
+                            && mv.from.file() != target.file()
+                            && board.en_passant() == Some(square) =>
+                    {
+                        board.set_piece(square, None);
+                        see_value(PieceType::Pawn)
+                    }
+                    _ => 0,
+                }
+            }
+        };
+        let mut on_target = mover;
+        if let Some(promotion) = mv.promotion {
+            on_target = Piece::new(mover.color, promotion);
+            gain += see_value(promotion) - see_value(PieceType::Pawn);
+        }
+        board.set_piece(mv.from, None);
+        board.set_piece(target, Some(on_target));
+
+        // Material balance for the side to capture after each capture
+        let mut gains = vec![gain];
+        let mut side = mover.color.opposite();
+        // The least valuable attacker captures next
+        let least_valuable = |board: &Board, side| {
+            board
+                .attackers_of(target, side)
+                .into_iter()
+                .min_by_key(|&square| board.get_piece(square).map(|p| see_value(p.piece_type)))
+        };
+        while let Some(from) = least_valuable(&board, side) {
+            let attacker = board
+                .get_piece(from)
+                .expect("attackers stand on their square");
+            if attacker.piece_type == PieceType::King
+                && !board.attackers_of(target, side.opposite()).is_empty()
+            {
+                break;
+            }
+
+            gains.push(see_value(on_target.piece_type) - gains[gains.len() - 1]);
+            on_target = attacker;
+            board.set_piece(from, None);
+            board.set_piece(target, Some(attacker));
+            side = side.opposite();
+        }
+
+        // Each side stops capturing when going on loses material
+        while gains.len() > 1 {
+            let last = gains.pop().expect("more than one gain");
+            let previous = gains.last_mut().expect("more than one gain");
+            *previous = -(-*previous).max(last);
+        }
+        gains[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    fn square(notation: &str) -> Square {
+        Square::new_from_notation(notation).unwrap()
+    }
+
+    fn squares(notations: &str) -> Vec<Square> {
+        notations.split_whitespace().map(square).collect()
+    }
+
+    fn exchange(board: &Board, text: &str) -> i32 {
+        board.static_exchange(board.parse_uci_move(text).unwrap())
+    }
+
+    #[test]
+    fn attacks_on_empty_and_own_squares() {
+        let position = board("4k3/8/8/8/4P3/2N5/8/R3K3 w - - 0 1");
+        assert_eq!(
+            position.attackers_of(square("b1"), Color::White),
+            squares("a1 c3")
+        );
+        // Pawns attack diagonally, not ahead
+        assert_eq!(
+            position.attackers_of(square("d5"), Color::White),
+            squares("c3 e4")
+        );
+        assert!(position.attackers_of(square("e5"), Color::White).is_empty());
+        // Pieces defend their own side
+        assert_eq!(
+            position.attackers_of(square("e4"), Color::White),
+            squares("c3")
+        );
+
+        let king = board("8/8/8/8/8/8/8/K6k w - - 0 1");
+        assert_eq!(king.attacked_squares(Color::White), squares("b1 a2 b2"));
+        assert_eq!(king.attacked_squares(Color::Black), squares("g1 g2 h2"));
+    }
+
+    #[test]
+    fn pin_lines() {
+        let position = board("4r1k1/8/8/8/1b6/4N3/3B4/4K3 w - - 0 1");
+        let mut pins = position.pinned_pieces(Color::White);
+        pins.sort_by_key(|pin| pin.square.to_notation());
+        assert_eq!(
+            pins,
+            [
+                Pin {
+                    square: square("d2"),
+                    pinner: square("b4"),
+                    line: squares("c3 b4"),
+                },
+                Pin {
+                    square: square("e3"),
+                    pinner: square("e8"),
+                    line: squares("e2 e4 e5 e6 e7 e8"),
+                },
+            ]
+        );
+
+        // Two pieces in the way, or a slider on the wrong kind of line, pin nothing
+        assert!(board("4r1k1/8/8/8/4N3/4N3/8/4K3 w - - 0 1")
+            .pinned_pieces(Color::White)
+            .is_empty());
+        assert!(board("6k1/8/8/8/1r6/8/3B4/4K3 w - - 0 1")
+            .pinned_pieces(Color::White)
+            .is_empty());
+    }
+
+    #[test]
+    fn checks() {
+        assert!(board("4k3/8/8/8/8/8/8/4K3 w - - 0 1").checkers().is_empty());
+        // Bishops do not attack along ranks
+        assert_eq!(
+            board("4k3/8/8/8/8/8/8/1b2K3 w - - 0 1").checkers(),
+            Vec::<Square>::new()
+        );
+        assert_eq!(
+            board("4k3/8/8/8/8/5n2/8/4K2r w - - 0 1").checkers(),
+            squares("h1 f3")
+        );
+        // After Nd6+ uncovers the rook on e1
+        assert_eq!(
+            board("4k3/8/3N4/8/8/8/8/4RK2 b - - 0 1").checkers(),
+            squares("e1 d6")
+        );
+    }
+
+    #[test]
+    fn hanging_pieces() {
+        // The a2 pawn is loose and the knight worth more than the pawn
+        // attacking it, the b3 pawn is defended against the queen
+        let position = board("rq2k3/8/3p4/4N3/8/1P6/P7/4K3 w - - 0 1");
+        assert_eq!(position.hanging_pieces(Color::White), squares("a2 e5"));
+        assert!(position.hanging_pieces(Color::Black).is_empty());
+    }
+
+    #[test]
+    fn exchanges() {
+        // Captures of loose and defended pieces
+        let position = board("4k3/8/2p5/3r4/8/8/8/3QK3 w - - 0 1");
+        assert_eq!(exchange(&position, "d1d5"), 500 - 900);
+        let position = board("4k3/8/8/3r4/8/8/8/3QK3 w - - 0 1");
+        assert_eq!(exchange(&position, "d1d5"), 500);
+
+        // En passant takes the pawn beside the target square
+        let position = board("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        assert_eq!(exchange(&position, "e5d6"), 100);
+        let position = board("3rk3/8/8/3pP3/8/8/8/4K3 w - d6 0 1");
+        assert_eq!(exchange(&position, "e5d6"), 0);
+
+        // Promotions gain the new piece, and lose it when it is taken back
+        let position = board("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(exchange(&position, "b7b8q"), 800);
+        let position = board("r3k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(exchange(&position, "b7b8q"), -100);
+        assert_eq!(exchange(&position, "b7b8n"), -100);
+
+        // A rook behind the first one joins in once the way is clear
+        let position = board("4k3/4r3/8/8/4p3/8/4R3/6K1 w - - 0 1");
+        assert_eq!(exchange(&position, "e2e4"), 100 - 500);
+        let position = board("4k3/4r3/8/8/4p3/8/4R3/4R1K1 w - - 0 1");
+        assert_eq!(exchange(&position, "e2e4"), 100);
+
+        // The king only takes back pieces that are not defended
+        let position = board("8/8/8/8/8/3k4/3p4/3Q2K1 w - - 0 1");
+        assert_eq!(exchange(&position, "d1d2"), 100 - 900);
+        let position = board("8/8/8/8/8/4k3/R2p4/3Q2K1 w - - 0 1");
+        assert_eq!(exchange(&position, "d1d2"), 100);
+    }
+}
@@ -1,6 +1,15 @@
-use crate::{Color, Piece, PieceType, Square};
+use crate::{Color, Move, Piece, PieceType, Square};
+
+/// Which castling moves are still allowed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CastlingRights {
+    pub white_king_side: bool,
+    pub white_queen_side: bool,
+    pub black_king_side: bool,
+    pub black_queen_side: bool,
+}
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Eq)]
 pub struct Board {
     squares: [[Option<Piece>; 8]; 8],
     en_passant: Option<Square>,
@@ -8,6 +17,9 @@ pub struct Board {
     castle_queen_side_white: bool,
     castle_king_side_black: bool,
     castle_queen_side_black: bool,
+    side_to_move: Color,
+    halfmove_clock: u32,
+    fullmove_number: u32,
 }
 
 impl Board {
@@ -19,6 +31,9 @@ impl Board {
             castle_queen_side_white: true,
             castle_king_side_black: true,
             castle_queen_side_black: true,
+            side_to_move: Color::White,
+            halfmove_clock: 0,
+            fullmove_number: 1,
         }
     }
 
@@ -69,6 +84,58 @@ impl Board {
         self.squares[square.rank() as usize][square.file() as usize] = piece;
     }
 
+    /// Get the square of a pawn that just moved two squares and can be taken en passant
+    pub fn en_passant(&self) -> Option<Square> {
+        self.en_passant
+    }
+
+    pub fn set_en_passant(&mut self, square: Option<Square>) {
+        self.en_passant = square;
+    }
+
+    pub fn castling_rights(&self) -> CastlingRights {
+        CastlingRights {
+            white_king_side: self.castle_king_side_white,
+            white_queen_side: self.castle_queen_side_white,
+            black_king_side: self.castle_king_side_black,
+            black_queen_side: self.castle_queen_side_black,
+        }
+    }
+
+    pub fn set_castling_rights(&mut self, rights: CastlingRights) {
+        self.castle_king_side_white = rights.white_king_side;
+        self.castle_queen_side_white = rights.white_queen_side;
+        self.castle_king_side_black = rights.black_king_side;
+        self.castle_queen_side_black = rights.black_queen_side;
+    }
+
+    /// Get the color to play next, switched by every move
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    pub fn set_side_to_move(&mut self, color: Color) {
+        self.side_to_move = color;
+    }
+
+    /// Get the number of half moves since the last capture or pawn move
+    pub fn halfmove_clock(&self) -> u32 {
+        self.halfmove_clock
+    }
+
+    pub fn set_halfmove_clock(&mut self, halfmove_clock: u32) {
+        self.halfmove_clock = halfmove_clock;
+    }
+
+    /// Get the number of the current full move, starting at 1 and increased after each black move
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    pub fn set_fullmove_number(&mut self, fullmove_number: u32) {
+        self.fullmove_number = fullmove_number;
+    }
+
     fn move_allows_en_passant(&mut self, piece: Piece, from: Square, to: Square) -> bool {
         if piece.piece_type != PieceType::Pawn {
             return false;
@@ -170,6 +237,18 @@ impl Board {
             Some(p) => p,
             None => return,
         };
+
+        // update move counters, pawn moves and captures reset the halfmove clock
+        if from_piece.piece_type == PieceType::Pawn || self.get_piece(to).is_some() {
+            self.halfmove_clock = 0;
+        } else {
+            self.halfmove_clock += 1;
+        }
+        if from_piece.color == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = from_piece.opposite_color();
+
         // move piece
         self.set_piece(to, Some(from_piece));
         self.set_piece(from, None);
@@ -192,6 +271,15 @@ impl Board {
         }
     }
 
+    /// Make a move, promoting the pawn if the move carries a promotion
+    pub fn apply_move(&mut self, mv: Move) {
+        self.make_move(mv.from, mv.to);
+
+        if let Some(promotion) = mv.promotion {
+            self.promote_square(mv.to, promotion);
+        }
+    }
+
     pub fn is_checkmated(&self, color: Color) -> bool {
         if !self.is_king_in_check(color) {
             return false;
@@ -240,6 +328,35 @@ impl Board {
         valid_moves
     }
 
+    /// Get every legal move for a color, with one move per promotion choice
+    pub fn get_all_valid_moves(&self, color: Color) -> Vec<Move> {
+        let mut all_moves = Vec::new();
+
+        for from in self.get_all_color_squares(color) {
+            let is_pawn = self
+                .get_piece(from)
+                .map(|p| p.piece_type == PieceType::Pawn)
+                .unwrap_or(false);
+
+            for to in self.get_valid_moves(from) {
+                if is_pawn && (to.rank() == 0 || to.rank() == 7) {
+                    for promotion in [
+                        PieceType::Queen,
+                        PieceType::Rook,
+                        PieceType::Bishop,
+                        PieceType::Knight,
+                    ] {
+                        all_moves.push(Move::with_promotion(from, to, promotion));
+                    }
+                } else {
+                    all_moves.push(Move::new(from, to));
+                }
+            }
+        }
+
+        all_moves
+    }
+
     fn is_square_in_check(&self, check_square: Square, color: Color) -> bool {
         for rank in 0..8 {
             for file in 0..8 {
@@ -345,7 +462,7 @@ impl Board {
         true
     }
 
-    fn get_all_color_squares(&self, color: Color) -> Vec<Square> {
+    pub(crate) fn get_all_color_squares(&self, color: Color) -> Vec<Square> {
         let mut all_color_squared: Vec<Square> = Vec::new();
         for rank in 0..8 {
             for file in 0..8 {
@@ -407,36 +524,23 @@ impl Board {
         all_moves
     }
 
-    fn can_attack_square(&self, from: Square, to: Square) -> bool {
+    /// Whether the piece on `from` attacks `to`, whatever stands there, so the
+    /// empty squares a castling king crosses and defended pieces count too
+    pub(crate) fn can_attack_square(&self, from: Square, to: Square) -> bool {
         let current = match self.get_piece(from) {
             Some(p) => p,
             None => return false,
         };
 
-        let target = match self.get_piece(to) {
-            Some(p) => p,
-            None => return false,
-        };
-
-        if target.color != current.opposite_color() {
-            return false;
-        }
-
         match current.piece_type {
-            PieceType::Knight => {
-                return PieceType::Knight.get_offsets().iter().any(
-                    |&(rank_offset, file_offset)| {
-                        from.offset(rank_offset, file_offset).ok() == Some(to)
-                    },
-                );
-            }
-            PieceType::King => {
-                return PieceType::King
+            PieceType::Knight | PieceType::King => {
+                current
+                    .piece_type
                     .get_offsets()
                     .iter()
                     .any(|&(rank_offset, file_offset)| {
                         from.offset(rank_offset, file_offset).ok() == Some(to)
-                    });
+                    })
             }
             PieceType::Pawn => {
                 let direction = match current.color {
@@ -444,25 +548,16 @@ impl Board {
                     Color::Black => -1,
                 };
 
-                // ToDo: Review this
-                if let Some(passant) = self.en_passant {
-                    if passant.offset(0, -1).ok() == Some(to)
-                        || passant.offset(0, -1).ok() == Some(to)
-                    {
-                        return true;
-                    }
-                }
-
-                return from.offset(direction, -1).ok() == Some(to)
-                    || from.offset(direction, 1).ok() == Some(to);
+                from.offset(direction, -1).ok() == Some(to)
+                    || from.offset(direction, 1).ok() == Some(to)
             }
             PieceType::Bishop | PieceType::Rook | PieceType::Queen => {
-                return self.can_sliding_piece_reach(from, to, current);
+                self.can_sliding_piece_reach(from, to, current)
             }
         }
     }
 
-    fn find_king_square(&self, color: Color) -> Option<Square> {
+    pub fn find_king_square(&self, color: Color) -> Option<Square> {
         for rank in 0..8 {
             for file in 0..8 {
                 if let Ok(s) = Square::new(rank, file) {
@@ -559,14 +654,13 @@ impl Board {
                 }
 
                 // En Passant rule
-                if let Some(passant) = self.en_passant {
+                if self.en_passant.is_some() {
                     for offset in [-1, 1] {
                         if let Ok(square_adjacent) = square.offset(0, offset) {
                             if Some(square_adjacent) == self.en_passant {
                                 if let Some(passant_piece) = self.get_piece(square_adjacent) {
                                     if passant_piece.color == piece.opposite_color() {
                                         // opponent is in en_passant square
-                                        println!("trying en_passant {passant:?}");
                                         if let Ok(s) = square.offset(direction, offset) {
                                             all_moves.push(s);
                                         }
@@ -608,3 +702,48 @@ impl Board {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(notation: &str) -> Square {
+        Square::new_from_notation(notation).unwrap()
+    }
+
+    /// Squares the king on its starting square can move to, castling included
+    fn king_targets(fen: &str) -> Vec<Square> {
+        let board = Board::from_fen(fen).unwrap();
+        let color = board.side_to_move();
+        let king = board.find_king_square(color).unwrap();
+        board.get_valid_moves(king)
+    }
+
+    #[test]
+    fn castling_needs_safe_squares() {
+        let both = king_targets("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        assert!(both.contains(&square("g1")) && both.contains(&square("c1")));
+
+        // Out of check
+        let targets = king_targets("4r1k1/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert!(!targets.contains(&square("g1")) && !targets.contains(&square("c1")));
+
+        // Through an attacked square, only on the side it is on
+        let targets = king_targets("5rk1/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert!(!targets.contains(&square("g1")) && targets.contains(&square("c1")));
+        let targets = king_targets("3r2k1/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert!(targets.contains(&square("g1")) && !targets.contains(&square("c1")));
+
+        // Into check
+        let targets = king_targets("6rk/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert!(!targets.contains(&square("g1")) && targets.contains(&square("c1")));
+
+        // The rook may pass an attacked square, the king may not
+        let targets = king_targets("1r4k1/8/8/8/8/8/8/R3K2R w KQ - 0 1");
+        assert!(targets.contains(&square("c1")));
+
+        // And the same for Black, attacked by a bishop
+        let targets = king_targets("r3k2r/8/8/8/8/B7/8/4K3 b kq - 0 1");
+        assert!(!targets.contains(&square("g8")) && targets.contains(&square("c8")));
+    }
+}
@@ -0,0 +1,27 @@
+use crate::{PieceType, Square};
+
+/// A move from one square to another, with the piece a pawn promotes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    pub from: Square,
+    pub to: Square,
+    pub promotion: Option<PieceType>,
+}
+
+impl Move {
+    pub fn new(from: Square, to: Square) -> Self {
+        Self {
+            from,
+            to,
+            promotion: None,
+        }
+    }
+
+    pub fn with_promotion(from: Square, to: Square, promotion: PieceType) -> Self {
+        Self {
+            from,
+            to,
+            promotion: Some(promotion),
+        }
+    }
+}
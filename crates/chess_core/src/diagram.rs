@@ -0,0 +1,143 @@
+use crate::{Board, CastlingRights, Color, Piece, PieceType, Square};
+use std::fmt;
+use std::str::FromStr;
+
+/// Draws the board as 8 lines from rank 8 down to rank 1, using the piece glyphs,
+/// or FEN letters with the alternate flag (`{:#}`). Empty squares are `.`
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for rank in (0..8).rev() {
+            let row: Vec<String> = (0..8)
+                .map(|file| {
+                    let square = Square::new(rank, file).expect("always valid");
+                    match self.get_piece(square) {
+                        Some(piece) if f.alternate() => piece.get_piece_char().to_string(),
+                        Some(piece) => piece.get_piece_ascii(),
+                        None => ".".to_string(),
+                    }
+                })
+                .collect();
+
+            write!(f, "{}", row.join(" "))?;
+            if rank > 0 {
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Same diagram as `{:#}` with coordinates, en passant and castling state,
+/// so failing assertions show a readable position
+impl fmt::Debug for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Board {{")?;
+        for (index, line) in format!("{:#}", self).lines().enumerate() {
+            writeln!(f, "  {} {}", 8 - index, line)?;
+        }
+        writeln!(f, "    a b c d e f g h")?;
+
+        let rights = self.castling_rights();
+        let castling: String = [
+            (rights.white_king_side, 'K'),
+            (rights.white_queen_side, 'Q'),
+            (rights.black_king_side, 'k'),
+            (rights.black_queen_side, 'q'),
+        ]
+        .iter()
+        .filter(|(allowed, _)| *allowed)
+        .map(|(_, symbol)| symbol)
+        .collect();
+
+        writeln!(
+            f,
+            "  {:?} to move, en passant: {}, castling: {}",
+            self.side_to_move(),
+            self.en_passant()
+                .map(|square| square.to_notation())
+                .unwrap_or_else(|| "-".to_string()),
+            if castling.is_empty() { "-" } else { &castling }
+        )?;
+        write!(f, "}}")
+    }
+}
+
+/// Reads a diagram of 8 ranks from rank 8 down to rank 1, as written by `Display`.
+/// Pieces can be FEN letters or glyphs, empty squares `.` or `-`, and whitespace is ignored.
+/// Castling is allowed wherever king and rook are still on their starting squares
+impl FromStr for Board {
+    type Err = &'static str;
+
+    fn from_str(diagram: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<Vec<char>> = diagram
+            .lines()
+            .map(|line| line.chars().filter(|c| !c.is_whitespace()).collect())
+            .filter(|row: &Vec<char>| !row.is_empty())
+            .collect();
+
+        if rows.len() != 8 {
+            return Err("Board diagram must have 8 ranks\n");
+        }
+
+        let mut board = Board::new();
+        for (index, row) in rows.iter().enumerate() {
+            if row.len() != 8 {
+                return Err("Each rank of a board diagram must have 8 squares\n");
+            }
+
+            let rank = 7 - index as u8;
+            for (file, &symbol) in row.iter().enumerate() {
+                let piece = match symbol {
+                    '.' | '-' => None,
+                    _ => {
+                        Some(Piece::from_symbol(symbol).ok_or("Unknown piece in board diagram\n")?)
+                    }
+                };
+                board.set_piece(Square::new(rank, file as u8)?, piece);
+            }
+        }
+
+        board.set_castling_rights(castling_rights_from_placement(&board));
+        Ok(board)
+    }
+}
+
+fn castling_rights_from_placement(board: &Board) -> CastlingRights {
+    let has_piece = |rank: u8, file: u8, color: Color, piece_type: PieceType| {
+        Square::new(rank, file)
+            .map(|square| board.get_piece(square) == Some(Piece::new(color, piece_type)))
+            .unwrap_or(false)
+    };
+
+    let white_king = has_piece(0, 4, Color::White, PieceType::King);
+    let black_king = has_piece(7, 4, Color::Black, PieceType::King);
+
+    CastlingRights {
+        white_king_side: white_king && has_piece(0, 7, Color::White, PieceType::Rook),
+        white_queen_side: white_king && has_piece(0, 0, Color::White, PieceType::Rook),
+        black_king_side: black_king && has_piece(7, 7, Color::Black, PieceType::Rook),
+        black_queen_side: black_king && has_piece(7, 0, Color::Black, PieceType::Rook),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diagram_round_trip() {
+        let positions = [
+            Board::new_starting_default(),
+            // Castling rights and counters as a diagram would give them
+            Board::from_fen("r3k2r/pp1n1ppp/2p1b3/q7/3P4/2N1BN2/PP3PPP/R2QK2R w KQkq - 0 1")
+                .unwrap(),
+            Board::from_fen("8/5k2/8/3p4/8/8/2K5/8 w - - 0 1").unwrap(),
+        ];
+
+        for board in positions {
+            assert_eq!(Board::from_str(&board.to_string()), Ok(board.clone()));
+            assert_eq!(Board::from_str(&format!("{:#}", board)), Ok(board));
+        }
+    }
+}
@@ -0,0 +1,999 @@
+use crate::{Board, Color, Move, PieceType, Square};
+use std::fmt;
+use std::path::Path;
+
+/// Scores positions for a search
+pub trait Evaluator: Send + Sync {
+    /// Score in centipawns from the side to move's view
+    fn evaluate(&self, board: &Board) -> i32;
+
+    /// Score split into terms, from White's view. Evaluators without
+    /// separate terms report a single one
+    fn explain(&self, board: &Board) -> EvalBreakdown {
+        let score = match board.side_to_move() {
+            Color::White => self.evaluate(board),
+            Color::Black => -self.evaluate(board),
+        };
+        EvalBreakdown {
+            phase: MAX_PHASE,
+            terms: vec![EvalTerm {
+                name: "Total",
+                middlegame: score,
+                endgame: score,
+            }],
+        }
+    }
+
+    /// State a search keeps in step with the moves it makes, for evaluators
+    /// that update incrementally. `None` evaluates every position from scratch
+    fn incremental(&self, _board: &Board) -> Option<Box<dyn IncrementalEval + '_>> {
+        None
+    }
+}
+
+/// Evaluation state following a search from the position it was created for
+pub trait IncrementalEval: Send {
+    /// Play `mv` from `before`, giving `after`
+    fn make_move(&mut self, before: &Board, mv: Move, after: &Board);
+
+    /// Go back to the position before the last move made
+    fn unmake_move(&mut self);
+
+    /// Score of the current position `board` in centipawns from the side to move's view
+    fn evaluate(&mut self, board: &Board) -> i32;
+}
+
+/// Game phase of the starting position, reaching 0 with only kings and pawns left
+pub const MAX_PHASE: i32 = 24;
+
+/// One term of an evaluation, White minus Black
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EvalTerm {
+    pub name: &'static str,
+    pub middlegame: i32,
+    pub endgame: i32,
+}
+
+impl EvalTerm {
+    /// Blend of both scores for a game phase
+    pub fn tapered(&self, phase: i32) -> i32 {
+        (self.middlegame * phase + self.endgame * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+/// Evaluation of a position split by term
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalBreakdown {
+    /// From `MAX_PHASE` in the opening down to 0 in pawn endgames
+    pub phase: i32,
+    pub terms: Vec<EvalTerm>,
+}
+
+impl EvalBreakdown {
+    /// Sum of all terms in centipawns, from White's view
+    pub fn total(&self) -> i32 {
+        self.terms.iter().map(|term| term.tapered(self.phase)).sum()
+    }
+}
+
+impl fmt::Display for EvalBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<16}{:>8}{:>8}{:>8}", "Term", "MG", "EG", "Total")?;
+        for term in &self.terms {
+            writeln!(
+                f,
+                "{:<16}{:>8}{:>8}{:>8}",
+                term.name,
+                term.middlegame,
+                term.endgame,
+                term.tapered(self.phase)
+            )?;
+        }
+        write!(
+            f,
+            "Phase {}/{}, total {:+} (White's view)",
+            self.phase,
+            MAX_PHASE,
+            self.total()
+        )
+    }
+}
+
+/// Weights of the handcrafted evaluation. Arrays by piece type are in
+/// pawn, knight, bishop, rook, queen, king order, and piece-square tables
+/// are written from White's side with a8 first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalWeights {
+    pub material_mg: [i32; 6],
+    pub material_eg: [i32; 6],
+    pub pst_mg: [[i32; 64]; 6],
+    pub pst_eg: [[i32; 64]; 6],
+    pub bishop_pair: [i32; 2],
+    pub doubled_pawn: [i32; 2],
+    pub isolated_pawn: [i32; 2],
+    /// By rank from the pawn's side, rank 2 being index 1
+    pub passed_pawn_mg: [i32; 8],
+    pub passed_pawn_eg: [i32; 8],
+    /// Own pawns one and two squares in front of the king
+    pub pawn_shield: [i32; 2],
+    /// Files next to the king without own pawns, and without any pawns
+    pub king_open_file: [i32; 2],
+    /// Per attack on the squares around the king, weighted by attacker
+    pub king_attack: i32,
+    pub mobility_mg: [i32; 6],
+    pub mobility_eg: [i32; 6],
+}
+
+#[rustfmt::skip]
+const PAWN_MG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    10,  10,  20,  30,  30,  20,  10,  10,
+     5,   5,  10,  25,  25,  10,   5,   5,
+     0,   0,   0,  20,  20,   0,   0,   0,
+     5,  -5, -10,   0,   0, -10,  -5,   5,
+     5,  10,  10, -20, -20,  10,  10,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const PAWN_EG: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+    80,  80,  80,  80,  80,  80,  80,  80,
+    50,  50,  50,  50,  50,  50,  50,  50,
+    30,  30,  30,  30,  30,  30,  30,  30,
+    15,  15,  15,  15,  15,  15,  15,  15,
+     5,   5,   5,   5,   5,   5,   5,   5,
+     0,   0,   0,   0,   0,   0,   0,   0,
+     0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT: [i32; 64] = [
+   -50, -40, -30, -30, -30, -30, -40, -50,
+   -40, -20,   0,   0,   0,   0, -20, -40,
+   -30,   0,  10,  15,  15,  10,   0, -30,
+   -30,   5,  15,  20,  20,  15,   5, -30,
+   -30,   0,  15,  20,  20,  15,   0, -30,
+   -30,   5,  10,  15,  15,  10,   5, -30,
+   -40, -20,   0,   5,   5,   0, -20, -40,
+   -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP: [i32; 64] = [
+   -20, -10, -10, -10, -10, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,  10,  10,   5,   0, -10,
+   -10,   5,   5,  10,  10,   5,   5, -10,
+   -10,   0,  10,  10,  10,  10,   0, -10,
+   -10,  10,  10,  10,  10,  10,  10, -10,
+   -10,   5,   0,   0,   0,   0,   5, -10,
+   -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK: [i32; 64] = [
+     0,   0,   0,   0,   0,   0,   0,   0,
+     5,  10,  10,  10,  10,  10,  10,   5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+    -5,   0,   0,   0,   0,   0,   0,  -5,
+     0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN: [i32; 64] = [
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+   -10,   0,   0,   0,   0,   0,   0, -10,
+   -10,   0,   5,   5,   5,   5,   0, -10,
+    -5,   0,   5,   5,   5,   5,   0,  -5,
+     0,   0,   5,   5,   5,   5,   0,  -5,
+   -10,   5,   5,   5,   5,   5,   0, -10,
+   -10,   0,   5,   0,   0,   0,   0, -10,
+   -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KING_MG: [i32; 64] = [
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -30, -40, -40, -50, -50, -40, -40, -30,
+   -20, -30, -30, -40, -40, -30, -30, -20,
+   -10, -20, -20, -20, -20, -20, -20, -10,
+    20,  20,   0,   0,   0,   0,  20,  20,
+    20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_EG: [i32; 64] = [
+   -50, -40, -30, -20, -20, -30, -40, -50,
+   -30, -20, -10,   0,   0, -10, -20, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  30,  40,  40,  30, -10, -30,
+   -30, -10,  20,  30,  30,  20, -10, -30,
+   -30, -30,   0,   0,   0,   0, -30, -30,
+   -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+impl Default for EvalWeights {
+    fn default() -> Self {
+        Self {
+            material_mg: [82, 337, 365, 477, 1025, 0],
+            material_eg: [94, 281, 297, 512, 936, 0],
+            pst_mg: [PAWN_MG, KNIGHT, BISHOP, ROOK, QUEEN, KING_MG],
+            pst_eg: [PAWN_EG, KNIGHT, BISHOP, ROOK, QUEEN, KING_EG],
+            bishop_pair: [30, 50],
+            doubled_pawn: [-10, -20],
+            isolated_pawn: [-10, -15],
+            passed_pawn_mg: [0, 5, 10, 15, 25, 40, 60, 0],
+            passed_pawn_eg: [0, 10, 15, 25, 45, 70, 110, 0],
+            pawn_shield: [10, 5],
+            king_open_file: [-10, -20],
+            king_attack: 8,
+            mobility_mg: [0, 4, 5, 2, 1, 0],
+            mobility_eg: [0, 4, 5, 4, 2, 0],
+        }
+    }
+}
+
+// Indices of the weights in `EvalWeights::to_params`
+const MATERIAL_MG: usize = 0;
+const MATERIAL_EG: usize = MATERIAL_MG + 6;
+const PST_MG: usize = MATERIAL_EG + 6;
+const PST_EG: usize = PST_MG + 6 * 64;
+const BISHOP_PAIR: usize = PST_EG + 6 * 64;
+const DOUBLED_PAWN: usize = BISHOP_PAIR + 2;
+const ISOLATED_PAWN: usize = DOUBLED_PAWN + 2;
+const PASSED_PAWN_MG: usize = ISOLATED_PAWN + 2;
+const PASSED_PAWN_EG: usize = PASSED_PAWN_MG + 8;
+const PAWN_SHIELD: usize = PASSED_PAWN_EG + 8;
+const KING_OPEN_FILE: usize = PAWN_SHIELD + 2;
+const KING_ATTACK: usize = KING_OPEN_FILE + 2;
+const MOBILITY_MG: usize = KING_ATTACK + 1;
+const MOBILITY_EG: usize = MOBILITY_MG + 6;
+
+/// Number of weights in `EvalWeights::to_params`
+pub const EVAL_PARAMS: usize = MOBILITY_EG + 6;
+
+/// Names of the weights in a weights file with their first index and count
+const PARAM_GROUPS: [(&str, usize, usize); 24] = [
+    ("material_mg", MATERIAL_MG, 6),
+    ("material_eg", MATERIAL_EG, 6),
+    ("pst_mg_pawn", PST_MG, 64),
+    ("pst_mg_knight", PST_MG + 64, 64),
+    ("pst_mg_bishop", PST_MG + 2 * 64, 64),
+    ("pst_mg_rook", PST_MG + 3 * 64, 64),
+    ("pst_mg_queen", PST_MG + 4 * 64, 64),
+    ("pst_mg_king", PST_MG + 5 * 64, 64),
+    ("pst_eg_pawn", PST_EG, 64),
+    ("pst_eg_knight", PST_EG + 64, 64),
+    ("pst_eg_bishop", PST_EG + 2 * 64, 64),
+    ("pst_eg_rook", PST_EG + 3 * 64, 64),
+    ("pst_eg_queen", PST_EG + 4 * 64, 64),
+    ("pst_eg_king", PST_EG + 5 * 64, 64),
+    ("bishop_pair", BISHOP_PAIR, 2),
+    ("doubled_pawn", DOUBLED_PAWN, 2),
+    ("isolated_pawn", ISOLATED_PAWN, 2),
+    ("passed_pawn_mg", PASSED_PAWN_MG, 8),
+    ("passed_pawn_eg", PASSED_PAWN_EG, 8),
+    ("pawn_shield", PAWN_SHIELD, 2),
+    ("king_open_file", KING_OPEN_FILE, 2),
+    ("king_attack", KING_ATTACK, 1),
+    ("mobility_mg", MOBILITY_MG, 6),
+    ("mobility_eg", MOBILITY_EG, 6),
+];
+
+impl EvalWeights {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All weights in one list, `EVAL_PARAMS` long
+    pub fn to_params(&self) -> Vec<i32> {
+        let mut params = Vec::with_capacity(EVAL_PARAMS);
+        params.extend_from_slice(&self.material_mg);
+        params.extend_from_slice(&self.material_eg);
+        for table in &self.pst_mg {
+            params.extend_from_slice(table);
+        }
+        for table in &self.pst_eg {
+            params.extend_from_slice(table);
+        }
+        params.extend_from_slice(&self.bishop_pair);
+        params.extend_from_slice(&self.doubled_pawn);
+        params.extend_from_slice(&self.isolated_pawn);
+        params.extend_from_slice(&self.passed_pawn_mg);
+        params.extend_from_slice(&self.passed_pawn_eg);
+        params.extend_from_slice(&self.pawn_shield);
+        params.extend_from_slice(&self.king_open_file);
+        params.push(self.king_attack);
+        params.extend_from_slice(&self.mobility_mg);
+        params.extend_from_slice(&self.mobility_eg);
+        params
+    }
+
+    /// Weights from a list in the order of `to_params`
+    pub fn from_params(params: &[i32]) -> Result<Self, &'static str> {
+        if params.len() != EVAL_PARAMS {
+            return Err("Wrong number of evaluation weights\n");
+        }
+        let mut weights = Self::default();
+        let copy = |target: &mut [i32], start: usize| {
+            target.copy_from_slice(&params[start..start + target.len()]);
+        };
+        copy(&mut weights.material_mg, MATERIAL_MG);
+        copy(&mut weights.material_eg, MATERIAL_EG);
+        for (index, table) in weights.pst_mg.iter_mut().enumerate() {
+            copy(table, PST_MG + index * 64);
+        }
+        for (index, table) in weights.pst_eg.iter_mut().enumerate() {
+            copy(table, PST_EG + index * 64);
+        }
+        copy(&mut weights.bishop_pair, BISHOP_PAIR);
+        copy(&mut weights.doubled_pawn, DOUBLED_PAWN);
+        copy(&mut weights.isolated_pawn, ISOLATED_PAWN);
+        copy(&mut weights.passed_pawn_mg, PASSED_PAWN_MG);
+        copy(&mut weights.passed_pawn_eg, PASSED_PAWN_EG);
+        copy(&mut weights.pawn_shield, PAWN_SHIELD);
+        copy(&mut weights.king_open_file, KING_OPEN_FILE);
+        weights.king_attack = params[KING_ATTACK];
+        copy(&mut weights.mobility_mg, MOBILITY_MG);
+        copy(&mut weights.mobility_eg, MOBILITY_EG);
+        Ok(weights)
+    }
+
+    /// Weights file text, a line per group of weights such as
+    /// `material_mg 82 337 365 477 1025 0`
+    pub fn to_text(&self) -> String {
+        let params = self.to_params();
+        let mut text = String::new();
+        for (name, start, count) in PARAM_GROUPS {
+            text.push_str(name);
+            for value in &params[start..start + count] {
+                text.push_str(&format!(" {}", value));
+            }
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Weights from a weights file. Groups the file leaves out keep their
+    /// default weights, and lines starting with `#` are comments
+    pub fn from_text(text: &str) -> Result<Self, &'static str> {
+        let mut params = Self::default().to_params();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let name = fields.next().unwrap_or_default();
+            let &(_, start, count) = PARAM_GROUPS
+                .iter()
+                .find(|(group, _, _)| *group == name)
+                .ok_or("Unknown evaluation weight\n")?;
+            let values = fields
+                .map(|value| value.parse::<i32>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| "Evaluation weights must be whole numbers\n")?;
+            if values.len() != count {
+                return Err("Wrong number of evaluation weights\n");
+            }
+            params[start..start + count].copy_from_slice(&values);
+        }
+        Self::from_params(&params)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let text =
+            std::fs::read_to_string(path).map_err(|_| "Failed to read evaluation weights\n")?;
+        Self::from_text(&text)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), &'static str> {
+        std::fs::write(path, self.to_text()).map_err(|_| "Failed to write evaluation weights\n")
+    }
+}
+
+/// Moves a piece usually has, mobility scores count the difference
+const MOBILITY_BASELINE: [i32; 6] = [0, 4, 7, 7, 14, 0];
+/// Weight of each piece type attacking the king's surroundings
+const KING_ATTACK_UNITS: [i32; 6] = [0, 2, 2, 3, 5, 0];
+/// Contribution of each piece type to the game phase
+const PHASE_WEIGHTS: [i32; 6] = [0, 1, 1, 2, 4, 0];
+
+/// Index of a piece type in tables by piece type, from pawn to king
+pub fn type_index(piece_type: PieceType) -> usize {
+    match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    }
+}
+
+fn color_index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+/// Index in a piece-square table, mirrored for Black
+fn table_index(square: Square, color: Color) -> usize {
+    let rank = match color {
+        Color::White => 7 - square.rank(),
+        Color::Black => square.rank(),
+    };
+    rank as usize * 8 + square.file() as usize
+}
+
+/// Rank counted from a side's own back rank
+fn relative_rank(square: Square, color: Color) -> u8 {
+    match color {
+        Color::White => square.rank(),
+        Color::Black => 7 - square.rank(),
+    }
+}
+
+/// A weight with its index in `EvalWeights::to_params`, so traces can tell
+/// weights apart
+#[derive(Debug, Clone, Copy)]
+struct Weight {
+    index: usize,
+    value: i32,
+}
+
+impl Weight {
+    fn new(index: usize, value: i32) -> Self {
+        Self { index, value }
+    }
+}
+
+/// Collects the weights that apply to a position, per color
+trait EvalSink {
+    /// Add `count` times a middlegame and an endgame weight for `color`
+    fn add(&mut self, color: Color, mg: Weight, eg: Weight, count: i32);
+
+    /// Add `count` times a weight that only counts in the middlegame
+    fn add_mg(&mut self, color: Color, mg: Weight, count: i32);
+}
+
+/// Middlegame and endgame scores of one term, per color
+#[derive(Debug, Clone, Copy, Default)]
+struct TermScores {
+    mg: [i32; 2],
+    eg: [i32; 2],
+}
+
+impl EvalSink for TermScores {
+    fn add(&mut self, color: Color, mg: Weight, eg: Weight, count: i32) {
+        self.mg[color_index(color)] += mg.value * count;
+        self.eg[color_index(color)] += eg.value * count;
+    }
+
+    fn add_mg(&mut self, color: Color, mg: Weight, count: i32) {
+        self.mg[color_index(color)] += mg.value * count;
+    }
+}
+
+impl TermScores {
+    fn to_term(self, name: &'static str) -> EvalTerm {
+        EvalTerm {
+            name,
+            middlegame: self.mg[0] - self.mg[1],
+            endgame: self.eg[0] - self.eg[1],
+        }
+    }
+}
+
+/// How often each weight counts in a position, White's minus Black's. The
+/// evaluation is linear in the weights, so a tuner can score a position for
+/// any weights from its trace alone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalTrace {
+    pub phase: i32,
+    /// Indices in `EvalWeights::to_params` with their middlegame count
+    pub mg: Vec<(usize, i32)>,
+    /// Indices with their endgame count
+    pub eg: Vec<(usize, i32)>,
+}
+
+impl EvalTrace {
+    /// Score from White's view for weights given by `EvalWeights::to_params`,
+    /// without the rounding of the evaluator
+    pub fn evaluate(&self, params: &[f64]) -> f64 {
+        let sum = |counts: &[(usize, i32)]| -> f64 {
+            counts
+                .iter()
+                .map(|&(index, count)| params[index] * count as f64)
+                .sum()
+        };
+        let phase = self.phase as f64;
+        (sum(&self.mg) * phase + sum(&self.eg) * (MAX_PHASE as f64 - phase)) / MAX_PHASE as f64
+    }
+
+    /// How much the score from White's view changes with each weight it uses
+    pub fn gradient(&self) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let mg_share = self.phase as f64 / MAX_PHASE as f64;
+        let mg = self
+            .mg
+            .iter()
+            .map(move |&(index, count)| (index, count as f64 * mg_share));
+        let eg = self
+            .eg
+            .iter()
+            .map(move |&(index, count)| (index, count as f64 * (1.0 - mg_share)));
+        mg.chain(eg)
+    }
+}
+
+/// Counts of every weight while a trace is taken
+struct TraceCounts {
+    mg: Vec<i32>,
+    eg: Vec<i32>,
+}
+
+impl EvalSink for TraceCounts {
+    fn add(&mut self, color: Color, mg: Weight, eg: Weight, count: i32) {
+        let sign = if color == Color::White { 1 } else { -1 };
+        self.mg[mg.index] += sign * count;
+        self.eg[eg.index] += sign * count;
+    }
+
+    fn add_mg(&mut self, color: Color, mg: Weight, count: i32) {
+        let sign = if color == Color::White { 1 } else { -1 };
+        self.mg[mg.index] += sign * count;
+    }
+}
+
+/// Material, tapered piece-square tables, pawn structure, king safety and mobility
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClassicalEvaluator {
+    pub weights: EvalWeights,
+}
+
+impl ClassicalEvaluator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_weights(weights: EvalWeights) -> Self {
+        Self { weights }
+    }
+
+    /// The weights that apply to a position, for tuning
+    pub fn trace(&self, board: &Board) -> EvalTrace {
+        let pieces = Self::pieces(board);
+        let mut counts = TraceCounts {
+            mg: vec![0; EVAL_PARAMS],
+            eg: vec![0; EVAL_PARAMS],
+        };
+        self.material(&pieces, &mut counts);
+        self.piece_squares(&pieces, &mut counts);
+        self.pawn_structure(&pieces, &mut counts);
+        let attack_units = self.mobility_and_king_attacks(board, &pieces, &mut counts);
+        self.king_safety(board, &pieces, attack_units, &mut counts);
+
+        let nonzero = |counts: Vec<i32>| {
+            counts
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, count)| count != 0)
+                .collect()
+        };
+        EvalTrace {
+            phase: Self::phase(&pieces),
+            mg: nonzero(counts.mg),
+            eg: nonzero(counts.eg),
+        }
+    }
+
+    fn pieces(board: &Board) -> Vec<(Square, Color, PieceType)> {
+        let mut pieces = Vec::with_capacity(32);
+        for rank in 0..8 {
+            for file in 0..8 {
+                let square = Square::new(rank, file).expect("always valid");
+                if let Some(piece) = board.get_piece(square) {
+                    pieces.push((square, piece.color, piece.piece_type));
+                }
+            }
+        }
+        pieces
+    }
+
+    fn phase(pieces: &[(Square, Color, PieceType)]) -> i32 {
+        pieces
+            .iter()
+            .map(|&(_, _, piece_type)| PHASE_WEIGHTS[type_index(piece_type)])
+            .sum::<i32>()
+            .min(MAX_PHASE)
+    }
+
+    fn material<S: EvalSink>(&self, pieces: &[(Square, Color, PieceType)], sink: &mut S) {
+        let w = &self.weights;
+        let mut bishops = [0; 2];
+
+        for &(_, color, piece_type) in pieces {
+            let index = type_index(piece_type);
+            sink.add(
+                color,
+                Weight::new(MATERIAL_MG + index, w.material_mg[index]),
+                Weight::new(MATERIAL_EG + index, w.material_eg[index]),
+                1,
+            );
+            if piece_type == PieceType::Bishop {
+                bishops[color_index(color)] += 1;
+            }
+        }
+
+        for color in [Color::White, Color::Black] {
+            if bishops[color_index(color)] >= 2 {
+                sink.add(
+                    color,
+                    Weight::new(BISHOP_PAIR, w.bishop_pair[0]),
+                    Weight::new(BISHOP_PAIR + 1, w.bishop_pair[1]),
+                    1,
+                );
+            }
+        }
+    }
+
+    fn piece_squares<S: EvalSink>(&self, pieces: &[(Square, Color, PieceType)], sink: &mut S) {
+        let w = &self.weights;
+        for &(square, color, piece_type) in pieces {
+            let index = type_index(piece_type);
+            let square_index = table_index(square, color);
+            let param = index * 64 + square_index;
+            sink.add(
+                color,
+                Weight::new(PST_MG + param, w.pst_mg[index][square_index]),
+                Weight::new(PST_EG + param, w.pst_eg[index][square_index]),
+                1,
+            );
+        }
+    }
+
+    fn pawn_structure<S: EvalSink>(&self, pieces: &[(Square, Color, PieceType)], sink: &mut S) {
+        let w = &self.weights;
+
+        // Pawn ranks by color and file
+        let mut pawns: [[Vec<u8>; 8]; 2] = Default::default();
+        for &(square, color, piece_type) in pieces {
+            if piece_type == PieceType::Pawn {
+                pawns[color_index(color)][square.file() as usize].push(square.rank());
+            }
+        }
+
+        for color in [Color::White, Color::Black] {
+            let own = &pawns[color_index(color)];
+            let enemy = &pawns[color_index(color.opposite())];
+
+            for file in 0..8 {
+                let count = own[file].len() as i32;
+                if count > 1 {
+                    sink.add(
+                        color,
+                        Weight::new(DOUBLED_PAWN, w.doubled_pawn[0]),
+                        Weight::new(DOUBLED_PAWN + 1, w.doubled_pawn[1]),
+                        count - 1,
+                    );
+                }
+
+                let neighbour_files = file.saturating_sub(1)..=(file + 1).min(7);
+                let isolated = neighbour_files
+                    .clone()
+                    .all(|neighbour| neighbour == file || own[neighbour].is_empty());
+                if isolated && count > 0 {
+                    sink.add(
+                        color,
+                        Weight::new(ISOLATED_PAWN, w.isolated_pawn[0]),
+                        Weight::new(ISOLATED_PAWN + 1, w.isolated_pawn[1]),
+                        count,
+                    );
+                }
+
+                for &rank in &own[file] {
+                    // No enemy pawn can stop or take it on its way
+                    let passed = neighbour_files.clone().all(|neighbour| {
+                        enemy[neighbour].iter().all(|&enemy_rank| match color {
+                            Color::White => enemy_rank <= rank,
+                            Color::Black => enemy_rank >= rank,
+                        })
+                    });
+                    if passed {
+                        let square = Square::new(rank, file as u8).expect("always valid");
+                        let relative = relative_rank(square, color) as usize;
+                        sink.add(
+                            color,
+                            Weight::new(PASSED_PAWN_MG + relative, w.passed_pawn_mg[relative]),
+                            Weight::new(PASSED_PAWN_EG + relative, w.passed_pawn_eg[relative]),
+                            1,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Mobility of every piece, returning the attacks each side makes around
+    /// the enemy king
+    fn mobility_and_king_attacks<S: EvalSink>(
+        &self,
+        board: &Board,
+        pieces: &[(Square, Color, PieceType)],
+        sink: &mut S,
+    ) -> [i32; 2] {
+        let w = &self.weights;
+        let mut attack_units = [0; 2];
+
+        let king_squares = [
+            board.find_king_square(Color::White),
+            board.find_king_square(Color::Black),
+        ];
+
+        for &(square, color, piece_type) in pieces {
+            let index = type_index(piece_type);
+            if !matches!(
+                piece_type,
+                PieceType::Knight | PieceType::Bishop | PieceType::Rook | PieceType::Queen
+            ) {
+                continue;
+            }
+
+            let enemy_king = king_squares[color_index(color.opposite())];
+            let near_enemy_king = |target: Square| {
+                enemy_king
+                    .map(|king| {
+                        king.rank().abs_diff(target.rank()) <= 1
+                            && king.file().abs_diff(target.file()) <= 1
+                    })
+                    .unwrap_or(false)
+            };
+
+            let sliding = piece_type != PieceType::Knight;
+            let mut moves = 0;
+            for &(rank_offset, file_offset) in piece_type.get_offsets() {
+                let max_distance = if sliding { 7 } else { 1 };
+                for distance in 1..=max_distance {
+                    let Ok(target) = square.offset(rank_offset * distance, file_offset * distance)
+                    else {
+                        break;
+                    };
+
+                    let occupant = board.get_piece(target);
+                    if occupant.map(|p| p.color != color).unwrap_or(true) {
+                        moves += 1;
+                    }
+                    if near_enemy_king(target) {
+                        attack_units[color_index(color)] += KING_ATTACK_UNITS[index];
+                    }
+                    if occupant.is_some() {
+                        break;
+                    }
+                }
+            }
+
+            sink.add(
+                color,
+                Weight::new(MOBILITY_MG + index, w.mobility_mg[index]),
+                Weight::new(MOBILITY_EG + index, w.mobility_eg[index]),
+                moves - MOBILITY_BASELINE[index],
+            );
+        }
+
+        attack_units
+    }
+
+    /// Pawn shield, open files and attacks around each king, mostly relevant in the middlegame
+    fn king_safety<S: EvalSink>(
+        &self,
+        board: &Board,
+        pieces: &[(Square, Color, PieceType)],
+        attack_units: [i32; 2],
+        sink: &mut S,
+    ) {
+        let w = &self.weights;
+
+        for color in [Color::White, Color::Black] {
+            let Some(king) = board.find_king_square(color) else {
+                continue;
+            };
+            let forward: i8 = match color {
+                Color::White => 1,
+                Color::Black => -1,
+            };
+
+            for file_offset in -1..=1 {
+                let Ok(file_square) = king.offset(0, file_offset) else {
+                    continue;
+                };
+                let file = file_square.file();
+
+                for distance in [1, 2] {
+                    if let Ok(shield) = king.offset(forward * distance, file_offset) {
+                        let is_own_pawn = board
+                            .get_piece(shield)
+                            .map(|p| p.color == color && p.piece_type == PieceType::Pawn)
+                            .unwrap_or(false);
+                        if is_own_pawn {
+                            let index = distance as usize - 1;
+                            sink.add_mg(
+                                color,
+                                Weight::new(PAWN_SHIELD + index, w.pawn_shield[index]),
+                                1,
+                            );
+                        }
+                    }
+                }
+
+                let pawn_on_file = |pawn_color: Color| {
+                    pieces.iter().any(|&(square, c, piece_type)| {
+                        c == pawn_color && piece_type == PieceType::Pawn && square.file() == file
+                    })
+                };
+                if !pawn_on_file(color) {
+                    // Half open when the enemy still has a pawn there
+                    let index = if pawn_on_file(color.opposite()) { 0 } else { 1 };
+                    sink.add_mg(
+                        color,
+                        Weight::new(KING_OPEN_FILE + index, w.king_open_file[index]),
+                        1,
+                    );
+                }
+            }
+
+            sink.add_mg(
+                color,
+                Weight::new(KING_ATTACK, w.king_attack),
+                -attack_units[color_index(color.opposite())],
+            );
+        }
+    }
+}
+
+impl Evaluator for ClassicalEvaluator {
+    fn evaluate(&self, board: &Board) -> i32 {
+        let score = self.explain(board).total();
+        match board.side_to_move() {
+            Color::White => score,
+            Color::Black => -score,
+        }
+    }
+
+    fn explain(&self, board: &Board) -> EvalBreakdown {
+        let pieces = Self::pieces(board);
+        let mut material = TermScores::default();
+        let mut piece_squares = TermScores::default();
+        let mut pawn_structure = TermScores::default();
+        let mut mobility = TermScores::default();
+        let mut king_safety = TermScores::default();
+        self.material(&pieces, &mut material);
+        self.piece_squares(&pieces, &mut piece_squares);
+        self.pawn_structure(&pieces, &mut pawn_structure);
+        let attack_units = self.mobility_and_king_attacks(board, &pieces, &mut mobility);
+        self.king_safety(board, &pieces, attack_units, &mut king_safety);
+
+        EvalBreakdown {
+            phase: Self::phase(&pieces),
+            terms: vec![
+                material.to_term("Material"),
+                piece_squares.to_term("Piece squares"),
+                pawn_structure.to_term("Pawn structure"),
+                king_safety.to_term("King safety"),
+                mobility.to_term("Mobility"),
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POSITIONS: [&str; 5] = [
+        "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1",
+        "r1bqk2r/pppp1ppp/2n2n2/2b1p3/2B1P3/3P1N2/PPP2PPP/RNBQK2R w KQkq - 1 5",
+        "r3k2r/pp1n1ppp/2p1b3/q7/3P4/2N1BN2/PP3PPP/R2QK2R w KQkq - 0 1",
+        "8/5k2/3p4/1p1Pp2p/pP2Pp1P/P4P1K/8/8 b - - 0 1",
+        "6k1/5pp1/7p/8/3Q4/8/1r3PPP/6K1 w - - 0 1",
+    ];
+
+    /// The same position with the colors swapped and the board turned over
+    fn mirrored(fen: &str) -> Board {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |text: &str| -> String {
+            text.chars()
+                .map(|c| {
+                    if c.is_ascii_uppercase() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        c.to_ascii_uppercase()
+                    }
+                })
+                .collect()
+        };
+        let ranks: Vec<&str> = fields[0].split('/').rev().collect();
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        Board::from_fen(&format!(
+            "{} {} {} - 0 1",
+            swap_case(&ranks.join("/")),
+            side,
+            swap_case(fields[2])
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn evaluation_is_symmetric() {
+        let evaluator = ClassicalEvaluator::new();
+        assert_eq!(evaluator.evaluate(&Board::new_starting_default()), 0);
+        for fen in POSITIONS {
+            let board = Board::from_fen(fen).unwrap();
+            assert_eq!(
+                evaluator.evaluate(&board),
+                evaluator.evaluate(&mirrored(fen)),
+                "{}",
+                fen
+            );
+        }
+    }
+
+    #[test]
+    fn breakdown_adds_up() {
+        let evaluator = ClassicalEvaluator::new();
+        for fen in POSITIONS {
+            let board = Board::from_fen(fen).unwrap();
+            let white_score = match board.side_to_move() {
+                Color::White => evaluator.evaluate(&board),
+                Color::Black => -evaluator.evaluate(&board),
+            };
+            let breakdown = evaluator.explain(&board);
+            assert_eq!(breakdown.total(), white_score);
+
+            // The trace gives the score up to the rounding of each term
+            let params: Vec<f64> = evaluator
+                .weights
+                .to_params()
+                .into_iter()
+                .map(f64::from)
+                .collect();
+            let traced = evaluator.trace(&board).evaluate(&params);
+            assert!(
+                (traced - white_score as f64).abs() <= breakdown.terms.len() as f64,
+                "{}: {} and {}",
+                fen,
+                traced,
+                white_score
+            );
+        }
+    }
+
+    #[test]
+    fn params_round_trip() {
+        let weights = EvalWeights::new();
+        let mut params = weights.to_params();
+        assert_eq!(params.len(), EVAL_PARAMS);
+        assert_eq!(EvalWeights::from_params(&params), Ok(weights.clone()));
+
+        // Every weight has its own place in the list
+        for (index, param) in params.iter_mut().enumerate() {
+            *param = index as i32;
+        }
+        let numbered = EvalWeights::from_params(&params).unwrap();
+        assert_ne!(numbered, weights);
+        assert_eq!(numbered.to_params(), params);
+
+        assert_eq!(
+            EvalWeights::from_params(&params[1..]),
+            Err("Wrong number of evaluation weights\n")
+        );
+    }
+}
@@ -1 +1,162 @@
-// FEN notation stuff -- later
+use crate::{Board, CastlingRights, Color, Piece, Square};
+
+/// FEN of the standard starting position
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+impl Board {
+    /// Read a position from Forsyth-Edwards Notation. The move counters are optional
+    pub fn from_fen(fen: &str) -> Result<Self, &'static str> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 {
+            return Err("FEN must have at least 4 fields\n");
+        }
+
+        let mut board = Board::new();
+
+        // Piece placement, from rank 8 down to rank 1
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err("FEN piece placement must have 8 ranks\n");
+        }
+        for (index, rank_text) in ranks.iter().enumerate() {
+            let rank = 7 - index as u8;
+            let mut file = 0u8;
+            for symbol in rank_text.chars() {
+                if let Some(empty) = symbol.to_digit(10) {
+                    file += empty as u8;
+                } else {
+                    let piece = Piece::from_symbol(symbol).ok_or("Unknown piece in FEN\n")?;
+                    board.set_piece(Square::new(rank, file)?, Some(piece));
+                    file += 1;
+                }
+
+                if file > 8 {
+                    return Err("FEN rank has more than 8 squares\n");
+                }
+            }
+            if file != 8 {
+                return Err("FEN rank has less than 8 squares\n");
+            }
+        }
+
+        board.set_side_to_move(match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => return Err("FEN side to move must be w or b\n"),
+        });
+
+        let castling = fields[2];
+        if castling != "-" && !castling.chars().all(|c| "KQkq".contains(c)) {
+            return Err("Invalid FEN castling rights\n");
+        }
+        board.set_castling_rights(CastlingRights {
+            white_king_side: castling.contains('K'),
+            white_queen_side: castling.contains('Q'),
+            black_king_side: castling.contains('k'),
+            black_queen_side: castling.contains('q'),
+        });
+
+        // FEN names the square behind the pawn, the board keeps the pawn itself
+        if fields[3] != "-" {
+            let target = Square::new_from_notation(fields[3])?;
+            let (pawn_offset, pawn_color) = match board.side_to_move() {
+                Color::White => (-1, Color::Black),
+                Color::Black => (1, Color::White),
+            };
+            let pawn_square = target.offset(pawn_offset, 0)?;
+            if board
+                .get_piece(pawn_square)
+                .map(|p| p.color == pawn_color)
+                .unwrap_or(false)
+            {
+                board.set_en_passant(Some(pawn_square));
+            }
+        }
+
+        if let Some(halfmove_clock) = fields.get(4) {
+            board.set_halfmove_clock(
+                halfmove_clock
+                    .parse()
+                    .map_err(|_| "Invalid FEN halfmove clock\n")?,
+            );
+        }
+        if let Some(fullmove_number) = fields.get(5) {
+            board.set_fullmove_number(
+                fullmove_number
+                    .parse()
+                    .map_err(|_| "Invalid FEN fullmove number\n")?,
+            );
+        }
+
+        Ok(board)
+    }
+
+    /// Write the position in Forsyth-Edwards Notation
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                let square = Square::new(rank, file).expect("always valid");
+                match self.get_piece(square) {
+                    Some(piece) => {
+                        if empty > 0 {
+                            fen.push_str(&empty.to_string());
+                            empty = 0;
+                        }
+                        fen.push(piece.get_piece_char());
+                    }
+                    None => empty += 1,
+                }
+            }
+            if empty > 0 {
+                fen.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                fen.push('/');
+            }
+        }
+
+        fen.push(' ');
+        fen.push(match self.side_to_move() {
+            Color::White => 'w',
+            Color::Black => 'b',
+        });
+
+        let rights = self.castling_rights();
+        let castling: String = [
+            (rights.white_king_side, 'K'),
+            (rights.white_queen_side, 'Q'),
+            (rights.black_king_side, 'k'),
+            (rights.black_queen_side, 'q'),
+        ]
+        .iter()
+        .filter(|(allowed, _)| *allowed)
+        .map(|(_, symbol)| symbol)
+        .collect();
+        fen.push(' ');
+        fen.push_str(if castling.is_empty() { "-" } else { &castling });
+
+        let en_passant_target = self.en_passant().and_then(|pawn_square| {
+            let behind = match self.get_piece(pawn_square)?.color {
+                Color::White => -1,
+                Color::Black => 1,
+            };
+            pawn_square.offset(behind, 0).ok()
+        });
+        fen.push(' ');
+        match en_passant_target {
+            Some(target) => fen.push_str(&target.to_notation()),
+            None => fen.push('-'),
+        }
+
+        fen.push_str(&format!(
+            " {} {}",
+            self.halfmove_clock(),
+            self.fullmove_number()
+        ));
+
+        fen
+    }
+}
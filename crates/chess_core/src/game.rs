@@ -1,4 +1,4 @@
-use crate::{Board, Color, Piece, Square};
+use crate::{Board, Color, GameEval, Move, PgnGame, Piece, Square};
 
 pub enum MoveType {
     Move,
@@ -13,6 +13,11 @@ pub struct Game {
     selected_square: Option<Square>,
     last_move: Option<(Square, Square)>,
     move_history: Vec<String>,
+    moves: Vec<Move>,
+    san_history: Vec<String>,
+    positions: Vec<Board>,
+    evaluations: Vec<Option<GameEval>>,
+    viewed_ply: Option<usize>,
 }
 
 impl Game {
@@ -23,9 +28,38 @@ impl Game {
             selected_square: None,
             last_move: None,
             move_history: Vec::new(),
+            moves: Vec::new(),
+            san_history: Vec::new(),
+            positions: Vec::new(),
+            evaluations: Vec::new(),
+            viewed_ply: None,
         }
     }
 
+    /// Start a game from a set up position, like one read from a FEN
+    pub fn from_board(board: Board) -> Self {
+        Self {
+            active_player: board.side_to_move(),
+            board,
+            ..Self::new()
+        }
+    }
+
+    /// Replay a game read from PGN, keeping the evaluations of its `[%eval]` comments
+    pub fn from_pgn(pgn: &PgnGame) -> Self {
+        let mut game = Self::from_board(pgn.start.clone());
+        for mv in &pgn.moves {
+            game.execute_move(mv.from, mv.to);
+            if let Some(piece_type) = mv.promotion {
+                game.promote_square(mv.to, piece_type);
+            }
+        }
+        for (index, evaluation) in pgn.evaluations.iter().enumerate() {
+            game.set_evaluation(index, *evaluation);
+        }
+        game
+    }
+
     /// Get the board reference
     pub fn board(&self) -> &Board {
         &self.board
@@ -51,6 +85,61 @@ impl Game {
         &self.move_history
     }
 
+    /// Get the moves played so far
+    pub fn moves(&self) -> &Vec<Move> {
+        &self.moves
+    }
+
+    /// Get the moves played so far in Standard Algebraic Notation
+    pub fn san_history(&self) -> &Vec<String> {
+        &self.san_history
+    }
+
+    /// Get the position before each move played, so `positions()[i]` is the board `moves()[i]` was made on
+    pub fn positions(&self) -> &Vec<Board> {
+        &self.positions
+    }
+
+    /// Get the evaluation of the position after each move, when known
+    pub fn evaluations(&self) -> &Vec<Option<GameEval>> {
+        &self.evaluations
+    }
+
+    /// Store the evaluation of the position after move `index`, from a review or a PGN `[%eval]` comment
+    pub fn set_evaluation(&mut self, index: usize, evaluation: Option<GameEval>) {
+        if let Some(slot) = self.evaluations.get_mut(index) {
+            *slot = evaluation;
+        }
+    }
+
+    /// Get the earlier position being looked at, as the number of moves played before it
+    pub fn viewed_ply(&self) -> Option<usize> {
+        self.viewed_ply
+    }
+
+    /// Look at the position after `ply` moves instead of the current one, `None` to go back to it
+    pub fn set_viewed_ply(&mut self, ply: Option<usize>) {
+        self.viewed_ply = ply.filter(|&ply| ply < self.positions.len());
+    }
+
+    /// Get the position to show: the one being looked at, or the current one
+    pub fn displayed_board(&self) -> &Board {
+        match self.viewed_ply {
+            Some(ply) => &self.positions[ply],
+            None => &self.board,
+        }
+    }
+
+    /// Get the move that led to the displayed position
+    pub fn displayed_last_move(&self) -> Option<(Square, Square)> {
+        match self.viewed_ply {
+            Some(ply) => ply
+                .checked_sub(1)
+                .map(|index| (self.moves[index].from, self.moves[index].to)),
+            None => self.last_move,
+        }
+    }
+
     /// Get valid moves for the currently selected piece
     pub fn get_valid_moves(&self, square: Square) -> Vec<Square> {
         self.board.get_valid_moves(square)
@@ -68,6 +157,14 @@ impl Game {
             // Check if it's a capture before making the move
             let is_capture = self.board.get_piece(to).is_some();
 
+            // Record the position and move, promotion is filled in later by promote_square
+            let mv = Move::new(from, to);
+            self.viewed_ply = None;
+            self.san_history.push(self.board.move_to_san(mv));
+            self.positions.push(self.board.clone());
+            self.moves.push(mv);
+            self.evaluations.push(None);
+
             self.board.make_move(from, to);
 
             // Check for check and checkmate after the move
@@ -135,6 +232,11 @@ impl Game {
         self.selected_square = None;
         self.last_move = None;
         self.move_history.clear();
+        self.moves.clear();
+        self.san_history.clear();
+        self.positions.clear();
+        self.evaluations.clear();
+        self.viewed_ply = None;
     }
 
     /// Get the square that needs promotion, if any
@@ -145,5 +247,15 @@ impl Game {
     /// Promote a pawn at the given square to a new piece type
     pub fn promote_square(&mut self, square: Square, piece_type: crate::PieceType) {
         self.board.promote_square(square, piece_type);
+
+        // Complete the promotion of the last recorded move
+        if let (Some(last), Some(position)) = (self.moves.last_mut(), self.positions.last()) {
+            if last.to == square && last.promotion.is_none() {
+                last.promotion = Some(piece_type);
+                if let Some(san) = self.san_history.last_mut() {
+                    *san = position.move_to_san(*last);
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,87 @@
+// Evaluations stored with the moves of a game record
+//
+// They use the `[%eval]` command of PGN comments: pawns from White's view like
+// `[%eval 0.35]` or `[%eval -1.20]`, or a mate like `[%eval #3]` when White
+// mates and `[%eval #-3]` when Black does. A checkmate on the board is
+// `[%eval #0]` when White gave it and `[%eval #-0]` when Black did. A search
+// depth may follow after a comma, as in `[%eval 0.35,22]`, and is ignored.
+
+use crate::Color;
+use std::fmt;
+
+/// Evaluation of a position from White's view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEval {
+    Centipawns(i32),
+    /// Moves until mate, negative when Black mates
+    Mate(i32),
+    /// Checkmate on the board, given by this side
+    Checkmate(Color),
+}
+
+impl GameEval {
+    /// Read the value of an `[%eval]` command, like `0.35` or `#-3`
+    pub fn parse(value: &str) -> Result<Self, &'static str> {
+        let value = value.split(',').next().unwrap_or("").trim();
+        if let Some(moves) = value.strip_prefix('#') {
+            let moves: i32 = moves.parse().map_err(|_| "Invalid mate in evaluation\n")?;
+            return Ok(match moves {
+                0 if value.starts_with("#-") => GameEval::Checkmate(Color::Black),
+                0 => GameEval::Checkmate(Color::White),
+                moves => GameEval::Mate(moves),
+            });
+        }
+        let pawns: f64 = value.parse().map_err(|_| "Invalid evaluation\n")?;
+        if !pawns.is_finite() {
+            return Err("Invalid evaluation\n");
+        }
+        Ok(GameEval::Centipawns((pawns * 100.0).round() as i32))
+    }
+
+    /// Find the `[%eval]` command in the text of a PGN comment
+    pub fn from_comment(comment: &str) -> Option<Self> {
+        let start = comment.find("[%eval")? + "[%eval".len();
+        let length = comment[start..].find(']')?;
+        Self::parse(&comment[start..start + length]).ok()
+    }
+
+    /// The evaluation as an `[%eval]` command for a PGN comment
+    pub fn to_comment(self) -> String {
+        match self {
+            GameEval::Centipawns(centipawns) => {
+                format!("[%eval {:.2}]", centipawns as f64 / 100.0)
+            }
+            GameEval::Mate(moves) => format!("[%eval #{}]", moves),
+            GameEval::Checkmate(color) => format!("[%eval {}]", checkmate_text(color)),
+        }
+    }
+
+    /// Centipawns clamped to `-limit..=limit`, mates counting as the limit
+    pub fn clamped_centipawns(self, limit: i32) -> i32 {
+        match self {
+            GameEval::Centipawns(centipawns) => centipawns.clamp(-limit, limit),
+            GameEval::Mate(moves) if moves < 0 => -limit,
+            GameEval::Mate(_) | GameEval::Checkmate(Color::White) => limit,
+            GameEval::Checkmate(Color::Black) => -limit,
+        }
+    }
+}
+
+impl fmt::Display for GameEval {
+    /// Pawns like `+0.35`, or mates like `#3` and `#-3`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameEval::Centipawns(centipawns) => write!(f, "{:+.2}", *centipawns as f64 / 100.0),
+            GameEval::Mate(moves) => write!(f, "#{}", moves),
+            GameEval::Checkmate(color) => write!(f, "{}", checkmate_text(*color)),
+        }
+    }
+}
+
+/// `#0` for a checkmate given by White, `#-0` for one given by Black
+fn checkmate_text(color: Color) -> &'static str {
+    match color {
+        Color::White => "#0",
+        Color::Black => "#-0",
+    }
+}
@@ -1,10 +1,42 @@
+mod attacks;
 mod board;
+mod chess_move;
+mod diagram;
+mod eval;
 mod fen;
 mod game;
+mod game_eval;
+mod nnue;
+mod notation;
+mod pgn;
 mod piece;
+mod puzzle;
 mod square;
+mod syzygy;
+mod tablegen;
+mod tuner;
 
-pub use board::Board;
+pub use attacks::{Pin, SEE_VALUES};
+pub use board::{Board, CastlingRights};
+pub use chess_move::Move;
+pub use eval::{
+    type_index, ClassicalEvaluator, EvalBreakdown, EvalTerm, EvalTrace, EvalWeights, Evaluator,
+    IncrementalEval, EVAL_PARAMS, MAX_PHASE,
+};
+pub use fen::STARTING_FEN;
 pub use game::*;
+pub use game_eval::GameEval;
+pub use nnue::{Accumulator, NnueNetwork};
+pub use notation::piece_letter;
+pub use pgn::{read_pgn, PgnGame};
 pub use piece::{Color, Piece, PieceType};
+pub use puzzle::{
+    Puzzle, PuzzleAnswer, PuzzleAttempt, PuzzleDatabase, PuzzleHistory, INITIAL_PUZZLE_RATING,
+};
 pub use square::Square;
+pub use syzygy::{SyzygyTablebase, TablebaseMove, Wdl};
+pub use tablegen::{Dtm, EndgameTables, TableSummary, MAX_TABLE_PIECES};
+pub use tuner::{
+    load_positions, positions_from_epd, positions_from_pgn, quiet_position, LabelledPosition,
+    Tuner, DEFAULT_SKIP_PLIES,
+};
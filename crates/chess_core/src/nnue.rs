@@ -0,0 +1,468 @@
+// Efficiently updatable neural network evaluation
+//
+// The network has one hidden layer seen from both sides. Its inputs are the
+// 768 piece-square features of a side: own or enemy piece, piece type and
+// square, with the board mirrored for Black. The hidden layer, the
+// accumulator, is the sum of the weights of the active features, so a move
+// only adds and subtracts the few features it changes instead of summing all
+// pieces again.
+//
+// Weights file format, all numbers little endian:
+//
+//   magic            4 bytes  "SCNN"
+//   version          u32      1
+//   hidden size      u32      H, from 1 to 4096
+//   feature weights  i16      768 * H, the H weights of feature 0 first
+//   feature biases   i16      H
+//   output weights   i16      2 * H, the side to move's half first
+//   output bias      i32
+//
+// A feature index is `(relation * 6 + piece type) * 64 + square`: relation 0
+// for the perspective's own pieces and 1 for the other side's, piece types
+// from pawn to king, and squares from a1 to h8 rank by rank, mirrored
+// vertically for Black. The evaluation in centipawns is
+//
+//   (sum of clamp(hidden, 0, 255) * output weight + output bias) * 400 / (255 * 64)
+
+use crate::eval::type_index;
+use crate::{Board, Color, Evaluator, IncrementalEval, Move, PieceType, Square};
+use std::path::Path;
+
+const MAGIC: &[u8; 4] = b"SCNN";
+const VERSION: u32 = 1;
+/// Inputs seen from one side
+pub const FEATURES: usize = 768;
+const MAX_HIDDEN: usize = 4096;
+
+/// Hidden values are clipped to `0..=QA`
+const QA: i32 = 255;
+/// Scale of the output weights
+const QB: i32 = 64;
+/// Centipawns of an output of 1.0
+const SCALE: i32 = 400;
+/// Keeps network scores clear of mate scores
+const MAX_SCORE: i32 = 20_000;
+
+/// Weights of a network, usable as an evaluator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NnueNetwork {
+    hidden: usize,
+    feature_weights: Vec<i16>,
+    feature_biases: Vec<i16>,
+    output_weights: Vec<i16>,
+    output_bias: i32,
+}
+
+/// Hidden layer of a position from both sides, White's half first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Accumulator {
+    values: Vec<i16>,
+}
+
+/// Feature of a piece on a square seen from `perspective`
+fn feature(perspective: Color, color: Color, piece_type: PieceType, square: Square) -> usize {
+    let relation = usize::from(color != perspective);
+    let rank = match perspective {
+        Color::White => square.rank(),
+        Color::Black => 7 - square.rank(),
+    };
+    (relation * 6 + type_index(piece_type)) * 64 + rank as usize * 8 + square.file() as usize
+}
+
+/// Squares a move can change: its own two, the rook of a castling and the
+/// pawn taken en passant
+fn changed_squares(before: &Board, mv: Move) -> [Option<Square>; 4] {
+    let rank = mv.from.rank();
+    let mut squares = [Some(mv.from), Some(mv.to), None, None];
+    match before.get_piece(mv.from).map(|piece| piece.piece_type) {
+        Some(PieceType::King) if mv.from.file().abs_diff(mv.to.file()) == 2 => {
+            let (rook_from, rook_to) = if mv.to.file() > mv.from.file() {
+                (7, 5)
+            } else {
+                (0, 3)
+            };
+            squares[2] = Square::new(rank, rook_from).ok();
+            squares[3] = Square::new(rank, rook_to).ok();
+        }
+        Some(PieceType::Pawn)
+            if mv.from.file() != mv.to.file() && before.get_piece(mv.to).is_none() =>
+        {
+            squares[2] = Square::new(rank, mv.to.file()).ok();
+        }
+        _ => {}
+    }
+    squares
+}
+
+/// Little endian reader over the bytes of a weights file
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    fn take(&mut self, count: usize) -> Result<&[u8], &'static str> {
+        if self.bytes.len() < count {
+            return Err("Network file is truncated\n");
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> Result<u32, &'static str> {
+        Ok(u32::from_le_bytes(
+            self.take(4)?.try_into().expect("4 bytes"),
+        ))
+    }
+
+    fn i32(&mut self) -> Result<i32, &'static str> {
+        Ok(i32::from_le_bytes(
+            self.take(4)?.try_into().expect("4 bytes"),
+        ))
+    }
+
+    fn i16s(&mut self, count: usize) -> Result<Vec<i16>, &'static str> {
+        Ok(self
+            .take(count * 2)?
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect())
+    }
+}
+
+impl NnueNetwork {
+    /// Whether bytes start like a network weights file
+    pub fn is_network(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    /// Read a network in the weights file format
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, &'static str> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            return Err("Not a network file\n");
+        }
+        if reader.u32()? != VERSION {
+            return Err("Unsupported network file version\n");
+        }
+        let hidden = reader.u32()? as usize;
+        if hidden == 0 || hidden > MAX_HIDDEN {
+            return Err("Invalid hidden layer size\n");
+        }
+
+        let network = Self {
+            hidden,
+            feature_weights: reader.i16s(FEATURES * hidden)?,
+            feature_biases: reader.i16s(hidden)?,
+            output_weights: reader.i16s(2 * hidden)?,
+            output_bias: reader.i32()?,
+        };
+        if !reader.bytes.is_empty() {
+            return Err("Network file has trailing data\n");
+        }
+        Ok(network)
+    }
+
+    /// Read a weights file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let bytes = std::fs::read(path).map_err(|_| "Failed to read network file\n")?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// The network in the weights file format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(16 + 2 * (FEATURES + 3) * self.hidden);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(self.hidden as u32).to_le_bytes());
+        for weights in [
+            &self.feature_weights,
+            &self.feature_biases,
+            &self.output_weights,
+        ] {
+            for weight in weights {
+                bytes.extend_from_slice(&weight.to_le_bytes());
+            }
+        }
+        bytes.extend_from_slice(&self.output_bias.to_le_bytes());
+        bytes
+    }
+
+    /// Network with small random weights, for experiments and checks
+    pub fn random(hidden: usize, seed: u64) -> Self {
+        let hidden = hidden.clamp(1, MAX_HIDDEN);
+        let mut state = seed.max(1);
+        let mut next = |range: i16| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % (2 * range as u64 + 1)) as i16 - range
+        };
+        Self {
+            hidden,
+            feature_weights: (0..FEATURES * hidden).map(|_| next(64)).collect(),
+            feature_biases: (0..hidden).map(|_| next(64)).collect(),
+            output_weights: (0..2 * hidden).map(|_| next(64)).collect(),
+            output_bias: next(1000) as i32,
+        }
+    }
+
+    /// Size of the hidden layer
+    pub fn hidden_size(&self) -> usize {
+        self.hidden
+    }
+
+    fn add_feature(&self, values: &mut [i16], feature: usize) {
+        let weights = &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden];
+        for (value, &weight) in values.iter_mut().zip(weights) {
+            *value = value.wrapping_add(weight);
+        }
+    }
+
+    fn remove_feature(&self, values: &mut [i16], feature: usize) {
+        let weights = &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden];
+        for (value, &weight) in values.iter_mut().zip(weights) {
+            *value = value.wrapping_sub(weight);
+        }
+    }
+
+    /// Accumulator of a position computed from all its pieces
+    pub fn refresh(&self, board: &Board) -> Accumulator {
+        let mut accumulator = Accumulator {
+            values: vec![0; 2 * self.hidden],
+        };
+        let (white, black) = accumulator.values.split_at_mut(self.hidden);
+        white.copy_from_slice(&self.feature_biases);
+        black.copy_from_slice(&self.feature_biases);
+        for rank in 0..8 {
+            for file in 0..8 {
+                let square = Square::new(rank, file).expect("always valid");
+                if let Some(piece) = board.get_piece(square) {
+                    self.add_feature(
+                        white,
+                        feature(Color::White, piece.color, piece.piece_type, square),
+                    );
+                    self.add_feature(
+                        black,
+                        feature(Color::Black, piece.color, piece.piece_type, square),
+                    );
+                }
+            }
+        }
+        accumulator
+    }
+
+    /// Update an accumulator of `before` to `after`, the position after `mv`
+    pub fn update(&self, accumulator: &mut Accumulator, before: &Board, mv: Move, after: &Board) {
+        let (white, black) = accumulator.values.split_at_mut(self.hidden);
+        for square in changed_squares(before, mv).into_iter().flatten() {
+            let old = before.get_piece(square);
+            let new = after.get_piece(square);
+            if old == new {
+                continue;
+            }
+            if let Some(piece) = old {
+                self.remove_feature(
+                    white,
+                    feature(Color::White, piece.color, piece.piece_type, square),
+                );
+                self.remove_feature(
+                    black,
+                    feature(Color::Black, piece.color, piece.piece_type, square),
+                );
+            }
+            if let Some(piece) = new {
+                self.add_feature(
+                    white,
+                    feature(Color::White, piece.color, piece.piece_type, square),
+                );
+                self.add_feature(
+                    black,
+                    feature(Color::Black, piece.color, piece.piece_type, square),
+                );
+            }
+        }
+    }
+
+    /// Score in centipawns from the side to move's view
+    pub fn output(&self, accumulator: &Accumulator, side_to_move: Color) -> i32 {
+        let (white, black) = accumulator.values.split_at(self.hidden);
+        let (us, them) = match side_to_move {
+            Color::White => (white, black),
+            Color::Black => (black, white),
+        };
+        let (our_weights, their_weights) = self.output_weights.split_at(self.hidden);
+
+        // Products fit in i32, sums of many of them may not
+        let mut sum = self.output_bias as i64;
+        for (values, weights) in [(us, our_weights), (them, their_weights)] {
+            sum += values
+                .iter()
+                .zip(weights)
+                .map(|(&value, &weight)| (value as i32).clamp(0, QA) * weight as i32)
+                .map(i64::from)
+                .sum::<i64>();
+        }
+        (sum * SCALE as i64 / (QA * QB) as i64).clamp(-MAX_SCORE as i64, MAX_SCORE as i64) as i32
+    }
+}
+
+impl Evaluator for NnueNetwork {
+    fn evaluate(&self, board: &Board) -> i32 {
+        self.output(&self.refresh(board), board.side_to_move())
+    }
+
+    fn incremental(&self, board: &Board) -> Option<Box<dyn IncrementalEval + '_>> {
+        Some(Box::new(NnueState {
+            network: self,
+            stack: vec![self.refresh(board)],
+            depth: 0,
+        }))
+    }
+}
+
+/// Accumulators along the line a search is looking at
+struct NnueState<'a> {
+    network: &'a NnueNetwork,
+    /// Reused between lines, entries past `depth` are stale
+    stack: Vec<Accumulator>,
+    depth: usize,
+}
+
+impl IncrementalEval for NnueState<'_> {
+    fn make_move(&mut self, before: &Board, mv: Move, after: &Board) {
+        if self.depth + 1 == self.stack.len() {
+            self.stack.push(self.stack[self.depth].clone());
+        } else {
+            let (done, rest) = self.stack.split_at_mut(self.depth + 1);
+            rest[0].values.copy_from_slice(&done[self.depth].values);
+        }
+        self.depth += 1;
+        self.network
+            .update(&mut self.stack[self.depth], before, mv, after);
+    }
+
+    fn unmake_move(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        self.network
+            .output(&self.stack[self.depth], board.side_to_move())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incremental_updates_match_refresh() {
+        let network = NnueNetwork::random(16, 7);
+        let lines: [(&str, &[&str]); 4] = [
+            // Captures and castling on both sides
+            (
+                "r3k2r/ppp2ppp/2nqbn2/3pp3/3PP3/2NQBN2/PPP2PPP/R3K2R w KQkq - 0 1",
+                &[
+                    "e1g1", "e8c8", "e4d5", "e6d5", "c3d5", "f6d5", "d4e5", "c6e5",
+                ],
+            ),
+            // En passant for both colors
+            (
+                "4k3/2p5/8/3P4/5p2/8/4P3/4K3 b - - 0 1",
+                &["c7c5", "d5c6", "e8d8", "e2e4", "f4e3", "c6c7"],
+            ),
+            // Promotions, with and without a capture
+            (
+                "1n6/P3k3/8/8/8/8/6p1/4K2R w - - 0 1",
+                &["a7b8q", "g2h1n", "b8a8", "h1g3"],
+            ),
+            ("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", &["a7a8r", "e8d7"]),
+        ];
+
+        for (fen, moves) in lines {
+            let start = Board::from_fen(fen).unwrap();
+            let mut state = NnueState {
+                network: &network,
+                stack: vec![network.refresh(&start)],
+                depth: 0,
+            };
+            let mut board = start.clone();
+            for text in moves {
+                let mv = board.parse_uci_move(text).unwrap();
+                let before = board.clone();
+                board.apply_move(mv);
+                state.make_move(&before, mv, &board);
+
+                let refreshed = network.refresh(&board);
+                assert_eq!(state.stack[state.depth], refreshed, "{} {}", fen, text);
+                assert_eq!(
+                    state.evaluate(&board),
+                    network.output(&refreshed, board.side_to_move())
+                );
+            }
+
+            for _ in moves {
+                state.unmake_move();
+            }
+            assert_eq!(state.evaluate(&start), network.evaluate(&start));
+        }
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let network = NnueNetwork::random(8, 3);
+        let bytes = network.to_bytes();
+        assert!(NnueNetwork::is_network(&bytes));
+        assert_eq!(NnueNetwork::from_bytes(&bytes), Ok(network));
+
+        assert_eq!(
+            NnueNetwork::from_bytes(&bytes[..bytes.len() - 1]),
+            Err("Network file is truncated\n")
+        );
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(
+            NnueNetwork::from_bytes(&trailing),
+            Err("Network file has trailing data\n")
+        );
+    }
+
+    /// Two hidden values counting the perspective's own and the other
+    /// side's pawns, 100 each
+    fn pawn_counting_network() -> Vec<u8> {
+        let hidden = 2;
+        let mut feature_weights = vec![0i16; FEATURES * hidden];
+        for square in 0..64 {
+            feature_weights[square * hidden] = 100;
+            feature_weights[(6 * 64 + square) * hidden + 1] = 100;
+        }
+        let mut bytes = b"SCNN".to_vec();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&(hidden as u32).to_le_bytes());
+        let biases = [0i16; 2];
+        let output_weights = [64i16, -64, -32, 32];
+        for weight in feature_weights.iter().chain(&biases).chain(&output_weights) {
+            bytes.extend_from_slice(&weight.to_le_bytes());
+        }
+        bytes.extend_from_slice(&640i32.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn reference_evaluations() {
+        let network = NnueNetwork::from_bytes(&pawn_counting_network()).unwrap();
+        let evaluate = |fen: &str| network.evaluate(&Board::from_fen(fen).unwrap());
+
+        // White sees (200, 100) and Black (100, 200):
+        // (640 + 200 * 64 - 100 * 64 - 100 * 32 + 200 * 32) * 400 / (255 * 64)
+        assert_eq!(evaluate("4k3/p7/8/8/8/8/PP6/4K3 w - - 0 1"), 250);
+        // (640 + 100 * 64 - 200 * 64 - 200 * 32 + 100 * 32) * 400 / (255 * 64)
+        assert_eq!(evaluate("4k3/p7/8/8/8/8/PP6/4K3 b - - 0 1"), -219);
+        // Three pawns clip to 255:
+        // (640 + 255 * 64 - 100 * 64 - 100 * 32 + 255 * 32) * 400 / (255 * 64)
+        assert_eq!(evaluate("4k3/p7/8/8/8/8/PPP5/4K3 w - - 0 1"), 380);
+        // Pieces other than pawns weigh nothing here
+        assert_eq!(evaluate("r3k3/p7/8/8/8/8/PP6/4K2R w - - 0 1"), 250);
+    }
+}
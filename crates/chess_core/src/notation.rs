@@ -0,0 +1,257 @@
+use crate::{Board, Move, PieceType, Square};
+
+impl Move {
+    /// Long algebraic notation used by UCI, e.g. `e2e4`, `e7e8q`
+    pub fn to_uci(&self) -> String {
+        let mut text = format!("{}{}", self.from.to_notation(), self.to.to_notation());
+        if let Some(promotion) = self.promotion {
+            text.push(piece_letter(promotion).to_ascii_lowercase());
+        }
+        text
+    }
+}
+
+impl Board {
+    /// Read a move in UCI long algebraic notation, checking it is legal for the side to move
+    pub fn parse_uci_move(&self, text: &str) -> Result<Move, &'static str> {
+        if !text.is_ascii() || (text.len() != 4 && text.len() != 5) {
+            return Err("UCI moves look like e2e4 or e7e8q\n");
+        }
+
+        let from = Square::new_from_notation(&text[0..2])?;
+        let to = Square::new_from_notation(&text[2..4])?;
+        let promotion = match text[4..].chars().next() {
+            None => None,
+            Some('q') => Some(PieceType::Queen),
+            Some('r') => Some(PieceType::Rook),
+            Some('b') => Some(PieceType::Bishop),
+            Some('n') => Some(PieceType::Knight),
+            Some(_) => return Err("Unknown promotion piece\n"),
+        };
+
+        let mv = Move {
+            from,
+            to,
+            promotion,
+        };
+        if self.get_all_valid_moves(self.side_to_move()).contains(&mv) {
+            Ok(mv)
+        } else {
+            Err("Illegal move\n")
+        }
+    }
+
+    /// Read a move in Standard Algebraic Notation, e.g. `Nbd7`, `exd6`, `O-O`, `e8=Q+`.
+    /// Check marks and annotations are optional, `0-0` and `e8Q` are accepted too
+    pub fn parse_san(&self, text: &str) -> Result<Move, &'static str> {
+        let san = text
+            .trim()
+            .trim_end_matches(['+', '#', '!', '?'])
+            .trim_end_matches("e.p.")
+            .trim_end();
+        if !san.is_ascii() || san.len() < 2 {
+            return Err("SAN moves look like e4, Nf3 or O-O\n");
+        }
+
+        let color = self.side_to_move();
+        let legal_moves = self.get_all_valid_moves(color);
+
+        // Castling is a king move of two files
+        let castle_file = match san {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None,
+        };
+        if let Some(file) = castle_file {
+            let king = self.find_king_square(color).ok_or("Illegal move\n")?;
+            return legal_moves
+                .into_iter()
+                .find(|mv| {
+                    mv.from == king
+                        && king.file() == 4
+                        && mv.to.rank() == king.rank()
+                        && mv.to.file() == file
+                })
+                .ok_or("Illegal move\n");
+        }
+
+        let mut chars: Vec<char> = san.chars().collect();
+
+        let piece_type = match chars[0] {
+            'K' => PieceType::King,
+            'Q' => PieceType::Queen,
+            'R' => PieceType::Rook,
+            'B' => PieceType::Bishop,
+            'N' => PieceType::Knight,
+            _ => PieceType::Pawn,
+        };
+        if piece_type != PieceType::Pawn {
+            chars.remove(0);
+        }
+
+        // Promotion, with or without `=`
+        let mut promotion = None;
+        if piece_type == PieceType::Pawn {
+            if let Some(&last) = chars.last() {
+                promotion = match last.to_ascii_uppercase() {
+                    'Q' => Some(PieceType::Queen),
+                    'R' => Some(PieceType::Rook),
+                    'B' => Some(PieceType::Bishop),
+                    'N' => Some(PieceType::Knight),
+                    _ => None,
+                };
+                if promotion.is_some() {
+                    chars.pop();
+                    if chars.last() == Some(&'=') {
+                        chars.pop();
+                    }
+                }
+            }
+        }
+
+        if chars.len() < 2 {
+            return Err("SAN moves look like e4, Nf3 or O-O\n");
+        }
+        let destination: String = chars.split_off(chars.len() - 2).into_iter().collect();
+        let to = Square::new_from_notation(&destination)?;
+
+        // What is left tells the origin apart: a file, a rank or both, and `x` for captures
+        let mut from_file = None;
+        let mut from_rank = None;
+        for c in chars {
+            match c {
+                'a'..='h' => from_file = Some(c as u8 - b'a'),
+                '1'..='8' => from_rank = Some(c as u8 - b'1'),
+                'x' | ':' | '-' => {}
+                _ => return Err("SAN moves look like e4, Nf3 or O-O\n"),
+            }
+        }
+
+        let mut candidates = legal_moves.into_iter().filter(|mv| {
+            mv.to == to
+                && mv.promotion == promotion
+                && self.get_piece(mv.from).map(|piece| piece.piece_type) == Some(piece_type)
+                && from_file.is_none_or(|file| mv.from.file() == file)
+                && from_rank.is_none_or(|rank| mv.from.rank() == rank)
+        });
+
+        match (candidates.next(), candidates.next()) {
+            (Some(mv), None) => Ok(mv),
+            (Some(_), Some(_)) => Err("Ambiguous move\n"),
+            (None, _) => Err("Illegal move\n"),
+        }
+    }
+
+    /// Read a move in either UCI coordinate notation or SAN, as engines speaking
+    /// the XBoard protocol may send both
+    pub fn parse_move(&self, text: &str) -> Result<Move, &'static str> {
+        let bytes = text.trim().as_bytes();
+        let is_coordinate = (bytes.len() == 4 || bytes.len() == 5)
+            && (b'a'..=b'h').contains(&bytes[0])
+            && (b'1'..=b'8').contains(&bytes[1])
+            && (b'a'..=b'h').contains(&bytes[2])
+            && (b'1'..=b'8').contains(&bytes[3]);
+
+        if is_coordinate {
+            self.parse_uci_move(&text.trim().to_ascii_lowercase())
+        } else {
+            self.parse_san(text)
+        }
+    }
+
+    /// Standard Algebraic Notation of a move made from this position, e.g. `Nbd7`, `exd6`, `O-O`, `e8=Q+`
+    pub fn move_to_san(&self, mv: Move) -> String {
+        let piece = match self.get_piece(mv.from) {
+            Some(p) => p,
+            None => return String::new(),
+        };
+
+        let mut san = String::new();
+        let file_distance = (mv.to.file() as i8 - mv.from.file() as i8).abs();
+
+        if piece.piece_type == PieceType::King && file_distance == 2 {
+            san.push_str(if mv.to.file() == 6 { "O-O" } else { "O-O-O" });
+        } else if piece.piece_type == PieceType::Pawn {
+            // Pawns changing file always capture, en passant included
+            if file_distance != 0 {
+                san.push(file_char(mv.from.file()));
+                san.push('x');
+            }
+            san.push_str(&mv.to.to_notation());
+
+            if let Some(promotion) = mv.promotion {
+                san.push('=');
+                san.push(piece_letter(promotion));
+            }
+        } else {
+            san.push(piece_letter(piece.piece_type));
+            san.push_str(&self.disambiguation(mv));
+            if self.get_piece(mv.to).is_some() {
+                san.push('x');
+            }
+            san.push_str(&mv.to.to_notation());
+        }
+
+        // Check and checkmate
+        let mut new_board = self.clone();
+        new_board.apply_move(mv);
+        let opponent_color = piece.opposite_color();
+        if new_board.is_checkmated(opponent_color) {
+            san.push('#');
+        } else if new_board.is_king_in_check(opponent_color) {
+            san.push('+');
+        }
+
+        san
+    }
+
+    /// File and/or rank needed to tell a move apart from identical pieces reaching the same square
+    fn disambiguation(&self, mv: Move) -> String {
+        let piece = match self.get_piece(mv.from) {
+            Some(p) => p,
+            None => return String::new(),
+        };
+
+        let rivals: Vec<Move> = self
+            .get_all_valid_moves(piece.color)
+            .into_iter()
+            .filter(|other| {
+                other.to == mv.to
+                    && other.from != mv.from
+                    && self.get_piece(other.from) == Some(piece)
+            })
+            .collect();
+
+        if rivals.is_empty() {
+            String::new()
+        } else if rivals
+            .iter()
+            .all(|other| other.from.file() != mv.from.file())
+        {
+            file_char(mv.from.file()).to_string()
+        } else if rivals
+            .iter()
+            .all(|other| other.from.rank() != mv.from.rank())
+        {
+            (mv.from.rank() + 1).to_string()
+        } else {
+            mv.from.to_notation()
+        }
+    }
+}
+
+/// Upper case letter used for a piece in algebraic notation
+pub fn piece_letter(piece_type: PieceType) -> char {
+    match piece_type {
+        PieceType::King => 'K',
+        PieceType::Queen => 'Q',
+        PieceType::Rook => 'R',
+        PieceType::Bishop => 'B',
+        PieceType::Knight => 'N',
+        PieceType::Pawn => 'P',
+    }
+}
+
+fn file_char(file: u8) -> char {
+    (b'a' + file) as char
+}
@@ -0,0 +1,196 @@
+// Reading games from PGN text
+//
+// Only the main line is kept: comments in braces or after `;`, variations in
+// parentheses and numeric annotation glyphs are skipped, except for the
+// `[%eval]` command of a brace comment after a move. Move numbers may be
+// glued to the move, as in `1.e4`. A game ends with its result, or with the
+// tags of the next game when the result is missing.
+
+use crate::{Board, GameEval, Move};
+
+/// One game of a PGN file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PgnGame {
+    /// Tag pairs in file order, such as `("White", "Carlsen")`
+    pub tags: Vec<(String, String)>,
+    /// The position of the `FEN` tag, or the starting position
+    pub start: Board,
+    pub moves: Vec<Move>,
+    /// Evaluation of the position after each move, from its `[%eval]` comment
+    pub evaluations: Vec<Option<GameEval>>,
+    /// `1-0`, `0-1`, `1/2-1/2` or `*`
+    pub result: String,
+}
+
+impl Default for PgnGame {
+    fn default() -> Self {
+        Self {
+            tags: Vec::new(),
+            start: Board::new_starting_default(),
+            moves: Vec::new(),
+            evaluations: Vec::new(),
+            result: "*".to_string(),
+        }
+    }
+}
+
+impl PgnGame {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|(tag, _)| tag == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// White's points, `None` for unfinished games
+    pub fn white_score(&self) -> Option<f64> {
+        match self.result.as_str() {
+            "1-0" => Some(1.0),
+            "0-1" => Some(0.0),
+            "1/2-1/2" => Some(0.5),
+            _ => None,
+        }
+    }
+}
+
+/// A tag pair line such as `[Event "Casual game"]`
+fn parse_tag(line: &str) -> Option<(String, String)> {
+    let inner = line.strip_prefix('[')?.strip_suffix(']')?;
+    let (name, value) = inner.split_once(char::is_whitespace)?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some((name.to_string(), value.replace("\\\"", "\"")))
+}
+
+/// Read every game of PGN text
+pub fn read_pgn(text: &str) -> Result<Vec<PgnGame>, &'static str> {
+    let mut games = Vec::new();
+    let mut game = PgnGame::new();
+    let mut board = game.start.clone();
+    let mut in_movetext = false;
+    let mut in_comment = false;
+    let mut comment = String::new();
+    let mut variation_depth = 0;
+
+    for line in text.lines() {
+        let line = line.trim();
+        if !in_comment && variation_depth == 0 && line.starts_with('[') {
+            // Tags of the next game when the last one had no result
+            if in_movetext {
+                games.push(std::mem::take(&mut game));
+                board = game.start.clone();
+                in_movetext = false;
+            }
+            if let Some((name, value)) = parse_tag(line) {
+                if name == "FEN" {
+                    game.start = Board::from_fen(&value)?;
+                    board = game.start.clone();
+                }
+                game.tags.push((name, value));
+            }
+            continue;
+        }
+
+        let mut word = String::new();
+        // A space at the end finishes the last word of the line
+        for symbol in line.chars().chain(std::iter::once(' ')) {
+            match symbol {
+                '}' if in_comment => {
+                    in_comment = false;
+                    // An evaluation belongs to the main line move before it
+                    let evaluation = GameEval::from_comment(&std::mem::take(&mut comment));
+                    let last = game.evaluations.last_mut().filter(|_| variation_depth == 0);
+                    if let (Some(evaluation), Some(last)) = (evaluation, last) {
+                        *last = Some(evaluation);
+                    }
+                }
+                _ if in_comment => comment.push(symbol),
+                '{' => in_comment = true,
+                '(' => variation_depth += 1,
+                ')' => variation_depth = (variation_depth - 1).max(0),
+                ';' if variation_depth == 0 => break,
+                _ if variation_depth > 0 => {}
+                _ if symbol.is_whitespace() => {
+                    let token = std::mem::take(&mut word);
+                    if matches!(token.as_str(), "1-0" | "0-1" | "1/2-1/2" | "*") {
+                        game.result = token;
+                        games.push(std::mem::take(&mut game));
+                        board = game.start.clone();
+                        in_movetext = false;
+                        continue;
+                    }
+                    let token = token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+                    match token {
+                        "" => {}
+                        _ if token.starts_with('$') => {}
+                        _ => {
+                            let mv = board.parse_san(token)?;
+                            board.apply_move(mv);
+                            game.moves.push(mv);
+                            game.evaluations.push(None);
+                            in_movetext = true;
+                        }
+                    }
+                }
+                _ => word.push(symbol),
+            }
+        }
+    }
+    if in_movetext {
+        games.push(game);
+    }
+    Ok(games)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Color;
+
+    #[test]
+    fn read_evaluations() {
+        let text = "[Event \"Scholar's mate\"]\n\
+            \n\
+            1. e4 {[%eval 0.35]} e5 {book} 2. Qh5 {[%eval 0.1,18]} (2. Nf3 {[%eval 0.40]})\n\
+            Nc6 3. Bc4 Nf6 {a blunder,\n\
+            [%eval #2]} 4. Qxf7# {[%eval #0]} 1-0\n";
+        let games = read_pgn(text).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].moves.len(), 7);
+        assert_eq!(
+            games[0].evaluations,
+            [
+                Some(GameEval::Centipawns(35)),
+                None,
+                Some(GameEval::Centipawns(10)),
+                None,
+                None,
+                Some(GameEval::Mate(2)),
+                Some(GameEval::Checkmate(Color::White)),
+            ]
+        );
+    }
+
+    #[test]
+    fn evaluation_comments_round_trip() {
+        for evaluation in [
+            GameEval::Centipawns(0),
+            GameEval::Centipawns(-120),
+            GameEval::Mate(3),
+            GameEval::Mate(-1),
+            GameEval::Checkmate(Color::White),
+            GameEval::Checkmate(Color::Black),
+        ] {
+            let comment = evaluation.to_comment();
+            assert_eq!(
+                GameEval::from_comment(&comment),
+                Some(evaluation),
+                "{}",
+                comment
+            );
+        }
+    }
+}
@@ -99,6 +99,44 @@ impl Piece {
         }
     }
 
+    /// Get the FEN letter of the piece, upper case for white and lower case for black
+    pub fn get_piece_char(&self) -> char {
+        let letter = match self.piece_type {
+            PieceType::King => 'k',
+            PieceType::Queen => 'q',
+            PieceType::Rook => 'r',
+            PieceType::Bishop => 'b',
+            PieceType::Knight => 'n',
+            PieceType::Pawn => 'p',
+        };
+
+        match self.color {
+            Color::White => letter.to_ascii_uppercase(),
+            Color::Black => letter,
+        }
+    }
+
+    /// Read a piece from its FEN letter or its Unicode glyph
+    pub fn from_symbol(symbol: char) -> Option<Self> {
+        let (color, piece_type) = match symbol {
+            'K' | '♔' => (Color::White, PieceType::King),
+            'Q' | '♕' => (Color::White, PieceType::Queen),
+            'R' | '♖' => (Color::White, PieceType::Rook),
+            'B' | '♗' => (Color::White, PieceType::Bishop),
+            'N' | '♘' => (Color::White, PieceType::Knight),
+            'P' | '♙' => (Color::White, PieceType::Pawn),
+            'k' | '♚' => (Color::Black, PieceType::King),
+            'q' | '♛' => (Color::Black, PieceType::Queen),
+            'r' | '♜' => (Color::Black, PieceType::Rook),
+            'b' | '♝' => (Color::Black, PieceType::Bishop),
+            'n' | '♞' => (Color::Black, PieceType::Knight),
+            'p' | '♟' => (Color::Black, PieceType::Pawn),
+            _ => return None,
+        };
+
+        Some(Self::new(color, piece_type))
+    }
+
     pub fn promote_to(&mut self, piece_type: PieceType) {
         self.piece_type = piece_type;
     }
@@ -0,0 +1,396 @@
+// Tactics puzzles in the format of the Lichess puzzle database
+//
+// Each CSV line reads `PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,
+// NbPlays,Themes,GameUrl,OpeningTags`, only the first four being required. The
+// FEN is the position before the opponent's move, the first of the space
+// separated UCI moves; the solver answers with the second, and so on. Themes
+// are separated by spaces. A header line starting with `PuzzleId` is skipped.
+//
+// The solve history is kept as CSV lines of `PuzzleId,Solved,PuzzleRating,Rating`,
+// the last field being the solver's rating after the attempt.
+
+use crate::{Board, Color, Move};
+use std::path::Path;
+
+/// Puzzle rating of a solver without history
+pub const INITIAL_PUZZLE_RATING: i32 = 1500;
+/// How much one attempt can move the solver's rating
+const RATING_FACTOR: f64 = 32.0;
+
+/// A position with the opponent's move and the moves that answer it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Puzzle {
+    pub id: String,
+    /// Position before the opponent's first move
+    pub board: Board,
+    /// Opponent's first move, then the solver's and opponent's moves in turn
+    pub moves: Vec<Move>,
+    pub rating: u32,
+    pub themes: Vec<String>,
+}
+
+/// How a move played in a puzzle was judged
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PuzzleAnswer {
+    /// The expected move, the opponent replies next
+    Correct,
+    /// The last move of the solution, or any move that mates
+    Solved,
+    Wrong,
+}
+
+impl Puzzle {
+    /// Read a puzzle from a line of the Lichess CSV export
+    pub fn from_csv_line(line: &str) -> Result<Self, &'static str> {
+        let fields: Vec<&str> = line.trim().split(',').collect();
+        if fields.len() < 4 {
+            return Err("Puzzle lines need an id, a FEN, moves and a rating\n");
+        }
+
+        let board = Board::from_fen(fields[1])?;
+        let mut position = board.clone();
+        let mut moves = Vec::new();
+        for text in fields[2].split_whitespace() {
+            let mv = position.parse_uci_move(text)?;
+            position.apply_move(mv);
+            moves.push(mv);
+        }
+        if moves.len() < 2 {
+            return Err("Puzzles need the opponent's move and an answer\n");
+        }
+
+        Ok(Self {
+            id: fields[0].to_string(),
+            board,
+            moves,
+            rating: fields[3].parse().map_err(|_| "Invalid puzzle rating\n")?,
+            themes: fields
+                .get(7)
+                .map(|themes| themes.split_whitespace().map(str::to_string).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Color of the side solving the puzzle, the one not to move in the FEN
+    pub fn solver_color(&self) -> Color {
+        self.board.side_to_move().opposite()
+    }
+
+    /// Position after the first `count` moves of the puzzle
+    pub fn position_after(&self, count: usize) -> Board {
+        let mut board = self.board.clone();
+        for &mv in self.moves.iter().take(count) {
+            board.apply_move(mv);
+        }
+        board
+    }
+
+    /// Judge a move played as the puzzle's move at `index`, an odd index since the
+    /// solver plays the second, fourth... moves
+    pub fn check_answer(&self, index: usize, mv: Move) -> PuzzleAnswer {
+        let Some(&expected) = self.moves.get(index) else {
+            return PuzzleAnswer::Wrong;
+        };
+
+        // Any mate solves it, not only the one in the solution
+        let mut after = self.position_after(index);
+        after.apply_move(mv);
+        if after.is_checkmated(after.side_to_move()) {
+            return PuzzleAnswer::Solved;
+        }
+
+        if mv != expected {
+            PuzzleAnswer::Wrong
+        } else if index + 1 >= self.moves.len() {
+            PuzzleAnswer::Solved
+        } else {
+            PuzzleAnswer::Correct
+        }
+    }
+}
+
+/// Puzzles read from a CSV file
+#[derive(Debug, Clone, Default)]
+pub struct PuzzleDatabase {
+    puzzles: Vec<Puzzle>,
+}
+
+impl PuzzleDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read every valid puzzle of a CSV text, returning how many lines were
+    /// skipped as invalid
+    pub fn import_csv(&mut self, text: &str) -> usize {
+        let mut skipped = 0;
+        for line in text.lines() {
+            if line.trim().is_empty() || line.starts_with("PuzzleId") {
+                continue;
+            }
+            match Puzzle::from_csv_line(line) {
+                Ok(puzzle) if !self.puzzles.iter().any(|known| known.id == puzzle.id) => {
+                    self.puzzles.push(puzzle)
+                }
+                Ok(_) => {}
+                Err(_) => skipped += 1,
+            }
+        }
+        skipped
+    }
+
+    /// Read the puzzles of a CSV file, returning how many lines were skipped
+    pub fn import_file<P: AsRef<Path>>(&mut self, path: P) -> Result<usize, &'static str> {
+        let text = std::fs::read_to_string(path).map_err(|_| "Failed to read puzzle file\n")?;
+        Ok(self.import_csv(&text))
+    }
+
+    pub fn puzzles(&self) -> &[Puzzle] {
+        &self.puzzles
+    }
+
+    pub fn len(&self) -> usize {
+        self.puzzles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.puzzles.is_empty()
+    }
+
+    /// The puzzle not tried yet whose rating is closest to `rating`
+    pub fn next_puzzle(&self, rating: i32, history: &PuzzleHistory) -> Option<&Puzzle> {
+        self.puzzles
+            .iter()
+            .filter(|puzzle| !history.has_attempted(&puzzle.id))
+            .min_by_key(|puzzle| (puzzle.rating as i32 - rating).abs())
+    }
+}
+
+/// One try at a puzzle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PuzzleAttempt {
+    pub id: String,
+    pub solved: bool,
+    pub puzzle_rating: u32,
+    /// The solver's rating after the attempt
+    pub rating: i32,
+}
+
+/// Puzzles tried so far with the solver's rating after each
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PuzzleHistory {
+    attempts: Vec<PuzzleAttempt>,
+}
+
+impl PuzzleHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a history saved with `to_csv`
+    pub fn from_csv(text: &str) -> Result<Self, &'static str> {
+        let mut attempts = Vec::new();
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.trim().split(',').collect();
+            let [id, solved, puzzle_rating, rating] = fields[..] else {
+                return Err("Invalid puzzle history line\n");
+            };
+            attempts.push(PuzzleAttempt {
+                id: id.to_string(),
+                solved: solved == "1",
+                puzzle_rating: puzzle_rating
+                    .parse()
+                    .map_err(|_| "Invalid puzzle history line\n")?,
+                rating: rating
+                    .parse()
+                    .map_err(|_| "Invalid puzzle history line\n")?,
+            });
+        }
+        Ok(Self { attempts })
+    }
+
+    pub fn to_csv(&self) -> String {
+        self.attempts
+            .iter()
+            .map(|attempt| {
+                format!(
+                    "{},{},{},{}\n",
+                    attempt.id,
+                    u8::from(attempt.solved),
+                    attempt.puzzle_rating,
+                    attempt.rating
+                )
+            })
+            .collect()
+    }
+
+    /// Read the history file, an empty history when there is none yet
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => Self::from_csv(&text),
+            Err(_) => Ok(Self::new()),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), &'static str> {
+        std::fs::write(path, self.to_csv()).map_err(|_| "Failed to write puzzle history\n")
+    }
+
+    pub fn attempts(&self) -> &[PuzzleAttempt] {
+        &self.attempts
+    }
+
+    /// The solver's current rating
+    pub fn rating(&self) -> i32 {
+        self.attempts
+            .last()
+            .map_or(INITIAL_PUZZLE_RATING, |attempt| attempt.rating)
+    }
+
+    pub fn solved_count(&self) -> usize {
+        self.attempts
+            .iter()
+            .filter(|attempt| attempt.solved)
+            .count()
+    }
+
+    pub fn has_attempted(&self, id: &str) -> bool {
+        self.attempts.iter().any(|attempt| attempt.id == id)
+    }
+
+    /// Record an attempt, rating it like a game against the puzzle, and
+    /// return the change of rating
+    pub fn record(&mut self, puzzle: &Puzzle, solved: bool) -> i32 {
+        let rating = self.rating();
+        let expected = 1.0 / (1.0 + 10f64.powf((puzzle.rating as f64 - rating as f64) / 400.0));
+        let score = if solved { 1.0 } else { 0.0 };
+        let change = (RATING_FACTOR * (score - expected)).round() as i32;
+        self.attempts.push(PuzzleAttempt {
+            id: puzzle.id.clone(),
+            solved,
+            puzzle_rating: puzzle.rating,
+            rating: rating + change,
+        });
+        change
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first puzzle of the Lichess database, a mate in two
+    const LICHESS_LINE: &str = "00sHx,q3k1nr/1pp1nQpp/3p4/1P2p3/4P3/B1PP1b2/B5PP/5K2 b k - 0 17,\
+        e8d7 a2e6 d7d8 f7f8,1760,80,83,72,mate mateIn2 middlegame short,\
+        https://lichess.org/yyznGmXs/black#34,Italian_Game Italian_Game_Classical_Variation";
+
+    /// After 1. Qf2 Kc1 both Qe1# and Qc2# mate, the solution gives Qe1#
+    const TWO_MATES_LINE: &str = "mates,8/8/8/8/8/1K6/5Q2/3k4 b - - 0 1,d1c1 f2e1,1200";
+
+    fn uci(board: &Board, text: &str) -> Move {
+        board.parse_uci_move(text).unwrap()
+    }
+
+    #[test]
+    fn read_csv_lines() {
+        let puzzle = Puzzle::from_csv_line(LICHESS_LINE).unwrap();
+        assert_eq!(puzzle.id, "00sHx");
+        assert_eq!(puzzle.moves.len(), 4);
+        assert_eq!(puzzle.rating, 1760);
+        assert_eq!(puzzle.themes, ["mate", "mateIn2", "middlegame", "short"]);
+        assert_eq!(puzzle.solver_color(), Color::White);
+
+        assert!(Puzzle::from_csv_line("short,8/8/8/8/8/8/8/8 w - - 0 1").is_err());
+        assert!(Puzzle::from_csv_line("one,8/8/8/8/8/1K6/5Q2/3k4 b - - 0 1,d1c1,1200").is_err());
+        assert!(Puzzle::from_csv_line("bad,8/8/8/8/8/1K6/5Q2/3k4 b - - 0 1,d1c1 f2e1,x").is_err());
+        assert!(
+            Puzzle::from_csv_line("illegal,8/8/8/8/8/1K6/5Q2/3k4 b - - 0 1,d1d3 f2e1,1200")
+                .is_err()
+        );
+
+        let mut database = PuzzleDatabase::new();
+        let text = format!(
+            "PuzzleId,FEN,Moves,Rating\n{}\n\nnot a puzzle\n{}\n{}\n",
+            LICHESS_LINE, TWO_MATES_LINE, TWO_MATES_LINE
+        );
+        // The duplicate is left out, the invalid line skipped
+        assert_eq!(database.import_csv(&text), 1);
+        assert_eq!(database.len(), 2);
+    }
+
+    #[test]
+    fn answers() {
+        let puzzle = Puzzle::from_csv_line(LICHESS_LINE).unwrap();
+        let position = puzzle.position_after(1);
+        assert_eq!(
+            puzzle.check_answer(1, uci(&position, "a2e6")),
+            PuzzleAnswer::Correct
+        );
+        assert_eq!(
+            puzzle.check_answer(1, uci(&position, "f7f8")),
+            PuzzleAnswer::Wrong
+        );
+        let position = puzzle.position_after(3);
+        assert_eq!(
+            puzzle.check_answer(3, uci(&position, "f7f8")),
+            PuzzleAnswer::Solved
+        );
+        assert_eq!(
+            puzzle.check_answer(5, uci(&position, "f7f8")),
+            PuzzleAnswer::Wrong
+        );
+    }
+
+    #[test]
+    fn any_mate_solves() {
+        let puzzle = Puzzle::from_csv_line(TWO_MATES_LINE).unwrap();
+        let position = puzzle.position_after(1);
+        assert_eq!(
+            puzzle.check_answer(1, uci(&position, "f2e1")),
+            PuzzleAnswer::Solved
+        );
+        assert_eq!(
+            puzzle.check_answer(1, uci(&position, "f2c2")),
+            PuzzleAnswer::Solved
+        );
+        assert_eq!(
+            puzzle.check_answer(1, uci(&position, "f2f3")),
+            PuzzleAnswer::Wrong
+        );
+    }
+
+    #[test]
+    fn rating_updates() {
+        let puzzle =
+            Puzzle::from_csv_line(TWO_MATES_LINE.replace("1200", "1500").as_str()).unwrap();
+        let mut history = PuzzleHistory::new();
+        assert_eq!(history.rating(), INITIAL_PUZZLE_RATING);
+
+        // Even odds win or lose half the factor
+        assert_eq!(history.record(&puzzle, true), 16);
+        assert_eq!(history.rating(), 1516);
+        assert_eq!(history.record(&puzzle, false), -17);
+        assert_eq!(history.rating(), 1499);
+        assert_eq!(history.solved_count(), 1);
+        assert!(history.has_attempted("mates"));
+
+        let saved = PuzzleHistory::from_csv(&history.to_csv()).unwrap();
+        assert_eq!(saved, history);
+        assert!(PuzzleHistory::from_csv("mates,1,1500").is_err());
+    }
+
+    #[test]
+    fn next_puzzle_is_closest_untried() {
+        let mut database = PuzzleDatabase::new();
+        database.import_csv(&format!("{}\n{}\n", LICHESS_LINE, TWO_MATES_LINE));
+        let mut history = PuzzleHistory::new();
+        assert_eq!(database.next_puzzle(1700, &history).unwrap().id, "00sHx");
+        assert_eq!(database.next_puzzle(1300, &history).unwrap().id, "mates");
+
+        let puzzle = database.puzzles()[1].clone();
+        history.record(&puzzle, true);
+        assert_eq!(database.next_puzzle(1300, &history).unwrap().id, "00sHx");
+        history.record(&database.puzzles()[0].clone(), false);
+        assert!(database.next_puzzle(1300, &history).is_none());
+    }
+}
@@ -19,8 +19,9 @@ impl Square {
             return Err("Error trying to read position notation\n");
         }
 
-        let file = ((bytes[0] as char).to_ascii_lowercase() as u8) - b'a';
-        let rank = (bytes[1] as u8) - b'1';
+        // wrapping keeps characters before 'a' or '1' out of bounds instead of overflowing
+        let file = ((bytes[0] as char).to_ascii_lowercase() as u8).wrapping_sub(b'a');
+        let rank = bytes[1].wrapping_sub(b'1');
 
         Self::new(rank, file)
     }
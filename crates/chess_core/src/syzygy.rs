@@ -0,0 +1,1573 @@
+// Syzygy endgame tablebase probing
+//
+// Reads the WDL (.rtbw) and DTZ (.rtbz) files written by the Syzygy generator.
+// The index encoding and the decompression follow the reference probing code:
+// a position is mapped to an index by grouping its pieces, and the table stores
+// the values of all indices compressed with recursive pairing and canonical
+// Huffman codes, split in blocks with a sparse index to find them.
+
+use crate::{Board, Color, Move, PieceType, Square};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Largest number of pieces (kings included) a Syzygy table can hold
+const TB_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// Flags of a compressed table
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+/// Win/draw/loss result for the side to move, taking the fifty-move rule into account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Wdl {
+    Loss,
+    /// Lost, but saved by the fifty-move rule
+    BlessedLoss,
+    Draw,
+    /// Won, but drawn by the fifty-move rule
+    CursedWin,
+    Win,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Self {
+        match value {
+            i32::MIN..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    fn value(self) -> i32 {
+        match self {
+            Wdl::Loss => -2,
+            Wdl::BlessedLoss => -1,
+            Wdl::Draw => 0,
+            Wdl::CursedWin => 1,
+            Wdl::Win => 2,
+        }
+    }
+
+    /// The same result seen from the other side
+    pub fn flip(self) -> Self {
+        Self::from_value(-self.value())
+    }
+}
+
+/// A legal move from a tablebase position with its outcome
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TablebaseMove {
+    pub mv: Move,
+    /// Result for the side playing the move
+    pub wdl: Wdl,
+    /// Plies until a capture or pawn move after playing the move, positive when winning
+    pub dtz: i32,
+}
+
+/// A set of Syzygy tables found in local directories, loaded on first use
+pub struct SyzygyTablebase {
+    tables: HashMap<String, TableFiles>,
+    max_pieces: usize,
+}
+
+struct TableFiles {
+    wdl_path: PathBuf,
+    dtz_path: Option<PathBuf>,
+    wdl: OnceLock<Option<Table>>,
+    dtz: OnceLock<Option<Table>>,
+}
+
+impl Default for SyzygyTablebase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyzygyTablebase {
+    pub fn new() -> Self {
+        Self {
+            tables: HashMap::new(),
+            max_pieces: 0,
+        }
+    }
+
+    /// Register every table of a directory, returning how many WDL tables were found
+    pub fn add_directory<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<usize> {
+        let mut found = 0;
+
+        for entry in std::fs::read_dir(path)? {
+            let wdl_path = entry?.path();
+            if wdl_path.extension().and_then(|e| e.to_str()) != Some("rtbw") {
+                continue;
+            }
+
+            let name = match wdl_path.file_stem().and_then(|s| s.to_str()) {
+                Some(name) if Material::from_name(name).is_some() => name.to_string(),
+                _ => continue,
+            };
+
+            let dtz_path = wdl_path.with_extension("rtbz");
+            let piece_count = name.len() - 1;
+            self.tables.insert(
+                name,
+                TableFiles {
+                    dtz_path: dtz_path.is_file().then_some(dtz_path),
+                    wdl_path,
+                    wdl: OnceLock::new(),
+                    dtz: OnceLock::new(),
+                },
+            );
+            self.max_pieces = self.max_pieces.max(piece_count);
+            found += 1;
+        }
+
+        Ok(found)
+    }
+
+    /// Get the largest number of pieces covered by the registered tables
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Check whether the tables can answer probes for a position
+    pub fn covers(&self, board: &Board) -> bool {
+        let material = Material::from_board(board);
+        material.piece_count() <= self.max_pieces
+            && !has_castling_rights(board)
+            && (material.piece_count() == 2 || self.find_table(&material).is_some())
+    }
+
+    /// Probe the win/draw/loss result of a position for the side to move
+    pub fn probe_wdl(&self, board: &Board) -> Result<Wdl, &'static str> {
+        self.check_probe(board)?;
+        Ok(self.search(board, false)?.0)
+    }
+
+    /// Probe the distance to zeroing of a position: the number of plies until a capture
+    /// or pawn move with perfect play, positive when the side to move wins, negative
+    /// when it loses and 0 for draws. Values beyond 100 are cursed wins or blessed losses
+    pub fn probe_dtz(&self, board: &Board) -> Result<i32, &'static str> {
+        self.check_probe(board)?;
+        self.dtz(board)
+    }
+
+    /// Get every legal move with its outcome, best moves first
+    pub fn best_moves(&self, board: &Board) -> Result<Vec<TablebaseMove>, &'static str> {
+        self.check_probe(board)?;
+
+        let mut moves = Vec::new();
+        for mv in board.get_all_valid_moves(board.side_to_move()) {
+            let zeroing = is_zeroing(board, mv);
+            let mut new_board = board.clone();
+            new_board.apply_move(mv);
+
+            let wdl = self.search(&new_board, false)?.0.flip();
+            let mut dtz = if zeroing {
+                dtz_before_zeroing(wdl)
+            } else {
+                // Count the move itself
+                let dtz = -self.dtz(&new_board)?;
+                dtz + dtz.signum()
+            };
+
+            // A mating move is always the best one
+            if dtz == 2 && new_board.is_checkmated(new_board.side_to_move()) {
+                dtz = 1;
+            }
+
+            moves.push(TablebaseMove { mv, wdl, dtz });
+        }
+
+        // Winning faster and losing slower first
+        moves.sort_by(|a, b| b.wdl.cmp(&a.wdl).then(a.dtz.cmp(&b.dtz)));
+        Ok(moves)
+    }
+
+    fn check_probe(&self, board: &Board) -> Result<(), &'static str> {
+        if Material::from_board(board).piece_count() > self.max_pieces.max(2) {
+            return Err("Too many pieces for the installed tablebases\n");
+        }
+        if has_castling_rights(board) {
+            return Err("Tablebases do not cover positions with castling rights\n");
+        }
+        Ok(())
+    }
+
+    /// WDL of a position resolving captures first: tables may store any value for
+    /// positions where a capture is the best move, since the generator knows it can
+    /// be recovered this way. With `check_zeroing` pawn moves are searched too, for DTZ
+    fn search(
+        &self,
+        board: &Board,
+        check_zeroing: bool,
+    ) -> Result<(Wdl, ProbeState), &'static str> {
+        let color = board.side_to_move();
+        let moves = board.get_all_valid_moves(color);
+
+        if moves.is_empty() {
+            let wdl = if board.is_king_in_check(color) {
+                Wdl::Loss
+            } else {
+                Wdl::Draw
+            };
+            return Ok((wdl, ProbeState::Ok));
+        }
+
+        let mut best_value = Wdl::Loss;
+        let mut move_count = 0;
+
+        for &mv in &moves {
+            let is_pawn_move = board
+                .get_piece(mv.from)
+                .map(|p| p.piece_type == PieceType::Pawn)
+                .unwrap_or(false);
+            if !is_capture(board, mv) && (!check_zeroing || !is_pawn_move) {
+                continue;
+            }
+
+            move_count += 1;
+            let mut new_board = board.clone();
+            new_board.apply_move(mv);
+            let value = self.search(&new_board, false)?.0.flip();
+
+            if value > best_value {
+                best_value = value;
+                if value == Wdl::Win {
+                    return Ok((value, ProbeState::ZeroingBestMove));
+                }
+            }
+        }
+
+        // Once every move was searched the stored value is not needed, and could
+        // even be wrong since tables ignore en passant rights
+        let no_more_moves = move_count == moves.len();
+        let value = if no_more_moves {
+            best_value
+        } else {
+            self.probe_wdl_table(board)?
+        };
+
+        if best_value >= value {
+            let state = if best_value > Wdl::Draw || no_more_moves {
+                ProbeState::ZeroingBestMove
+            } else {
+                ProbeState::Ok
+            };
+            return Ok((best_value, state));
+        }
+
+        Ok((value, ProbeState::Ok))
+    }
+
+    fn dtz(&self, board: &Board) -> Result<i32, &'static str> {
+        let color = board.side_to_move();
+        let (wdl, state) = self.search(board, true)?;
+
+        // No stored value for draws, and a 'don't care' one when zeroing is best
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if state == ProbeState::ZeroingBestMove {
+            return Ok(dtz_before_zeroing(wdl));
+        }
+
+        if let Some(dtz) = self.probe_dtz_table(board, wdl)? {
+            let fifty_move_rule = matches!(wdl, Wdl::BlessedLoss | Wdl::CursedWin);
+            return Ok((dtz + if fifty_move_rule { 100 } else { 0 }) * wdl.value().signum());
+        }
+
+        // The table only stores the other side to move: search one ply and
+        // take the move that keeps the result with the lowest DTZ
+        let mut min_dtz = i32::MAX;
+        for mv in board.get_all_valid_moves(color) {
+            let zeroing = is_zeroing(board, mv);
+            let mut new_board = board.clone();
+            new_board.apply_move(mv);
+
+            // For zeroing moves the DTZ is the one before making them,
+            // the search after the move only gives its sign
+            let mut dtz = if zeroing {
+                -dtz_before_zeroing(self.search(&new_board, false)?.0)
+            } else {
+                -self.dtz(&new_board)?
+            };
+
+            if dtz == 1 && new_board.is_checkmated(new_board.side_to_move()) {
+                min_dtz = 1;
+            }
+
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+
+            if dtz < min_dtz && dtz.signum() == wdl.value().signum() {
+                min_dtz = dtz;
+            }
+        }
+
+        Ok(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    fn find_table(&self, material: &Material) -> Option<(&TableFiles, bool)> {
+        let name = material.name(false);
+        if let Some(files) = self.tables.get(&name) {
+            return Some((files, false));
+        }
+        let flipped_name = material.name(true);
+        self.tables.get(&flipped_name).map(|files| (files, true))
+    }
+
+    fn probe_wdl_table(&self, board: &Board) -> Result<Wdl, &'static str> {
+        let material = Material::from_board(board);
+        if material.piece_count() == 2 {
+            return Ok(Wdl::Draw);
+        }
+
+        let (files, black_stronger) = self.find_table(&material).ok_or("Missing tablebase\n")?;
+        let table = files
+            .wdl
+            .get_or_init(|| Table::load(&files.wdl_path, TableKind::Wdl))
+            .as_ref()
+            .ok_or("Failed to read tablebase file\n")?;
+
+        let value = table
+            .probe(board, black_stronger, Wdl::Draw)
+            .ok_or("Corrupt tablebase file\n")?;
+        Ok(Wdl::from_value(value))
+    }
+
+    /// `None` when the table only stores the other side to move
+    fn probe_dtz_table(&self, board: &Board, wdl: Wdl) -> Result<Option<i32>, &'static str> {
+        let material = Material::from_board(board);
+        let (files, black_stronger) = self.find_table(&material).ok_or("Missing tablebase\n")?;
+        let dtz_path = files.dtz_path.as_ref().ok_or("Missing DTZ tablebase\n")?;
+        let table = files
+            .dtz
+            .get_or_init(|| Table::load(dtz_path, TableKind::Dtz))
+            .as_ref()
+            .ok_or("Failed to read tablebase file\n")?;
+
+        Ok(table.probe(board, black_stronger, wdl))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProbeState {
+    Ok,
+    /// The best move is a capture or pawn move, so the stored DTZ is meaningless
+    ZeroingBestMove,
+}
+
+/// DTZ of the move before a zeroing move, known from the result alone
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+        Wdl::Draw => 0,
+    }
+}
+
+fn is_capture(board: &Board, mv: Move) -> bool {
+    match board.get_piece(mv.from) {
+        // en passant captures land on an empty square
+        Some(piece) if piece.piece_type == PieceType::Pawn => mv.from.file() != mv.to.file(),
+        Some(_) => board.get_piece(mv.to).is_some(),
+        None => false,
+    }
+}
+
+fn is_zeroing(board: &Board, mv: Move) -> bool {
+    is_capture(board, mv)
+        || board
+            .get_piece(mv.from)
+            .map(|p| p.piece_type == PieceType::Pawn)
+            .unwrap_or(false)
+}
+
+/// Castling is only possible while king and rook are on their starting squares
+pub(crate) fn has_castling_rights(board: &Board) -> bool {
+    let rights = board.castling_rights();
+    let on = |notation: &str, color: Color, piece_type: PieceType| {
+        Square::new_from_notation(notation)
+            .map(|square| {
+                board
+                    .get_piece(square)
+                    .map(|p| p.color == color && p.piece_type == piece_type)
+                    .unwrap_or(false)
+            })
+            .unwrap_or(false)
+    };
+
+    let white_king = on("e1", Color::White, PieceType::King);
+    let black_king = on("e8", Color::Black, PieceType::King);
+    (rights.white_king_side && white_king && on("h1", Color::White, PieceType::Rook))
+        || (rights.white_queen_side && white_king && on("a1", Color::White, PieceType::Rook))
+        || (rights.black_king_side && black_king && on("h8", Color::Black, PieceType::Rook))
+        || (rights.black_queen_side && black_king && on("a8", Color::Black, PieceType::Rook))
+}
+
+/// Pieces of each side, counted by type in K, Q, R, B, N, P order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Material {
+    white: [u8; 6],
+    black: [u8; 6],
+}
+
+const MATERIAL_ORDER: [PieceType; 6] = [
+    PieceType::King,
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+const MATERIAL_LETTERS: [char; 6] = ['K', 'Q', 'R', 'B', 'N', 'P'];
+
+impl Material {
+    fn from_board(board: &Board) -> Self {
+        let mut material = Material {
+            white: [0; 6],
+            black: [0; 6],
+        };
+
+        for rank in 0..8 {
+            for file in 0..8 {
+                let square = Square::new(rank, file).expect("always valid");
+                if let Some(piece) = board.get_piece(square) {
+                    let index = MATERIAL_ORDER
+                        .iter()
+                        .position(|&t| t == piece.piece_type)
+                        .expect("all piece types listed");
+                    match piece.color {
+                        Color::White => material.white[index] += 1,
+                        Color::Black => material.black[index] += 1,
+                    }
+                }
+            }
+        }
+
+        material
+    }
+
+    /// Read a table name like `KRvK`, the first side being white
+    fn from_name(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let count = |side: &str| -> Option<[u8; 6]> {
+            let mut counts = [0; 6];
+            for letter in side.chars() {
+                counts[MATERIAL_LETTERS.iter().position(|&l| l == letter)?] += 1;
+            }
+            (counts[0] == 1).then_some(counts)
+        };
+
+        let material = Material {
+            white: count(white)?,
+            black: count(black)?,
+        };
+        (material.piece_count() <= TB_PIECES).then_some(material)
+    }
+
+    fn name(&self, flipped: bool) -> String {
+        let side = |counts: &[u8; 6]| -> String {
+            counts
+                .iter()
+                .zip(MATERIAL_LETTERS)
+                .flat_map(|(&count, letter)| std::iter::repeat_n(letter, count as usize))
+                .collect()
+        };
+
+        let (first, second) = if flipped {
+            (&self.black, &self.white)
+        } else {
+            (&self.white, &self.black)
+        };
+        format!("{}v{}", side(first), side(second))
+    }
+
+    fn piece_count(&self) -> usize {
+        self.white
+            .iter()
+            .chain(self.black.iter())
+            .map(|&c| c as usize)
+            .sum()
+    }
+
+    fn has_pawns(&self) -> bool {
+        self.white[5] + self.black[5] > 0
+    }
+
+    /// Whether a side has a piece other than the king that is alone of its type
+    fn has_unique_pieces(&self) -> bool {
+        self.white[1..].contains(&1) || self.black[1..].contains(&1)
+    }
+
+    fn is_symmetric(&self) -> bool {
+        self.white == self.black
+    }
+}
+
+/// Index tables shared by every probe
+struct Maps {
+    map_pawns: [u64; 64],
+    map_b1h1h7: [u64; 64],
+    map_a1d1d4: [u64; 64],
+    map_kk: [[u64; 64]; 10],
+    binomial: [[u64; 64]; TB_PIECES],
+    lead_pawn_idx: [[u64; 64]; TB_PIECES],
+    lead_pawns_size: [[u64; 4]; TB_PIECES],
+}
+
+// Squares are numbered a1 = 0, b1 = 1 ... h8 = 63 like in the table files
+fn rank_of(square: usize) -> usize {
+    square >> 3
+}
+
+fn file_of(square: usize) -> usize {
+    square & 7
+}
+
+/// Position of a square relative to the a1-h8 diagonal: negative below, positive above
+fn off_a1h8(square: usize) -> i32 {
+    rank_of(square) as i32 - file_of(square) as i32
+}
+
+fn flip_file(square: usize) -> usize {
+    square ^ 7
+}
+
+fn flip_rank(square: usize) -> usize {
+    square ^ 56
+}
+
+fn maps() -> &'static Maps {
+    static MAPS: OnceLock<Maps> = OnceLock::new();
+    MAPS.get_or_init(Maps::new)
+}
+
+impl Maps {
+    fn new() -> Self {
+        let mut maps = Maps {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; TB_PIECES],
+            lead_pawn_idx: [[0; 64]; TB_PIECES],
+            lead_pawns_size: [[0; 4]; TB_PIECES],
+        };
+
+        // Squares below the a1-h8 diagonal to 0..27
+        let mut code = 0;
+        for square in 0..64 {
+            if off_a1h8(square) < 0 {
+                maps.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        // Squares of the a1-d1-d4 triangle to 0..9, the diagonal ones last
+        let mut diagonal = Vec::new();
+        code = 0;
+        for square in 0..=27 {
+            if off_a1h8(square) < 0 && file_of(square) <= 3 {
+                maps.map_a1d1d4[square] = code;
+                code += 1;
+            } else if off_a1h8(square) == 0 && file_of(square) <= 3 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            maps.map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // The 462 legal placements of two kings with the first one in the a1-d1-d4
+        // triangle. With the first king on the diagonal, the second one can't be above it
+        let mut both_on_diagonal = Vec::new();
+        code = 0;
+        for index in 0..10 {
+            for first in 0..=27 {
+                // b1 is mapped to 0 like every square outside the triangle
+                if maps.map_a1d1d4[first] != index || (index == 0 && first != 1) {
+                    continue;
+                }
+                for second in 0..64 {
+                    let touching = rank_of(first).abs_diff(rank_of(second)) <= 1
+                        && file_of(first).abs_diff(file_of(second)) <= 1;
+                    if touching || (off_a1h8(first) == 0 && off_a1h8(second) > 0) {
+                        continue;
+                    }
+                    if off_a1h8(first) == 0 && off_a1h8(second) == 0 {
+                        both_on_diagonal.push((index, second));
+                    } else {
+                        maps.map_kk[index as usize][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (index, second) in both_on_diagonal {
+            maps.map_kk[index as usize][second] = code;
+            code += 1;
+        }
+
+        // binomial[k][n]: ways to choose k elements from n
+        maps.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..TB_PIECES.min(n + 1) {
+                maps.binomial[k][n] = if k > 0 {
+                    maps.binomial[k - 1][n - 1]
+                } else {
+                    0
+                } + if k < n { maps.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // map_pawns encodes a2-h7 so the leading pawn, the one closest to the edge
+        // and then lowest on its file, has the highest value
+        let mut available_squares = 47;
+        for lead_pawns_count in 1..=5 {
+            for file in 0..4 {
+                let mut index = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    if lead_pawns_count == 1 {
+                        maps.map_pawns[square] = available_squares;
+                        available_squares -= 1;
+                        maps.map_pawns[flip_file(square)] = available_squares;
+                        available_squares = available_squares.saturating_sub(1);
+                    }
+                    maps.lead_pawn_idx[lead_pawns_count][square] = index;
+                    index += maps.binomial[lead_pawns_count - 1][maps.map_pawns[square] as usize];
+                }
+                maps.lead_pawns_size[lead_pawns_count][file] = index;
+            }
+        }
+
+        maps
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TableKind {
+    Wdl,
+    Dtz,
+}
+
+/// Decoding information of one compressed sub-table
+#[derive(Debug, Clone, Default)]
+struct PairsData {
+    flags: u8,
+    min_sym_len: u8,
+    block_size: u64,
+    span: u64,
+    num_blocks: u32,
+    block_length_size: u32,
+    sparse_index_size: u64,
+    // Offsets into the file
+    lowest_sym: usize,
+    btree: usize,
+    block_length: usize,
+    sparse_index: usize,
+    data: usize,
+    base64: Vec<u64>,
+    symlen: Vec<u8>,
+    pieces: [u8; TB_PIECES],
+    group_idx: [u64; TB_PIECES + 1],
+    group_len: [usize; TB_PIECES + 1],
+    /// Start of the DTZ value maps for win, loss, cursed win and blessed loss
+    map_idx: [usize; 4],
+}
+
+struct Table {
+    kind: TableKind,
+    data: Vec<u8>,
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    symmetric: bool,
+    /// Pawns of the leading color, then of the other color
+    pawn_count: [usize; 2],
+    /// Sub-tables by side to move (WDL only) and by file of the leading pawn
+    items: [Vec<PairsData>; 2],
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64_be(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+fn shift_left(value: u64, shift: i32) -> u64 {
+    if (0..64).contains(&shift) {
+        value << shift
+    } else {
+        0
+    }
+}
+
+fn shift_right(value: u64, shift: i32) -> u64 {
+    if (0..64).contains(&shift) {
+        value >> shift
+    } else {
+        0
+    }
+}
+
+impl PairsData {
+    /// Left child symbol of a pair, or the value of a leaf symbol
+    fn left(&self, data: &[u8], sym: usize) -> Option<usize> {
+        let lr = data.get(self.btree + 3 * sym..self.btree + 3 * sym + 3)?;
+        Some((((lr[1] & 0xf) as usize) << 8) | lr[0] as usize)
+    }
+
+    fn right(&self, data: &[u8], sym: usize) -> Option<usize> {
+        let lr = data.get(self.btree + 3 * sym..self.btree + 3 * sym + 3)?;
+        Some(((lr[2] as usize) << 4) | (lr[1] >> 4) as usize)
+    }
+
+    /// Read the header of the compression, returning the offset after it
+    fn set_sizes(&mut self, data: &[u8], mut offset: usize) -> Option<usize> {
+        self.flags = *data.get(offset)?;
+        offset += 1;
+
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            // The single value every position has
+            self.min_sym_len = *data.get(offset)?;
+            return Some(offset + 1);
+        }
+
+        let groups = self.group_len.iter().position(|&len| len == 0)?;
+        let table_size = self.group_idx[groups];
+
+        self.block_size = 1 << data.get(offset)?;
+        self.span = 1 << data.get(offset + 1)?;
+        self.sparse_index_size = table_size.div_ceil(self.span);
+        let padding = *data.get(offset + 2)? as u32;
+        self.num_blocks = read_u32(data, offset + 3)?;
+        self.block_length_size = self.num_blocks + padding;
+        let max_sym_len = *data.get(offset + 7)?;
+        self.min_sym_len = *data.get(offset + 8)?;
+        offset += 9;
+        self.lowest_sym = offset;
+
+        // Canonical Huffman codes: longer codes have lower values, so base64[l] is
+        // the lowest code of length min_sym_len + l, left aligned on 64 bits
+        let lengths = (max_sym_len.checked_sub(self.min_sym_len)? as usize) + 1;
+        self.base64 = vec![0; lengths];
+        for i in (0..lengths.saturating_sub(1)).rev() {
+            let lowest = read_u16(data, self.lowest_sym + 2 * i)? as u64;
+            let next_lowest = read_u16(data, self.lowest_sym + 2 * (i + 1))? as u64;
+            self.base64[i] = self.base64[i + 1]
+                .wrapping_add(lowest)
+                .wrapping_sub(next_lowest)
+                / 2;
+        }
+        for (i, base) in self.base64.iter_mut().enumerate() {
+            *base = shift_left(*base, 64 - i as i32 - self.min_sym_len as i32);
+        }
+        offset += lengths * 2;
+
+        // Recursive pairing: each symbol stands for a pair of symbols
+        let symbols = read_u16(data, offset)? as usize;
+        offset += 2;
+        self.btree = offset;
+        self.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                self.symlen[sym] = self.set_symlen(data, sym, &mut visited)?;
+            }
+        }
+
+        Some(offset + symbols * 3 + (symbols & 1))
+    }
+
+    /// Number of values (minus one) a symbol expands to
+    fn set_symlen(&mut self, data: &[u8], sym: usize, visited: &mut Vec<bool>) -> Option<u8> {
+        visited[sym] = true;
+        let right = self.right(data, sym)?;
+        if right == 0xfff {
+            return Some(0);
+        }
+        let left = self.left(data, sym)?;
+
+        if !*visited.get(left)? {
+            self.symlen[left] = self.set_symlen(data, left, visited)?;
+        }
+        if !*visited.get(right)? {
+            self.symlen[right] = self.set_symlen(data, right, visited)?;
+        }
+
+        Some(
+            self.symlen[left]
+                .wrapping_add(self.symlen[right])
+                .wrapping_add(1),
+        )
+    }
+
+    /// Get the value stored at an index
+    fn decompress(&self, data: &[u8], index: u64) -> Option<i32> {
+        if self.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(self.min_sym_len as i32);
+        }
+
+        // The sparse index points at the block and offset of index k * span + span / 2
+        let k = (index / self.span) as usize;
+        let entry = self.sparse_index + 6 * k;
+        let mut block = read_u32(data, entry)? as usize;
+        let mut offset = read_u16(data, entry + 4)? as i64;
+        offset += (index % self.span) as i64 - (self.span / 2) as i64;
+
+        // Move through the blocks until the one holding the index
+        let block_length = |block: usize| -> Option<i64> {
+            Some(read_u16(data, self.block_length + 2 * block)? as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        // Decode symbols from the start of the block until the one containing the value
+        let mut pointer = self.data + block * self.block_size as usize;
+        let mut buffer = read_u64_be(data, pointer)?;
+        pointer += 8;
+        let mut buffer_size = 64;
+        let min_sym_len = self.min_sym_len as i32;
+
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buffer < *self.base64.get(len)? {
+                len += 1;
+            }
+
+            sym = shift_right(buffer - self.base64[len], 64 - len as i32 - min_sym_len) as usize;
+            sym += read_u16(data, self.lowest_sym + 2 * len)? as usize;
+
+            let values = *self.symlen.get(sym)? as i64 + 1;
+            if offset < values {
+                break;
+            }
+            offset -= values;
+
+            let bits = len as i32 + min_sym_len;
+            buffer = shift_left(buffer, bits);
+            buffer_size -= bits;
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                buffer |= shift_left(read_u32_be(data, pointer)? as u64, 64 - buffer_size);
+                pointer += 4;
+            }
+        }
+
+        // Expand the pairs down to the leaf holding the value
+        while self.symlen[sym] != 0 {
+            let left = self.left(data, sym)?;
+            let left_values = *self.symlen.get(left)? as i64 + 1;
+            if offset < left_values {
+                sym = left;
+            } else {
+                offset -= left_values;
+                sym = self.right(data, sym)?;
+            }
+        }
+
+        Some(self.left(data, sym)? as i32)
+    }
+}
+
+impl Table {
+    fn load(path: &Path, kind: TableKind) -> Option<Self> {
+        let data = std::fs::read(path).ok()?;
+        let name = path.file_stem()?.to_str()?;
+        Self::parse(data, name, kind)
+    }
+
+    fn parse(data: Vec<u8>, name: &str, kind: TableKind) -> Option<Self> {
+        let magic = match kind {
+            TableKind::Wdl => WDL_MAGIC,
+            TableKind::Dtz => DTZ_MAGIC,
+        };
+        if data.get(..4)? != magic {
+            return None;
+        }
+
+        let material = Material::from_name(name)?;
+
+        // The leading color has the fewest pawns, white when equal
+        let white_pawns = material.white[5] as usize;
+        let black_pawns = material.black[5] as usize;
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads {
+            [white_pawns, black_pawns]
+        } else {
+            [black_pawns, white_pawns]
+        };
+
+        let mut table = Table {
+            kind,
+            data: Vec::new(),
+            piece_count: material.piece_count(),
+            has_pawns: material.has_pawns(),
+            has_unique_pieces: material.has_unique_pieces(),
+            symmetric: material.is_symmetric(),
+            pawn_count,
+            items: [Vec::new(), Vec::new()],
+        };
+        table.init(&data)?;
+        table.data = data;
+        Some(table)
+    }
+
+    fn get(&self, stm: usize, file: usize) -> Option<&PairsData> {
+        let side = if self.kind == TableKind::Wdl { stm } else { 0 };
+        self.items[side].get(if self.has_pawns { file } else { 0 })
+    }
+
+    fn init(&mut self, data: &[u8]) -> Option<()> {
+        const SPLIT: u8 = 1;
+        const HAS_PAWNS: u8 = 2;
+
+        let header = *data.get(4)?;
+        if (header & HAS_PAWNS != 0) != self.has_pawns
+            || (self.kind == TableKind::Wdl && (header & SPLIT != 0) == self.symmetric)
+        {
+            return None;
+        }
+
+        let sides = if self.kind == TableKind::Wdl && !self.symmetric {
+            2
+        } else {
+            1
+        };
+        let files = if self.has_pawns { 4 } else { 1 };
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let maps = maps();
+
+        for side in 0..sides {
+            self.items[side] = vec![PairsData::default(); files];
+        }
+
+        // Piece order and group encoding order of each sub-table
+        let mut offset = 5;
+        for file in 0..files {
+            let first = *data.get(offset)?;
+            let second = if both_pawns {
+                *data.get(offset + 1)?
+            } else {
+                0xff
+            };
+            let order = [
+                [(first & 0xf) as usize, (second & 0xf) as usize],
+                [(first >> 4) as usize, (second >> 4) as usize],
+            ];
+            offset += 1 + both_pawns as usize;
+
+            for k in 0..self.piece_count {
+                let byte = *data.get(offset)?;
+                for side in 0..sides {
+                    self.items[side][file].pieces[k] =
+                        if side == 0 { byte & 0xf } else { byte >> 4 };
+                }
+                offset += 1;
+            }
+
+            for (side, &order) in order.iter().enumerate().take(sides) {
+                let mut pairs = std::mem::take(&mut self.items[side][file]);
+                self.set_groups(&mut pairs, order, file, maps);
+                self.items[side][file] = pairs;
+            }
+        }
+        offset += offset & 1;
+
+        for file in 0..files {
+            for side in 0..sides {
+                offset = self.items[side][file].set_sizes(data, offset)?;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            offset = self.set_dtz_map(data, offset, files)?;
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let pairs = &mut self.items[side][file];
+                pairs.sparse_index = offset;
+                offset += pairs.sparse_index_size as usize * 6;
+            }
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let pairs = &mut self.items[side][file];
+                pairs.block_length = offset;
+                offset += pairs.block_length_size as usize * 2;
+            }
+        }
+
+        for file in 0..files {
+            for side in 0..sides {
+                let pairs = &mut self.items[side][file];
+                offset = (offset + 0x3f) & !0x3f;
+                pairs.data = offset;
+                offset += pairs.num_blocks as usize * pairs.block_size as usize;
+            }
+        }
+
+        Some(())
+    }
+
+    /// Split the pieces into groups encoded together and compute the factor of each group
+    fn set_groups(&self, pairs: &mut PairsData, order: [usize; 2], file: usize, maps: &Maps) {
+        let mut n = 0;
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        pairs.group_len[0] = 1;
+
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || pairs.pieces[i] == pairs.pieces[i - 1] {
+                pairs.group_len[n] += 1;
+            } else {
+                n += 1;
+                pairs.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        pairs.group_len[n] = 0;
+
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares =
+            64 - pairs.group_len[0] - if both_pawns { pairs.group_len[1] } else { 0 };
+        let mut index: u64 = 1;
+
+        let mut k = 0;
+        while next < n || k == order[0] || k == order[1] {
+            if k == order[0] {
+                // Leading pawns or pieces
+                pairs.group_idx[0] = index;
+                index *= if self.has_pawns {
+                    maps.lead_pawns_size[pairs.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                // Remaining pawns
+                pairs.group_idx[1] = index;
+                index *= maps.binomial[pairs.group_len[1]][48 - pairs.group_len[0]];
+            } else {
+                // Remaining pieces
+                pairs.group_idx[next] = index;
+                index *= maps.binomial[pairs.group_len[next]][free_squares];
+                free_squares -= pairs.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        pairs.group_idx[n] = index;
+    }
+
+    /// DTZ values are stored by frequency, the maps give back the real values
+    fn set_dtz_map(&mut self, data: &[u8], mut offset: usize, files: usize) -> Option<usize> {
+        for file in 0..files {
+            let pairs = &mut self.items[0][file];
+            if pairs.flags & FLAG_MAPPED == 0 {
+                continue;
+            }
+
+            if pairs.flags & FLAG_WIDE != 0 {
+                offset += offset & 1;
+                for i in 0..4 {
+                    pairs.map_idx[i] = offset + 2;
+                    offset += 2 * read_u16(data, offset)? as usize + 2;
+                }
+            } else {
+                for i in 0..4 {
+                    pairs.map_idx[i] = offset + 1;
+                    offset += *data.get(offset)? as usize + 1;
+                }
+            }
+        }
+
+        Some(offset + (offset & 1))
+    }
+
+    /// Look up the value of a position. For DTZ tables `None` means the table
+    /// stores the other side to move, or the file is corrupt
+    fn probe(&self, board: &Board, black_stronger: bool, wdl: Wdl) -> Option<i32> {
+        let (stm, table_file, index) = self.index(board, black_stronger)?;
+
+        // DTZ tables only store one side to move
+        if self.kind == TableKind::Dtz {
+            let flags = self.get(stm, table_file)?.flags;
+            let both_sides = self.symmetric && !self.has_pawns;
+            if (flags & FLAG_STM) as usize != stm && !both_sides {
+                return None;
+            }
+        }
+
+        let value = self.get(stm, table_file)?.decompress(&self.data, index)?;
+        self.map_score(table_file, value, wdl)
+    }
+
+    /// Side to move and leading pawn file of the sub-table holding a
+    /// position, with the index of the position in it
+    fn index(&self, board: &Board, black_stronger: bool) -> Option<(usize, usize, u64)> {
+        let maps = maps();
+        let side_to_move = match board.side_to_move() {
+            Color::White => 0,
+            Color::Black => 1,
+        };
+
+        // Tables are stored with the stronger side as white, and symmetric ones
+        // with white to move, so positions may need colors and ranks flipped
+        let flip = black_stronger || (self.symmetric && side_to_move == 1);
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let stm = side_to_move ^ flip as usize;
+
+        // (square, piece code) of every piece, P=1 .. K=6 plus 8 for black
+        let mut all_pieces = Vec::with_capacity(TB_PIECES);
+        for square in 0..64 {
+            let board_square = Square::new((square / 8) as u8, (square % 8) as u8).ok()?;
+            if let Some(piece) = board.get_piece(board_square) {
+                let code = match piece.piece_type {
+                    PieceType::Pawn => 1,
+                    PieceType::Knight => 2,
+                    PieceType::Bishop => 3,
+                    PieceType::Rook => 4,
+                    PieceType::Queen => 5,
+                    PieceType::King => 6,
+                } + if piece.color == Color::Black { 8 } else { 0 };
+                all_pieces.push((square, code));
+            }
+        }
+
+        let mut squares = [0usize; TB_PIECES];
+        let mut pieces = [0u8; TB_PIECES];
+        let mut size = 0;
+        let mut lead_pawns_count = 0;
+        let mut lead_pawn_code = None;
+        let mut table_file = 0;
+
+        if self.has_pawns {
+            // Pawns of the reference color lead, the one with the highest
+            // map_pawns value picks the sub-table
+            let code = self.items[0].first()?.pieces[0] ^ flip_color;
+            lead_pawn_code = Some(code);
+            for &(square, piece) in &all_pieces {
+                if piece == code {
+                    squares[size] = square ^ flip_squares;
+                    pieces[size] = code ^ flip_color;
+                    size += 1;
+                }
+            }
+            lead_pawns_count = size;
+
+            let lead = (0..lead_pawns_count)
+                .max_by_key(|&i| (maps.map_pawns[squares[i]], std::cmp::Reverse(i)))?;
+            squares.swap(0, lead);
+            table_file = file_of(squares[0]).min(7 - file_of(squares[0]));
+        }
+
+        for &(square, piece) in &all_pieces {
+            if Some(piece) == lead_pawn_code {
+                continue;
+            }
+            squares[size] = square ^ flip_squares;
+            pieces[size] = piece ^ flip_color;
+            size += 1;
+        }
+
+        let pairs = self.get(stm, table_file)?;
+
+        // Same piece order as the table
+        for i in lead_pawns_count..size.saturating_sub(1) {
+            for j in (i + 1)..size {
+                if pairs.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // The leading piece goes to the a-d files
+        if file_of(squares[0]) > 3 {
+            for square in squares.iter_mut().take(size) {
+                *square = flip_file(*square);
+            }
+        }
+
+        let mut index: u64;
+        if self.has_pawns {
+            index = maps.lead_pawn_idx[lead_pawns_count][squares[0]];
+            squares[1..lead_pawns_count].sort_by_key(|&square| maps.map_pawns[square]);
+            for (i, &square) in squares.iter().enumerate().take(lead_pawns_count).skip(1) {
+                index += maps.binomial[i][maps.map_pawns[square] as usize];
+            }
+        } else {
+            // Without pawns, the leading piece also goes to ranks 1-4
+            if rank_of(squares[0]) > 3 {
+                for square in squares.iter_mut().take(size) {
+                    *square = flip_rank(*square);
+                }
+            }
+
+            // And the first leading piece off the diagonal goes below it
+            for i in 0..pairs.group_len[0] {
+                if off_a1h8(squares[i]) == 0 {
+                    continue;
+                }
+                if off_a1h8(squares[i]) > 0 {
+                    for square in squares.iter_mut().take(size).skip(i) {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+
+            index = if self.has_unique_pieces {
+                let s = &squares;
+                let adjust1 = (s[1] > s[0]) as u64;
+                let adjust2 = (s[2] > s[0]) as u64 + (s[2] > s[1]) as u64;
+
+                if off_a1h8(s[0]) != 0 {
+                    (maps.map_a1d1d4[s[0]] * 63 + (s[1] as u64 - adjust1)) * 62 + s[2] as u64
+                        - adjust2
+                } else if off_a1h8(s[1]) != 0 {
+                    (6 * 63 + rank_of(s[0]) as u64 * 28 + maps.map_b1h1h7[s[1]]) * 62 + s[2] as u64
+                        - adjust2
+                } else if off_a1h8(s[2]) != 0 {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + rank_of(s[0]) as u64 * 7 * 28
+                        + (rank_of(s[1]) as u64 - adjust1) * 28
+                        + maps.map_b1h1h7[s[2]]
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + rank_of(s[0]) as u64 * 7 * 6
+                        + (rank_of(s[1]) as u64 - adjust1) * 6
+                        + (rank_of(s[2]) as u64 - adjust2)
+                }
+            } else {
+                maps.map_kk[maps.map_a1d1d4[squares[0]] as usize][squares[1]]
+            };
+        }
+
+        // Remaining groups, each encoded as a combination of the squares left
+        index *= pairs.group_idx[0];
+        let mut group_start = pairs.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+
+        while pairs.group_len[next] != 0 {
+            let group_end = group_start + pairs.group_len[next];
+            squares[group_start..group_end].sort_unstable();
+
+            let mut n = 0;
+            for i in 0..pairs.group_len[next] {
+                let square = squares[group_start + i];
+                let adjust = squares[..group_start]
+                    .iter()
+                    .filter(|&&s| square > s)
+                    .count();
+                let available = square - adjust - if remaining_pawns { 8 } else { 0 };
+                n += maps.binomial[i + 1][available];
+            }
+
+            remaining_pawns = false;
+            index += n * pairs.group_idx[next];
+            group_start = group_end;
+            next += 1;
+        }
+
+        Some((stm, table_file, index))
+    }
+
+    fn map_score(&self, file: usize, value: i32, wdl: Wdl) -> Option<i32> {
+        if self.kind == TableKind::Wdl {
+            return Some(value - 2);
+        }
+
+        let pairs = self.get(0, file)?;
+        let map_index = match wdl {
+            Wdl::Win | Wdl::Draw => 0,
+            Wdl::Loss => 1,
+            Wdl::CursedWin => 2,
+            Wdl::BlessedLoss => 3,
+        };
+
+        let mut value = value;
+        if pairs.flags & FLAG_MAPPED != 0 {
+            let start = pairs.map_idx[map_index];
+            value = if pairs.flags & FLAG_WIDE != 0 {
+                read_u16(&self.data, start + 2 * value as usize)? as i32
+            } else {
+                *self.data.get(start + value as usize)? as i32
+            };
+        }
+
+        // Values may be stored in moves rather than plies
+        if (wdl == Wdl::Win && pairs.flags & FLAG_WIN_PLIES == 0)
+            || (wdl == Wdl::Loss && pairs.flags & FLAG_LOSS_PLIES == 0)
+            || wdl == Wdl::CursedWin
+            || wdl == Wdl::BlessedLoss
+        {
+            value *= 2;
+        }
+
+        Some(value + 1)
+    }
+}
+
+// The fixtures are the real KQvK, KRvK, KPvK and KPvKP tables from the
+// Syzygy distribution, checked against the endgame table generator
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CastlingRights, Dtm, EndgameTables, Piece};
+
+    /// Real tables the probing tests read
+    const FIXTURES: [&str; 7] = [
+        "KQvK.rtbw",
+        "KQvK.rtbz",
+        "KRvK.rtbw",
+        "KRvK.rtbz",
+        "KPvK.rtbw",
+        "KPvK.rtbz",
+        "KPvKP.rtbw",
+    ];
+
+    fn fixture_directory() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/syzygy")
+    }
+
+    fn fixture_tablebase() -> SyzygyTablebase {
+        for name in FIXTURES {
+            assert!(
+                fixture_directory().join(name).is_file(),
+                "missing fixture table {}",
+                name
+            );
+        }
+        let mut tablebase = SyzygyTablebase::new();
+        assert_eq!(tablebase.add_directory(fixture_directory()).unwrap(), 4);
+        tablebase
+    }
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    /// Every placement of the pieces of a material with either side to move
+    fn positions(material: &Material) -> Vec<Board> {
+        let mut pieces = Vec::new();
+        for (side, color) in [
+            (&material.white, Color::White),
+            (&material.black, Color::Black),
+        ] {
+            for (&count, &piece_type) in side.iter().zip(&MATERIAL_ORDER) {
+                for _ in 0..count {
+                    pieces.push(Piece::new(color, piece_type));
+                }
+            }
+        }
+
+        let mut boards = Vec::new();
+        let mut squares = Vec::new();
+        place(&pieces, &mut squares, &mut boards);
+        boards
+    }
+
+    fn place(pieces: &[Piece], squares: &mut Vec<Square>, boards: &mut Vec<Board>) {
+        if squares.len() == pieces.len() {
+            for color in [Color::White, Color::Black] {
+                let mut board = Board::new();
+                board.set_castling_rights(CastlingRights {
+                    white_king_side: false,
+                    white_queen_side: false,
+                    black_king_side: false,
+                    black_queen_side: false,
+                });
+                for (&piece, &square) in pieces.iter().zip(squares.iter()) {
+                    board.set_piece(square, Some(piece));
+                }
+                board.set_side_to_move(color);
+                boards.push(board);
+            }
+            return;
+        }
+
+        let piece = pieces[squares.len()];
+        for index in 0..64 {
+            let square = Square::new(index / 8, index % 8).unwrap();
+            let back_rank = square.rank() == 0 || square.rank() == 7;
+            if squares.contains(&square) || (piece.piece_type == PieceType::Pawn && back_rank) {
+                continue;
+            }
+            squares.push(square);
+            place(pieces, squares, boards);
+            squares.pop();
+        }
+    }
+
+    /// WDL and DTZ of a position from its distance to mate, which is also the
+    /// distance to zeroing without pawns
+    fn expected(dtm: Dtm) -> (Wdl, i32) {
+        match dtm {
+            Dtm::Win(plies) => (Wdl::Win, plies as i32),
+            Dtm::Loss(plies) => (Wdl::Loss, -(plies.max(1) as i32)),
+            Dtm::Draw => (Wdl::Draw, 0),
+        }
+    }
+
+    /// The same position with the colors swapped and the board turned over
+    fn mirrored(board: &Board) -> Board {
+        let mut mirrored = board.clone();
+        for index in 0..64 {
+            let square = Square::new(index / 8, index % 8).unwrap();
+            let piece = board
+                .get_piece(square)
+                .map(|piece| Piece::new(piece.color.opposite(), piece.piece_type));
+            mirrored.set_piece(Square::new(7 - index / 8, index % 8).unwrap(), piece);
+        }
+        mirrored.set_side_to_move(board.side_to_move().opposite());
+        mirrored
+    }
+
+    #[test]
+    #[ignore = "needs the real Syzygy tables in fixtures/syzygy"]
+    fn probes_match_the_endgame_tables() {
+        let tablebase = fixture_tablebase();
+        let mut tables = EndgameTables::new();
+        for (name, with_dtz) in [("KQvK", true), ("KRvK", true), ("KPvK", false)] {
+            tables.generate(name).unwrap();
+            let material = Material::from_name(name).unwrap();
+            for board in positions(&material).into_iter().step_by(211) {
+                let Ok(dtm) = tables.probe(&board) else {
+                    continue;
+                };
+                let (wdl, dtz) = expected(dtm);
+                for board in [mirrored(&board), board] {
+                    assert_eq!(tablebase.probe_wdl(&board), Ok(wdl), "{:?}", board);
+                    if with_dtz {
+                        assert_eq!(tablebase.probe_dtz(&board), Ok(dtz), "{:?}", board);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "needs the real Syzygy tables in fixtures/syzygy"]
+    fn probe_known_positions() {
+        let tablebase = fixture_tablebase();
+
+        // Mate in one, with the mating move first
+        let mate = board("k7/8/1K6/8/8/8/8/7Q w - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&mate), Ok(Wdl::Win));
+        assert_eq!(tablebase.probe_dtz(&mate), Ok(1));
+        let best = tablebase.best_moves(&mate).unwrap()[0];
+        assert_eq!((best.wdl, best.dtz), (Wdl::Win, 1));
+        let mut after = mate.clone();
+        after.apply_move(best.mv);
+        assert!(after.is_checkmated(Color::Black));
+
+        let rook_mate = board("k7/8/1K6/8/8/8/8/7R w - - 0 1");
+        assert_eq!(tablebase.probe_dtz(&rook_mate), Ok(1));
+        let mated = board("k6R/8/1K6/8/8/8/8/8 b - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&mated), Ok(Wdl::Loss));
+
+        let stalemate = board("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&stalemate), Ok(Wdl::Draw));
+        assert_eq!(tablebase.probe_dtz(&stalemate), Ok(0));
+        let hanging_queen = board("8/8/8/8/8/2k5/2Q5/6K1 b - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&hanging_queen), Ok(Wdl::Draw));
+
+        let bare_kings = board("8/8/8/8/8/8/8/K1k5 w - - 0 1");
+        assert_eq!(tablebase.probe_wdl(&bare_kings), Ok(Wdl::Draw));
+        assert!(tablebase
+            .probe_wdl(&board("r3k3/8/8/8/8/8/8/4K3 w q - 0 1"))
+            .is_err());
+        assert!(tablebase
+            .probe_wdl(&board("4k3/8/8/8/8/8/8/RN2K3 w - - 0 1"))
+            .is_err());
+    }
+
+    #[test]
+    #[ignore = "needs the real Syzygy tables in fixtures/syzygy"]
+    fn probe_en_passant() {
+        let tablebase = fixture_tablebase();
+
+        // Tables store positions without en passant rights, here a draw, while
+        // taking en passant wins
+        let en_passant = board("8/6K1/1k6/5pP1/8/8/8/8 w - f6 0 1");
+        let mut no_en_passant = en_passant.clone();
+        no_en_passant.set_en_passant(None);
+        assert_eq!(tablebase.probe_wdl(&no_en_passant), Ok(Wdl::Draw));
+        assert_eq!(tablebase.probe_wdl(&en_passant), Ok(Wdl::Win));
+        assert_eq!(tablebase.probe_dtz(&en_passant), Ok(1));
+
+        let best = tablebase.best_moves(&en_passant).unwrap()[0];
+        assert_eq!(best.mv, en_passant.parse_uci_move("g5f6").unwrap());
+        assert_eq!((best.wdl, best.dtz), (Wdl::Win, 1));
+    }
+
+    #[test]
+    fn unreadable_tables() {
+        let mut header = WDL_MAGIC.to_vec();
+        assert!(Table::parse(header.clone(), "KQvK", TableKind::Dtz).is_none());
+        assert!(Table::parse(vec![0; 64], "KQvK", TableKind::Wdl).is_none());
+        // A header cut short after the magic bytes
+        header.push(0);
+        assert!(Table::parse(header, "KQvK", TableKind::Wdl).is_none());
+    }
+}
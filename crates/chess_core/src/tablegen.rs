@@ -0,0 +1,965 @@
+// Endgame tables generated by retrograde analysis
+//
+// Every placement of the pieces of a material signature like `KRvKP`, with
+// either side to move, gets an index: the side to move followed by the squares
+// of the pieces as base-64 digits, in the order white king, white pieces from
+// the queen down to the pawns, then the same for Black. Generation starts from
+// the mates and works backwards one ply at a time through the moves that lead
+// to the positions found so far, which gives each position its exact distance
+// to mate. Captures and promotions leave the table and are looked up in the
+// smaller tables, generated first. Tables know nothing of castling and en
+// passant.
+//
+// Saved tables hold the magic `SCTB`, a version byte, the length and name of
+// the signature, the number of positions as a little-endian u32, then runs of
+// equal values: the value byte followed by the length of the run as a LEB128
+// number. A value is 0 for a draw, 255 for an impossible placement, otherwise
+// the distance to mate in plies plus one, odd distances being wins for the side
+// to move and even ones losses.
+
+use crate::syzygy::has_castling_rights;
+use crate::{Board, Color, Move, PieceType, Square};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Largest number of pieces, kings included, a generated table can hold
+pub const MAX_TABLE_PIECES: usize = 4;
+
+const MAGIC: [u8; 4] = *b"SCTB";
+const VERSION: u8 = 1;
+const EXTENSION: &str = "sctb";
+
+const DRAW: u8 = 0;
+const ILLEGAL: u8 = 255;
+/// Longest distance to mate a value can hold
+const MAX_PLIES: usize = 253;
+/// Marks positions a capture or promotion keeps from being lost
+const ESCAPE: u8 = 255;
+
+const MATERIAL_ORDER: [PieceType; 6] = [
+    PieceType::King,
+    PieceType::Queen,
+    PieceType::Rook,
+    PieceType::Bishop,
+    PieceType::Knight,
+    PieceType::Pawn,
+];
+const MATERIAL_LETTERS: [char; 6] = ['K', 'Q', 'R', 'B', 'N', 'P'];
+
+/// Distance to mate with perfect play for the side to move, in plies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dtm {
+    Win(u32),
+    Loss(u32),
+    Draw,
+}
+
+impl Dtm {
+    fn from_value(value: u8) -> Option<Self> {
+        match value {
+            DRAW => Some(Dtm::Draw),
+            ILLEGAL => None,
+            _ => {
+                let plies = value as u32 - 1;
+                Some(if plies % 2 == 1 {
+                    Dtm::Win(plies)
+                } else {
+                    Dtm::Loss(plies)
+                })
+            }
+        }
+    }
+
+    /// Moves until mate, counting the mating move
+    pub fn moves(self) -> u32 {
+        match self {
+            Dtm::Win(plies) => plies.div_ceil(2),
+            Dtm::Loss(plies) => plies / 2,
+            Dtm::Draw => 0,
+        }
+    }
+
+    /// The result for the side that played the move leading to this one
+    pub fn before_move(self) -> Self {
+        match self {
+            Dtm::Win(plies) => Dtm::Loss(plies + 1),
+            Dtm::Loss(plies) => Dtm::Win(plies + 1),
+            Dtm::Draw => Dtm::Draw,
+        }
+    }
+
+    /// Higher for results the side to move prefers: faster wins, slower losses
+    fn preference(self) -> i64 {
+        match self {
+            Dtm::Win(plies) => i64::MAX - plies as i64,
+            Dtm::Draw => 0,
+            Dtm::Loss(plies) => i64::MIN + plies as i64,
+        }
+    }
+}
+
+/// Counts of the positions of a table, with either side to move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableSummary {
+    pub wins: u64,
+    pub draws: u64,
+    pub losses: u64,
+    /// Longest win in the table, in plies
+    pub longest_mate: u32,
+}
+
+/// Generated tables, by material
+pub struct EndgameTables {
+    tables: HashMap<Material, Vec<u8>>,
+}
+
+impl Default for EndgameTables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EndgameTables {
+    pub fn new() -> Self {
+        Self {
+            tables: HashMap::new(),
+        }
+    }
+
+    /// Generate the table of a signature like `KRvKP`, and the smaller tables it
+    /// needs for captures and promotions, unless they are already there
+    pub fn generate(&mut self, name: &str) -> Result<(), &'static str> {
+        let material = Material::from_name(name).ok_or("Invalid material signature\n")?;
+        if material.piece_count() > MAX_TABLE_PIECES {
+            return Err("Too many pieces to generate a table\n");
+        }
+        self.generate_material(material.canonical().0)
+    }
+
+    fn generate_material(&mut self, material: Material) -> Result<(), &'static str> {
+        if material.piece_count() == 2 || self.tables.contains_key(&material) {
+            return Ok(());
+        }
+        for successor in material.successors() {
+            self.generate_material(successor.canonical().0)?;
+        }
+        let values = Generator::new(material, self).run()?;
+        self.tables.insert(material, values);
+        Ok(())
+    }
+
+    /// Names of the tables that are there, like `KRvKP`
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.tables.keys().map(Material::name).collect();
+        names.sort_by_key(|name| (name.len(), name.clone()));
+        names
+    }
+
+    /// Counts of the positions of a table
+    pub fn summary(&self, name: &str) -> Option<TableSummary> {
+        let material = Material::from_name(name)?.canonical().0;
+        let values = self.tables.get(&material)?;
+        let mut summary = TableSummary {
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            longest_mate: 0,
+        };
+        for &value in values {
+            match Dtm::from_value(value) {
+                Some(Dtm::Win(plies)) => {
+                    summary.wins += 1;
+                    summary.longest_mate = summary.longest_mate.max(plies);
+                }
+                Some(Dtm::Loss(_)) => summary.losses += 1,
+                Some(Dtm::Draw) => summary.draws += 1,
+                None => {}
+            }
+        }
+        Some(summary)
+    }
+
+    /// Check whether the tables can answer probes for a position
+    pub fn covers(&self, board: &Board) -> bool {
+        self.check_probe(board).is_ok()
+    }
+
+    /// Probe the distance to mate of a position for the side to move
+    pub fn probe(&self, board: &Board) -> Result<Dtm, &'static str> {
+        let position = self.check_probe(board)?;
+        Dtm::from_value(self.value(&position)).ok_or("Illegal position\n")
+    }
+
+    /// Get every legal move with its result for the side playing it, best moves first
+    pub fn best_moves(&self, board: &Board) -> Result<Vec<(Move, Dtm)>, &'static str> {
+        self.check_probe(board)?;
+
+        let mut moves = Vec::new();
+        for mv in board.get_all_valid_moves(board.side_to_move()) {
+            let mut new_board = board.clone();
+            new_board.apply_move(mv);
+            moves.push((mv, self.probe(&new_board)?.before_move()));
+        }
+
+        moves.sort_by_key(|&(_, dtm)| std::cmp::Reverse(dtm.preference()));
+        Ok(moves)
+    }
+
+    fn check_probe(&self, board: &Board) -> Result<Position, &'static str> {
+        if has_castling_rights(board) {
+            return Err("Endgame tables do not cover positions with castling rights\n");
+        }
+        let color = board.side_to_move();
+        if board.en_passant().is_some() {
+            // A pawn moving sideways onto an empty square takes en passant
+            let can_capture = board.get_all_valid_moves(color).iter().any(|mv| {
+                mv.from.file() != mv.to.file()
+                    && board.get_piece(mv.to).is_none()
+                    && board
+                        .get_piece(mv.from)
+                        .is_some_and(|piece| piece.piece_type == PieceType::Pawn)
+            });
+            if can_capture {
+                return Err("Endgame tables do not cover en passant captures\n");
+            }
+        }
+
+        let mut position = Position::empty(color);
+        for rank in 0..8 {
+            for file in 0..8 {
+                let square = Square::new(rank, file).expect("always valid");
+                if let Some(piece) = board.get_piece(square) {
+                    if position.len == MAX_TABLE_PIECES {
+                        return Err("Too many pieces for the endgame tables\n");
+                    }
+                    position.push(Man {
+                        color: piece.color,
+                        piece_type: piece.piece_type,
+                        square: rank * 8 + file,
+                    });
+                }
+            }
+        }
+
+        let material = Material::from_position(&position);
+        if material.counts[0][0] != 1 || material.counts[1][0] != 1 {
+            return Err("Endgame tables need one king of each color\n");
+        }
+        if material.piece_count() > 2 && !self.tables.contains_key(&material.canonical().0) {
+            return Err("No endgame table for this material\n");
+        }
+        Ok(position)
+    }
+
+    /// Stored value of a position, kings alone being a draw
+    fn value(&self, position: &Position) -> u8 {
+        let (material, index) = position.canonical_index();
+        match self.tables.get(&material) {
+            Some(values) => values[index],
+            None => DRAW,
+        }
+    }
+
+    /// Save every table to a directory, returning how many were written
+    pub fn save_directory<P: AsRef<Path>>(&self, path: P) -> std::io::Result<usize> {
+        std::fs::create_dir_all(&path)?;
+        for (material, values) in &self.tables {
+            let file = path
+                .as_ref()
+                .join(format!("{}.{}", material.name(), EXTENSION));
+            std::fs::write(file, encode_table(material, values))?;
+        }
+        Ok(self.tables.len())
+    }
+
+    /// Load every table of a directory, returning how many were read
+    pub fn add_directory<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<usize> {
+        let mut found = 0;
+
+        for entry in std::fs::read_dir(path)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            if let Ok((material, values)) = decode_table(&std::fs::read(&path)?) {
+                self.tables.insert(material, values);
+                found += 1;
+            }
+        }
+
+        Ok(found)
+    }
+}
+
+fn encode_table(material: &Material, values: &[u8]) -> Vec<u8> {
+    let name = material.name();
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.push(name.len() as u8);
+    bytes.extend_from_slice(name.as_bytes());
+    bytes.extend_from_slice(&(values.len() as u32).to_le_bytes());
+
+    for run in values.chunk_by(|a, b| a == b) {
+        bytes.push(run[0]);
+        let mut length = run.len();
+        loop {
+            let low = (length & 0x7f) as u8;
+            length >>= 7;
+            if length == 0 {
+                bytes.push(low);
+                break;
+            }
+            bytes.push(low | 0x80);
+        }
+    }
+    bytes
+}
+
+fn decode_table(bytes: &[u8]) -> Result<(Material, Vec<u8>), &'static str> {
+    let mut reader = bytes.iter().copied();
+    let mut next = || reader.next().ok_or("Truncated endgame table\n");
+
+    let magic = [next()?, next()?, next()?, next()?];
+    if magic != MAGIC || next()? != VERSION {
+        return Err("Not an endgame table\n");
+    }
+    let name: Vec<u8> = (0..next()?).map(|_| next()).collect::<Result<_, _>>()?;
+    let material = std::str::from_utf8(&name)
+        .ok()
+        .and_then(Material::from_name)
+        .filter(|material| {
+            material.piece_count() <= MAX_TABLE_PIECES && material.canonical().0 == *material
+        })
+        .ok_or("Invalid material signature\n")?;
+    let size = u32::from_le_bytes([next()?, next()?, next()?, next()?]) as usize;
+    if size != material.table_size() {
+        return Err("Endgame table has the wrong size\n");
+    }
+
+    let mut values = Vec::with_capacity(size);
+    while values.len() < size {
+        let value = next()?;
+        let mut length = 0usize;
+        for shift in (0..).step_by(7) {
+            let byte = next()?;
+            if shift > 28 {
+                return Err("Invalid run in endgame table\n");
+            }
+            length |= ((byte & 0x7f) as usize) << shift;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        if length > size - values.len() {
+            return Err("Invalid run in endgame table\n");
+        }
+        values.resize(values.len() + length, value);
+    }
+    Ok((material, values))
+}
+
+/// Number of pieces of each type, in `MATERIAL_ORDER`, white first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Material {
+    counts: [[u8; 6]; 2],
+}
+
+impl Material {
+    fn from_name(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let mut counts = [[0; 6]; 2];
+        for (side, letters) in [white, black].iter().enumerate() {
+            if !letters.starts_with('K') {
+                return None;
+            }
+            for letter in letters.chars() {
+                let index = MATERIAL_LETTERS.iter().position(|&l| l == letter)?;
+                counts[side][index] += 1;
+            }
+            if counts[side][0] != 1 {
+                return None;
+            }
+        }
+        Some(Self { counts })
+    }
+
+    fn from_position(position: &Position) -> Self {
+        let mut counts = [[0; 6]; 2];
+        for man in position.men() {
+            counts[color_index(man.color)][material_index(man.piece_type)] += 1;
+        }
+        Self { counts }
+    }
+
+    fn name(&self) -> String {
+        let side = |counts: &[u8; 6]| -> String {
+            counts
+                .iter()
+                .zip(MATERIAL_LETTERS)
+                .flat_map(|(&count, letter)| std::iter::repeat_n(letter, count as usize))
+                .collect()
+        };
+        format!("{}v{}", side(&self.counts[0]), side(&self.counts[1]))
+    }
+
+    fn piece_count(&self) -> usize {
+        self.counts
+            .iter()
+            .flatten()
+            .map(|&count| count as usize)
+            .sum()
+    }
+
+    fn table_size(&self) -> usize {
+        2 << (6 * self.piece_count())
+    }
+
+    /// The material with the stronger side as White, and whether colors were swapped
+    fn canonical(&self) -> (Self, bool) {
+        let strength = |counts: &[u8; 6]| (counts.iter().sum::<u8>(), *counts);
+        if strength(&self.counts[1]) > strength(&self.counts[0]) {
+            let counts = [self.counts[1], self.counts[0]];
+            (Self { counts }, true)
+        } else {
+            (*self, false)
+        }
+    }
+
+    /// Pieces in table order
+    fn pieces(&self) -> Vec<(Color, PieceType)> {
+        [Color::White, Color::Black]
+            .into_iter()
+            .flat_map(|color| {
+                MATERIAL_ORDER
+                    .iter()
+                    .zip(self.counts[color_index(color)])
+                    .flat_map(move |(&piece_type, count)| {
+                        std::iter::repeat_n((color, piece_type), count as usize)
+                    })
+            })
+            .collect()
+    }
+
+    /// Materials reached by one capture or promotion
+    fn successors(&self) -> Vec<Self> {
+        let mut successors = Vec::new();
+        for side in 0..2 {
+            for index in 1..6 {
+                if self.counts[side][index] == 0 {
+                    continue;
+                }
+                let mut captured = *self;
+                captured.counts[side][index] -= 1;
+                successors.push(captured);
+            }
+            if self.counts[side][5] > 0 {
+                for index in 1..5 {
+                    let mut promoted = *self;
+                    promoted.counts[side][5] -= 1;
+                    promoted.counts[side][index] += 1;
+                    successors.push(promoted);
+                }
+            }
+        }
+        successors
+    }
+}
+
+fn color_index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+fn material_index(piece_type: PieceType) -> usize {
+    MATERIAL_ORDER
+        .iter()
+        .position(|&t| t == piece_type)
+        .expect("all piece types listed")
+}
+
+/// A piece on a square numbered from a1 = 0 to h8 = 63
+#[derive(Debug, Clone, Copy)]
+struct Man {
+    color: Color,
+    piece_type: PieceType,
+    square: u8,
+}
+
+impl Man {
+    /// Whether the piece attacks a square, the squares in between being checked
+    /// against `occupied`
+    fn attacks(&self, target: u8, occupied: u64) -> bool {
+        let rank_delta = (target / 8) as i8 - (self.square / 8) as i8;
+        let file_delta = (target % 8) as i8 - (self.square % 8) as i8;
+        let (ranks, files) = (rank_delta.abs(), file_delta.abs());
+        match self.piece_type {
+            PieceType::Pawn => files == 1 && rank_delta == pawn_direction(self.color),
+            PieceType::Knight => ranks * files == 2,
+            PieceType::King => ranks.max(files) == 1,
+            PieceType::Bishop => ranks == files && ranks != 0 && self.path_clear(target, occupied),
+            PieceType::Rook => (ranks == 0) != (files == 0) && self.path_clear(target, occupied),
+            PieceType::Queen => {
+                (ranks == files || ranks == 0 || files == 0)
+                    && ranks + files != 0
+                    && self.path_clear(target, occupied)
+            }
+        }
+    }
+
+    fn path_clear(&self, target: u8, occupied: u64) -> bool {
+        let rank_step = ((target / 8) as i8 - (self.square / 8) as i8).signum();
+        let file_step = ((target % 8) as i8 - (self.square % 8) as i8).signum();
+        let step = rank_step * 8 + file_step;
+        let mut square = self.square as i8 + step;
+        while square != target as i8 {
+            if occupied & (1 << square) != 0 {
+                return false;
+            }
+            square += step;
+        }
+        true
+    }
+
+    /// Squares a knight, bishop, rook, queen or king moves to, stopping at and
+    /// including the first occupied square of each direction
+    fn for_each_target(&self, occupied: u64, mut f: impl FnMut(u8)) {
+        let sliding = matches!(
+            self.piece_type,
+            PieceType::Bishop | PieceType::Rook | PieceType::Queen
+        );
+        for &(rank_step, file_step) in self.piece_type.get_offsets() {
+            let mut rank = (self.square / 8) as i8;
+            let mut file = (self.square % 8) as i8;
+            loop {
+                rank += rank_step;
+                file += file_step;
+                if !(0..8).contains(&rank) || !(0..8).contains(&file) {
+                    break;
+                }
+                let square = (rank * 8 + file) as u8;
+                f(square);
+                if !sliding || occupied & (1 << square) != 0 {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+fn pawn_direction(color: Color) -> i8 {
+    match color {
+        Color::White => 1,
+        Color::Black => -1,
+    }
+}
+
+/// Pieces of a table position, side to move included
+#[derive(Debug, Clone, Copy)]
+struct Position {
+    men: [Man; MAX_TABLE_PIECES],
+    len: usize,
+    side_to_move: Color,
+}
+
+impl Position {
+    fn empty(side_to_move: Color) -> Self {
+        let man = Man {
+            color: Color::White,
+            piece_type: PieceType::King,
+            square: 0,
+        };
+        Self {
+            men: [man; MAX_TABLE_PIECES],
+            len: 0,
+            side_to_move,
+        }
+    }
+
+    fn men(&self) -> &[Man] {
+        &self.men[..self.len]
+    }
+
+    fn push(&mut self, man: Man) {
+        self.men[self.len] = man;
+        self.len += 1;
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.men.copy_within(index + 1..self.len, index);
+        self.len -= 1;
+    }
+
+    fn occupied(&self) -> u64 {
+        self.men()
+            .iter()
+            .fold(0, |occupied, man| occupied | 1 << man.square)
+    }
+
+    fn man_on(&self, square: u8) -> Option<usize> {
+        self.men().iter().position(|man| man.square == square)
+    }
+
+    fn in_check(&self, color: Color, occupied: u64) -> bool {
+        let Some(king) = self
+            .men()
+            .iter()
+            .find(|man| man.color == color && man.piece_type == PieceType::King)
+        else {
+            return false;
+        };
+        self.men()
+            .iter()
+            .any(|man| man.color != color && man.attacks(king.square, occupied))
+    }
+
+    /// Pieces on different squares, no pawns on the first or last rank and the
+    /// side that just moved not in check
+    fn is_legal(&self) -> bool {
+        let occupied = self.occupied();
+        occupied.count_ones() as usize == self.len
+            && !self.men().iter().any(|man| {
+                man.piece_type == PieceType::Pawn && (man.square < 8 || man.square >= 56)
+            })
+            && !self.in_check(self.side_to_move.opposite(), occupied)
+    }
+
+    /// Legal moves of the side to move as the positions they lead to, with
+    /// whether the move stays in the same table
+    fn for_each_move(&self, mut f: impl FnMut(Position, bool)) {
+        let occupied = self.occupied();
+        let color = self.side_to_move;
+        let mut play = |index: usize, target: u8, promotion: Option<PieceType>| {
+            let mut child = *self;
+            child.side_to_move = color.opposite();
+            child.men[index].square = target;
+            let mut same_table = true;
+            if let Some(piece_type) = promotion {
+                child.men[index].piece_type = piece_type;
+                same_table = false;
+            }
+            if let Some(captured) = self.man_on(target) {
+                child.remove(captured);
+                same_table = false;
+            }
+            if !child.in_check(color, child.occupied()) {
+                f(child, same_table);
+            }
+        };
+
+        for (index, man) in self.men().iter().enumerate() {
+            if man.color != color {
+                continue;
+            }
+            if man.piece_type != PieceType::Pawn {
+                man.for_each_target(occupied, |target| match self.man_on(target) {
+                    Some(other) if self.men[other].color == color => {}
+                    _ => play(index, target, None),
+                });
+                continue;
+            }
+
+            let direction = pawn_direction(color) * 8;
+            let promotions: &[Option<PieceType>] = match man.square as i8 + direction {
+                0..=7 | 56..=63 => &[
+                    Some(PieceType::Queen),
+                    Some(PieceType::Rook),
+                    Some(PieceType::Bishop),
+                    Some(PieceType::Knight),
+                ],
+                _ => &[None],
+            };
+            let forward = (man.square as i8 + direction) as u8;
+            if occupied & (1 << forward) == 0 {
+                for &promotion in promotions {
+                    play(index, forward, promotion);
+                }
+                let start_rank = match color {
+                    Color::White => 1,
+                    Color::Black => 6,
+                };
+                let double = (forward as i8 + direction) as u8;
+                if man.square / 8 == start_rank && occupied & (1 << double) == 0 {
+                    play(index, double, None);
+                }
+            }
+            for file_step in [-1, 1] {
+                let file = (man.square % 8) as i8 + file_step;
+                if !(0..8).contains(&file) {
+                    continue;
+                }
+                let target = (forward as i8 + file_step) as u8;
+                if let Some(other) = self.man_on(target) {
+                    if self.men[other].color != color {
+                        for &promotion in promotions {
+                            play(index, target, promotion);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Legal positions of the same table with the other side to move that lead
+    /// here by a move that is neither a capture nor a promotion
+    fn for_each_unmove(&self, mut f: impl FnMut(Position)) {
+        let occupied = self.occupied();
+        let color = self.side_to_move.opposite();
+        let mut unplay = |index: usize, origin: u8| {
+            let mut parent = *self;
+            parent.side_to_move = color;
+            parent.men[index].square = origin;
+            if parent.is_legal() {
+                f(parent);
+            }
+        };
+
+        for (index, man) in self.men().iter().enumerate() {
+            if man.color != color {
+                continue;
+            }
+            if man.piece_type != PieceType::Pawn {
+                man.for_each_target(occupied, |origin| {
+                    if occupied & (1 << origin) == 0 {
+                        unplay(index, origin);
+                    }
+                });
+                continue;
+            }
+
+            // Pawns stand between the second and seventh ranks
+            let relative_rank = match color {
+                Color::White => man.square / 8,
+                Color::Black => 7 - man.square / 8,
+            };
+            let direction = pawn_direction(color) * 8;
+            let back = (man.square as i8 - direction) as u8;
+            if relative_rank >= 2 && occupied & (1 << back) == 0 {
+                unplay(index, back);
+                let double = (back as i8 - direction) as u8;
+                if relative_rank == 3 && occupied & (1 << double) == 0 {
+                    unplay(index, double);
+                }
+            }
+        }
+    }
+
+    /// Canonical material of the position and its index in that table
+    fn canonical_index(&self) -> (Material, usize) {
+        let (material, mirrored) = Material::from_position(self).canonical();
+        let mut position = *self;
+        if mirrored {
+            position.side_to_move = position.side_to_move.opposite();
+            for man in &mut position.men[..position.len] {
+                man.color = man.color.opposite();
+                man.square ^= 56;
+            }
+        }
+        position.men[..position.len]
+            .sort_by_key(|man| (color_index(man.color), material_index(man.piece_type)));
+        (material, position.index())
+    }
+
+    /// Index of a position whose pieces are in table order
+    fn index(&self) -> usize {
+        self.men()
+            .iter()
+            .fold(color_index(self.side_to_move), |index, man| {
+                index << 6 | man.square as usize
+            })
+    }
+}
+
+/// State of the retrograde analysis of one table
+struct Generator<'a> {
+    tables: &'a EndgameTables,
+    pieces: Vec<(Color, PieceType)>,
+    values: Vec<u8>,
+    /// Moves staying in the table not yet known to lose
+    counts: Vec<u8>,
+    /// `ESCAPE`, or the distance a loss has through moves leaving the table plus one
+    exits: Vec<u8>,
+    /// Positions whose value is known to be reached at a ply, through moves leaving the table
+    pending: Vec<Vec<u32>>,
+}
+
+impl<'a> Generator<'a> {
+    fn new(material: Material, tables: &'a EndgameTables) -> Self {
+        let size = material.table_size();
+        Self {
+            tables,
+            pieces: material.pieces(),
+            values: vec![DRAW; size],
+            counts: vec![0; size],
+            exits: vec![0; size],
+            pending: vec![Vec::new(); MAX_PLIES + 2],
+        }
+    }
+
+    fn position(&self, index: usize) -> Position {
+        let mut position = Position::empty(if index >> (6 * self.pieces.len()) == 0 {
+            Color::White
+        } else {
+            Color::Black
+        });
+        for (number, &(color, piece_type)) in self.pieces.iter().enumerate() {
+            let shift = 6 * (self.pieces.len() - 1 - number);
+            position.push(Man {
+                color,
+                piece_type,
+                square: (index >> shift & 63) as u8,
+            });
+        }
+        position
+    }
+
+    fn run(mut self) -> Result<Vec<u8>, &'static str> {
+        // Mates, and the counts of moves every other position has
+        let mut frontier = Vec::new();
+        for index in 0..self.values.len() {
+            let position = self.position(index);
+            if !position.is_legal() {
+                self.values[index] = ILLEGAL;
+                continue;
+            }
+
+            let mut count = 0u8;
+            let mut exit_count = 0;
+            let mut fastest_win = None;
+            let mut slowest_loss = 0;
+            let mut escape = false;
+            position.for_each_move(|child, same_table| {
+                if same_table {
+                    count += 1;
+                    return;
+                }
+                exit_count += 1;
+                match Dtm::from_value(self.tables.value(&child)) {
+                    Some(Dtm::Loss(plies)) => {
+                        let plies = plies as usize + 1;
+                        fastest_win = Some(fastest_win.unwrap_or(usize::MAX).min(plies));
+                    }
+                    Some(Dtm::Win(plies)) => slowest_loss = slowest_loss.max(plies as usize + 1),
+                    _ => escape = true,
+                }
+            });
+
+            self.counts[index] = count;
+            if let Some(plies) = fastest_win {
+                self.pending[plies].push(index as u32);
+                escape = true;
+            }
+            if escape {
+                self.exits[index] = ESCAPE;
+            } else if count == 0 && exit_count == 0 {
+                if position.in_check(position.side_to_move, position.occupied()) {
+                    self.values[index] = 1;
+                    frontier.push(index as u32);
+                }
+            } else if count == 0 {
+                self.pending[slowest_loss].push(index as u32);
+            } else {
+                self.exits[index] = slowest_loss as u8;
+            }
+        }
+
+        // Positions found at one ply give the positions one ply further
+        let mut unmoves = Vec::new();
+        let mut plies = 0;
+        while !frontier.is_empty() || self.pending[plies + 1..].iter().any(|p| !p.is_empty()) {
+            if plies == MAX_PLIES {
+                return Err("Distance to mate too long for the table format\n");
+            }
+            let next_plies = plies + 1;
+            let lost = plies % 2 == 0;
+            let mut next = Vec::new();
+            for &index in &frontier {
+                unmoves.clear();
+                self.position(index as usize)
+                    .for_each_unmove(|parent| unmoves.push(parent.index()));
+                for &parent in &unmoves {
+                    if self.values[parent] != DRAW {
+                        continue;
+                    }
+                    if lost {
+                        self.values[parent] = next_plies as u8 + 1;
+                        next.push(parent as u32);
+                        continue;
+                    }
+                    self.counts[parent] -= 1;
+                    if self.counts[parent] > 0 || self.exits[parent] == ESCAPE {
+                        continue;
+                    }
+                    let exit = self.exits[parent] as usize;
+                    if exit <= next_plies {
+                        self.values[parent] = next_plies as u8 + 1;
+                        next.push(parent as u32);
+                    } else {
+                        self.pending[exit].push(parent as u32);
+                    }
+                }
+            }
+
+            for index in std::mem::take(&mut self.pending[next_plies]) {
+                if self.values[index as usize] == DRAW {
+                    self.values[index as usize] = next_plies as u8 + 1;
+                    next.push(index);
+                }
+            }
+            frontier = next;
+            plies = next_plies;
+        }
+
+        Ok(self.values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Longest win of a table in moves, generating it first
+    fn longest_win(tables: &mut EndgameTables, name: &str) -> u32 {
+        tables.generate(name).unwrap();
+        tables.summary(name).unwrap().longest_mate.div_ceil(2)
+    }
+
+    #[test]
+    fn longest_wins_of_three_piece_tables() {
+        let mut tables = EndgameTables::new();
+        for (name, moves) in [("KQvK", 10), ("KRvK", 16), ("KPvK", 28)] {
+            assert_eq!(longest_win(&mut tables, name), moves, "{}", name);
+        }
+    }
+
+    // Takes half a minute in release builds and far longer in debug ones, run
+    // it with `cargo test --release -p chess-core -- --ignored`
+    #[test]
+    #[ignore]
+    fn longest_win_of_bishop_and_knight() {
+        assert_eq!(longest_win(&mut EndgameTables::new(), "KBNvK"), 33);
+    }
+
+    #[test]
+    fn en_passant_is_not_covered() {
+        let tables = EndgameTables::new();
+        let board = Board::from_fen("8/8/8/3pP3/8/8/8/K6k w - d6 0 1").unwrap();
+        assert_eq!(
+            tables.probe(&board),
+            Err("Endgame tables do not cover en passant captures\n")
+        );
+        // Black to move could not take, the pawn is just a pawn
+        let board = Board::from_fen("8/8/8/8/3pP3/8/8/K6k w - - 0 1").unwrap();
+        assert_eq!(
+            tables.probe(&board),
+            Err("No endgame table for this material\n")
+        );
+        // Taking en passant would leave the king in check from the rook
+        let board = Board::from_fen("8/8/8/K2pP2r/8/8/8/7k w - d6 0 1").unwrap();
+        assert_eq!(
+            tables.probe(&board),
+            Err("Too many pieces for the endgame tables\n")
+        );
+    }
+}
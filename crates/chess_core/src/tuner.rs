@@ -0,0 +1,376 @@
+// Texel tuning of the classical evaluation weights
+//
+// Each labelled position carries the result of its game as White's points.
+// A capture-only quiescence search finds the quiet position behind it, whose
+// evaluation trace makes the score linear in the weights. The predicted result
+// is `1 / (1 + 10^(-K * score / 400))`, with the scaling `K` fitted to the
+// starting weights first; the weights are then moved by Adam gradient descent
+// on the mean squared difference between predictions and results.
+//
+// Labels come from PGN games, every position after the first plies of a game
+// with a result, or from EPD lines whose result is given as `c9 "1-0";`, in
+// brackets as `[1.0]` or `[1-0]`, or as a trailing `1-0`, `0-1` or `1/2-1/2`.
+// Positions with the side to move in check or without legal moves are left
+// out, as quiescence does not resolve them.
+
+use crate::eval::type_index;
+use crate::{read_pgn, Board, ClassicalEvaluator, EvalTrace, EvalWeights, Evaluator, Move};
+use std::path::Path;
+
+/// Captures the quiescence search follows from one position at most
+const QUIESCENCE_DEPTH: u32 = 8;
+/// Plies at the start of a PGN game left out, as they repeat across games
+pub const DEFAULT_SKIP_PLIES: usize = 8;
+
+const ADAM_BETA1: f64 = 0.9;
+const ADAM_BETA2: f64 = 0.999;
+const ADAM_EPSILON: f64 = 1e-8;
+
+/// A position with the points White scored in its game
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelledPosition {
+    pub board: Board,
+    /// 1 for a White win, 0.5 for a draw and 0 for a loss
+    pub result: f64,
+}
+
+/// Whether a position is worth tuning on
+fn is_usable(board: &Board) -> bool {
+    let color = board.side_to_move();
+    !board.is_king_in_check(color) && !board.get_all_valid_moves(color).is_empty()
+}
+
+/// Positions of the finished games in PGN text, leaving out the first
+/// `skip_plies` of each game
+pub fn positions_from_pgn(
+    text: &str,
+    skip_plies: usize,
+) -> Result<Vec<LabelledPosition>, &'static str> {
+    let mut positions = Vec::new();
+    for game in read_pgn(text)? {
+        let Some(result) = game.white_score() else {
+            continue;
+        };
+        let mut board = game.start.clone();
+        for (ply, &mv) in game.moves.iter().enumerate() {
+            board.apply_move(mv);
+            if ply + 1 >= skip_plies && is_usable(&board) {
+                positions.push(LabelledPosition {
+                    board: board.clone(),
+                    result,
+                });
+            }
+        }
+    }
+    Ok(positions)
+}
+
+/// White's points from the text after the position of an EPD line
+fn epd_result(operations: &str) -> Option<f64> {
+    let points = |text: &str| match text {
+        "1-0" | "1.0" | "1" => Some(1.0),
+        "0-1" | "0.0" | "0" => Some(0.0),
+        "1/2-1/2" | "0.5" | "1/2" => Some(0.5),
+        _ => None,
+    };
+    if let Some((_, rest)) = operations.split_once("c9 \"") {
+        return rest.split('"').next().and_then(points);
+    }
+    if let Some((_, rest)) = operations.split_once('[') {
+        return rest.split(']').next().and_then(|text| points(text.trim()));
+    }
+    // Bare numbers are left to the move counters of FEN lines
+    operations
+        .split_whitespace()
+        .map(|token| token.trim_end_matches(';'))
+        .rfind(|token| token.contains('-'))
+        .and_then(points)
+}
+
+/// Positions of EPD or FEN lines with the result of their game
+pub fn positions_from_epd(text: &str) -> Result<Vec<LabelledPosition>, &'static str> {
+    let mut positions = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.splitn(5, char::is_whitespace).collect();
+        if fields.len() < 5 {
+            return Err("EPD line without a result\n");
+        }
+        let board = Board::from_fen(&fields[..4].join(" "))?;
+        let result = epd_result(fields[4]).ok_or("EPD line without a result\n")?;
+        if is_usable(&board) {
+            positions.push(LabelledPosition { board, result });
+        }
+    }
+    Ok(positions)
+}
+
+/// Read labelled positions, from PGN for files ending in `.pgn` and EPD otherwise
+pub fn load_positions<P: AsRef<Path>>(
+    path: P,
+    skip_plies: usize,
+) -> Result<Vec<LabelledPosition>, &'static str> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|_| "Failed to read position file\n")?;
+    if path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pgn"))
+    {
+        positions_from_pgn(&text, skip_plies)
+    } else {
+        positions_from_epd(&text)
+    }
+}
+
+/// Captures, en passant included, and promotions, the most valuable victim
+/// taken by the least valuable attacker first
+fn noisy_moves(board: &Board) -> Vec<Move> {
+    let victim_value = |mv: &Move| {
+        let victim = board
+            .get_piece(mv.to)
+            .map(|piece| type_index(piece.piece_type) as i32 + 1);
+        let attacker = board
+            .get_piece(mv.from)
+            .map(|piece| type_index(piece.piece_type) as i32)
+            .unwrap_or(0);
+        // A pawn moving sideways onto an empty square takes en passant
+        let en_passant = attacker == 0 && mv.from.file() != mv.to.file();
+        match victim {
+            Some(victim) => Some(victim * 8 - attacker),
+            None if en_passant => Some(8),
+            None => mv.promotion.map(|_| 0),
+        }
+    };
+    let mut moves: Vec<(i32, Move)> = board
+        .get_all_valid_moves(board.side_to_move())
+        .into_iter()
+        .filter_map(|mv| victim_value(&mv).map(|value| (value, mv)))
+        .collect();
+    moves.sort_by_key(|&(value, _)| -value);
+    moves.into_iter().map(|(_, mv)| mv).collect()
+}
+
+/// Score from the side to move's view and the quiet position the best line
+/// of captures ends in
+fn quiescence(
+    evaluator: &ClassicalEvaluator,
+    board: &Board,
+    mut alpha: i32,
+    beta: i32,
+    depth: u32,
+) -> (i32, Board) {
+    let stand_pat = evaluator.evaluate(board);
+    let mut best = (stand_pat, board.clone());
+    if stand_pat >= beta || depth == 0 {
+        return best;
+    }
+    alpha = alpha.max(stand_pat);
+
+    for mv in noisy_moves(board) {
+        let mut next = board.clone();
+        next.apply_move(mv);
+        let (score, leaf) = quiescence(evaluator, &next, -beta, -alpha, depth - 1);
+        let score = -score;
+        if score > best.0 {
+            best = (score, leaf);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+    }
+    best
+}
+
+/// The quiet position a capture sequence from `board` settles in
+pub fn quiet_position(evaluator: &ClassicalEvaluator, board: &Board) -> Board {
+    quiescence(evaluator, board, -i32::MAX, i32::MAX, QUIESCENCE_DEPTH).1
+}
+
+/// Predicted points for White from a score in centipawns
+fn sigmoid(scaling: f64, score: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-scaling * score / 400.0))
+}
+
+/// Tunes the weights of a classical evaluator on labelled positions
+pub struct Tuner {
+    entries: Vec<(EvalTrace, f64)>,
+    params: Vec<f64>,
+    /// Scaling of scores in the sigmoid
+    scaling: f64,
+    pub learning_rate: f64,
+    /// Adam moment estimates and steps taken
+    momentum: Vec<f64>,
+    velocity: Vec<f64>,
+    steps: i32,
+}
+
+impl Tuner {
+    /// Trace the quiet position behind each labelled position, starting from
+    /// the weights of `evaluator`
+    pub fn new(evaluator: &ClassicalEvaluator, positions: &[LabelledPosition]) -> Self {
+        let entries = positions
+            .iter()
+            .map(|position| {
+                let quiet = quiet_position(evaluator, &position.board);
+                (evaluator.trace(&quiet), position.result)
+            })
+            .collect();
+        let params: Vec<f64> = evaluator
+            .weights
+            .to_params()
+            .into_iter()
+            .map(f64::from)
+            .collect();
+        let count = params.len();
+        Self {
+            entries,
+            params,
+            scaling: 1.0,
+            learning_rate: 1.0,
+            momentum: vec![0.0; count],
+            velocity: vec![0.0; count],
+            steps: 0,
+        }
+    }
+
+    pub fn positions(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn scaling(&self) -> f64 {
+        self.scaling
+    }
+
+    /// Mean squared error of the predictions for a scaling
+    fn error_with(&self, scaling: f64) -> f64 {
+        if self.entries.is_empty() {
+            return 0.0;
+        }
+        let total: f64 = self
+            .entries
+            .iter()
+            .map(|(trace, result)| {
+                let predicted = sigmoid(scaling, trace.evaluate(&self.params));
+                (result - predicted).powi(2)
+            })
+            .sum();
+        total / self.entries.len() as f64
+    }
+
+    /// Mean squared error of the predictions with the current weights
+    pub fn error(&self) -> f64 {
+        self.error_with(self.scaling)
+    }
+
+    /// Fit the scaling to the current weights by golden section search,
+    /// returning it
+    pub fn fit_scaling(&mut self) -> f64 {
+        let ratio = (5f64.sqrt() - 1.0) / 2.0;
+        let (mut low, mut high) = (0.01, 5.0);
+        while high - low > 1e-4 {
+            let left = high - ratio * (high - low);
+            let right = low + ratio * (high - low);
+            if self.error_with(left) < self.error_with(right) {
+                high = right;
+            } else {
+                low = left;
+            }
+        }
+        self.scaling = (low + high) / 2.0;
+        self.scaling
+    }
+
+    /// One Adam step over all positions, returning the error before it
+    pub fn step(&mut self) -> f64 {
+        let mut gradient = vec![0.0; self.params.len()];
+        let mut error = 0.0;
+        let slope = self.scaling * std::f64::consts::LN_10 / 400.0;
+        for (trace, result) in &self.entries {
+            let predicted = sigmoid(self.scaling, trace.evaluate(&self.params));
+            error += (result - predicted).powi(2);
+            // Derivative of the squared error by the score
+            let derivative = -2.0 * (result - predicted) * predicted * (1.0 - predicted) * slope;
+            for (index, coefficient) in trace.gradient() {
+                gradient[index] += derivative * coefficient;
+            }
+        }
+        if self.entries.is_empty() {
+            return 0.0;
+        }
+        let count = self.entries.len() as f64;
+
+        self.steps += 1;
+        let momentum_correction = 1.0 - ADAM_BETA1.powi(self.steps);
+        let velocity_correction = 1.0 - ADAM_BETA2.powi(self.steps);
+        for (index, gradient) in gradient.into_iter().enumerate() {
+            let gradient = gradient / count;
+            let momentum = &mut self.momentum[index];
+            let velocity = &mut self.velocity[index];
+            *momentum = ADAM_BETA1 * *momentum + (1.0 - ADAM_BETA1) * gradient;
+            *velocity = ADAM_BETA2 * *velocity + (1.0 - ADAM_BETA2) * gradient * gradient;
+            self.params[index] -= self.learning_rate * (*momentum / momentum_correction)
+                / ((*velocity / velocity_correction).sqrt() + ADAM_EPSILON);
+        }
+        error / count
+    }
+
+    /// The tuned weights, rounded to whole centipawns
+    pub fn weights(&self) -> EvalWeights {
+        let params: Vec<i32> = self
+            .params
+            .iter()
+            .map(|&value| value.round() as i32)
+            .collect();
+        EvalWeights::from_params(&params).expect("one value per weight")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LABELLED: &str = "\
+rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - c9 \"1/2-1/2\";
+4k3/8/8/8/8/8/3QPPP1/4K3 w - - c9 \"1-0\";
+4k3/8/8/8/8/8/3RPPP1/4K3 b - - c9 \"1-0\";
+3qk3/4ppp1/8/8/8/8/8/4K3 w - - c9 \"0-1\";
+3rk3/4ppp1/8/8/8/8/8/4K3 b - - c9 \"0-1\";
+4k3/pp6/8/8/8/8/PPPPP3/4K3 w - - c9 \"1-0\";
+4k3/ppppp3/8/8/8/8/PP6/4K3 b - - c9 \"0-1\";
+";
+
+    #[test]
+    fn tuning_lowers_the_error() {
+        let positions = positions_from_epd(LABELLED).unwrap();
+        assert_eq!(positions.len(), 7);
+        let mut tuner = Tuner::new(&ClassicalEvaluator::new(), &positions);
+        tuner.fit_scaling();
+        let before = tuner.error();
+        for _ in 0..20 {
+            tuner.step();
+        }
+        assert!(tuner.error() < before);
+    }
+
+    #[test]
+    fn tuned_weights_text_round_trip() {
+        let positions = positions_from_epd(LABELLED).unwrap();
+        let mut tuner = Tuner::new(&ClassicalEvaluator::new(), &positions);
+        for _ in 0..5 {
+            tuner.step();
+        }
+        let weights = tuner.weights();
+        assert_ne!(weights, EvalWeights::new());
+        assert_eq!(EvalWeights::from_text(&weights.to_text()), Ok(weights));
+    }
+
+    #[test]
+    fn noisy_moves_take_en_passant() {
+        let board = Board::from_fen("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").unwrap();
+        let moves: Vec<String> = noisy_moves(&board).iter().map(Move::to_uci).collect();
+        assert_eq!(moves, ["e5d6"]);
+    }
+}
@@ -0,0 +1,123 @@
+// Fixed depth searches of a set of positions, to compare engine speed
+// between versions and machines and to measure how the search scales with
+// threads. Every position starts from an empty hash table so runs repeat.
+
+use crate::{Engine, SearchLimits};
+use chess_core::Board;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Positions searched by a bench, from the opening to the endgame
+pub const BENCH_POSITIONS: [&str; 12] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 10",
+    "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+    "rnbqkb1r/pp1p1ppp/4pn2/2p5/2PP4/2N5/PP2PPPP/R1BQKBNR w KQkq - 0 4",
+    "r1bq1rk1/pp2bppp/2n1pn2/3p4/2PP4/2N1PN2/PP1B1PPP/R2QKB1R w KQ - 0 8",
+    "2rq1rk1/pp1bppbp/2np1np1/8/3NP3/1BN1BP2/PPPQ2PP/2KR3R b - - 0 11",
+    "r1b2rk1/2q1bppp/p2ppn2/1p6/3BPP2/2NB4/PPPQ2PP/2KR3R w - - 0 13",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - - 7 19",
+    "6k1/6p1/6Pp/ppp5/3pn2P/1P3K2/1PP2P2/3N4 b - - 0 1",
+    "8/8/8/8/5kp1/P7/8/1K1N4 w - - 0 1",
+    "8/3k4/8/8/8/4B3/4KB2/2B5 w - - 0 1",
+    "8/2k5/8/8/8/8/3KR3/8 w - - 0 1",
+];
+
+/// Search depth of a bench unless given
+pub const DEFAULT_BENCH_DEPTH: u32 = 5;
+
+/// Totals of one bench run
+#[derive(Debug, Clone)]
+pub struct BenchResult {
+    pub threads: usize,
+    pub depth: u32,
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    /// Nodes per second
+    pub fn nps(&self) -> u64 {
+        let millis = self.elapsed.as_millis().max(1) as u64;
+        self.nodes * 1000 / millis
+    }
+}
+
+/// Search every bench position to `depth` with a number of threads
+pub fn run(depth: u32, threads: usize) -> BenchResult {
+    let mut engine = Engine::new();
+    engine.set_threads(threads);
+    let limits = SearchLimits {
+        depth: Some(depth),
+        ..Default::default()
+    };
+
+    let mut result = BenchResult {
+        threads: engine.threads(),
+        depth,
+        nodes: 0,
+        elapsed: Duration::ZERO,
+    };
+    for fen in BENCH_POSITIONS {
+        let board = Board::from_fen(fen).expect("bench positions are valid");
+        engine.new_game();
+        let search = engine.search(&board, &limits);
+        result.nodes += search.nodes;
+        result.elapsed += search.elapsed;
+    }
+    result
+}
+
+/// Time to depth with 1, 2, 4 and more threads up to `max_threads`, written
+/// as a table with the speedup over a single thread
+pub fn scaling<W: Write>(depth: u32, max_threads: usize, output: &mut W) -> io::Result<()> {
+    writeln!(
+        output,
+        "{:>7} {:>10} {:>12} {:>10} {:>8}",
+        "threads", "time (ms)", "nodes", "nps", "speedup"
+    )?;
+
+    let mut single_thread_time = None;
+    let mut threads = 1;
+    loop {
+        let result = run(depth, threads);
+        let baseline = *single_thread_time.get_or_insert(result.elapsed);
+        writeln!(
+            output,
+            "{:>7} {:>10} {:>12} {:>10} {:>7.2}x",
+            result.threads,
+            result.elapsed.as_millis(),
+            result.nodes,
+            result.nps(),
+            baseline.as_secs_f64() / result.elapsed.as_secs_f64().max(1e-9)
+        )?;
+
+        if threads >= max_threads {
+            return Ok(());
+        }
+        threads = (threads * 2).min(max_threads);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_thread_searches_repeat() {
+        let limits = SearchLimits {
+            depth: Some(3),
+            ..Default::default()
+        };
+        for fen in BENCH_POSITIONS {
+            let board = Board::from_fen(fen).unwrap();
+            let [first, second] = [(); 2].map(|_| {
+                let mut engine = Engine::new();
+                engine.set_threads(1);
+                engine.search(&board, &limits)
+            });
+            assert_eq!(first.best_move, second.best_move, "{}", fen);
+            assert_eq!(first.nodes, second.nodes, "{}", fen);
+        }
+    }
+}
@@ -0,0 +1,1117 @@
+// Engine against engine matches, to measure whether a change helps
+//
+// Two engines, each the built-in engine with its own options or an external
+// UCI or XBoard process, play games from the openings of a suite, the first
+// engine taking White in even games and Black in odd ones so every opening is
+// played from both sides. The runner keeps the clocks and passes both to the
+// engines, which budget their own time and lose when their flag falls. Games
+// end by the rules, a resignation, a time forfeit or an engine failure, or are
+// adjudicated by tablebases, resign and draw scores or a move limit. Finished
+// games are written as PGN, with the engines' scores as `[%eval]` comments,
+// and summed into an Elo difference; an SPRT can stop the match as soon as its
+// result is clear.
+
+use crate::sprt::{MatchScore, Sprt, SprtResult};
+use crate::uci::ENGINE_NAME;
+use crate::{
+    position_key, search, white_score, ClockLimits, Engine, EngineProtocol, ExternalEngine, Score,
+    SearchLimits, Skill, UciEvent, MATE_SCORE,
+};
+use chess_core::{read_pgn, Board, Color, GameEval, Move, SyzygyTablebase, Wdl, STARTING_FEN};
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How late an engine may move before it loses on time
+const TIME_MARGIN: Duration = Duration::from_millis(100);
+/// How long an external engine told to stop may take to move before it is
+/// given up on
+const LATE_MOVE_GRACE: Duration = Duration::from_secs(2);
+/// Longest search of an external engine limited by depth or nodes only
+const MAX_SEARCH_TIME: Duration = Duration::from_secs(600);
+/// Width of PGN movetext lines
+const PGN_LINE_WIDTH: usize = 80;
+
+/// An engine taking part in a match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerConfig {
+    pub name: String,
+    /// Binary and protocol of an external engine, `None` for the built-in one
+    pub command: Option<(PathBuf, EngineProtocol)>,
+    /// Options set before the first game. The built-in engine knows `Hash`,
+    /// `Threads`, `EvalFile`, `Skill Level` and `UCI_Elo`
+    pub options: Vec<(String, String)>,
+}
+
+impl PlayerConfig {
+    pub fn built_in() -> Self {
+        Self {
+            name: ENGINE_NAME.to_string(),
+            command: None,
+            options: Vec::new(),
+        }
+    }
+
+    /// An external engine named after its binary
+    pub fn external<P: AsRef<Path>>(path: P, protocol: EngineProtocol) -> Self {
+        let path = path.as_ref();
+        Self {
+            name: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            command: Some((path.to_path_buf(), protocol)),
+            options: Vec::new(),
+        }
+    }
+
+    /// Start the engine and set its options
+    fn start(&self) -> Result<Box<dyn Player>, &'static str> {
+        match &self.command {
+            None => {
+                let mut engine = Engine::new();
+                for (name, value) in &self.options {
+                    set_built_in_option(&mut engine, name, value)?;
+                }
+                Ok(Box::new(engine))
+            }
+            Some((path, protocol)) => {
+                // Engines run in their own directory, so relative paths would
+                // no longer lead to them; names found on the PATH stay as they are
+                let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+                let mut engine = protocol.start(path)?;
+                for (name, value) in &self.options {
+                    engine.set_option(name, Some(value))?;
+                }
+                Ok(Box::new(engine))
+            }
+        }
+    }
+}
+
+/// Set one of the UCI options of `chess-uci` on an engine run in process
+fn set_built_in_option(engine: &mut Engine, name: &str, value: &str) -> Result<(), &'static str> {
+    let number = || {
+        value
+            .parse::<u32>()
+            .map_err(|_| "Option value must be a number\n")
+    };
+    match name.to_ascii_lowercase().as_str() {
+        "hash" => engine.set_hash_size(number()? as usize),
+        "threads" => engine.set_threads(number()? as usize),
+        "evalfile" => engine.set_eval_file(Some(value))?,
+        "skill level" => engine.set_skill(Skill::from_level(number()?)),
+        "uci_elo" => engine.set_skill(Skill::from_elo(number()?)),
+        _ => return Err("Unknown option of the built-in engine\n"),
+    }
+    Ok(())
+}
+
+/// How long the engines may think
+#[derive(Debug, Clone)]
+pub enum TimeControl {
+    /// A clock for each side with `base` time for every `moves` moves, or the
+    /// whole game, and an increment after each move
+    Clock {
+        moves: Option<u32>,
+        base: Duration,
+        increment: Duration,
+    },
+    /// The same limits for every move, like a fixed time, depth or node count
+    PerMove(SearchLimits),
+}
+
+impl TimeControl {
+    /// Read a clock like `60+0.6` or `40/300`, in seconds
+    pub fn parse(text: &str) -> Result<Self, &'static str> {
+        let (moves, rest) = match text.split_once('/') {
+            Some((moves, rest)) => (
+                Some(moves.parse().map_err(|_| "Invalid time control moves\n")?),
+                rest,
+            ),
+            None => (None, text),
+        };
+        let (base, increment) = rest.split_once('+').unwrap_or((rest, "0"));
+        let seconds = |value: &str| {
+            value
+                .parse::<f64>()
+                .ok()
+                .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                .map(Duration::from_secs_f64)
+                .ok_or("Invalid time control\n")
+        };
+        if moves == Some(0) {
+            return Err("Invalid time control moves\n");
+        }
+        Ok(TimeControl::Clock {
+            moves,
+            base: seconds(base)?,
+            increment: seconds(increment)?,
+        })
+    }
+
+    /// The PGN `TimeControl` tag
+    fn pgn_tag(&self) -> String {
+        match self {
+            TimeControl::Clock {
+                moves,
+                base,
+                increment,
+            } => {
+                let mut tag = match moves {
+                    Some(moves) => format!("{}/{}", moves, base.as_secs_f64()),
+                    None => format!("{}", base.as_secs_f64()),
+                };
+                if !increment.is_zero() {
+                    tag.push_str(&format!("+{}", increment.as_secs_f64()));
+                }
+                tag
+            }
+            TimeControl::PerMove(limits) => match limits.movetime {
+                Some(movetime) => format!("1/{}", movetime.as_secs_f64()),
+                None => "-".to_string(),
+            },
+        }
+    }
+}
+
+/// Resign once both engines have put one side at least `score` centipawns
+/// behind for `moves` moves each
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResignRule {
+    pub moves: u32,
+    pub score: i32,
+}
+
+/// Draw once both engines have scored the position within `score`
+/// centipawns for `moves` moves each, from move `move_number` on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrawRule {
+    pub move_number: u32,
+    pub moves: u32,
+    pub score: i32,
+}
+
+/// Ways to end games before the rules do, none by default
+#[derive(Default)]
+pub struct Adjudication {
+    /// Positions the tables cover end with their result
+    pub tablebase: Option<SyzygyTablebase>,
+    pub resign: Option<ResignRule>,
+    pub draw: Option<DrawRule>,
+    /// Games still going after this many moves are drawn
+    pub max_moves: Option<u32>,
+}
+
+/// A starting position of the games, with the moves played to reach it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opening {
+    pub board: Board,
+    pub moves: Vec<Move>,
+}
+
+impl Opening {
+    /// The standard starting position
+    pub fn starting() -> Self {
+        Self {
+            board: Board::new_starting_default(),
+            moves: Vec::new(),
+        }
+    }
+}
+
+/// Read an opening suite, PGN for files ending in `.pgn` and EPD otherwise
+pub fn load_openings<P: AsRef<Path>>(path: P) -> Result<Vec<Opening>, &'static str> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path).map_err(|_| "Failed to read opening file\n")?;
+    let openings = if path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pgn"))
+    {
+        read_pgn_openings(&text)?
+    } else {
+        read_epd_openings(&text)?
+    };
+    if openings.is_empty() {
+        return Err("Opening file has no positions\n");
+    }
+    Ok(openings)
+}
+
+/// Read one position per line from the first four fields of EPD or FEN lines
+pub fn read_epd_openings(text: &str) -> Result<Vec<Opening>, &'static str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+            Ok(Opening {
+                board: Board::from_fen(&fields.join(" "))?,
+                moves: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// Read the games of a PGN file as openings, from their `FEN` tag or the
+/// starting position. Comments, variations and annotations are skipped
+pub fn read_pgn_openings(text: &str) -> Result<Vec<Opening>, &'static str> {
+    Ok(read_pgn(text)?
+        .into_iter()
+        .map(|game| Opening {
+            board: game.start,
+            moves: game.moves,
+        })
+        .collect())
+}
+
+/// Everything a match needs
+pub struct MatchConfig {
+    /// The engine under test, whose score is reported, and its opponent
+    pub players: [PlayerConfig; 2],
+    /// Played in turn, each from both sides; the starting position when empty
+    pub openings: Vec<Opening>,
+    pub games: u32,
+    pub time_control: TimeControl,
+    pub adjudication: Adjudication,
+    /// Games played at the same time
+    pub concurrency: usize,
+    /// Stops the match once the test accepts a hypothesis
+    pub sprt: Option<Sprt>,
+    /// File the games are appended to
+    pub pgn_path: Option<PathBuf>,
+}
+
+/// Play a match, writing progress to `output`, and return the score of the
+/// first engine
+pub fn run<W: Write>(config: &MatchConfig, output: &mut W) -> Result<MatchScore, &'static str> {
+    let mut pgn = match &config.pgn_path {
+        Some(path) => Some(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|_| "Failed to open PGN file\n")?,
+        ),
+        None => None,
+    };
+    let names = [&config.players[0].name, &config.players[1].name];
+    let next_game = AtomicU32::new(0);
+    let stop = AtomicBool::new(false);
+    let mut score = MatchScore::new();
+    let mut error = None;
+    let mut sprt_result = SprtResult::Continue;
+
+    thread::scope(|scope| -> Result<(), &'static str> {
+        let (sender, receiver) = channel();
+        for _ in 0..config.concurrency.max(1) {
+            let sender = sender.clone();
+            let next_game = &next_game;
+            let stop = &stop;
+            scope.spawn(move || play_games(config, next_game, stop, sender));
+        }
+        drop(sender);
+
+        // Games already started when the match stops are still counted
+        for message in receiver {
+            let record = match message {
+                Ok(record) => record,
+                Err(message) => {
+                    stop.store(true, Ordering::Relaxed);
+                    error.get_or_insert(message);
+                    continue;
+                }
+            };
+
+            match record.first_player_points() {
+                Some(true) => score.wins += 1,
+                Some(false) => score.losses += 1,
+                None => score.draws += 1,
+            }
+            if let Some(pgn) = &mut pgn {
+                record
+                    .write_pgn(&config.time_control, pgn)
+                    .map_err(|_| "Failed to write PGN file\n")?;
+            }
+            let write = |output: &mut W| -> io::Result<()> {
+                writeln!(
+                    output,
+                    "Finished game {} ({} vs {}): {} {{{}}}",
+                    record.round,
+                    record.white,
+                    record.black,
+                    record.outcome.pgn_result(),
+                    record.termination
+                )?;
+                writeln!(output, "Score of {} vs {}: {}", names[0], names[1], score)?;
+                if let Some(sprt) = &config.sprt {
+                    let (lower, upper) = sprt.bounds();
+                    writeln!(
+                        output,
+                        "LLR: {:.2} ({:.2}, {:.2}) [{:.2}, {:.2}]",
+                        sprt.llr(&score),
+                        lower,
+                        upper,
+                        sprt.elo0,
+                        sprt.elo1
+                    )?;
+                }
+                output.flush()
+            };
+            write(output).map_err(|_| "Failed to write match output\n")?;
+
+            if let Some(sprt) = &config.sprt {
+                if sprt_result == SprtResult::Continue {
+                    sprt_result = sprt.result(&score);
+                    if sprt_result != SprtResult::Continue {
+                        stop.store(true, Ordering::Relaxed);
+                    }
+                }
+            }
+        }
+        Ok(())
+    })?;
+
+    if let Some(error) = error {
+        return Err(error);
+    }
+    write_summary(config, &score, sprt_result, output)
+        .map_err(|_| "Failed to write match output\n")?;
+    Ok(score)
+}
+
+/// Elo difference, and the SPRT outcome when there is a test
+fn write_summary<W: Write>(
+    config: &MatchConfig,
+    score: &MatchScore,
+    sprt_result: SprtResult,
+    output: &mut W,
+) -> io::Result<()> {
+    match score.elo() {
+        Some((elo, margin)) => writeln!(
+            output,
+            "Elo difference: {:.1} +/- {:.1}, LOS: {:.1} %, DrawRatio: {:.1} %",
+            elo,
+            margin,
+            score.los() * 100.0,
+            score.draw_ratio() * 100.0
+        )?,
+        None => writeln!(
+            output,
+            "Elo difference: unknown, LOS: {:.1} %, DrawRatio: {:.1} %",
+            score.los() * 100.0,
+            score.draw_ratio() * 100.0
+        )?,
+    }
+    if let Some(sprt) = &config.sprt {
+        let (lower, upper) = sprt.bounds();
+        let verdict = match sprt_result {
+            SprtResult::AcceptH0 => "H0 was accepted",
+            SprtResult::AcceptH1 => "H1 was accepted",
+            SprtResult::Continue => "no decision",
+        };
+        writeln!(
+            output,
+            "SPRT: llr {:.2}, lbound {:.2}, ubound {:.2} - {}",
+            sprt.llr(score),
+            lower,
+            upper,
+            verdict
+        )?;
+    }
+    writeln!(output, "Finished match")
+}
+
+/// Play games until every game has started or the match stops, with engines
+/// of its own for this thread
+fn play_games(
+    config: &MatchConfig,
+    next_game: &AtomicU32,
+    stop: &AtomicBool,
+    sender: Sender<Result<GameRecord, &'static str>>,
+) {
+    let mut players: [Option<Box<dyn Player>>; 2] = [None, None];
+    loop {
+        if stop.load(Ordering::Relaxed) {
+            return;
+        }
+        let index = next_game.fetch_add(1, Ordering::Relaxed);
+        if index >= config.games {
+            return;
+        }
+
+        // Engines that failed in the last game are started again
+        for (player, player_config) in players.iter_mut().zip(&config.players) {
+            if player.is_none() {
+                match player_config.start() {
+                    Ok(started) => *player = Some(started),
+                    Err(error) => {
+                        let _ = sender.send(Err(error));
+                        return;
+                    }
+                }
+            }
+        }
+
+        let record = play_game(config, index, &mut players);
+        if let Some(failed) = record.failed_player {
+            players[failed] = None;
+        }
+        if sender.send(Ok(record)).is_err() {
+            return;
+        }
+    }
+}
+
+/// How a game ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Win(Color),
+    Draw,
+}
+
+impl Outcome {
+    fn pgn_result(self) -> &'static str {
+        match self {
+            Outcome::Win(Color::White) => "1-0",
+            Outcome::Win(Color::Black) => "0-1",
+            Outcome::Draw => "1/2-1/2",
+        }
+    }
+}
+
+/// A finished game with what is needed for its PGN
+struct GameRecord {
+    round: u32,
+    white: String,
+    black: String,
+    /// Index of the first engine's color, 0 for White
+    first_player_color: usize,
+    opening: Opening,
+    /// Moves played by the engines after the opening, with their scores
+    moves: Vec<Move>,
+    evals: Vec<Option<GameEval>>,
+    outcome: Outcome,
+    termination: String,
+    /// PGN `Termination` tag
+    termination_tag: &'static str,
+    /// Engine that crashed, broke the rules or never moved, to start again
+    failed_player: Option<usize>,
+}
+
+impl GameRecord {
+    /// Whether the first engine won, `None` for draws
+    fn first_player_points(&self) -> Option<bool> {
+        match self.outcome {
+            Outcome::Win(color) => Some(color_index(color) == self.first_player_color),
+            Outcome::Draw => None,
+        }
+    }
+
+    fn write_pgn<W: Write>(&self, time_control: &TimeControl, output: &mut W) -> io::Result<()> {
+        let start = &self.opening.board;
+        writeln!(output, "[Event \"chess-match\"]")?;
+        writeln!(output, "[Site \"?\"]")?;
+        writeln!(output, "[Date \"{}\"]", pgn_date())?;
+        writeln!(output, "[Round \"{}\"]", self.round)?;
+        writeln!(output, "[White \"{}\"]", self.white)?;
+        writeln!(output, "[Black \"{}\"]", self.black)?;
+        writeln!(output, "[Result \"{}\"]", self.outcome.pgn_result())?;
+        let fen = start.to_fen();
+        if fen != STARTING_FEN {
+            writeln!(output, "[SetUp \"1\"]")?;
+            writeln!(output, "[FEN \"{}\"]", fen)?;
+        }
+        writeln!(output, "[TimeControl \"{}\"]", time_control.pgn_tag())?;
+        writeln!(output, "[Termination \"{}\"]", self.termination_tag)?;
+        writeln!(output)?;
+
+        let mut tokens = Vec::new();
+        let mut board = start.clone();
+        let book_moves = self.opening.moves.len();
+        let evals = std::iter::repeat_n(None, book_moves).chain(self.evals.iter().copied());
+        for (ply, (&mv, eval)) in self
+            .opening
+            .moves
+            .iter()
+            .chain(&self.moves)
+            .zip(evals)
+            .enumerate()
+        {
+            // Move numbers stay on the line of their move
+            let number = board.fullmove_number();
+            let san = board.move_to_san(mv);
+            tokens.push(if board.side_to_move() == Color::White {
+                format!("{}. {}", number, san)
+            } else if ply == 0 {
+                format!("{}... {}", number, san)
+            } else {
+                san
+            });
+            if ply + 1 == book_moves {
+                tokens.push("{book}".to_string());
+            }
+            if let Some(eval) = eval {
+                tokens.push(format!("{{{}}}", eval.to_comment()));
+            }
+            board.apply_move(mv);
+        }
+        tokens.push(format!("{{{}}}", self.termination));
+        tokens.push(self.outcome.pgn_result().to_string());
+
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + 1 + token.len() > PGN_LINE_WIDTH {
+                writeln!(output, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(output, "{}", line)?;
+        writeln!(output)?;
+        output.flush()
+    }
+}
+
+fn color_index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+fn color_name(color: Color) -> &'static str {
+    match color {
+        Color::White => "White",
+        Color::Black => "Black",
+    }
+}
+
+/// Today's date as `yyyy.mm.dd`
+fn pgn_date() -> String {
+    let Ok(since_epoch) = SystemTime::now().duration_since(UNIX_EPOCH) else {
+        return "????.??.??".to_string();
+    };
+    // Civil date from the days since 1970-01-01, after Howard Hinnant
+    let days = (since_epoch.as_secs() / 86_400) as i64 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// Time left on the clock of one side
+struct Clock {
+    left: Duration,
+    moves_played: u32,
+}
+
+impl Clock {
+    /// Take the time of a move off the clock, `false` when it ran out
+    fn spend(&mut self, time_control: &TimeControl, elapsed: Duration) -> bool {
+        let TimeControl::Clock {
+            moves,
+            base,
+            increment,
+        } = time_control
+        else {
+            return true;
+        };
+        if elapsed > self.left + TIME_MARGIN {
+            return false;
+        }
+        self.left = self.left.saturating_sub(elapsed) + *increment;
+        self.moves_played += 1;
+        if moves.is_some_and(|moves| self.moves_played.is_multiple_of(moves)) {
+            self.left += *base;
+        }
+        true
+    }
+}
+
+/// Limits of the next move of `side`, with both clocks for engines to manage
+/// their own time
+fn move_limits(time_control: &TimeControl, clocks: &[Clock; 2], side: Color) -> SearchLimits {
+    match time_control {
+        TimeControl::Clock {
+            moves, increment, ..
+        } => {
+            let moves_played = clocks[color_index(side)].moves_played;
+            SearchLimits {
+                clock: Some(ClockLimits {
+                    white_time: clocks[0].left,
+                    black_time: clocks[1].left,
+                    white_increment: *increment,
+                    black_increment: *increment,
+                    moves_to_go: moves.map(|moves| moves - moves_played % moves),
+                }),
+                ..Default::default()
+            }
+        }
+        TimeControl::PerMove(limits) => limits.clone(),
+    }
+}
+
+/// Play game `index` of the match
+fn play_game(
+    config: &MatchConfig,
+    index: u32,
+    players: &mut [Option<Box<dyn Player>>; 2],
+) -> GameRecord {
+    let opening = if config.openings.is_empty() {
+        Opening::starting()
+    } else {
+        config.openings[(index / 2) as usize % config.openings.len()].clone()
+    };
+    // Player playing each color, the first engine has White in even games
+    let seats = if index.is_multiple_of(2) {
+        [0, 1]
+    } else {
+        [1, 0]
+    };
+    let mut record = GameRecord {
+        round: index + 1,
+        white: config.players[seats[0]].name.clone(),
+        black: config.players[seats[1]].name.clone(),
+        first_player_color: (index % 2) as usize,
+        opening: opening.clone(),
+        moves: Vec::new(),
+        evals: Vec::new(),
+        outcome: Outcome::Draw,
+        termination: String::new(),
+        termination_tag: "normal",
+        failed_player: None,
+    };
+
+    let mut board = opening.board.clone();
+    let mut positions = Vec::new();
+    for &mv in &opening.moves {
+        positions.push(board.clone());
+        board.apply_move(mv);
+    }
+    let mut moves = opening.moves.clone();
+    let mut clocks = [(); 2].map(|_| Clock {
+        left: match &config.time_control {
+            TimeControl::Clock { base, .. } => *base,
+            TimeControl::PerMove(_) => Duration::ZERO,
+        },
+        moves_played: 0,
+    });
+
+    for (color, &seat) in [Color::White, Color::Black].iter().zip(&seats) {
+        let player = players[seat]
+            .as_mut()
+            .expect("players start before the game");
+        if let Err(error) = player.start_game(u64::from(index) + 1) {
+            record.outcome = Outcome::Win(color.opposite());
+            record.termination = format!("{} engine failed: {}", color_name(*color), error.trim());
+            record.termination_tag = "rules infraction";
+            record.failed_player = Some(seat);
+            return record;
+        }
+    }
+
+    loop {
+        if let Some((outcome, termination)) = rules_outcome(&board, &positions) {
+            record.outcome = outcome;
+            record.termination = termination.to_string();
+            break;
+        }
+        if let Some((outcome, termination)) =
+            adjudicate(&config.adjudication, &board, &moves, &record.evals)
+        {
+            record.outcome = outcome;
+            record.termination = termination;
+            record.termination_tag = "adjudication";
+            break;
+        }
+
+        let side = board.side_to_move();
+        let seat = seats[color_index(side)];
+        let limits = move_limits(&config.time_control, &clocks, side);
+        let clock = &mut clocks[color_index(side)];
+        let player = players[seat]
+            .as_mut()
+            .expect("players start before the game");
+        let start = Instant::now();
+        let result = player.choose_move(&opening.board, &moves, &positions, &board, &limits);
+        let elapsed = start.elapsed();
+
+        let (mv, score) = match result {
+            Ok(Turn::Move(mv, score)) => (mv, score),
+            Ok(Turn::Resign) => {
+                record.outcome = Outcome::Win(side.opposite());
+                record.termination = format!("{} resigns", color_name(side));
+                break;
+            }
+            // The engine may still be thinking, so it is started again
+            Ok(Turn::OutOfTime) => {
+                record.outcome = Outcome::Win(side.opposite());
+                record.termination = format!("{} loses on time", color_name(side));
+                record.termination_tag = "time forfeit";
+                record.failed_player = Some(seat);
+                break;
+            }
+            Err(error) => {
+                record.outcome = Outcome::Win(side.opposite());
+                record.termination =
+                    format!("{} engine failed: {}", color_name(side), error.trim());
+                record.termination_tag = "rules infraction";
+                record.failed_player = Some(seat);
+                break;
+            }
+        };
+        if !clock.spend(&config.time_control, elapsed) {
+            record.outcome = Outcome::Win(side.opposite());
+            record.termination = format!("{} loses on time", color_name(side));
+            record.termination_tag = "time forfeit";
+            break;
+        }
+
+        record.moves.push(mv);
+        record
+            .evals
+            .push(score.map(|score| game_eval(white_score(score, side))));
+        positions.push(board.clone());
+        board.apply_move(mv);
+        moves.push(mv);
+    }
+    record
+}
+
+/// An engine score from White's view as a PGN evaluation
+fn game_eval(score: Score) -> GameEval {
+    match score {
+        Score::Centipawns(centipawns) => GameEval::Centipawns(centipawns),
+        Score::Mate(moves) => GameEval::Mate(moves),
+    }
+}
+
+/// Result of a position the rules end
+fn rules_outcome(board: &Board, positions: &[Board]) -> Option<(Outcome, &'static str)> {
+    let side = board.side_to_move();
+    if board.is_checkmated(side) {
+        let termination = match side {
+            Color::White => "Black mates",
+            Color::Black => "White mates",
+        };
+        return Some((Outcome::Win(side.opposite()), termination));
+    }
+    if board.is_stalemate(side) {
+        return Some((Outcome::Draw, "Draw by stalemate"));
+    }
+    if board.halfmove_clock() >= 100 {
+        return Some((Outcome::Draw, "Draw by fifty moves rule"));
+    }
+    if search::is_insufficient_material(board) {
+        return Some((Outcome::Draw, "Draw by insufficient mating material"));
+    }
+    let key = position_key(board);
+    let repetitions = positions
+        .iter()
+        .filter(|position| position_key(position) == key)
+        .count();
+    if repetitions >= 2 {
+        return Some((Outcome::Draw, "Draw by 3-fold repetition"));
+    }
+    None
+}
+
+/// Result of a game ended early by the adjudication rules
+fn adjudicate(
+    adjudication: &Adjudication,
+    board: &Board,
+    moves: &[Move],
+    evals: &[Option<GameEval>],
+) -> Option<(Outcome, String)> {
+    if let Some(tablebase) = &adjudication.tablebase {
+        if tablebase.covers(board) {
+            if let Ok(wdl) = tablebase.probe_wdl(board) {
+                let side = board.side_to_move();
+                let outcome = match wdl {
+                    Wdl::Win => Outcome::Win(side),
+                    Wdl::Loss => Outcome::Win(side.opposite()),
+                    _ => Outcome::Draw,
+                };
+                let termination = match outcome {
+                    Outcome::Win(color) => format!("{} wins by tablebase", color_name(color)),
+                    Outcome::Draw => "Draw by tablebase".to_string(),
+                };
+                return Some((outcome, termination));
+            }
+        }
+    }
+
+    // Centipawns from White's view of the last engine moves, newest first
+    let recent = |plies: u32| -> Option<Vec<i32>> {
+        let plies = plies as usize;
+        if evals.len() < plies {
+            return None;
+        }
+        evals[evals.len() - plies..]
+            .iter()
+            .map(|eval| eval.map(|eval| eval.clamped_centipawns(MATE_SCORE)))
+            .collect()
+    };
+
+    if let Some(rule) = adjudication.resign {
+        if let Some(scores) = recent(2 * rule.moves.max(1)) {
+            for loser in [Color::White, Color::Black] {
+                let sign = if loser == Color::White { 1 } else { -1 };
+                if scores.iter().all(|&score| sign * score <= -rule.score) {
+                    return Some((
+                        Outcome::Win(loser.opposite()),
+                        format!("{} resigns", color_name(loser)),
+                    ));
+                }
+            }
+        }
+    }
+
+    if let Some(rule) = adjudication.draw {
+        if board.fullmove_number() >= rule.move_number {
+            if let Some(scores) = recent(2 * rule.moves.max(1)) {
+                if scores.iter().all(|score| score.abs() <= rule.score) {
+                    return Some((Outcome::Draw, "Draw by adjudication".to_string()));
+                }
+            }
+        }
+    }
+
+    if let Some(max_moves) = adjudication.max_moves {
+        if moves.len() >= 2 * max_moves as usize {
+            return Some((Outcome::Draw, "Draw by move limit".to_string()));
+        }
+    }
+    None
+}
+
+/// An engine playing in a match
+/// What a player does on its turn
+enum Turn {
+    /// A move with the engine's score from the side to move's view
+    Move(Move, Option<Score>),
+    Resign,
+    /// No move came even after the engine was told to stop
+    OutOfTime,
+}
+
+trait Player {
+    /// Forget the last game; `seed` varies the choices of a weakened engine
+    fn start_game(&mut self, seed: u64) -> Result<(), &'static str>;
+
+    /// Choose a move for `board`, reached from `start` by `moves` through
+    /// `positions`, or resign
+    fn choose_move(
+        &mut self,
+        start: &Board,
+        moves: &[Move],
+        positions: &[Board],
+        board: &Board,
+        limits: &SearchLimits,
+    ) -> Result<Turn, &'static str>;
+}
+
+impl Player for Engine {
+    fn start_game(&mut self, seed: u64) -> Result<(), &'static str> {
+        self.new_game();
+        self.set_seed(seed);
+        Ok(())
+    }
+
+    fn choose_move(
+        &mut self,
+        _start: &Board,
+        _moves: &[Move],
+        positions: &[Board],
+        board: &Board,
+        limits: &SearchLimits,
+    ) -> Result<Turn, &'static str> {
+        self.set_history(positions);
+        let result = self.search(board, limits);
+        let mv = result.best_move.ok_or("Engine found no move\n")?;
+        Ok(Turn::Move(mv, Some(result.score)))
+    }
+}
+
+impl Player for Box<dyn ExternalEngine> {
+    fn start_game(&mut self, _seed: u64) -> Result<(), &'static str> {
+        self.new_game()?;
+        self.wait_ready()
+    }
+
+    fn choose_move(
+        &mut self,
+        start: &Board,
+        moves: &[Move],
+        _positions: &[Board],
+        board: &Board,
+        limits: &SearchLimits,
+    ) -> Result<Turn, &'static str> {
+        self.set_position(start, moves)?;
+        self.go(limits)?;
+
+        // Engines on a clock manage their time and are only stopped once their
+        // flag falls, others at their move time. XBoard ones only get whole seconds
+        let search_time = match &limits.clock {
+            Some(clock) => clock.side(board.side_to_move()).0 + TIME_MARGIN,
+            None => limits.movetime.unwrap_or(MAX_SEARCH_TIME),
+        };
+        let mut deadline = Instant::now() + search_time;
+        let mut stopped = false;
+        let mut score = None;
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                if stopped {
+                    return Ok(Turn::OutOfTime);
+                }
+                self.stop()?;
+                stopped = true;
+                deadline = now + LATE_MOVE_GRACE;
+            }
+            match self.wait_event(deadline - now)? {
+                Some(UciEvent::Info(info))
+                    if info.multipv.unwrap_or(1) == 1 && !info.bound && info.score.is_some() =>
+                {
+                    score = info.score;
+                }
+                Some(UciEvent::BestMove(Some(text))) => {
+                    let mv = board
+                        .parse_uci_move(&text)
+                        .map_err(|_| "Engine played an illegal move\n")?;
+                    return Ok(Turn::Move(mv, score));
+                }
+                // Only XBoard engines can resign, UCI ones have moves left to play
+                Some(UciEvent::BestMove(None)) if self.protocol() == EngineProtocol::Xboard => {
+                    return Ok(Turn::Resign)
+                }
+                Some(UciEvent::BestMove(None)) => return Err("Engine gave no move\n"),
+                Some(UciEvent::Info(_)) | None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pgn_keeps_evaluations() {
+        let mut board = Board::new_starting_default();
+        let mut play = |text: &str| {
+            let mv = board.parse_uci_move(text).unwrap();
+            board.apply_move(mv);
+            mv
+        };
+        let opening = Opening {
+            board: Board::new_starting_default(),
+            moves: vec![play("e2e4")],
+        };
+        let moves: Vec<Move> = ["e7e5", "d1h5", "b8c6", "f1c4", "g8f6", "h5f7"]
+            .into_iter()
+            .map(play)
+            .collect();
+        let evals = vec![
+            Some(GameEval::Centipawns(-20)),
+            Some(GameEval::Centipawns(15)),
+            None,
+            Some(GameEval::Centipawns(30)),
+            Some(GameEval::Mate(1)),
+            Some(GameEval::Checkmate(Color::White)),
+        ];
+        let record = GameRecord {
+            round: 1,
+            white: "First".to_string(),
+            black: "Second".to_string(),
+            first_player_color: 0,
+            opening,
+            moves: moves.clone(),
+            evals: evals.clone(),
+            outcome: Outcome::Win(Color::White),
+            termination: "White mates".to_string(),
+            termination_tag: "normal",
+            failed_player: None,
+        };
+        let time_control = TimeControl::parse("10+0.1").unwrap();
+
+        let mut pgn = Vec::new();
+        record.write_pgn(&time_control, &mut pgn).unwrap();
+        let games = read_pgn(&String::from_utf8(pgn).unwrap()).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].moves[1..], moves[..]);
+        assert_eq!(games[0].evaluations[0], None);
+        assert_eq!(games[0].evaluations[1..], evals[..]);
+        assert_eq!(games[0].result, "1-0");
+    }
+
+    /// UCI engine that answers the handshake but never moves
+    #[cfg(unix)]
+    const SILENT_ENGINE: &str = r#"#!/bin/sh
+while read -r line; do
+    case "$line" in
+        uci) echo "id name Silent"; echo "uciok" ;;
+        isready) echo "readyok" ;;
+        quit) exit 0 ;;
+    esac
+done
+"#;
+
+    #[cfg(unix)]
+    #[test]
+    fn silent_engine_loses_on_time() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = std::env::temp_dir().join(format!("silent-uci-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("silent-engine");
+        std::fs::write(&path, SILENT_ENGINE).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config = MatchConfig {
+            players: [
+                PlayerConfig::built_in(),
+                PlayerConfig::external(&path, EngineProtocol::Uci),
+            ],
+            openings: Vec::new(),
+            games: 1,
+            time_control: TimeControl::parse("0.5+0").unwrap(),
+            adjudication: Adjudication::default(),
+            concurrency: 1,
+            sprt: None,
+            pgn_path: None,
+        };
+        let mut players = config.players.clone().map(|player| player.start().ok());
+        let record = play_game(&config, 0, &mut players);
+        assert_eq!(record.moves.len(), 1);
+        assert_eq!(record.outcome, Outcome::Win(Color::White));
+        assert_eq!(record.termination, "Black loses on time");
+        assert_eq!(record.termination_tag, "time forfeit");
+        assert_eq!(record.failed_player, Some(1));
+
+        let mut pgn = Vec::new();
+        record.write_pgn(&config.time_control, &mut pgn).unwrap();
+        let games = read_pgn(&String::from_utf8(pgn).unwrap()).unwrap();
+        assert_eq!(games[0].tag("Termination"), Some("time forfeit"));
+        assert_eq!(games[0].result, "1-0");
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
@@ -0,0 +1,199 @@
+// Common ground of the UCI and XBoard clients
+//
+// Both protocols run the engine as a child process talking over stdin and
+// stdout. A reader thread forwards the engine output line by line, so callers
+// can poll without blocking.
+
+use crate::{SearchLimits, UciEngine, UciEvent, UciOption, XboardEngine};
+use chess_core::{Board, Game, Move};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long an engine may take to answer during the handshake or a sync
+pub(crate) const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long an engine may take to exit after `quit`
+const QUIT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Protocol an external engine speaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EngineProtocol {
+    #[default]
+    Uci,
+    /// The Chess Engine Communication Protocol used by XBoard and WinBoard
+    Xboard,
+}
+
+impl EngineProtocol {
+    pub const ALL: [EngineProtocol; 2] = [EngineProtocol::Uci, EngineProtocol::Xboard];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EngineProtocol::Uci => "UCI",
+            EngineProtocol::Xboard => "XBoard",
+        }
+    }
+
+    /// Read a protocol name like `uci` or `xboard`, case insensitive
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "uci" => Some(EngineProtocol::Uci),
+            "xboard" | "cecp" | "winboard" => Some(EngineProtocol::Xboard),
+            _ => None,
+        }
+    }
+
+    /// Start the engine at `path` and perform the handshake of this protocol
+    pub fn start<P: AsRef<Path>>(&self, path: P) -> Result<Box<dyn ExternalEngine>, &'static str> {
+        Ok(match self {
+            EngineProtocol::Uci => Box::new(UciEngine::start(path)?),
+            EngineProtocol::Xboard => Box::new(XboardEngine::start(path)?),
+        })
+    }
+}
+
+/// An engine running in another process, whatever protocol it speaks.
+/// Moves in events are in UCI notation for both protocols
+pub trait ExternalEngine: Send {
+    fn name(&self) -> &str;
+
+    fn author(&self) -> &str;
+
+    fn protocol(&self) -> EngineProtocol;
+
+    /// Options announced during the handshake
+    fn options(&self) -> &[UciOption];
+
+    /// Set an option, without a value for buttons
+    fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), &'static str>;
+
+    /// Wait until the engine has processed everything sent so far.
+    /// Output that arrives meanwhile is dropped
+    fn wait_ready(&mut self) -> Result<(), &'static str>;
+
+    /// Tell the engine the next position belongs to another game, without waiting for it
+    fn new_game(&mut self) -> Result<(), &'static str>;
+
+    /// Send a position as a starting board and the moves played from it
+    fn set_position(&mut self, start: &Board, moves: &[Move]) -> Result<(), &'static str>;
+
+    /// Send the current position of a game with the moves leading to it
+    fn set_game(&mut self, game: &Game) -> Result<(), &'static str> {
+        let start = game.positions().first().unwrap_or(game.board()).clone();
+        self.set_position(&start, game.moves())
+    }
+
+    /// Start searching the last position sent; without limits the search runs until stopped
+    fn go(&mut self, limits: &SearchLimits) -> Result<(), &'static str>;
+
+    /// Ask the engine to send its best move as soon as possible
+    fn stop(&mut self) -> Result<(), &'static str>;
+
+    /// Get the next search progress or best move, without waiting.
+    /// Returns an error once the engine has exited
+    fn poll(&mut self) -> Result<Option<UciEvent>, &'static str>;
+
+    /// Wait for the next search progress or best move up to `timeout`
+    fn wait_event(&mut self, timeout: Duration) -> Result<Option<UciEvent>, &'static str>;
+}
+
+/// Child process of an engine with its output arriving line by line
+pub(crate) struct EngineProcess {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+}
+
+impl EngineProcess {
+    pub(crate) fn spawn(path: &Path) -> Result<Self, &'static str> {
+        let mut command = Command::new(path);
+        // Engines often look for their network files next to the binary
+        if let Some(directory) = path.parent().filter(|parent| parent.is_dir()) {
+            command.current_dir(directory);
+        }
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|_| "Failed to start engine\n")?;
+
+        let stdin = child.stdin.take().ok_or("Failed to start engine\n")?;
+        let stdout = child.stdout.take().ok_or("Failed to start engine\n")?;
+
+        let (sender, lines) = channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            lines,
+        })
+    }
+
+    /// Send one command line
+    pub(crate) fn send(&mut self, command: &str) -> Result<(), &'static str> {
+        writeln!(self.stdin, "{}", command)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| "Engine is not running\n")
+    }
+
+    /// Wait for the next line, failing once `deadline` passes
+    pub(crate) fn next_line(&mut self, deadline: Instant) -> Result<String, &'static str> {
+        self.recv_line(deadline.saturating_duration_since(Instant::now()))?
+            .ok_or("Engine did not answer in time\n")
+    }
+
+    /// Wait for the next line up to `timeout`
+    pub(crate) fn recv_line(&mut self, timeout: Duration) -> Result<Option<String>, &'static str> {
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(Some(line)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err("Engine exited\n"),
+        }
+    }
+
+    /// Get the next line if one arrived
+    pub(crate) fn try_line(&mut self) -> Result<Option<String>, &'static str> {
+        match self.lines.try_recv() {
+            Ok(line) => Ok(Some(line)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err("Engine exited\n"),
+        }
+    }
+}
+
+impl Drop for EngineProcess {
+    /// Ask the engine to quit, killing it if it does not
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        let deadline = Instant::now() + QUIT_TIMEOUT;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Name of an engine before it tells its own, the file name of its binary
+pub(crate) fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
@@ -0,0 +1,26 @@
+pub mod bench;
+pub mod engine_match;
+mod external;
+mod ordering;
+mod problem;
+mod search;
+mod skill;
+mod sprt;
+mod tt;
+pub mod uci;
+mod uci_client;
+pub mod xboard;
+mod xboard_client;
+mod zobrist;
+
+pub use external::{EngineProtocol, ExternalEngine};
+pub use problem::{MateSolution, MateSolver, SolutionNode, Stipulation, MAX_MATE_MOVES};
+pub use search::{
+    white_score, ClockLimits, Engine, PvLine, Score, SearchLimits, SearchResult, StopHandle,
+    MATE_SCORE, MAX_MULTIPV, MAX_THREADS,
+};
+pub use skill::{Skill, MAX_ELO, MAX_SKILL_LEVEL, MIN_ELO};
+pub use sprt::{elo_to_score, score_to_elo, MatchScore, Sprt, SprtResult};
+pub use uci_client::{UciEngine, UciEvent, UciInfo, UciOption, UciOptionKind};
+pub use xboard_client::XboardEngine;
+pub use zobrist::position_key;
@@ -0,0 +1,120 @@
+use crate::zobrist::square_index;
+use chess_core::{Board, Color, Move, PieceType};
+
+/// Deepest ply the search keeps per-ply data for
+pub(crate) const MAX_PLY: usize = 128;
+
+const TT_MOVE_SCORE: i32 = 1_000_000;
+const CAPTURE_SCORE: i32 = 100_000;
+const PROMOTION_SCORE: i32 = 90_000;
+const KILLER_SCORE: i32 = 80_000;
+
+/// Heuristics remembering which quiet moves caused cutoffs
+pub(crate) struct MoveOrdering {
+    killers: Vec<[Option<Move>; 2]>,
+    history: Vec<[[i32; 64]; 64]>,
+}
+
+/// Rank of a piece for most valuable victim / least valuable attacker
+fn piece_rank(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 4,
+        PieceType::Queen => 5,
+        PieceType::King => 6,
+    }
+}
+
+fn color_index(color: Color) -> usize {
+    match color {
+        Color::White => 0,
+        Color::Black => 1,
+    }
+}
+
+/// Whether a move takes a piece, en passant included
+pub(crate) fn is_capture(board: &Board, mv: Move) -> bool {
+    match board.get_piece(mv.from) {
+        Some(piece) if piece.piece_type == PieceType::Pawn => mv.from.file() != mv.to.file(),
+        Some(_) => board.get_piece(mv.to).is_some(),
+        None => false,
+    }
+}
+
+/// Captures and promotions, the moves searched in quiescence
+pub(crate) fn is_tactical(board: &Board, mv: Move) -> bool {
+    is_capture(board, mv) || mv.promotion == Some(PieceType::Queen)
+}
+
+impl MoveOrdering {
+    pub fn new() -> Self {
+        Self {
+            killers: vec![[None; 2]; MAX_PLY],
+            history: vec![[[0; 64]; 64]; 2],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Sort moves best first: hash move, captures by MVV-LVA, promotions, killers, history
+    pub fn sort(&self, board: &Board, moves: &mut [Move], tt_move: Option<Move>, ply: usize) {
+        let color = color_index(board.side_to_move());
+        let killers = self.killers.get(ply).copied().unwrap_or([None; 2]);
+
+        moves.sort_by_cached_key(|&mv| {
+            let score = if Some(mv) == tt_move {
+                TT_MOVE_SCORE
+            } else if is_capture(board, mv) {
+                // en passant victims are not on the target square
+                let victim = board
+                    .get_piece(mv.to)
+                    .map(|p| piece_rank(p.piece_type))
+                    .unwrap_or(1);
+                let attacker = board
+                    .get_piece(mv.from)
+                    .map(|p| piece_rank(p.piece_type))
+                    .unwrap_or(1);
+                CAPTURE_SCORE + victim * 10 - attacker
+            } else if mv.promotion == Some(PieceType::Queen) {
+                PROMOTION_SCORE
+            } else if killers[0] == Some(mv) {
+                KILLER_SCORE
+            } else if killers[1] == Some(mv) {
+                KILLER_SCORE - 1
+            } else if mv.promotion.is_some() {
+                // under-promotions last
+                -TT_MOVE_SCORE
+            } else {
+                self.history[color][square_index(mv.from)][square_index(mv.to)]
+            };
+            std::cmp::Reverse(score)
+        });
+    }
+
+    /// Remember a quiet move that failed high
+    pub fn record_cutoff(&mut self, board: &Board, mv: Move, ply: usize, depth: i32) {
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0] != Some(mv) {
+                killers[1] = killers[0];
+                killers[0] = Some(mv);
+            }
+        }
+
+        let color = color_index(board.side_to_move());
+        let entry = &mut self.history[color][square_index(mv.from)][square_index(mv.to)];
+        *entry += depth * depth;
+
+        // Keep history below the killer scores
+        if *entry > KILLER_SCORE / 2 {
+            for from in self.history[color].iter_mut() {
+                for score in from.iter_mut() {
+                    *score /= 2;
+                }
+            }
+        }
+    }
+}
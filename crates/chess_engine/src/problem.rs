@@ -0,0 +1,598 @@
+// Solver for chess problems. It proves or refutes a stipulation, like a forced
+// mate in N moves for the side to move, by trying every move against every
+// defence, and lays out the full solution tree so that cooks, more than one key
+// move, and duals, more than one way for the attacker to go on after a defence,
+// show up.
+//
+// Helpmates count the moves of the side that mates, the side to move moving
+// first: in h#2 the side to move plays, the other side answers, and after the
+// next pair of moves the side to move is mated. Only solutions of exactly that
+// length are helpmates.
+
+use crate::search::StopHandle;
+use crate::zobrist::position_key;
+use chess_core::{Board, Color, Move};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Longest mate the solver is meant for, longer ones take too long
+pub const MAX_MATE_MOVES: u32 = 8;
+
+/// What the side to move has to achieve in a chess problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stipulation {
+    /// Mate the other side against any defence
+    Direct,
+    /// Both sides cooperate so that the side to move is mated
+    Help,
+    /// Force the other side to give mate, against its will
+    SelfMate,
+    /// A selfmate where either side has to give mate whenever it can
+    Reflex,
+}
+
+impl Stipulation {
+    pub const ALL: [Stipulation; 4] = [
+        Stipulation::Direct,
+        Stipulation::Help,
+        Stipulation::SelfMate,
+        Stipulation::Reflex,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stipulation::Direct => "Mate",
+            Stipulation::Help => "Helpmate",
+            Stipulation::SelfMate => "Selfmate",
+            Stipulation::Reflex => "Reflexmate",
+        }
+    }
+
+    /// Problem notation before the number of moves, like `h#` in `h#2`
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Stipulation::Direct => "#",
+            Stipulation::Help => "h#",
+            Stipulation::SelfMate => "s#",
+            Stipulation::Reflex => "r#",
+        }
+    }
+}
+
+/// A move of a solution with the replies that follow it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolutionNode {
+    pub mv: Move,
+    pub replies: Vec<SolutionNode>,
+}
+
+/// Every way the side to move meets a stipulation in `moves` moves: first
+/// moves that work, each with all defences, each with the moves that still
+/// work. In helpmates every move is one that leads to mate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MateSolution {
+    pub stipulation: Stipulation,
+    pub moves: u32,
+    pub tree: Vec<SolutionNode>,
+}
+
+impl MateSolution {
+    /// Whether the stipulation can be met at all
+    pub fn is_mate(&self) -> bool {
+        !self.tree.is_empty()
+    }
+
+    /// First moves that work
+    pub fn keys(&self) -> Vec<Move> {
+        self.tree.iter().map(|node| node.mv).collect()
+    }
+
+    /// Whether the problem has more than one solution: more than one key
+    /// move, or for helpmates more than one line
+    pub fn is_cooked(&self) -> bool {
+        match self.stipulation {
+            Stipulation::Help => self.solutions().len() > 1,
+            _ => self.tree.len() > 1,
+        }
+    }
+
+    /// Every line of the tree from the first move to its end
+    pub fn solutions(&self) -> Vec<Vec<Move>> {
+        let mut solutions = Vec::new();
+        let mut line = Vec::new();
+        for node in &self.tree {
+            collect_lines(node, &mut line, &mut solutions);
+        }
+        solutions
+    }
+
+    /// Lines ending with a defence after which the attacker has more than one
+    /// move that still works. Helpmates have no defences, so no duals
+    pub fn duals(&self) -> Vec<Vec<Move>> {
+        let mut duals = Vec::new();
+        if self.stipulation == Stipulation::Help {
+            return duals;
+        }
+        let mut line = Vec::new();
+        for key in &self.tree {
+            collect_duals(key, &mut line, &mut duals);
+        }
+        duals
+    }
+
+    /// The solution in problem notation: helpmates as one line per solution,
+    /// other stipulations as a tree with a move per line indented by ply and
+    /// key moves marked with `!`
+    pub fn to_text(&self, board: &Board) -> String {
+        let mut text = String::new();
+        if self.stipulation == Stipulation::Help {
+            for line in self.solutions() {
+                text.push_str(&line_text(board, &line));
+                text.push('\n');
+            }
+        } else {
+            for key in &self.tree {
+                write_node(board, key, 0, &mut text);
+            }
+        }
+        text
+    }
+}
+
+impl fmt::Display for MateSolution {
+    /// The stipulation in problem notation, like `s#2`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.stipulation.symbol(), self.moves)
+    }
+}
+
+fn collect_lines(node: &SolutionNode, line: &mut Vec<Move>, lines: &mut Vec<Vec<Move>>) {
+    line.push(node.mv);
+    if node.replies.is_empty() {
+        lines.push(line.clone());
+    }
+    for reply in &node.replies {
+        collect_lines(reply, line, lines);
+    }
+    line.pop();
+}
+
+fn collect_duals(attack: &SolutionNode, line: &mut Vec<Move>, duals: &mut Vec<Vec<Move>>) {
+    line.push(attack.mv);
+    for defence in &attack.replies {
+        line.push(defence.mv);
+        if defence.replies.len() > 1 {
+            duals.push(line.clone());
+        }
+        for next in &defence.replies {
+            collect_duals(next, line, duals);
+        }
+        line.pop();
+    }
+    line.pop();
+}
+
+/// Move number and dots before a move, like `1.` or `1...`
+fn move_number(board: &Board) -> String {
+    let dots = match board.side_to_move() {
+        Color::White => ".",
+        Color::Black => "...",
+    };
+    format!("{}{}", board.fullmove_number(), dots)
+}
+
+/// A line of moves in SAN with move numbers, like `1... Kd5 2. Qe4 Kc5 3. Qd4#`
+fn line_text(board: &Board, line: &[Move]) -> String {
+    let mut board = board.clone();
+    let mut text = String::new();
+    for (index, &mv) in line.iter().enumerate() {
+        if index == 0 || board.side_to_move() == Color::White {
+            if index > 0 {
+                text.push(' ');
+            }
+            text.push_str(&move_number(&board));
+        }
+        text.push(' ');
+        text.push_str(&board.move_to_san(mv));
+        board.apply_move(mv);
+    }
+    text
+}
+
+fn write_node(board: &Board, node: &SolutionNode, ply: usize, text: &mut String) {
+    let mark = if ply == 0 { "!" } else { "" };
+    text.push_str(&format!(
+        "{}{} {}{}\n",
+        "  ".repeat(ply),
+        move_number(board),
+        board.move_to_san(node.mv),
+        mark
+    ));
+
+    let mut child = board.clone();
+    child.apply_move(node.mv);
+    for reply in &node.replies {
+        write_node(&child, reply, ply + 1, text);
+    }
+}
+
+/// Exhaustive solver for mates, helpmates, selfmates and reflexmates
+pub struct MateSolver {
+    stipulation: Stipulation,
+    /// Whether the attacker to move in a position meets the stipulation within
+    /// a number of moves, or for helpmates plies
+    cache: HashMap<(u64, u32), bool>,
+    /// Last defence that refuted an attacker move, by moves left, tried first
+    refutations: Vec<Option<Move>>,
+    nodes: u64,
+    stop: Arc<AtomicBool>,
+}
+
+impl Default for MateSolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MateSolver {
+    pub fn new() -> Self {
+        Self {
+            stipulation: Stipulation::Direct,
+            cache: HashMap::new(),
+            refutations: vec![None; MAX_MATE_MOVES as usize + 1],
+            nodes: 0,
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Handle to interrupt a solve from another thread
+    pub fn stop_handle(&self) -> StopHandle {
+        StopHandle(self.stop.clone())
+    }
+
+    /// Positions visited by the last solve
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Find every way to meet a stipulation in `moves` moves for the side to
+    /// move, at most that many except for helpmates, or `None` when stopped
+    /// first or `moves` is not within `1..=MAX_MATE_MOVES`
+    pub fn solve(
+        &mut self,
+        board: &Board,
+        stipulation: Stipulation,
+        moves: u32,
+    ) -> Option<MateSolution> {
+        if moves == 0 || moves > MAX_MATE_MOVES {
+            return None;
+        }
+        self.stipulation = stipulation;
+        self.cache.clear();
+        self.refutations.fill(None);
+        self.nodes = 0;
+        self.stop.store(false, Ordering::Relaxed);
+
+        let tree = if stipulation == Stipulation::Help {
+            self.help_tree(board, 2 * moves)?
+        } else {
+            self.attack_tree(board, moves)?
+        };
+        Some(MateSolution {
+            stipulation,
+            moves,
+            tree,
+        })
+    }
+
+    /// Attacker moves from `board` that work within `moves`, with their defences
+    fn attack_tree(&mut self, board: &Board, moves: u32) -> Option<Vec<SolutionNode>> {
+        let mut tree = Vec::new();
+        for (mv, child) in self.attacker_moves(board, moves) {
+            if self.defender_loses(&child, moves - 1)? {
+                let replies = self.defence_tree(&child, moves - 1)?;
+                tree.push(SolutionNode { mv, replies });
+            }
+        }
+        Some(tree)
+    }
+
+    fn defence_tree(&mut self, board: &Board, moves: u32) -> Option<Vec<SolutionNode>> {
+        let mut tree = Vec::new();
+        for (mv, child) in self.defences(board) {
+            let replies = if child.is_checkmated(child.side_to_move()) {
+                Vec::new()
+            } else {
+                self.attack_tree(&child, moves)?
+            };
+            tree.push(SolutionNode { mv, replies });
+        }
+        Some(tree)
+    }
+
+    /// Whether the side to move meets the stipulation within `moves` against any defence
+    fn attacker_wins(&mut self, board: &Board, moves: u32) -> Option<bool> {
+        if moves == 0 {
+            return Some(false);
+        }
+        let key = (position_key(board), moves);
+        if let Some(&wins) = self.cache.get(&key) {
+            return Some(wins);
+        }
+
+        let mut wins = false;
+        for (_, child) in self.attacker_moves(board, moves) {
+            if self.defender_loses(&child, moves - 1)? {
+                wins = true;
+                break;
+            }
+        }
+        self.cache.insert(key, wins);
+        Some(wins)
+    }
+
+    /// Whether the defender to move loses whatever it plays: it is mated now in
+    /// a direct mate, or has to mate in a selfmate, or else the attacker still
+    /// wins within `moves` after every defence
+    fn defender_loses(&mut self, board: &Board, moves: u32) -> Option<bool> {
+        if self.stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.nodes += 1;
+
+        let color = board.side_to_move();
+        let mut defences = self.defences(board);
+        if defences.is_empty() {
+            return Some(self.stipulation == Stipulation::Direct && board.is_king_in_check(color));
+        }
+
+        // A defence that refuted a sibling often refutes this move too
+        if let Some(refutation) = self.refutations[moves as usize] {
+            if let Some(index) = defences.iter().position(|&(mv, _)| mv == refutation) {
+                defences.swap(0, index);
+            }
+        }
+        for (mv, child) in defences {
+            let wins = if self.stipulation != Stipulation::Direct
+                && child.is_checkmated(child.side_to_move())
+            {
+                true
+            } else {
+                self.attacker_wins(&child, moves)?
+            };
+            if !wins {
+                self.refutations[moves as usize] = Some(mv);
+                return Some(false);
+            }
+        }
+        Some(true)
+    }
+
+    /// Legal moves of the attacker with the positions they lead to, checks and
+    /// captures first. On the last move of a direct mate only checks can mate,
+    /// and in a reflexmate an attacker able to mate has no good moves
+    fn attacker_moves(&self, board: &Board, moves: u32) -> Vec<(Move, Board)> {
+        let children = ordered_moves(board);
+        match self.stipulation {
+            Stipulation::Direct if moves == 1 => children
+                .into_iter()
+                .filter(|(_, child)| child.is_king_in_check(child.side_to_move()))
+                .collect(),
+            Stipulation::Reflex if children.iter().any(|(_, child)| gives_mate(child)) => {
+                Vec::new()
+            }
+            _ => children,
+        }
+    }
+
+    /// Legal moves of the defender with the positions they lead to. In a
+    /// reflexmate a defender able to mate has to
+    fn defences(&self, board: &Board) -> Vec<(Move, Board)> {
+        let children = ordered_moves(board);
+        if self.stipulation == Stipulation::Reflex
+            && children.iter().any(|(_, child)| gives_mate(child))
+        {
+            return children
+                .into_iter()
+                .filter(|(_, child)| gives_mate(child))
+                .collect();
+        }
+        children
+    }
+
+    /// Cooperative moves from `board` that lead to the side to move at the
+    /// start being mated after exactly `plies` more plies
+    fn help_tree(&mut self, board: &Board, plies: u32) -> Option<Vec<SolutionNode>> {
+        let mut tree = Vec::new();
+        for (mv, child) in help_moves(board, plies) {
+            if self.helps_mate(&child, plies - 1)? {
+                let replies = self.help_tree(&child, plies - 1)?;
+                tree.push(SolutionNode { mv, replies });
+            }
+        }
+        Some(tree)
+    }
+
+    fn helps_mate(&mut self, board: &Board, plies: u32) -> Option<bool> {
+        if self.stop.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.nodes += 1;
+
+        if plies == 0 {
+            return Some(board.is_checkmated(board.side_to_move()));
+        }
+        let key = (position_key(board), plies);
+        if let Some(&mates) = self.cache.get(&key) {
+            return Some(mates);
+        }
+
+        let mut mates = false;
+        for (_, child) in help_moves(board, plies) {
+            if self.helps_mate(&child, plies - 1)? {
+                mates = true;
+                break;
+            }
+        }
+        self.cache.insert(key, mates);
+        Some(mates)
+    }
+}
+
+/// Whether the side to move in a position reached by a move is mated
+fn gives_mate(child: &Board) -> bool {
+    child.is_checkmated(child.side_to_move())
+}
+
+/// Legal moves with the positions they lead to, checks and captures first
+fn ordered_moves(board: &Board) -> Vec<(Move, Board)> {
+    let color = board.side_to_move();
+    let mut children: Vec<(u8, Move, Board)> = board
+        .get_all_valid_moves(color)
+        .into_iter()
+        .map(|mv| {
+            let mut child = board.clone();
+            child.apply_move(mv);
+            let check = child.is_king_in_check(color.opposite());
+            let capture = board.get_piece(mv.to).is_some();
+            let order = match (check, capture) {
+                (true, _) => 0,
+                (false, true) => 1,
+                (false, false) => 2,
+            };
+            (order, mv, child)
+        })
+        .collect();
+    children.sort_by_key(|&(order, _, _)| order);
+    children
+        .into_iter()
+        .map(|(_, mv, child)| (mv, child))
+        .collect()
+}
+
+/// Moves of a helpmate with `plies` left, the last one has to give check
+fn help_moves(board: &Board, plies: u32) -> Vec<(Move, Board)> {
+    let mut children = ordered_moves(board);
+    if plies == 1 {
+        children.retain(|(_, child)| child.is_king_in_check(child.side_to_move()));
+    }
+    children
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    /// Moves in UCI notation played one after another from `board`
+    fn line(board: &Board, moves: &str) -> Vec<Move> {
+        let mut board = board.clone();
+        moves
+            .split_whitespace()
+            .map(|text| {
+                let mv = board.parse_uci_move(text).unwrap();
+                board.apply_move(mv);
+                mv
+            })
+            .collect()
+    }
+
+    fn solve(board: &Board, stipulation: Stipulation, moves: u32) -> MateSolution {
+        MateSolver::new().solve(board, stipulation, moves).unwrap()
+    }
+
+    #[test]
+    fn direct_mate_key() {
+        let position = board("8/8/8/8/2R4p/8/3K4/k7 w - - 0 1");
+        assert!(!solve(&position, Stipulation::Direct, 1).is_mate());
+
+        let solution = solve(&position, Stipulation::Direct, 2);
+        assert_eq!(solution.to_string(), "#2");
+        assert_eq!(solution.keys(), line(&position, "d2c2"));
+        assert!(!solution.is_cooked());
+        assert!(solution.duals().is_empty());
+        assert_eq!(
+            solution.solutions(),
+            [
+                line(&position, "d2c2 a1a2 c4a4"),
+                line(&position, "d2c2 h4h3 c4a4")
+            ]
+        );
+        assert_eq!(
+            solution.to_text(&position),
+            "1. Kc2!\n  1... Ka2\n    2. Ra4#\n  1... h3\n    2. Ra4#\n"
+        );
+    }
+
+    #[test]
+    fn cooked_problem() {
+        let position = board("7k/8/5K2/8/8/8/8/R7 w - - 0 1");
+        let solution = solve(&position, Stipulation::Direct, 2);
+        assert!(solution.is_cooked());
+        assert_eq!(
+            solution.keys(),
+            [line(&position, "f6g6"), line(&position, "f6f7")].concat()
+        );
+    }
+
+    #[test]
+    fn duals() {
+        // After 1. Qf2 Kc1 both Qe1# and Qc2# mate
+        let position = board("8/8/8/8/5Q2/1K6/8/3k4 w - - 0 1");
+        let solution = solve(&position, Stipulation::Direct, 2);
+        assert!(!solution.is_cooked());
+        assert_eq!(solution.duals(), [line(&position, "f4f2 d1c1")]);
+    }
+
+    #[test]
+    fn selfmate_and_reflexmate() {
+        // Black's pawns can only take the rook on g2, both captures mate
+        let position = board("k7/p5R1/P1N5/8/8/5p1p/5P1P/6BK w - - 0 1");
+        let solution = solve(&position, Stipulation::SelfMate, 1);
+        assert_eq!(solution.to_string(), "s#1");
+        assert_eq!(
+            solution.solutions(),
+            [line(&position, "g7g2 f3g2"), line(&position, "g7g2 h3g2")]
+        );
+        // White could mate with Rg8 so has to, which is no reflexmate
+        assert!(!solve(&position, Stipulation::Reflex, 1).is_mate());
+
+        // Black only has to promote with mate in the reflexmate
+        let position = board("8/8/7Q/8/8/3k4/pK6/8 w - - 0 1");
+        assert!(!solve(&position, Stipulation::SelfMate, 1).is_mate());
+        let solution = solve(&position, Stipulation::Reflex, 1);
+        assert_eq!(solution.to_string(), "r#1");
+        assert_eq!(solution.solutions(), [line(&position, "b2c1 a2a1q")]);
+    }
+
+    #[test]
+    fn helpmate() {
+        let position = board("8/8/2p5/4R3/8/1K6/4k3/8 b - - 0 1");
+        let solution = solve(&position, Stipulation::Help, 2);
+        assert_eq!(solution.to_string(), "h#2");
+        assert!(!solution.is_cooked());
+        assert_eq!(
+            solution.solutions(),
+            [line(&position, "e2d1 b3c3 d1c1 e5e1")]
+        );
+        assert_eq!(solution.to_text(&position), "1... Kd1 2. Kc3 Kc1 3. Re1#\n");
+        // Helpmates have to take all their moves
+        assert!(!solve(&position, Stipulation::Help, 1).is_mate());
+    }
+
+    #[test]
+    fn moves_out_of_range() {
+        let position = board("7k/8/5K2/8/8/8/8/R7 w - - 0 1");
+        let mut solver = MateSolver::new();
+        assert_eq!(solver.solve(&position, Stipulation::Direct, 0), None);
+        assert_eq!(
+            solver.solve(&position, Stipulation::Direct, MAX_MATE_MOVES + 1),
+            None
+        );
+    }
+}
@@ -0,0 +1,1051 @@
+use crate::ordering::{is_capture, is_tactical, MoveOrdering, MAX_PLY};
+use crate::skill::Skill;
+use crate::tt::{Bound, Entry, TranspositionTable};
+use crate::zobrist::{position_key, XorShift};
+use chess_core::{
+    Board, ClassicalEvaluator, Color, EvalWeights, Evaluator, IncrementalEval, Move, NnueNetwork,
+    PieceType, Square,
+};
+use std::fmt;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Score of being checkmated at the root, mates further away score less
+pub const MATE_SCORE: i32 = 30_000;
+/// Scores beyond this are mates
+const MATE_BOUND: i32 = MATE_SCORE - MAX_PLY as i32;
+const INFINITY: i32 = MATE_SCORE + 1;
+
+const DEFAULT_HASH_MB: usize = 16;
+pub(crate) const MAX_DEPTH: u32 = 64;
+/// Most search threads an engine runs
+pub const MAX_THREADS: usize = 256;
+/// Most root moves searched with their own principal variation
+pub const MAX_MULTIPV: usize = 64;
+
+/// Evaluation of a position from the side to move's view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    Centipawns(i32),
+    /// Moves until mate, negative when the side to move gets mated
+    Mate(i32),
+}
+
+impl Score {
+    fn from_internal(score: i32) -> Self {
+        if score >= MATE_BOUND {
+            Score::Mate((MATE_SCORE - score + 1) / 2)
+        } else if score <= -MATE_BOUND {
+            Score::Mate(-(MATE_SCORE + score) / 2)
+        } else {
+            Score::Centipawns(score)
+        }
+    }
+
+    /// Score as centipawns, mates counting as a very large advantage
+    pub fn as_centipawns(self) -> i32 {
+        match self {
+            Score::Centipawns(centipawns) => centipawns,
+            Score::Mate(moves) if moves > 0 => MATE_SCORE - 2 * moves + 1,
+            Score::Mate(moves) => -MATE_SCORE - 2 * moves,
+        }
+    }
+
+    /// The same score seen from the other side
+    pub fn flip(self) -> Self {
+        match self {
+            Score::Centipawns(centipawns) => Score::Centipawns(-centipawns),
+            Score::Mate(moves) => Score::Mate(-moves),
+        }
+    }
+}
+
+impl fmt::Display for Score {
+    /// Pawns like `+0.35`, or mates like `#3` and `#-3`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Score::Centipawns(centipawns) => write!(f, "{:+.2}", *centipawns as f64 / 100.0),
+            Score::Mate(moves) => write!(f, "#{}", moves),
+        }
+    }
+}
+
+/// When to stop searching; without limits the search runs until stopped
+#[derive(Debug, Clone, Default)]
+pub struct SearchLimits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub movetime: Option<Duration>,
+    /// Clocks of the game; without a move time the search budgets from them
+    pub clock: Option<ClockLimits>,
+}
+
+/// Time left on both clocks, as UCI `wtime`, `btime`, `winc`, `binc` and `movestogo`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockLimits {
+    pub white_time: Duration,
+    pub black_time: Duration,
+    pub white_increment: Duration,
+    pub black_increment: Duration,
+    /// Moves until the next time control, `None` when the time is for the rest of the game
+    pub moves_to_go: Option<u32>,
+}
+
+impl ClockLimits {
+    /// Time left and increment of one side
+    pub fn side(&self, color: Color) -> (Duration, Duration) {
+        match color {
+            Color::White => (self.white_time, self.white_increment),
+            Color::Black => (self.black_time, self.black_increment),
+        }
+    }
+}
+
+/// Time kept back for communication delays when playing on a clock
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+impl SearchLimits {
+    /// Limits for one move of a game played on a clock. Without a number of
+    /// moves until the next time control, the rest of the game counts as 30 moves
+    pub fn from_clock(time_left: Duration, increment: Duration, moves_to_go: Option<u32>) -> Self {
+        let moves_to_go = moves_to_go.unwrap_or(30).max(1);
+        let available = time_left.saturating_sub(MOVE_OVERHEAD);
+        let movetime = (time_left / moves_to_go + increment * 3 / 4)
+            .min(available)
+            .max(Duration::from_millis(10));
+
+        Self {
+            movetime: Some(movetime),
+            ..Default::default()
+        }
+    }
+
+    /// The limits with a move time budgeted from the clock of `color`, when
+    /// no move time is given
+    pub fn budget(&self, color: Color) -> Self {
+        match (self.movetime, self.clock) {
+            (None, Some(clock)) => {
+                let (time_left, increment) = clock.side(color);
+                Self {
+                    movetime: Self::from_clock(time_left, increment, clock.moves_to_go).movetime,
+                    ..self.clone()
+                }
+            }
+            _ => self.clone(),
+        }
+    }
+}
+
+/// Outcome of a completed search iteration
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    pub best_move: Option<Move>,
+    pub score: Score,
+    pub depth: u32,
+    /// Deepest ply reached, quiescence included
+    pub seldepth: u32,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    /// Principal variation, starting with the best move
+    pub pv: Vec<Move>,
+    /// Best root moves with their own variations, best first. A weakened
+    /// engine may play another move than the first one
+    pub lines: Vec<PvLine>,
+}
+
+/// A root move with its score and principal variation
+#[derive(Debug, Clone, PartialEq)]
+pub struct PvLine {
+    pub score: Score,
+    /// Principal variation, starting with the root move
+    pub pv: Vec<Move>,
+}
+
+impl SearchResult {
+    /// Nodes per second
+    pub fn nps(&self) -> u64 {
+        let millis = self.elapsed.as_millis().max(1) as u64;
+        self.nodes * 1000 / millis
+    }
+}
+
+/// Stops a running search from another thread
+#[derive(Debug, Clone)]
+pub struct StopHandle(pub(crate) Arc<AtomicBool>);
+
+impl StopHandle {
+    /// Make the search return its best move as soon as possible
+    pub fn stop(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Withdraw a stop request no search has seen yet
+    pub fn clear(&self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Alpha-beta searcher keeping its hash table between searches
+pub struct Engine {
+    evaluator: Arc<dyn Evaluator>,
+    tt: Arc<TranspositionTable>,
+    ordering: MoveOrdering,
+    /// Move ordering of each helper thread, one less than the threads
+    helper_orderings: Vec<MoveOrdering>,
+    stop: Arc<AtomicBool>,
+    history: Vec<u64>,
+    multipv: usize,
+    skill: Skill,
+    rng: XorShift,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Self {
+            evaluator: Arc::new(ClassicalEvaluator::new()),
+            tt: Arc::new(TranspositionTable::new(DEFAULT_HASH_MB)),
+            ordering: MoveOrdering::new(),
+            helper_orderings: Vec::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+            history: Vec::new(),
+            multipv: 1,
+            skill: Skill::full(),
+            rng: XorShift::new(random_seed()),
+        }
+    }
+
+    /// Score positions with another evaluator
+    pub fn set_evaluator(&mut self, evaluator: Arc<dyn Evaluator>) {
+        self.evaluator = evaluator;
+        self.tt.clear();
+    }
+
+    pub fn evaluator(&self) -> &Arc<dyn Evaluator> {
+        &self.evaluator
+    }
+
+    /// Evaluate with the network or the classical evaluator weights in a
+    /// weights file, or with the default classical evaluator without one. On
+    /// error the evaluator stays as it was
+    pub fn set_eval_file<P: AsRef<Path>>(&mut self, path: Option<P>) -> Result<(), &'static str> {
+        let Some(path) = path else {
+            self.set_evaluator(Arc::new(ClassicalEvaluator::new()));
+            return Ok(());
+        };
+        let bytes = std::fs::read(path).map_err(|_| "Failed to read weights file\n")?;
+        if NnueNetwork::is_network(&bytes) {
+            self.set_evaluator(Arc::new(NnueNetwork::from_bytes(&bytes)?));
+        } else {
+            let text = std::str::from_utf8(&bytes).map_err(|_| "Not a weights file\n")?;
+            let weights = EvalWeights::from_text(text)?;
+            self.set_evaluator(Arc::new(ClassicalEvaluator::with_weights(weights)));
+        }
+        Ok(())
+    }
+
+    /// Resize the hash table, clearing it
+    pub fn set_hash_size(&mut self, size_mb: usize) {
+        self.tt = Arc::new(TranspositionTable::new(size_mb));
+    }
+
+    /// Search with this many threads sharing the hash table. With a single
+    /// thread, the default, a search only depends on the position, the
+    /// limits other than time and what earlier searches left in the table
+    pub fn set_threads(&mut self, threads: usize) {
+        let helpers = threads.clamp(1, MAX_THREADS) - 1;
+        self.helper_orderings
+            .resize_with(helpers, MoveOrdering::new);
+    }
+
+    pub fn threads(&self) -> usize {
+        self.helper_orderings.len() + 1
+    }
+
+    /// Search this many best root moves, each with its own principal variation
+    pub fn set_multipv(&mut self, count: usize) {
+        self.multipv = count.clamp(1, MAX_MULTIPV);
+    }
+
+    pub fn multipv(&self) -> usize {
+        self.multipv
+    }
+
+    /// Play at another strength
+    pub fn set_skill(&mut self, skill: Skill) {
+        self.skill = skill;
+    }
+
+    pub fn skill(&self) -> Skill {
+        self.skill
+    }
+
+    /// Seed the random choices of a weakened engine, to replay the same games
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = XorShift::new(seed);
+    }
+
+    /// Forget everything learned from previous searches
+    pub fn new_game(&mut self) {
+        self.tt.clear();
+        self.ordering.clear();
+        self.helper_orderings
+            .iter_mut()
+            .for_each(MoveOrdering::clear);
+        self.history.clear();
+    }
+
+    /// Set the positions played before the one to search, to detect repetitions
+    pub fn set_history(&mut self, positions: &[Board]) {
+        self.history = positions.iter().map(position_key).collect();
+    }
+
+    /// Get a handle to stop searches from another thread. A stop request
+    /// applies to the running search, or to the next one if none is running
+    pub fn stop_handle(&self) -> StopHandle {
+        StopHandle(self.stop.clone())
+    }
+
+    /// Search a position within limits. Depth 1 is always completed so
+    /// a move is returned whenever one exists
+    pub fn search(&mut self, board: &Board, limits: &SearchLimits) -> SearchResult {
+        self.search_with_info(board, limits, |_| {})
+    }
+
+    /// Search a position, reporting the result of every completed iteration
+    pub fn search_with_info<F: FnMut(&SearchResult)>(
+        &mut self,
+        board: &Board,
+        limits: &SearchLimits,
+        mut on_info: F,
+    ) -> SearchResult {
+        let limits = &self.skill.limit(&limits.budget(board.side_to_move()));
+        let line_count = self.multipv.max(self.skill.candidate_lines());
+        let start = Instant::now();
+        let helper_nodes = AtomicU64::new(0);
+        let helpers_stop = AtomicBool::new(false);
+        let evaluator = self.evaluator.as_ref();
+        let tt = self.tt.as_ref();
+
+        // Lazy SMP: helpers search the same position and share what they
+        // find through the hash table, only the main thread's result counts
+        let mut result = thread::scope(|scope| {
+            for (index, ordering) in self.helper_orderings.iter_mut().enumerate() {
+                let mut helper = Searcher {
+                    evaluator,
+                    tt,
+                    ordering,
+                    stop: &helpers_stop,
+                    limits,
+                    start,
+                    nodes: 0,
+                    seldepth: 0,
+                    root_depth: 0,
+                    aborted: false,
+                    path: self.history.clone(),
+                    pv: vec![Vec::new(); MAX_PLY + 1],
+                    excluded: Vec::new(),
+                    is_helper: true,
+                    helper_nodes: &helper_nodes,
+                    reported_nodes: 0,
+                    incremental: evaluator.incremental(board),
+                };
+                // Every other helper starts one ply deeper so threads spread over depths
+                scope.spawn(move || helper.search_helper(board, 1 + index as u32 % 2));
+            }
+
+            let mut searcher = Searcher {
+                evaluator,
+                tt,
+                ordering: &mut self.ordering,
+                stop: &self.stop,
+                limits,
+                start,
+                nodes: 0,
+                seldepth: 0,
+                root_depth: 0,
+                aborted: false,
+                path: self.history.clone(),
+                pv: vec![Vec::new(); MAX_PLY + 1],
+                excluded: Vec::new(),
+                is_helper: false,
+                helper_nodes: &helper_nodes,
+                reported_nodes: 0,
+                incremental: evaluator.incremental(board),
+            };
+            let result = searcher.iterate(board, line_count, &mut on_info);
+            helpers_stop.store(true, Ordering::Relaxed);
+            result
+        });
+
+        // Helpers have all finished and reported their nodes
+        result.nodes += helper_nodes.load(Ordering::Relaxed);
+        result.elapsed = start.elapsed();
+        self.stop.store(false, Ordering::Relaxed);
+
+        let chosen = self.skill.choose(&result.lines, &mut self.rng);
+        if chosen > 0 {
+            let line = &result.lines[chosen];
+            result.best_move = Some(line.pv[0]);
+            result.score = line.score;
+            result.pv = line.pv.clone();
+        }
+        result
+    }
+}
+
+/// Seed for the random choices of a weakened engine, different every run
+fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or(0)
+}
+
+/// State of one search
+struct Searcher<'a> {
+    evaluator: &'a dyn Evaluator,
+    tt: &'a TranspositionTable,
+    ordering: &'a mut MoveOrdering,
+    stop: &'a AtomicBool,
+    limits: &'a SearchLimits,
+    start: Instant,
+    nodes: u64,
+    seldepth: usize,
+    root_depth: u32,
+    aborted: bool,
+    /// Keys of the game positions and of the current search line
+    path: Vec<u64>,
+    /// Principal variation found at each ply
+    pv: Vec<Vec<Move>>,
+    /// Root moves already searched as earlier lines
+    excluded: Vec<Move>,
+    is_helper: bool,
+    /// Nodes of the helper threads, reported every few nodes
+    helper_nodes: &'a AtomicU64,
+    /// Nodes of this helper already added to `helper_nodes`
+    reported_nodes: u64,
+    /// Evaluator state following the moves made, if it updates incrementally
+    incremental: Option<Box<dyn IncrementalEval + 'a>>,
+}
+
+/// Mate scores are stored relative to the node, not the root
+fn score_to_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_BOUND {
+        score + ply as i32
+    } else if score <= -MATE_BOUND {
+        score - ply as i32
+    } else {
+        score
+    }
+}
+
+fn score_from_tt(score: i32, ply: usize) -> i32 {
+    if score >= MATE_BOUND {
+        score - ply as i32
+    } else if score <= -MATE_BOUND {
+        score + ply as i32
+    } else {
+        score
+    }
+}
+
+/// Neither side has enough material to mate: bare kings or a single minor piece
+pub(crate) fn is_insufficient_material(board: &Board) -> bool {
+    let mut minor_pieces = 0;
+    for rank in 0..8 {
+        for file in 0..8 {
+            let square = Square::new(rank, file).expect("always valid");
+            match board.get_piece(square).map(|p| p.piece_type) {
+                None | Some(PieceType::King) => {}
+                Some(PieceType::Knight) | Some(PieceType::Bishop) => minor_pieces += 1,
+                Some(_) => return false,
+            }
+        }
+    }
+    minor_pieces <= 1
+}
+
+impl Searcher<'_> {
+    /// Iterative deepening of the main thread, searching `line_count` best root moves
+    fn iterate<F: FnMut(&SearchResult)>(
+        &mut self,
+        board: &Board,
+        line_count: usize,
+        on_info: &mut F,
+    ) -> SearchResult {
+        let mut result = SearchResult {
+            best_move: None,
+            score: Score::Centipawns(0),
+            depth: 0,
+            seldepth: 0,
+            nodes: 0,
+            elapsed: Duration::ZERO,
+            pv: Vec::new(),
+            lines: Vec::new(),
+        };
+
+        let max_depth = self.limits.depth.unwrap_or(MAX_DEPTH).clamp(1, MAX_DEPTH);
+        for depth in 1..=max_depth {
+            self.root_depth = depth;
+            self.seldepth = 0;
+
+            // Each line searches the root moves the previous lines did not take
+            self.excluded.clear();
+            let mut lines: Vec<PvLine> = Vec::new();
+            let mut score = 0;
+            while lines.len() < line_count {
+                let line_score = self.negamax(board, depth as i32, 0, -INFINITY, INFINITY);
+                if self.aborted {
+                    break;
+                }
+                if lines.is_empty() {
+                    score = line_score;
+                }
+                let Some(&mv) = self.pv[0].first() else {
+                    break;
+                };
+                self.excluded.push(mv);
+                lines.push(PvLine {
+                    score: Score::from_internal(line_score),
+                    pv: self.pv[0].clone(),
+                });
+            }
+
+            if self.aborted {
+                // Lines of the unfinished iteration replace their older versions
+                if !lines.is_empty() {
+                    for line in &result.lines {
+                        if lines.len() < line_count
+                            && !lines.iter().any(|new| new.pv[0] == line.pv[0])
+                        {
+                            lines.push(line.clone());
+                        }
+                    }
+                    result.best_move = Some(lines[0].pv[0]);
+                    result.score = lines[0].score;
+                    result.pv = lines[0].pv.clone();
+                    result.lines = lines;
+                }
+                break;
+            }
+
+            result = SearchResult {
+                best_move: lines.first().map(|line| line.pv[0]),
+                score: Score::from_internal(score),
+                depth,
+                seldepth: self.seldepth as u32,
+                nodes: self.total_nodes(),
+                elapsed: self.start.elapsed(),
+                pv: lines
+                    .first()
+                    .map(|line| line.pv.clone())
+                    .unwrap_or_default(),
+                lines,
+            };
+            on_info(&result);
+
+            // No moves to search, or a mate found by a full-width search
+            if result.best_move.is_none() || MATE_SCORE - score.abs() <= depth as i32 {
+                break;
+            }
+
+            // The next iteration would not finish in the remaining time
+            if let Some(movetime) = self.limits.movetime {
+                if self.start.elapsed() * 2 > movetime {
+                    break;
+                }
+            }
+        }
+
+        // The helpers' nodes are added once they are done
+        result.nodes = self.nodes;
+        result
+    }
+
+    /// Iterative deepening of a helper thread until the main thread is done
+    fn search_helper(&mut self, board: &Board, first_depth: u32) {
+        for depth in first_depth..=MAX_DEPTH {
+            self.root_depth = depth;
+            let score = self.negamax(board, depth as i32, 0, -INFINITY, INFINITY);
+            if self.aborted || MATE_SCORE - score.abs() <= depth as i32 {
+                break;
+            }
+        }
+        let unreported = self.nodes - self.reported_nodes;
+        self.helper_nodes.fetch_add(unreported, Ordering::Relaxed);
+    }
+
+    /// Nodes searched by all threads so far
+    fn total_nodes(&self) -> u64 {
+        self.nodes + self.helper_nodes.load(Ordering::Relaxed)
+    }
+
+    fn make_move(&mut self, board: &Board, mv: Move, child: &Board) {
+        if let Some(incremental) = &mut self.incremental {
+            incremental.make_move(board, mv, child);
+        }
+    }
+
+    fn unmake_move(&mut self) {
+        if let Some(incremental) = &mut self.incremental {
+            incremental.unmake_move();
+        }
+    }
+
+    fn evaluate(&mut self, board: &Board) -> i32 {
+        match &mut self.incremental {
+            Some(incremental) => incremental.evaluate(board),
+            None => self.evaluator.evaluate(board),
+        }
+    }
+
+    /// Check the limits every few nodes, never during the first iteration
+    /// of the main thread. Helpers only stop when the main thread is done
+    fn should_abort(&mut self) -> bool {
+        if self.aborted {
+            return true;
+        }
+        if self.is_helper {
+            if self.nodes - self.reported_nodes >= 1024 {
+                let unreported = self.nodes - self.reported_nodes;
+                self.helper_nodes.fetch_add(unreported, Ordering::Relaxed);
+                self.reported_nodes = self.nodes;
+                self.aborted = self.stop.load(Ordering::Relaxed);
+            }
+            return self.aborted;
+        }
+        if self.root_depth <= 1 {
+            return false;
+        }
+
+        if let Some(max_nodes) = self.limits.nodes {
+            if self.total_nodes() >= max_nodes {
+                self.aborted = true;
+            }
+        }
+
+        if self.nodes.is_multiple_of(1024) {
+            if self.stop.load(Ordering::Relaxed) {
+                self.aborted = true;
+            }
+            if let Some(movetime) = self.limits.movetime {
+                if self.start.elapsed() >= movetime {
+                    self.aborted = true;
+                }
+            }
+        }
+
+        self.aborted
+    }
+
+    fn is_draw(&self, board: &Board, key: u64) -> bool {
+        if board.halfmove_clock() >= 100 || is_insufficient_material(board) {
+            return true;
+        }
+
+        // Positions before the last capture or pawn move can't repeat
+        self.path
+            .iter()
+            .rev()
+            .take(board.halfmove_clock() as usize)
+            .any(|&earlier| earlier == key)
+    }
+
+    fn negamax(
+        &mut self,
+        board: &Board,
+        mut depth: i32,
+        ply: usize,
+        mut alpha: i32,
+        mut beta: i32,
+    ) -> i32 {
+        self.pv[ply].clear();
+        if self.should_abort() {
+            return 0;
+        }
+
+        let key = position_key(board);
+        if ply > 0 {
+            if self.is_draw(board, key) {
+                return 0;
+            }
+
+            // No line can beat a mate already found closer to the root
+            alpha = alpha.max(-MATE_SCORE + ply as i32);
+            beta = beta.min(MATE_SCORE - ply as i32 - 1);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        let color = board.side_to_move();
+        let in_check = board.is_king_in_check(color);
+        if in_check {
+            depth += 1;
+        }
+
+        if depth <= 0 {
+            return self.quiescence(board, ply, alpha, beta);
+        }
+
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+        if ply >= MAX_PLY - 1 {
+            return self.evaluate(board);
+        }
+
+        let tt_entry = self.tt.probe(key);
+        let tt_move = tt_entry.and_then(|entry| entry.best_move);
+        if let Some(entry) = tt_entry.filter(|entry| ply > 0 && entry.depth >= depth) {
+            let score = score_from_tt(entry.score, ply);
+            match entry.bound {
+                Bound::Exact => return score,
+                Bound::Lower if score >= beta => return score,
+                Bound::Upper if score <= alpha => return score,
+                _ => {}
+            }
+        }
+
+        let mut moves = board.get_all_valid_moves(color);
+        if moves.is_empty() {
+            return if in_check {
+                -MATE_SCORE + ply as i32
+            } else {
+                0
+            };
+        }
+        if ply == 0 && !self.excluded.is_empty() {
+            moves.retain(|mv| !self.excluded.contains(mv));
+            if moves.is_empty() {
+                return -INFINITY;
+            }
+        }
+        self.ordering.sort(board, &mut moves, tt_move, ply);
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+
+        self.path.push(key);
+        for (index, &mv) in moves.iter().enumerate() {
+            let mut child = board.clone();
+            child.apply_move(mv);
+            self.make_move(board, mv, &child);
+
+            // Principal variation search: later moves are first tried with a null window
+            let score = if index == 0 {
+                -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha)
+            } else {
+                let score = -self.negamax(&child, depth - 1, ply + 1, -alpha - 1, -alpha);
+                if score > alpha && score < beta {
+                    -self.negamax(&child, depth - 1, ply + 1, -beta, -alpha)
+                } else {
+                    score
+                }
+            };
+            self.unmake_move();
+
+            if self.aborted {
+                self.path.pop();
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mv);
+
+                if score > alpha {
+                    alpha = score;
+                    let mut pv = vec![mv];
+                    pv.extend_from_slice(&self.pv[ply + 1]);
+                    self.pv[ply] = pv;
+
+                    if alpha >= beta {
+                        if !is_capture(board, mv) && mv.promotion.is_none() {
+                            self.ordering.record_cutoff(board, mv, ply, depth);
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+        self.path.pop();
+
+        // The best of the remaining root moves is not the best of the position
+        if ply == 0 && !self.excluded.is_empty() {
+            return best_score;
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        self.tt.store(Entry {
+            key,
+            best_move,
+            score: score_to_tt(best_score, ply),
+            depth,
+            bound,
+        });
+
+        best_score
+    }
+
+    /// Search captures and promotions until the position is quiet
+    fn quiescence(&mut self, board: &Board, ply: usize, mut alpha: i32, beta: i32) -> i32 {
+        self.pv[ply].clear();
+        if self.should_abort() {
+            return 0;
+        }
+
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+
+        let color = board.side_to_move();
+        let all_moves = board.get_all_valid_moves(color);
+        if all_moves.is_empty() {
+            return if board.is_king_in_check(color) {
+                -MATE_SCORE + ply as i32
+            } else {
+                0
+            };
+        }
+
+        // The side to move can usually do at least as well as the static evaluation
+        let stand_pat = self.evaluate(board);
+        if stand_pat >= beta || ply >= MAX_PLY - 1 {
+            return stand_pat;
+        }
+        alpha = alpha.max(stand_pat);
+
+        let mut moves: Vec<Move> = all_moves
+            .into_iter()
+            .filter(|&mv| is_tactical(board, mv))
+            .collect();
+        self.ordering.sort(board, &mut moves, None, ply);
+
+        let mut best_score = stand_pat;
+        for mv in moves {
+            let mut child = board.clone();
+            child.apply_move(mv);
+            self.make_move(board, mv, &child);
+            let score = -self.quiescence(&child, ply + 1, -beta, -alpha);
+            self.unmake_move();
+
+            if self.aborted {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
+                    let mut pv = vec![mv];
+                    pv.extend_from_slice(&self.pv[ply + 1]);
+                    self.pv[ply] = pv;
+
+                    if alpha >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+
+        best_score
+    }
+}
+
+/// Convert a score from the side to move's view to White's view
+pub fn white_score(score: Score, side_to_move: Color) -> Score {
+    match side_to_move {
+        Color::White => score,
+        Color::Black => score.flip(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::from_fen(fen).unwrap()
+    }
+
+    fn depth(depth: u32) -> SearchLimits {
+        SearchLimits {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        // Ra7 cuts the king off and Rb8 mates
+        let mut position = board("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1");
+        let result = Engine::new().search(&position, &depth(5));
+        assert_eq!(result.score, Score::Mate(2));
+        assert_eq!(result.pv.len(), 3);
+        // A mate found by a full-width search ends the iterations
+        assert!(result.depth < 5);
+        for &mv in &result.pv {
+            assert!(position
+                .get_all_valid_moves(position.side_to_move())
+                .contains(&mv));
+            position.apply_move(mv);
+        }
+        assert!(position.is_checkmated(Color::Black));
+
+        // The defender sees its mate coming
+        let defender = board("7k/R7/8/8/8/8/8/1R4K1 b - - 0 1");
+        let result = Engine::new().search(&defender, &depth(4));
+        assert_eq!(result.score, Score::Mate(-1));
+    }
+
+    #[test]
+    fn positions_without_moves() {
+        let stalemate = board("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1");
+        let result = Engine::new().search(&stalemate, &depth(3));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, Score::Centipawns(0));
+
+        let mated = board("k6R/8/1K6/8/8/8/8/8 b - - 0 1");
+        let result = Engine::new().search(&mated, &depth(3));
+        assert_eq!(result.best_move, None);
+        assert_eq!(result.score, Score::Mate(0));
+    }
+
+    #[test]
+    fn repetition_is_a_draw() {
+        // A queen down, White can only go back to a position seen before
+        let position = board("6k1/8/8/8/8/8/q7/6K1 w - - 4 30");
+        let retreat = position.parse_uci_move("g1h1").unwrap();
+        let mut repeated = position.clone();
+        repeated.apply_move(retreat);
+
+        let result = Engine::new().search(&position, &depth(3));
+        assert!(result.score.as_centipawns() < -500);
+
+        let mut engine = Engine::new();
+        engine.set_history(&[repeated]);
+        let result = engine.search(&position, &depth(3));
+        assert_eq!(result.best_move, Some(retreat));
+        assert_eq!(result.score, Score::Centipawns(0));
+    }
+
+    #[test]
+    fn depth_and_node_limits() {
+        let board = Board::new_starting_default();
+        let result = Engine::new().search(&board, &depth(2));
+        assert_eq!(result.depth, 2);
+        assert!(result.best_move.is_some());
+
+        // The first iteration always finishes
+        let nodes = SearchLimits {
+            nodes: Some(1),
+            ..Default::default()
+        };
+        let result = Engine::new().search(&board, &nodes);
+        assert_eq!(result.depth, 1);
+        assert!(result.best_move.is_some());
+
+        let nodes = SearchLimits {
+            nodes: Some(3000),
+            ..Default::default()
+        };
+        let result = Engine::new().search(&board, &nodes);
+        assert!(result.nodes <= 3001, "{} nodes", result.nodes);
+        assert!(result.best_move.is_some());
+    }
+
+    #[test]
+    fn stop_handle() {
+        let board = Board::new_starting_default();
+        let mut engine = Engine::new();
+
+        // A stop before the search applies to it at the first check after
+        // its first iteration, and is then cleared
+        engine.stop_handle().stop();
+        let result = engine.search(&board, &SearchLimits::default());
+        assert!(result.nodes < 2048, "{} nodes", result.nodes);
+        assert!(result.best_move.is_some());
+        assert_eq!(engine.search(&board, &depth(4)).depth, 4);
+
+        // And stops a search without limits from another thread
+        let stop = engine.stop_handle();
+        let stopper = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            stop.stop();
+        });
+        let result = engine.search(&board, &SearchLimits::default());
+        stopper.join().unwrap();
+        assert!(result.best_move.is_some());
+        assert!(result.depth < MAX_DEPTH);
+    }
+
+    #[test]
+    fn multipv_lines() {
+        let position = board("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3");
+        let mut engine = Engine::new();
+        engine.set_multipv(4);
+        let result = engine.search(&position, &depth(3));
+        assert_eq!(result.lines.len(), 4);
+
+        // Different root moves, best first, the first one being the result
+        let mut roots: Vec<Move> = result.lines.iter().map(|line| line.pv[0]).collect();
+        assert_eq!(Some(roots[0]), result.best_move);
+        assert_eq!(result.lines[0].score, result.score);
+        for pair in result.lines.windows(2) {
+            assert!(pair[0].score.as_centipawns() >= pair[1].score.as_centipawns());
+        }
+        roots.sort_by_key(|mv| (mv.from.rank_file(), mv.to.rank_file()));
+        roots.dedup();
+        assert_eq!(roots.len(), 4);
+
+        // No more lines than moves
+        let mate = board("k6R/8/1K6/8/8/8/8/8 b - - 0 1");
+        let king = board("k7/8/1K6/8/8/8/8/7R b - - 0 1");
+        assert!(engine.search(&mate, &depth(2)).lines.is_empty());
+        assert_eq!(
+            engine.search(&king, &depth(2)).lines.len(),
+            king.get_all_valid_moves(Color::Black).len()
+        );
+    }
+
+    #[test]
+    fn helper_threads() {
+        let mut engine = Engine::new();
+        engine.set_threads(0);
+        assert_eq!(engine.threads(), 1);
+        engine.set_threads(MAX_THREADS + 1);
+        assert_eq!(engine.threads(), MAX_THREADS);
+
+        // Helpers share the work but only the main thread's result counts
+        engine.set_threads(3);
+        let board = board("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1");
+        // The line may end early at a hash table entry a helper stored
+        let result = engine.search(&board, &depth(4));
+        assert_eq!(result.score, Score::Mate(2));
+        assert!(board
+            .get_all_valid_moves(Color::White)
+            .contains(&result.best_move.unwrap()));
+
+        let board = Board::new_starting_default();
+        let result = engine.search(&board, &depth(3));
+        assert_eq!(result.depth, 3);
+        assert!(board
+            .get_all_valid_moves(Color::White)
+            .contains(&result.best_move.unwrap()));
+    }
+}
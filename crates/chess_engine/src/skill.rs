@@ -0,0 +1,241 @@
+// Playing below full strength
+//
+// A weakened engine searches shallower and fewer nodes, scores its best few
+// root moves and then picks one of them at random, leaning towards the better
+// ones the higher the level. Now and then it also makes a deliberate
+// inaccuracy, choosing any move that doesn't lose too much.
+
+use crate::search::{PvLine, Score, SearchLimits};
+use crate::zobrist::XorShift;
+
+/// Level of an engine playing at full strength
+pub const MAX_SKILL_LEVEL: u32 = 20;
+/// Rough rating of the weakest level, the scale is not calibrated
+pub const MIN_ELO: u32 = 600;
+/// Rough rating of the strongest weakened level
+pub const MAX_ELO: u32 = 2000;
+
+/// Root moves scored by a weakened engine to choose from
+const CANDIDATE_LINES: usize = 4;
+/// Largest loss the engine accepts when it makes a deliberate inaccuracy
+const INACCURACY_MARGIN: i32 = 250;
+/// Chance of an inaccuracy at level 0, decreasing to none at full strength
+const MAX_INACCURACY_CHANCE: f64 = 0.2;
+
+/// How well the engine plays, from level 0 up to full strength at level 20
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Skill {
+    level: f64,
+}
+
+impl Default for Skill {
+    fn default() -> Self {
+        Self::full()
+    }
+}
+
+impl Skill {
+    pub fn full() -> Self {
+        Self {
+            level: MAX_SKILL_LEVEL as f64,
+        }
+    }
+
+    /// Skill of a level from 0 to 20, higher levels clamped to full strength
+    pub fn from_level(level: u32) -> Self {
+        Self {
+            level: level.min(MAX_SKILL_LEVEL) as f64,
+        }
+    }
+
+    /// Skill playing at about the given rating, between `MIN_ELO` and `MAX_ELO`
+    pub fn from_elo(elo: u32) -> Self {
+        let elo = elo.clamp(MIN_ELO, MAX_ELO);
+        // The top of the range is still weakened, only level 20 is full strength
+        let fraction = (elo - MIN_ELO) as f64 / (MAX_ELO - MIN_ELO) as f64;
+        Self {
+            level: fraction * (MAX_SKILL_LEVEL - 1) as f64,
+        }
+    }
+
+    pub fn level(&self) -> f64 {
+        self.level
+    }
+
+    /// Rough rating of this skill, `None` at full strength
+    pub fn elo(&self) -> Option<u32> {
+        if self.is_full() {
+            return None;
+        }
+        let fraction = self.level / (MAX_SKILL_LEVEL - 1) as f64;
+        Some(MIN_ELO + (fraction * (MAX_ELO - MIN_ELO) as f64).round() as u32)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.level >= MAX_SKILL_LEVEL as f64
+    }
+
+    /// Limits capped to the depth and nodes this skill may search
+    pub(crate) fn limit(&self, limits: &SearchLimits) -> SearchLimits {
+        if self.is_full() {
+            return limits.clone();
+        }
+        let max_depth = 1 + (self.level / 2.0) as u32;
+        let max_nodes = (200.0 * 1.5f64.powf(self.level)) as u64;
+        SearchLimits {
+            depth: Some(limits.depth.map_or(max_depth, |depth| depth.min(max_depth))),
+            nodes: Some(limits.nodes.map_or(max_nodes, |nodes| nodes.min(max_nodes))),
+            movetime: limits.movetime,
+            clock: limits.clock,
+        }
+    }
+
+    /// Number of root moves to score before choosing
+    pub(crate) fn candidate_lines(&self) -> usize {
+        if self.is_full() {
+            1
+        } else {
+            CANDIDATE_LINES
+        }
+    }
+
+    /// Index of the line to play among lines sorted best first
+    pub(crate) fn choose(&self, lines: &[PvLine], rng: &mut XorShift) -> usize {
+        if self.is_full() || lines.len() < 2 {
+            return 0;
+        }
+
+        // Walking into a mate the engine has seen is not believable at any level
+        let candidates: Vec<(usize, i32)> = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| !matches!(line.score, Score::Mate(moves) if moves <= 0))
+            .map(|(index, line)| (index, line.score.as_centipawns().clamp(-2000, 2000)))
+            .collect();
+        let Some(&(_, top)) = candidates.first() else {
+            return 0;
+        };
+
+        let inaccuracy_chance = MAX_INACCURACY_CHANCE * (1.0 - self.level / MAX_SKILL_LEVEL as f64);
+        if random_fraction(rng) < inaccuracy_chance {
+            let playable: Vec<usize> = candidates
+                .iter()
+                .filter(|(_, score)| top - score <= INACCURACY_MARGIN)
+                .map(|&(index, _)| index)
+                .collect();
+            return playable[rng.next_u64() as usize % playable.len()];
+        }
+
+        // Each move gets a random bonus that grows with the weakness and with
+        // how much it loses, so weak levels often prefer a worse move
+        let weakness = 120.0 - 2.0 * self.level;
+        let worst = candidates.last().map_or(top, |&(_, score)| score);
+        let delta = (top - worst).min(100) as f64;
+        let mut best = (0, f64::MIN);
+        for &(index, score) in &candidates {
+            let loss = (top - score) as f64;
+            let bonus = (weakness * loss + delta * random_fraction(rng) * weakness) / 128.0;
+            let value = score as f64 + bonus;
+            if value > best.1 {
+                best = (index, value);
+            }
+        }
+        best.0
+    }
+}
+
+/// Random number in `0.0..1.0`
+fn random_fraction(rng: &mut XorShift) -> f64 {
+    (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::Engine;
+    use chess_core::{Board, Move};
+
+    const POSITIONS: [&str; 3] = [
+        "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 2 3",
+        "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+    ];
+
+    /// Moves a weakened engine plays in the positions with a seed
+    fn play(level: u32, seed: u64) -> Vec<Move> {
+        let mut engine = Engine::new();
+        engine.set_skill(Skill::from_level(level));
+        engine.set_seed(seed);
+        POSITIONS
+            .iter()
+            .map(|fen| {
+                let board = Board::from_fen(fen).unwrap();
+                let mv = engine
+                    .search(&board, &SearchLimits::default())
+                    .best_move
+                    .unwrap();
+                assert!(board
+                    .get_all_valid_moves(board.side_to_move())
+                    .contains(&mv));
+                mv
+            })
+            .collect()
+    }
+
+    #[test]
+    fn weakened_moves_are_legal_and_repeat() {
+        for level in [0, 6] {
+            for seed in 1..=2 {
+                assert_eq!(play(level, seed), play(level, seed), "level {}", level);
+            }
+        }
+    }
+
+    #[test]
+    fn levels_and_ratings() {
+        assert!(Skill::full().is_full());
+        assert_eq!(Skill::full().elo(), None);
+        assert_eq!(Skill::from_level(30), Skill::full());
+        assert_eq!(Skill::from_elo(MIN_ELO).level(), 0.0);
+        assert_eq!(Skill::from_elo(100), Skill::from_elo(MIN_ELO));
+        // Only level 20 plays at full strength
+        assert!(!Skill::from_elo(MAX_ELO).is_full());
+        for elo in [MIN_ELO, 1200, MAX_ELO] {
+            assert_eq!(Skill::from_elo(elo).elo(), Some(elo));
+        }
+    }
+
+    #[test]
+    fn limits_shrink_with_the_level() {
+        let limits = SearchLimits {
+            depth: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(Skill::full().limit(&limits).depth, Some(10));
+        assert_eq!(Skill::full().limit(&limits).nodes, None);
+
+        let weak = Skill::from_level(0).limit(&limits);
+        assert_eq!((weak.depth, weak.nodes), (Some(1), Some(200)));
+        let stronger = Skill::from_level(10).limit(&SearchLimits::default());
+        assert_eq!(stronger.depth, Some(6));
+        assert!(stronger.nodes > weak.nodes);
+    }
+
+    #[test]
+    fn mated_lines_are_never_chosen() {
+        let lines = [
+            PvLine {
+                score: Score::Centipawns(-50),
+                pv: Vec::new(),
+            },
+            PvLine {
+                score: Score::Mate(-2),
+                pv: Vec::new(),
+            },
+        ];
+        let mut rng = XorShift::new(5);
+        for _ in 0..100 {
+            assert_eq!(Skill::from_level(0).choose(&lines, &mut rng), 0);
+        }
+    }
+}
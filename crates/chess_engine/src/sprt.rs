@@ -0,0 +1,302 @@
+// Statistics of engine matches: the Elo difference a score stands for, with
+// its 95% error bars and the likelihood of superiority, and the sequential
+// probability ratio test that stops a match once it is clear whether the
+// difference lies below `elo0` or above `elo1`.
+//
+// Elo is logistic, a score `s` standing for `-400 log10(1 / s - 1)`. The log
+// likelihood ratio uses the normal approximation of the trinomial model
+// (win, draw, loss), like the GSPRT of Fishtest and cutechess-cli.
+
+use std::fmt;
+
+/// Quantile of the normal distribution for 95% error bars
+const CONFIDENCE_QUANTILE: f64 = 1.959964;
+
+/// Games won, drawn and lost by the first engine of a match
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchScore {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchScore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    /// Points per game, a draw counting half
+    pub fn score(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.5;
+        }
+        (self.wins as f64 + self.draws as f64 / 2.0) / self.games() as f64
+    }
+
+    pub fn draw_ratio(&self) -> f64 {
+        if self.games() == 0 {
+            return 0.0;
+        }
+        self.draws as f64 / self.games() as f64
+    }
+
+    /// Variance of the points of one game
+    fn variance(&self) -> f64 {
+        variance(self.wins as f64, self.draws as f64, self.losses as f64)
+    }
+
+    /// Elo difference with the half width of its 95% confidence interval, not
+    /// known before the first engine has won and lost or drawn a game
+    pub fn elo(&self) -> Option<(f64, f64)> {
+        let score = self.score();
+        if self.games() == 0 || score <= 0.0 || score >= 1.0 {
+            return None;
+        }
+        let deviation = (self.variance() / self.games() as f64).sqrt();
+        let low = score_to_elo((score - CONFIDENCE_QUANTILE * deviation).max(f64::EPSILON));
+        let high = score_to_elo((score + CONFIDENCE_QUANTILE * deviation).min(1.0 - f64::EPSILON));
+        Some((score_to_elo(score), (high - low) / 2.0))
+    }
+
+    /// Likelihood of superiority: the probability the first engine is the
+    /// stronger one, from its wins and losses
+    pub fn los(&self) -> f64 {
+        let decisive = (self.wins + self.losses) as f64;
+        if decisive == 0.0 {
+            return 0.5;
+        }
+        let difference = self.wins as f64 - self.losses as f64;
+        0.5 * (1.0 + erf(difference / (2.0 * decisive).sqrt()))
+    }
+}
+
+impl fmt::Display for MatchScore {
+    /// Wins, losses and draws like cutechess-cli, `12 - 8 - 20 [0.550] 40`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} - {} - {} [{:.3}] {}",
+            self.wins,
+            self.losses,
+            self.draws,
+            self.score(),
+            self.games()
+        )
+    }
+}
+
+/// Variance of the points of one game for counts of wins, draws and losses
+fn variance(wins: f64, draws: f64, losses: f64) -> f64 {
+    let games = wins + draws + losses;
+    let score = (wins + draws / 2.0) / games;
+    (wins * (1.0 - score).powi(2) + draws * (0.5 - score).powi(2) + losses * score.powi(2)) / games
+}
+
+/// Expected score of an engine `elo` points stronger than its opponent
+pub fn elo_to_score(elo: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-elo / 400.0))
+}
+
+/// Elo difference that gives an expected `score` between 0 and 1
+pub fn score_to_elo(score: f64) -> f64 {
+    -400.0 * (1.0 / score - 1.0).log10()
+}
+
+/// Error function, with the approximation 7.1.26 of Abramowitz and Stegun
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let polynomial = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let value = 1.0 - polynomial * (-x * x).exp();
+    if x < 0.0 {
+        -value
+    } else {
+        value
+    }
+}
+
+/// Outcome of a sequential probability ratio test so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SprtResult {
+    /// More games are needed
+    Continue,
+    /// The difference is at most `elo0`
+    AcceptH0,
+    /// The difference is at least `elo1`
+    AcceptH1,
+}
+
+/// A sequential probability ratio test of H0: Elo difference `elo0` against
+/// H1: `elo1`, with error rates `alpha` (accepting H1 wrongly) and `beta`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sprt {
+    pub elo0: f64,
+    pub elo1: f64,
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Default for Sprt {
+    /// The usual test that a change gains up to 5 Elo, at 5% error rates
+    fn default() -> Self {
+        Self {
+            elo0: 0.0,
+            elo1: 5.0,
+            alpha: 0.05,
+            beta: 0.05,
+        }
+    }
+}
+
+impl Sprt {
+    pub fn new(elo0: f64, elo1: f64, alpha: f64, beta: f64) -> Result<Self, &'static str> {
+        if !elo0.is_finite() || !elo1.is_finite() || elo0 >= elo1 {
+            return Err("SPRT elo0 must be below elo1\n");
+        }
+        let valid_rate = |rate: f64| rate > 0.0 && rate < 0.5;
+        if !valid_rate(alpha) || !valid_rate(beta) {
+            return Err("SPRT alpha and beta must be between 0 and 0.5\n");
+        }
+        Ok(Self {
+            elo0,
+            elo1,
+            alpha,
+            beta,
+        })
+    }
+
+    /// Log likelihood ratio bounds, below the first H0 is accepted and above
+    /// the second H1
+    pub fn bounds(&self) -> (f64, f64) {
+        (
+            (self.beta / (1.0 - self.alpha)).ln(),
+            ((1.0 - self.beta) / self.alpha).ln(),
+        )
+    }
+
+    /// Log likelihood ratio of H1 against H0 for a match score
+    pub fn llr(&self, score: &MatchScore) -> f64 {
+        if score.games() == 0 {
+            return 0.0;
+        }
+        // Half a game more of each result keeps the variance from vanishing
+        // while a side has won or drawn everything
+        let variance = variance(
+            score.wins as f64 + 0.5,
+            score.draws as f64 + 0.5,
+            score.losses as f64 + 0.5,
+        );
+        let score0 = elo_to_score(self.elo0);
+        let score1 = elo_to_score(self.elo1);
+        score.games() as f64 * (score1 - score0) * (2.0 * score.score() - score0 - score1)
+            / (2.0 * variance)
+    }
+
+    pub fn result(&self, score: &MatchScore) -> SprtResult {
+        let llr = self.llr(score);
+        let (lower, upper) = self.bounds();
+        if llr <= lower {
+            SprtResult::AcceptH0
+        } else if llr >= upper {
+            SprtResult::AcceptH1
+        } else {
+            SprtResult::Continue
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(wins: u32, draws: u32, losses: u32) -> MatchScore {
+        MatchScore {
+            wins,
+            draws,
+            losses,
+        }
+    }
+
+    fn assert_close(value: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (value - expected).abs() <= tolerance,
+            "{} is not {}",
+            value,
+            expected
+        );
+    }
+
+    #[test]
+    fn elo_conversions() {
+        assert_close(score_to_elo(0.5), 0.0, 1e-9);
+        // 400 log10(3)
+        assert_close(score_to_elo(0.75), 190.8485, 1e-4);
+        assert_close(elo_to_score(100.0), 0.640065, 1e-6);
+        assert_close(elo_to_score(-100.0), 0.359935, 1e-6);
+        assert_close(score_to_elo(elo_to_score(35.0)), 35.0, 1e-9);
+    }
+
+    #[test]
+    fn elo_and_error_bars() {
+        let (elo, error) = score(30, 40, 30).elo().unwrap();
+        assert_close(elo, 0.0, 1e-9);
+        assert!(error > 0.0);
+
+        let (elo, error) = score(60, 100, 40).elo().unwrap();
+        assert_close(elo, 34.8601, 1e-3);
+        assert_close(error, 34.1593, 1e-3);
+
+        // Not known without both results
+        assert_eq!(score(0, 0, 0).elo(), None);
+        assert_eq!(score(5, 0, 0).elo(), None);
+        assert_eq!(score(0, 3, 2).elo().map(|(elo, _)| elo < 0.0), Some(true));
+    }
+
+    #[test]
+    fn likelihood_of_superiority() {
+        assert_eq!(score(0, 10, 0).los(), 0.5);
+        assert_close(score(25, 50, 25).los(), 0.5, 1e-9);
+        // 0.5 (1 + erf((W - L) / sqrt(2 (W + L))))
+        assert_close(score(10, 0, 0).los(), 0.999217, 1e-6);
+        assert_close(score(60, 80, 40).los(), 0.977250, 1e-6);
+        assert_close(score(40, 80, 60).los(), 0.022750, 1e-6);
+    }
+
+    #[test]
+    fn log_likelihood_ratio() {
+        let sprt = Sprt::default();
+        let (lower, upper) = sprt.bounds();
+        assert_close(lower, -2.944439, 1e-6);
+        assert_close(upper, 2.944439, 1e-6);
+
+        assert_eq!(sprt.llr(&MatchScore::new()), 0.0);
+        // An even score sits between hypotheses placed around 0 Elo
+        let even = score(100, 200, 100);
+        assert_close(
+            Sprt::new(-5.0, 5.0, 0.05, 0.05).unwrap().llr(&even),
+            0.0,
+            1e-9,
+        );
+        assert!(sprt.llr(&even) < 0.0);
+
+        // 1200 - 1000 - 1800 against [0, 5]: score 0.525, game variance
+        // 0.1369 with the half games, LLR = N (s1 - s0) (2 s - s0 - s1) / 2 var
+        let ahead = score(1200, 1800, 1000);
+        assert_close(sprt.llr(&ahead), 4.49986, 1e-4);
+        assert_eq!(sprt.result(&ahead), SprtResult::AcceptH1);
+        assert_eq!(sprt.result(&score(10, 20, 10)), SprtResult::Continue);
+        assert_eq!(sprt.result(&score(1000, 1800, 1200)), SprtResult::AcceptH0);
+    }
+
+    #[test]
+    fn invalid_tests() {
+        assert!(Sprt::new(5.0, 0.0, 0.05, 0.05).is_err());
+        assert!(Sprt::new(0.0, 5.0, 0.0, 0.05).is_err());
+        assert!(Sprt::new(0.0, 5.0, 0.05, 0.5).is_err());
+    }
+}
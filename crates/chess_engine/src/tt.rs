@@ -0,0 +1,197 @@
+// Transposition table shared by all search threads
+//
+// Each slot is two atomic words, the packed entry and its key xor-ed with it.
+// A slot torn by two threads writing at once no longer matches its key on
+// probe, so threads never read a mix of two entries and need no lock.
+
+use crate::zobrist::square_index;
+use chess_core::{Move, PieceType, Square};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How a stored score relates to the real one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Bound {
+    Exact,
+    /// Real score is at least the stored one (fail high)
+    Lower,
+    /// Real score is at most the stored one (fail low)
+    Upper,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Entry {
+    pub key: u64,
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: i32,
+    pub bound: Bound,
+}
+
+/// Fixed size hash table of search results, replacing entries by depth
+pub(crate) struct TranspositionTable {
+    slots: Vec<Slot>,
+}
+
+struct Slot {
+    /// Key xor-ed with the data, zero while empty
+    check: AtomicU64,
+    data: AtomicU64,
+}
+
+impl TranspositionTable {
+    pub fn new(size_mb: usize) -> Self {
+        let count = (size_mb.max(1) * 1024 * 1024 / std::mem::size_of::<Slot>()).max(1);
+        Self {
+            slots: (0..count)
+                .map(|_| Slot {
+                    check: AtomicU64::new(0),
+                    data: AtomicU64::new(0),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.check.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn slot(&self, key: u64) -> &Slot {
+        &self.slots[(key % self.slots.len() as u64) as usize]
+    }
+
+    pub fn probe(&self, key: u64) -> Option<Entry> {
+        let slot = self.slot(key);
+        let data = slot.data.load(Ordering::Relaxed);
+        let check = slot.check.load(Ordering::Relaxed);
+        if data == 0 || check ^ data != key {
+            return None;
+        }
+        Some(unpack(key, data))
+    }
+
+    pub fn store(&self, entry: Entry) {
+        let slot = self.slot(entry.key);
+        let old = self.probe(entry.key);
+        let replace = match old {
+            // Keep deeper results of the same position
+            Some(old) => entry.depth >= old.depth || entry.bound == Bound::Exact,
+            None => true,
+        };
+        if replace {
+            // Keep the old move when the new search found none
+            let best_move = entry
+                .best_move
+                .or_else(|| old.and_then(|old| old.best_move));
+            let data = pack(&Entry { best_move, ..entry });
+            slot.data.store(data, Ordering::Relaxed);
+            slot.check.store(entry.key ^ data, Ordering::Relaxed);
+        }
+    }
+}
+
+// Data layout, from the lowest bit: move (16 bits), score (16), depth (16),
+// bound (2) and a set bit so that no stored entry packs to zero
+
+const PROMOTIONS: [PieceType; 4] = [
+    PieceType::Knight,
+    PieceType::Bishop,
+    PieceType::Rook,
+    PieceType::Queen,
+];
+
+fn pack(entry: &Entry) -> u64 {
+    let mv = match entry.best_move {
+        Some(mv) => {
+            let promotion = PROMOTIONS
+                .iter()
+                .position(|&piece_type| Some(piece_type) == mv.promotion)
+                .map_or(0, |index| index + 1);
+            1 << 15 | promotion << 12 | square_index(mv.from) << 6 | square_index(mv.to)
+        }
+        None => 0,
+    } as u64;
+    let bound = match entry.bound {
+        Bound::Exact => 0,
+        Bound::Lower => 1,
+        Bound::Upper => 2,
+    };
+
+    mv | (entry.score as i16 as u16 as u64) << 16
+        | (entry.depth as i16 as u16 as u64) << 32
+        | bound << 48
+        | 1 << 50
+}
+
+fn unpack(key: u64, data: u64) -> Entry {
+    let square =
+        |index: u64| Square::new((index / 8) as u8, (index % 8) as u8).expect("always valid");
+    let best_move = (data & 1 << 15 != 0).then(|| Move {
+        from: square(data >> 6 & 63),
+        to: square(data & 63),
+        promotion: match data >> 12 & 7 {
+            0 => None,
+            index => Some(PROMOTIONS[index as usize - 1]),
+        },
+    });
+    let bound = match data >> 48 & 3 {
+        0 => Bound::Exact,
+        1 => Bound::Lower,
+        _ => Bound::Upper,
+    };
+
+    Entry {
+        key,
+        best_move,
+        score: (data >> 16) as u16 as i16 as i32,
+        depth: (data >> 32) as u16 as i16 as i32,
+        bound,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pack_round_trip() {
+        let squares: Vec<Square> = (0..64)
+            .map(|index| Square::new(index / 8, index % 8).unwrap())
+            .collect();
+        let promotions = [None].into_iter().chain(PROMOTIONS.map(Some));
+        let mut moves = vec![None];
+        for promotion in promotions {
+            for &from in &squares {
+                for &to in &squares {
+                    moves.push(Some(Move {
+                        from,
+                        to,
+                        promotion,
+                    }));
+                }
+            }
+        }
+
+        let bounds = [Bound::Exact, Bound::Lower, Bound::Upper];
+        let values = [(0, 0), (-30_001, -1), (30_001, 64), (-1, 255)];
+        for (index, &best_move) in moves.iter().enumerate() {
+            let (score, depth) = values[index % values.len()];
+            let entry = Entry {
+                key: (index as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15),
+                best_move,
+                score,
+                depth,
+                bound: bounds[index % bounds.len()],
+            };
+            let data = pack(&entry);
+            assert_ne!(data, 0);
+            let unpacked = unpack(entry.key, data);
+            assert_eq!(unpacked.best_move, entry.best_move);
+            assert_eq!(unpacked.score, entry.score);
+            assert_eq!(unpacked.depth, entry.depth);
+            assert_eq!(unpacked.bound, entry.bound);
+        }
+    }
+}
@@ -0,0 +1,500 @@
+// Universal Chess Interface server
+//
+// Reads commands line by line and answers on the output. Searches run on their
+// own thread so `stop`, `isready` and `quit` are handled while thinking; that
+// thread writes the `info` and `bestmove` lines itself.
+
+use crate::{
+    Engine, Score, SearchLimits, SearchResult, Skill, StopHandle, MAX_ELO, MAX_SKILL_LEVEL,
+    MAX_THREADS, MIN_ELO,
+};
+use chess_core::{Board, Color};
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Name reported to GUIs
+pub const ENGINE_NAME: &str = "SimplyChess";
+pub const ENGINE_AUTHOR: &str = "the SimplyChess developers";
+
+const DEFAULT_HASH_MB: usize = 16;
+const MAX_HASH_MB: usize = 1024;
+
+/// Answer UCI commands from `input` on `output` until `quit` or the end of input
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) {
+    let mut server = UciServer::new(output);
+    for line in input.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if !server.handle_command(&line) {
+            break;
+        }
+    }
+    server.finish_search(true);
+}
+
+/// State of a UCI session
+pub struct UciServer<W: Write + Send + 'static> {
+    output: Arc<Mutex<W>>,
+    /// The engine while idle, it moves to the search thread while searching
+    engine: Option<Engine>,
+    stop_handle: StopHandle,
+    search: Option<JoinHandle<Engine>>,
+    /// Set by `stop`, ends searches started with `go infinite`
+    stop_requested: Arc<AtomicBool>,
+    board: Board,
+    history: Vec<Board>,
+    skill_level: u32,
+    /// `UCI_LimitStrength`, when set `UCI_Elo` decides the strength instead of `Skill Level`
+    limit_strength: bool,
+    elo: u32,
+}
+
+/// Write a line and flush it so the GUI sees it right away
+pub(crate) fn send<W: Write>(output: &Mutex<W>, line: &str) {
+    if let Ok(mut output) = output.lock() {
+        let _ = writeln!(output, "{}", line);
+        let _ = output.flush();
+    }
+}
+
+fn info_line(result: &SearchResult) -> String {
+    let score = match result.score {
+        Score::Centipawns(centipawns) => format!("cp {}", centipawns),
+        Score::Mate(moves) => format!("mate {}", moves),
+    };
+    let pv: Vec<String> = result.pv.iter().map(|mv| mv.to_uci()).collect();
+
+    format!(
+        "info depth {} seldepth {} score {} nodes {} nps {} time {} pv {}",
+        result.depth,
+        result.seldepth,
+        score,
+        result.nodes,
+        result.nps(),
+        result.elapsed.as_millis(),
+        pv.join(" ")
+    )
+}
+
+impl<W: Write + Send + 'static> UciServer<W> {
+    pub fn new(output: W) -> Self {
+        let engine = Engine::new();
+        Self {
+            output: Arc::new(Mutex::new(output)),
+            stop_handle: engine.stop_handle(),
+            engine: Some(engine),
+            search: None,
+            stop_requested: Arc::new(AtomicBool::new(false)),
+            board: Board::new_starting_default(),
+            history: Vec::new(),
+            skill_level: MAX_SKILL_LEVEL,
+            limit_strength: false,
+            elo: MAX_ELO,
+        }
+    }
+
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+
+    /// Handle one command line
+    /// Returns false when the session should end
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, arguments)) = tokens.split_first() else {
+            return true;
+        };
+
+        match command {
+            "uci" => {
+                self.send(&format!("id name {}", ENGINE_NAME));
+                self.send(&format!("id author {}", ENGINE_AUTHOR));
+                self.send(&format!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH_MB, MAX_HASH_MB
+                ));
+                self.send("option name Clear Hash type button");
+                self.send(&format!(
+                    "option name Threads type spin default 1 min 1 max {}",
+                    MAX_THREADS
+                ));
+                self.send("option name EvalFile type string default <empty>");
+                self.send(&format!(
+                    "option name Skill Level type spin default {} min 0 max {}",
+                    MAX_SKILL_LEVEL, MAX_SKILL_LEVEL
+                ));
+                self.send("option name UCI_LimitStrength type check default false");
+                self.send(&format!(
+                    "option name UCI_Elo type spin default {} min {} max {}",
+                    MAX_ELO, MIN_ELO, MAX_ELO
+                ));
+                self.send("uciok");
+            }
+            "isready" => self.send("readyok"),
+            "ucinewgame" => {
+                self.finish_search(true);
+                if let Some(engine) = &mut self.engine {
+                    engine.new_game();
+                }
+                self.board = Board::new_starting_default();
+                self.history.clear();
+            }
+            "position" => {
+                self.finish_search(true);
+                if let Err(error) = self.set_position(arguments) {
+                    self.send(&format!("info string {}", error.trim()));
+                }
+            }
+            "setoption" => {
+                self.finish_search(true);
+                self.set_option(arguments);
+            }
+            "go" => {
+                self.finish_search(true);
+                self.go(arguments);
+            }
+            "stop" => self.finish_search(true),
+            "quit" => return false,
+            // Commands without effect here
+            "debug" | "register" | "ponderhit" => {}
+            _ => self.send(&format!("info string Unknown command: {}", command)),
+        }
+
+        true
+    }
+
+    /// `position [startpos | fen <fen>] [moves <move>...]`
+    fn set_position(&mut self, arguments: &[&str]) -> Result<(), &'static str> {
+        let moves_index = arguments.iter().position(|&token| token == "moves");
+        let (setup, moves) = match moves_index {
+            Some(index) => (&arguments[..index], &arguments[index + 1..]),
+            None => (arguments, &[][..]),
+        };
+
+        let mut board = match setup.split_first() {
+            Some((&"startpos", _)) => Board::new_starting_default(),
+            Some((&"fen", fen)) => Board::from_fen(&fen.join(" "))?,
+            _ => return Err("Expected startpos or fen\n"),
+        };
+
+        // Like other engines keep the moves before an illegal one
+        self.history.clear();
+        let mut result = Ok(());
+        for text in moves {
+            match board.parse_uci_move(text) {
+                Ok(mv) => {
+                    self.history.push(board.clone());
+                    board.apply_move(mv);
+                }
+                Err(error) => {
+                    result = Err(error);
+                    break;
+                }
+            }
+        }
+
+        self.board = board;
+        result
+    }
+
+    /// `setoption name <name> [value <value>]`
+    fn set_option(&mut self, arguments: &[&str]) {
+        let value_index = arguments.iter().position(|&token| token == "value");
+        let name_end = value_index.unwrap_or(arguments.len());
+        let name = arguments[1.min(name_end)..name_end].join(" ");
+        let value = value_index
+            .map(|index| arguments[index + 1..].join(" "))
+            .unwrap_or_default();
+
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+
+        match name.to_lowercase().as_str() {
+            "hash" => match value.parse::<usize>() {
+                Ok(size_mb) => engine.set_hash_size(size_mb.clamp(1, MAX_HASH_MB)),
+                Err(_) => self.send("info string Hash must be a number of megabytes"),
+            },
+            "clear hash" => engine.new_game(),
+            "threads" => match value.parse::<usize>() {
+                Ok(threads) => engine.set_threads(threads),
+                Err(_) => self.send("info string Threads must be a number"),
+            },
+            "evalfile" => {
+                // Without a network file the classical evaluator plays
+                let path =
+                    Some(value.as_str()).filter(|path| !path.is_empty() && *path != "<empty>");
+                if let Err(error) = engine.set_eval_file(path) {
+                    self.send(&format!("info string {}", error.trim()));
+                }
+            }
+            "skill level" => match value.parse::<u32>() {
+                Ok(level) => self.skill_level = level.min(MAX_SKILL_LEVEL),
+                Err(_) => self.send("info string Skill Level must be a number from 0 to 20"),
+            },
+            "uci_limitstrength" => self.limit_strength = value == "true",
+            "uci_elo" => match value.parse::<u32>() {
+                Ok(elo) => self.elo = elo.clamp(MIN_ELO, MAX_ELO),
+                Err(_) => self.send("info string UCI_Elo must be a number"),
+            },
+            _ => self.send(&format!("info string Unknown option: {}", name)),
+        }
+
+        let skill = if self.limit_strength {
+            Skill::from_elo(self.elo)
+        } else {
+            Skill::from_level(self.skill_level)
+        };
+        if let Some(engine) = &mut self.engine {
+            engine.set_skill(skill);
+        }
+    }
+
+    /// `go` with clock, depth, nodes, movetime or infinite limits
+    fn go(&mut self, arguments: &[&str]) {
+        let Some(mut engine) = self.engine.take() else {
+            return;
+        };
+
+        let number = |name: &str| -> Option<u64> {
+            let index = arguments.iter().position(|&token| token == name)?;
+            arguments.get(index + 1)?.parse().ok()
+        };
+        let millis = |name: &str| number(name).map(Duration::from_millis);
+
+        let (time_left, increment) = match self.board.side_to_move() {
+            Color::White => (millis("wtime"), millis("winc")),
+            Color::Black => (millis("btime"), millis("binc")),
+        };
+
+        let mut limits = match time_left {
+            Some(time_left) => SearchLimits::from_clock(
+                time_left,
+                increment.unwrap_or_default(),
+                number("movestogo").map(|moves| moves as u32),
+            ),
+            None => SearchLimits::default(),
+        };
+        if let Some(depth) = number("depth") {
+            limits.depth = Some(depth as u32);
+        }
+        if let Some(nodes) = number("nodes") {
+            limits.nodes = Some(nodes);
+        }
+        if let Some(movetime) = millis("movetime") {
+            limits.movetime = Some(movetime);
+        }
+        let infinite = arguments.contains(&"infinite");
+
+        let output = self.output.clone();
+        let stop_requested = self.stop_requested.clone();
+        stop_requested.store(false, Ordering::Relaxed);
+        self.stop_handle.clear();
+        let board = self.board.clone();
+        let history = self.history.clone();
+
+        self.search = Some(thread::spawn(move || {
+            engine.set_history(&history);
+            let result = engine.search_with_info(&board, &limits, |info| {
+                send(&output, &info_line(info));
+            });
+
+            // After `go infinite` the best move is only sent once stopped
+            while infinite && !stop_requested.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(5));
+            }
+
+            let best_move = result
+                .best_move
+                .map(|mv| mv.to_uci())
+                .unwrap_or_else(|| "0000".to_string());
+            send(&output, &format!("bestmove {}", best_move));
+            engine
+        }));
+    }
+
+    /// Wait for the running search, stopping it first if asked
+    fn finish_search(&mut self, stop: bool) {
+        let Some(search) = self.search.take() else {
+            return;
+        };
+
+        if stop {
+            self.stop_requested.store(true, Ordering::Relaxed);
+            self.stop_handle.stop();
+        }
+        let engine = search.join().unwrap_or_else(|_| Engine::new());
+        self.stop_handle = engine.stop_handle();
+        self.engine = Some(engine);
+        // The search may have ended before seeing the request
+        self.stop_handle.clear();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Output the test reads back while the server keeps writing to it
+    #[derive(Debug, Clone, Default)]
+    pub(crate) struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedOutput {
+        pub(crate) fn lines(&self) -> Vec<String> {
+            let bytes = self.0.lock().unwrap();
+            String::from_utf8_lossy(&bytes)
+                .lines()
+                .map(str::to_string)
+                .collect()
+        }
+    }
+
+    impl Write for SharedOutput {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn session(commands: &[&str]) -> (UciServer<SharedOutput>, SharedOutput) {
+        let output = SharedOutput::default();
+        let mut server = UciServer::new(output.clone());
+        for command in commands {
+            assert!(server.handle_command(command));
+        }
+        (server, output)
+    }
+
+    /// Moves of the `bestmove` lines
+    fn best_moves(output: &SharedOutput) -> Vec<String> {
+        output
+            .lines()
+            .iter()
+            .filter_map(|line| line.strip_prefix("bestmove "))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn handshake() {
+        let (mut server, output) = session(&["uci", "isready"]);
+        let lines = output.lines();
+        assert_eq!(lines[0], format!("id name {}", ENGINE_NAME));
+        assert!(lines.contains(&"option name Threads type spin default 1 min 1 max 256".into()));
+        assert_eq!(lines[lines.len() - 2..], ["uciok", "readyok"]);
+
+        assert!(server.handle_command("frobnicate"));
+        assert!(!server.handle_command("quit"));
+        assert_eq!(
+            output.lines().last().unwrap(),
+            "info string Unknown command: frobnicate"
+        );
+    }
+
+    #[test]
+    fn position_and_go_depth() {
+        let (mut server, output) = session(&[
+            "ucinewgame",
+            "position startpos moves e2e4 e7e5 g1f3",
+            "go depth 3",
+        ]);
+        server.finish_search(false);
+
+        let board =
+            Board::from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2")
+                .unwrap();
+        assert_eq!(server.board, board);
+        assert_eq!(server.history.len(), 3);
+
+        let lines = output.lines();
+        for depth in 1..=3 {
+            let prefix = format!("info depth {} ", depth);
+            assert!(lines.iter().any(|line| line.starts_with(&prefix)));
+        }
+        let best_moves = best_moves(&output);
+        assert_eq!(best_moves.len(), 1);
+        assert!(board.parse_uci_move(&best_moves[0]).is_ok());
+
+        // Moves up to an illegal one are kept
+        let (server, output) =
+            session(&["position fen 8/8/8/8/8/2k5/8/K7 w - - 0 1 moves a1a2 a2a3"]);
+        assert_eq!(server.board.to_fen(), "8/8/8/8/8/2k5/K7/8 b - - 1 1");
+        assert_eq!(output.lines().len(), 1);
+        assert!(output.lines()[0].starts_with("info string "));
+    }
+
+    #[test]
+    fn clock_limits_finish() {
+        let (mut server, output) = session(&[
+            "position startpos",
+            "go wtime 1000 btime 1000 winc 0 binc 0",
+        ]);
+        server.finish_search(false);
+        assert_eq!(best_moves(&output).len(), 1);
+    }
+
+    #[test]
+    fn set_options() {
+        let (server, output) = session(&[
+            "setoption name Threads value 3",
+            "setoption name Skill Level value 5",
+        ]);
+        let engine = server.engine.as_ref().unwrap();
+        assert_eq!(engine.threads(), 3);
+        assert_eq!(engine.skill(), Skill::from_level(5));
+        assert!(output.lines().is_empty());
+
+        // UCI_Elo only counts with UCI_LimitStrength
+        let (server, _) = session(&[
+            "setoption name UCI_Elo value 1200",
+            "setoption name UCI_LimitStrength value true",
+        ]);
+        assert_eq!(server.engine.unwrap().skill(), Skill::from_elo(1200));
+
+        let (_, output) = session(&[
+            "setoption name Hash value lots",
+            "setoption name Contempt value 10",
+        ]);
+        assert_eq!(
+            output.lines(),
+            [
+                "info string Hash must be a number of megabytes",
+                "info string Unknown option: Contempt"
+            ]
+        );
+    }
+
+    #[test]
+    fn stop_infinite_search() {
+        let (mut server, output) = session(&["position startpos", "go infinite"]);
+        thread::sleep(Duration::from_millis(200));
+        // The best move only comes once stopped
+        assert!(best_moves(&output).is_empty());
+        assert!(server.handle_command("stop"));
+
+        let best_moves = best_moves(&output);
+        assert_eq!(best_moves.len(), 1);
+        assert!(Board::new_starting_default()
+            .parse_uci_move(&best_moves[0])
+            .is_ok());
+        assert!(server.engine.is_some());
+    }
+
+    #[test]
+    fn run_stops_at_the_end_of_input() {
+        let output = SharedOutput::default();
+        run(
+            "uci\nposition startpos moves d2d4\ngo infinite\n".as_bytes(),
+            output.clone(),
+        );
+        assert_eq!(best_moves(&output).len(), 1);
+    }
+}
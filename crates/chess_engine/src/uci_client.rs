@@ -0,0 +1,448 @@
+// Client side of the Universal Chess Interface
+
+use crate::external::{file_name, EngineProcess, HANDSHAKE_TIMEOUT};
+use crate::{EngineProtocol, ExternalEngine, Score, SearchLimits};
+use chess_core::{Board, Move, STARTING_FEN};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Type and limits of an engine option
+#[derive(Debug, Clone, PartialEq)]
+pub enum UciOptionKind {
+    Check {
+        default: bool,
+    },
+    Spin {
+        default: i64,
+        min: i64,
+        max: i64,
+    },
+    Combo {
+        default: String,
+        choices: Vec<String>,
+    },
+    Button,
+    String {
+        default: String,
+    },
+}
+
+/// An option announced by the engine during the handshake
+#[derive(Debug, Clone, PartialEq)]
+pub struct UciOption {
+    pub name: String,
+    pub kind: UciOptionKind,
+}
+
+impl UciOption {
+    /// Parse an `option name ... type ...` line
+    pub fn parse(line: &str) -> Option<Self> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        if tokens.first() != Some(&"option") {
+            return None;
+        }
+
+        // Values run until the next keyword, names and defaults may contain spaces
+        let keywords = ["name", "type", "default", "min", "max", "var"];
+        let mut name = None;
+        let mut kind = None;
+        let mut default = None;
+        let mut min = None;
+        let mut max = None;
+        let mut choices = Vec::new();
+
+        let mut index = 1;
+        while index < tokens.len() {
+            let keyword = tokens[index];
+            let end = (index + 1..tokens.len())
+                .find(|&i| keywords.contains(&tokens[i]))
+                .unwrap_or(tokens.len());
+            let value = tokens[index + 1..end].join(" ");
+            match keyword {
+                "name" => name = Some(value),
+                "type" => kind = Some(value),
+                "default" => default = Some(value),
+                "min" => min = value.parse().ok(),
+                "max" => max = value.parse().ok(),
+                "var" => choices.push(value),
+                _ => {}
+            }
+            index = end;
+        }
+
+        let default = default.unwrap_or_default();
+        let kind = match kind?.as_str() {
+            "check" => UciOptionKind::Check {
+                default: default == "true",
+            },
+            "spin" => UciOptionKind::Spin {
+                default: default.parse().unwrap_or(0),
+                min: min.unwrap_or(i64::MIN),
+                max: max.unwrap_or(i64::MAX),
+            },
+            "combo" => UciOptionKind::Combo { default, choices },
+            "button" => UciOptionKind::Button,
+            "string" => UciOptionKind::String {
+                // `<empty>` is how engines announce an empty default
+                default: if default == "<empty>" {
+                    String::new()
+                } else {
+                    default
+                },
+            },
+            _ => return None,
+        };
+
+        Some(Self { name: name?, kind })
+    }
+
+    /// Default value as it would be sent with `setoption`, `None` for buttons
+    pub fn default_value(&self) -> Option<String> {
+        match &self.kind {
+            UciOptionKind::Check { default } => Some(default.to_string()),
+            UciOptionKind::Spin { default, .. } => Some(default.to_string()),
+            UciOptionKind::Combo { default, .. } => Some(default.clone()),
+            UciOptionKind::Button => None,
+            UciOptionKind::String { default } => Some(default.clone()),
+        }
+    }
+}
+
+/// Search progress reported on an `info` line; fields the engine left out are `None`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UciInfo {
+    pub depth: Option<u32>,
+    pub seldepth: Option<u32>,
+    /// Line number when the engine reports several best lines
+    pub multipv: Option<u32>,
+    /// Score from the side to move's view
+    pub score: Option<Score>,
+    /// Set when the score is only a lower or upper bound
+    pub bound: bool,
+    pub nodes: Option<u64>,
+    pub nps: Option<u64>,
+    pub time: Option<Duration>,
+    /// Principal variation as UCI moves like `e2e4`
+    pub pv: Vec<String>,
+    /// Free text sent with `info string`
+    pub string: Option<String>,
+}
+
+impl UciInfo {
+    /// Parse an `info ...` line
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("info") {
+            return None;
+        }
+
+        let mut info = UciInfo::default();
+        while let Some(token) = tokens.next() {
+            match token {
+                "depth" => info.depth = tokens.next().and_then(|value| value.parse().ok()),
+                "seldepth" => info.seldepth = tokens.next().and_then(|value| value.parse().ok()),
+                "multipv" => info.multipv = tokens.next().and_then(|value| value.parse().ok()),
+                "nodes" => info.nodes = tokens.next().and_then(|value| value.parse().ok()),
+                "nps" => info.nps = tokens.next().and_then(|value| value.parse().ok()),
+                "time" => {
+                    info.time = tokens
+                        .next()
+                        .and_then(|value| value.parse().ok())
+                        .map(Duration::from_millis)
+                }
+                "score" => {
+                    let kind = tokens.next();
+                    let value = tokens.next().and_then(|value| value.parse().ok());
+                    info.score = match (kind, value) {
+                        (Some("cp"), Some(value)) => Some(Score::Centipawns(value)),
+                        (Some("mate"), Some(value)) => Some(Score::Mate(value)),
+                        _ => None,
+                    };
+                }
+                "lowerbound" | "upperbound" => info.bound = true,
+                // The rest of the line belongs to these
+                "pv" => {
+                    info.pv = tokens.by_ref().map(str::to_string).collect();
+                }
+                "string" => {
+                    info.string = Some(tokens.by_ref().collect::<Vec<_>>().join(" "));
+                }
+                _ => {}
+            }
+        }
+
+        Some(info)
+    }
+}
+
+/// Output of a searching engine
+#[derive(Debug, Clone, PartialEq)]
+pub enum UciEvent {
+    Info(UciInfo),
+    /// The chosen move in UCI notation, `None` when the engine has no legal move or resigns
+    BestMove(Option<String>),
+}
+
+impl UciEvent {
+    /// Parse an `info` or `bestmove` line
+    pub fn parse(line: &str) -> Option<Self> {
+        let mut tokens = line.split_whitespace();
+        match tokens.next()? {
+            "info" => UciInfo::parse(line).map(UciEvent::Info),
+            "bestmove" => {
+                let best_move = tokens
+                    .next()
+                    .filter(|&text| text != "0000" && text != "(none)");
+                Some(UciEvent::BestMove(best_move.map(str::to_string)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// An external engine speaking UCI
+pub struct UciEngine {
+    process: EngineProcess,
+    name: String,
+    author: String,
+    options: Vec<UciOption>,
+}
+
+impl UciEngine {
+    /// Start the engine at `path` and perform the `uci` handshake
+    pub fn start<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let path = path.as_ref();
+        let mut engine = Self {
+            process: EngineProcess::spawn(path)?,
+            name: file_name(path),
+            author: String::new(),
+            options: Vec::new(),
+        };
+
+        engine.send("uci")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let line = engine.process.next_line(deadline)?;
+            if line.trim() == "uciok" {
+                break;
+            } else if let Some(name) = line.strip_prefix("id name ") {
+                engine.name = name.trim().to_string();
+            } else if let Some(author) = line.strip_prefix("id author ") {
+                engine.author = author.trim().to_string();
+            } else if let Some(option) = UciOption::parse(&line) {
+                engine.options.push(option);
+            }
+        }
+
+        Ok(engine)
+    }
+
+    /// Send one command line
+    pub fn send(&mut self, command: &str) -> Result<(), &'static str> {
+        self.process.send(command)
+    }
+}
+
+impl ExternalEngine for UciEngine {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn author(&self) -> &str {
+        &self.author
+    }
+
+    fn protocol(&self) -> EngineProtocol {
+        EngineProtocol::Uci
+    }
+
+    fn options(&self) -> &[UciOption] {
+        &self.options
+    }
+
+    fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), &'static str> {
+        match value {
+            Some(value) => self.send(&format!("setoption name {} value {}", name, value)),
+            None => self.send(&format!("setoption name {}", name)),
+        }
+    }
+
+    fn wait_ready(&mut self) -> Result<(), &'static str> {
+        self.send("isready")?;
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        while self.process.next_line(deadline)?.trim() != "readyok" {}
+        Ok(())
+    }
+
+    fn new_game(&mut self) -> Result<(), &'static str> {
+        self.send("ucinewgame")
+    }
+
+    fn set_position(&mut self, start: &Board, moves: &[Move]) -> Result<(), &'static str> {
+        let fen = start.to_fen();
+        let mut command = if fen == STARTING_FEN {
+            "position startpos".to_string()
+        } else {
+            format!("position fen {}", fen)
+        };
+
+        if !moves.is_empty() {
+            command.push_str(" moves");
+            for mv in moves {
+                command.push(' ');
+                command.push_str(&mv.to_uci());
+            }
+        }
+        self.send(&command)
+    }
+
+    fn go(&mut self, limits: &SearchLimits) -> Result<(), &'static str> {
+        let mut command = "go".to_string();
+        if let Some(depth) = limits.depth {
+            command.push_str(&format!(" depth {}", depth));
+        }
+        if let Some(nodes) = limits.nodes {
+            command.push_str(&format!(" nodes {}", nodes));
+        }
+        if let Some(movetime) = limits.movetime {
+            command.push_str(&format!(" movetime {}", movetime.as_millis()));
+        }
+        if let Some(clock) = &limits.clock {
+            command.push_str(&format!(
+                " wtime {} btime {} winc {} binc {}",
+                clock.white_time.as_millis(),
+                clock.black_time.as_millis(),
+                clock.white_increment.as_millis(),
+                clock.black_increment.as_millis()
+            ));
+            if let Some(moves_to_go) = clock.moves_to_go {
+                command.push_str(&format!(" movestogo {}", moves_to_go));
+            }
+        }
+        if command == "go" {
+            command.push_str(" infinite");
+        }
+        self.send(&command)
+    }
+
+    fn stop(&mut self) -> Result<(), &'static str> {
+        self.send("stop")
+    }
+
+    fn poll(&mut self) -> Result<Option<UciEvent>, &'static str> {
+        while let Some(line) = self.process.try_line()? {
+            if let Some(event) = UciEvent::parse(&line) {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+
+    fn wait_event(&mut self, timeout: Duration) -> Result<Option<UciEvent>, &'static str> {
+        let deadline = Instant::now() + timeout;
+        while let Some(line) = self
+            .process
+            .recv_line(deadline.saturating_duration_since(Instant::now()))?
+        {
+            if let Some(event) = UciEvent::parse(&line) {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClockLimits;
+
+    /// Engine answering the handshake and every search with e7e5, writing the
+    /// commands it reads to `commands.log` next to it
+    #[cfg(unix)]
+    const MOCK_ENGINE: &str = r#"#!/bin/sh
+while read -r line; do
+    echo "$line" >> commands.log
+    case "$line" in
+        uci)
+            echo "id name Mock Engine"
+            echo "id author Nobody"
+            echo "option name Hash type spin default 16 min 1 max 1024"
+            echo "option name Ponder type check default false"
+            echo "uciok"
+            ;;
+        isready) echo "readyok" ;;
+        go*)
+            echo "info depth 1 score cp 20 nodes 30 pv e7e5"
+            echo "bestmove e7e5"
+            ;;
+        quit|exit) exit 0 ;;
+    esac
+done
+"#;
+
+    #[cfg(unix)]
+    #[test]
+    fn mock_engine_session() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = std::env::temp_dir().join(format!("mock-uci-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("mock-engine");
+        std::fs::write(&path, MOCK_ENGINE).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut engine = UciEngine::start(&path).unwrap();
+        assert_eq!(engine.name(), "Mock Engine");
+        assert_eq!(engine.author(), "Nobody");
+        let options: Vec<&str> = engine.options().iter().map(|o| o.name.as_str()).collect();
+        assert_eq!(options, ["Hash", "Ponder"]);
+
+        engine.set_option("Hash", Some("64")).unwrap();
+        engine.wait_ready().unwrap();
+        let board = Board::new_starting_default();
+        let e4 = board.parse_uci_move("e2e4").unwrap();
+        engine.set_position(&board, &[e4]).unwrap();
+        let limits = SearchLimits {
+            depth: Some(5),
+            clock: Some(ClockLimits {
+                white_time: Duration::from_secs(60),
+                black_time: Duration::from_secs(90),
+                white_increment: Duration::from_secs(1),
+                black_increment: Duration::ZERO,
+                moves_to_go: Some(20),
+            }),
+            ..Default::default()
+        };
+        engine.go(&limits).unwrap();
+
+        let Ok(Some(UciEvent::Info(info))) = engine.wait_event(HANDSHAKE_TIMEOUT) else {
+            panic!("expected search progress");
+        };
+        assert_eq!(info.score, Some(Score::Centipawns(20)));
+        assert_eq!(info.pv, ["e7e5"]);
+        assert_eq!(
+            engine.wait_event(HANDSHAKE_TIMEOUT),
+            Ok(Some(UciEvent::BestMove(Some("e7e5".to_string()))))
+        );
+
+        // Once the engine is gone polling reports it
+        engine.send("exit").unwrap();
+        assert_eq!(engine.wait_event(HANDSHAKE_TIMEOUT), Err("Engine exited\n"));
+        drop(engine);
+
+        let commands = std::fs::read_to_string(directory.join("commands.log")).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(
+            commands.lines().collect::<Vec<_>>(),
+            [
+                "uci",
+                "setoption name Hash value 64",
+                "isready",
+                "position startpos moves e2e4",
+                "go depth 5 wtime 60000 btime 90000 winc 1000 binc 0 movestogo 20",
+                "exit"
+            ]
+        );
+    }
+}
@@ -0,0 +1,579 @@
+// XBoard/WinBoard protocol server (CECP version 2)
+//
+// Unlike UCI the engine keeps the game itself: moves arrive one at a time and
+// the engine answers on its own turn unless in force mode. Searches run on their
+// own thread like in the UCI server; the move it prints is applied to the game
+// before the next command is handled.
+
+use crate::uci::{send, ENGINE_NAME};
+use crate::{Engine, Score, SearchLimits, SearchResult, Skill, StopHandle, MAX_SKILL_LEVEL};
+use chess_core::{Board, Color, Move};
+use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+const MAX_HASH_MB: usize = 1024;
+/// Scores from this far out are mates, `100000 + N` for a mate in N moves
+const MATE_SCORE: i32 = 100000;
+
+/// Answer XBoard commands from `input` on `output` until `quit` or the end of input
+pub fn run<R: BufRead, W: Write + Send + 'static>(input: R, output: W) {
+    let mut server = XboardServer::new(output);
+    for line in input.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if !server.handle_command(&line) {
+            break;
+        }
+    }
+    server.finish_search(true, true);
+}
+
+/// Time control set by `level`, `st` and `sd`, and the clock sent with `time`
+struct TimeControl {
+    /// Moves per session, 0 when the base time is for the whole game
+    moves_per_session: u32,
+    base: Duration,
+    increment: Duration,
+    /// Fixed time per move from `st`
+    move_time: Option<Duration>,
+    depth: Option<u32>,
+    /// Engine clock from the last `time`
+    time_left: Option<Duration>,
+}
+
+impl Default for TimeControl {
+    /// 40 moves in 5 minutes until the GUI says otherwise
+    fn default() -> Self {
+        Self {
+            moves_per_session: 40,
+            base: Duration::from_secs(300),
+            increment: Duration::ZERO,
+            move_time: None,
+            depth: None,
+            time_left: None,
+        }
+    }
+}
+
+impl TimeControl {
+    /// Read the arguments of `level MPS BASE INC`, BASE in minutes or `minutes:seconds`
+    fn set_level(&mut self, arguments: &[&str]) -> Result<(), &'static str> {
+        let [moves, base, increment] = arguments else {
+            return Err("Expected level MPS BASE INC\n");
+        };
+        let moves = moves.parse().map_err(|_| "Invalid level\n")?;
+        let base = match base.split_once(':') {
+            Some((minutes, seconds)) => {
+                let minutes: u64 = minutes.parse().map_err(|_| "Invalid level\n")?;
+                let seconds: u64 = seconds.parse().map_err(|_| "Invalid level\n")?;
+                Duration::from_secs(minutes * 60 + seconds)
+            }
+            None => {
+                let minutes: f64 = base.parse().map_err(|_| "Invalid level\n")?;
+                Duration::from_secs_f64(minutes.max(0.0) * 60.0)
+            }
+        };
+        let increment: f64 = increment.parse().map_err(|_| "Invalid level\n")?;
+
+        self.moves_per_session = moves;
+        self.base = base;
+        self.increment = Duration::from_secs_f64(increment.max(0.0));
+        self.move_time = None;
+        Ok(())
+    }
+
+    /// Limits for the next move of the game on `board`
+    fn limits(&self, board: &Board) -> SearchLimits {
+        let mut limits = match self.move_time {
+            Some(move_time) => SearchLimits {
+                movetime: Some(move_time),
+                ..Default::default()
+            },
+            None => {
+                let moves_to_go = (self.moves_per_session > 0).then(|| {
+                    let played = board.fullmove_number().saturating_sub(1) % self.moves_per_session;
+                    self.moves_per_session - played
+                });
+                SearchLimits::from_clock(
+                    self.time_left.unwrap_or(self.base),
+                    self.increment,
+                    moves_to_go,
+                )
+            }
+        };
+        limits.depth = self.depth;
+        limits
+    }
+}
+
+/// Score as sent in thinking output
+fn xboard_score(score: Score) -> i32 {
+    match score {
+        Score::Centipawns(centipawns) => centipawns,
+        Score::Mate(moves) if moves > 0 => MATE_SCORE + moves,
+        Score::Mate(moves) => -MATE_SCORE + moves,
+    }
+}
+
+/// Thinking output `ply score time nodes pv`, time in centiseconds and the variation in SAN
+fn thinking_line(board: &Board, result: &SearchResult) -> String {
+    let mut board = board.clone();
+    let mut pv = Vec::new();
+    for &mv in &result.pv {
+        pv.push(board.move_to_san(mv));
+        board.apply_move(mv);
+    }
+
+    format!(
+        "{} {} {} {} {}",
+        result.depth,
+        xboard_score(result.score),
+        result.elapsed.as_millis() / 10,
+        result.nodes,
+        pv.join(" ")
+    )
+}
+
+/// Result to claim when the game on `board` is over
+fn game_result(board: &Board) -> Option<&'static str> {
+    let side = board.side_to_move();
+    if board.is_checkmated(side) {
+        Some(match side {
+            Color::White => "0-1 {Black mates}",
+            Color::Black => "1-0 {White mates}",
+        })
+    } else if board.is_stalemate(side) {
+        Some("1/2-1/2 {Stalemate}")
+    } else if board.halfmove_clock() >= 100 {
+        Some("1/2-1/2 {Fifty move rule}")
+    } else {
+        None
+    }
+}
+
+/// State of an XBoard session
+pub struct XboardServer<W: Write + Send + 'static> {
+    output: Arc<Mutex<W>>,
+    /// The engine while idle, it moves to the search thread while searching
+    engine: Option<Engine>,
+    stop_handle: StopHandle,
+    /// Gives back the engine and the move it printed, if any
+    search: Option<JoinHandle<(Engine, Option<Move>)>>,
+    /// Set under the output lock when the running search must not print its move
+    discard: Arc<AtomicBool>,
+    board: Board,
+    /// Positions before each move, for `undo` and repetitions
+    history: Vec<Board>,
+    /// In force mode the engine only checks moves and never thinks
+    force: bool,
+    engine_color: Color,
+    post: bool,
+    clock: TimeControl,
+}
+
+impl<W: Write + Send + 'static> XboardServer<W> {
+    pub fn new(output: W) -> Self {
+        let engine = Engine::new();
+        Self {
+            output: Arc::new(Mutex::new(output)),
+            stop_handle: engine.stop_handle(),
+            engine: Some(engine),
+            search: None,
+            discard: Arc::new(AtomicBool::new(false)),
+            board: Board::new_starting_default(),
+            history: Vec::new(),
+            force: false,
+            engine_color: Color::Black,
+            post: false,
+            clock: TimeControl::default(),
+        }
+    }
+
+    fn send(&self, line: &str) {
+        send(&self.output, line);
+    }
+
+    /// Handle one command line
+    /// Returns false when the session should end
+    pub fn handle_command(&mut self, line: &str) -> bool {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        let Some((&command, arguments)) = tokens.split_first() else {
+            return true;
+        };
+
+        match command {
+            "protover" => {
+                self.send(&format!(
+                    "feature myname=\"{}\" ping=1 setboard=1 playother=1 usermove=1 san=0 \
+                     time=1 draw=0 sigint=0 sigterm=0 reuse=1 analyze=0 colors=0 name=0 smp=1",
+                    ENGINE_NAME
+                ));
+                self.send(&format!(
+                    "feature option=\"Hash -spin 16 1 {}\"",
+                    MAX_HASH_MB
+                ));
+                self.send(&format!(
+                    "feature option=\"Skill Level -spin {} 0 {}\"",
+                    MAX_SKILL_LEVEL, MAX_SKILL_LEVEL
+                ));
+                self.send("feature option=\"EvalFile -file \"");
+                self.send("feature done=1");
+            }
+            "new" => {
+                self.finish_search(true, true);
+                if let Some(engine) = &mut self.engine {
+                    engine.new_game();
+                }
+                self.board = Board::new_starting_default();
+                self.history.clear();
+                self.force = false;
+                self.engine_color = Color::Black;
+                self.clock.depth = None;
+                self.clock.time_left = None;
+            }
+            "force" => {
+                self.finish_search(true, true);
+                self.force = true;
+            }
+            "go" => {
+                self.finish_search(false, false);
+                self.force = false;
+                self.engine_color = self.board.side_to_move();
+                self.think();
+            }
+            "playother" => {
+                self.finish_search(false, false);
+                self.force = false;
+                self.engine_color = self.board.side_to_move().opposite();
+            }
+            "usermove" => {
+                self.finish_search(false, false);
+                let text = arguments.first().copied().unwrap_or_default();
+                match self.board.parse_move(text) {
+                    Ok(mv) => {
+                        self.play(mv);
+                        self.think();
+                    }
+                    Err(_) => self.send(&format!("Illegal move: {}", text)),
+                }
+            }
+            "?" => self.finish_search(true, false),
+            "undo" => {
+                self.finish_search(true, true);
+                self.undo(1);
+            }
+            "remove" => {
+                self.finish_search(true, true);
+                self.undo(2);
+            }
+            "setboard" => {
+                self.finish_search(true, true);
+                match Board::from_fen(&arguments.join(" ")) {
+                    Ok(board) => {
+                        self.board = board;
+                        self.history.clear();
+                    }
+                    Err(_) => self.send("tellusererror Illegal position"),
+                }
+            }
+            "result" => {
+                self.finish_search(true, true);
+                self.force = true;
+            }
+            "level" => {
+                if let Err(error) = self.clock.set_level(arguments) {
+                    self.send(&format!("Error ({}): {}", error.trim(), line.trim()));
+                }
+            }
+            "st" => match arguments
+                .first()
+                .and_then(|value| value.parse::<f64>().ok())
+            {
+                Some(seconds) => {
+                    self.clock.move_time = Some(Duration::from_secs_f64(seconds.max(0.01)))
+                }
+                None => self.send(&format!("Error (invalid time): {}", line.trim())),
+            },
+            "sd" => match arguments.first().and_then(|value| value.parse().ok()) {
+                Some(depth) => self.clock.depth = Some(depth),
+                None => self.send(&format!("Error (invalid depth): {}", line.trim())),
+            },
+            "time" => {
+                // Centiseconds on the engine's clock
+                if let Some(centiseconds) = arguments
+                    .first()
+                    .and_then(|value| value.parse::<u64>().ok())
+                {
+                    self.clock.time_left = Some(Duration::from_millis(centiseconds * 10));
+                }
+            }
+            "cores" => match arguments.first().and_then(|value| value.parse().ok()) {
+                Some(threads) => {
+                    if let Some(engine) = &mut self.engine {
+                        engine.set_threads(threads);
+                    }
+                }
+                None => self.send(&format!("Error (invalid number of cores): {}", line.trim())),
+            },
+            "ping" => self.send(&format!("pong {}", arguments.join(" "))),
+            "post" => self.post = true,
+            "nopost" => self.post = false,
+            "option" => self.set_option(&arguments.join(" ")),
+            "quit" => return false,
+            // Commands without effect here
+            "xboard" | "accepted" | "rejected" | "otim" | "hard" | "easy" | "random"
+            | "computer" | "name" | "rating" | "ics" | "draw" | "hint" | "bk" | "white"
+            | "black" => {}
+            _ => self.send(&format!("Error (unknown command): {}", command)),
+        }
+
+        true
+    }
+
+    /// `option NAME=VALUE`
+    fn set_option(&mut self, text: &str) {
+        let (name, value) = text.split_once('=').unwrap_or((text, ""));
+        let Some(engine) = &mut self.engine else {
+            return;
+        };
+
+        match name.trim() {
+            "Hash" => match value.trim().parse::<usize>() {
+                Ok(size_mb) => engine.set_hash_size(size_mb.clamp(1, MAX_HASH_MB)),
+                Err(_) => self.send(&format!("Error (invalid value): {}", value)),
+            },
+            "EvalFile" => {
+                let path = Some(value.trim()).filter(|path| !path.is_empty());
+                if let Err(error) = engine.set_eval_file(path) {
+                    self.send(&format!("Error ({}): {}", error.trim(), value));
+                }
+            }
+            "Skill Level" => match value.trim().parse::<u32>() {
+                Ok(level) => engine.set_skill(Skill::from_level(level)),
+                Err(_) => self.send(&format!("Error (invalid value): {}", value)),
+            },
+            _ => self.send(&format!("Error (unknown option): {}", name)),
+        }
+    }
+
+    fn play(&mut self, mv: Move) {
+        self.history.push(self.board.clone());
+        self.board.apply_move(mv);
+    }
+
+    fn undo(&mut self, moves: usize) {
+        for _ in 0..moves {
+            if let Some(board) = self.history.pop() {
+                self.board = board;
+            }
+        }
+    }
+
+    /// Start searching if it is the engine's turn and the game goes on
+    fn think(&mut self) {
+        if self.force || self.board.side_to_move() != self.engine_color {
+            return;
+        }
+        if game_result(&self.board).is_some() {
+            return;
+        }
+        let Some(mut engine) = self.engine.take() else {
+            return;
+        };
+
+        let output = self.output.clone();
+        let discard = self.discard.clone();
+        discard.store(false, Ordering::Relaxed);
+        self.stop_handle.clear();
+        let limits = self.clock.limits(&self.board);
+        let board = self.board.clone();
+        let history = self.history.clone();
+        let post = self.post;
+
+        self.search = Some(thread::spawn(move || {
+            engine.set_history(&history);
+            let result = engine.search_with_info(&board, &limits, |info| {
+                if post {
+                    send(&output, &thinking_line(&board, info));
+                }
+            });
+
+            // Checked under the lock, so the move is either printed and played or dropped
+            let Ok(mut output) = output.lock() else {
+                return (engine, None);
+            };
+            if discard.load(Ordering::Relaxed) {
+                return (engine, None);
+            }
+            let Some(mv) = result.best_move else {
+                return (engine, None);
+            };
+
+            let _ = writeln!(output, "move {}", mv.to_uci());
+            let mut after = board.clone();
+            after.apply_move(mv);
+            if let Some(result) = game_result(&after) {
+                let _ = writeln!(output, "{}", result);
+            }
+            let _ = output.flush();
+            drop(output);
+            (engine, Some(mv))
+        }));
+    }
+
+    /// Wait for the running search and play the move it printed.
+    /// `stop` makes it move now, `discard` keeps it from moving at all
+    fn finish_search(&mut self, stop: bool, discard: bool) {
+        let Some(search) = self.search.take() else {
+            return;
+        };
+
+        if discard {
+            let _output = self.output.lock();
+            self.discard.store(true, Ordering::Relaxed);
+        }
+        if stop || discard {
+            self.stop_handle.stop();
+        }
+
+        let (engine, played) = search.join().unwrap_or_else(|_| (Engine::new(), None));
+        self.stop_handle = engine.stop_handle();
+        self.engine = Some(engine);
+        // The search may have ended before seeing the request
+        self.stop_handle.clear();
+
+        if let Some(mv) = played {
+            self.play(mv);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uci::tests::SharedOutput;
+
+    fn session(commands: &[&str]) -> (XboardServer<SharedOutput>, SharedOutput) {
+        let output = SharedOutput::default();
+        let mut server = XboardServer::new(output.clone());
+        for command in commands {
+            assert!(server.handle_command(command));
+        }
+        (server, output)
+    }
+
+    /// Moves the engine printed
+    fn engine_moves(output: &SharedOutput) -> Vec<String> {
+        output
+            .lines()
+            .iter()
+            .filter_map(|line| line.strip_prefix("move "))
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn handshake() {
+        let (mut server, output) = session(&["xboard", "protover 2", "ping 7"]);
+        let lines = output.lines();
+        assert!(lines[0].starts_with(&format!("feature myname=\"{}\"", ENGINE_NAME)));
+        assert_eq!(lines[lines.len() - 2..], ["feature done=1", "pong 7"]);
+
+        assert!(server.handle_command("frobnicate"));
+        assert!(!server.handle_command("quit"));
+        assert_eq!(
+            output.lines().last().unwrap(),
+            "Error (unknown command): frobnicate"
+        );
+    }
+
+    #[test]
+    fn usermove_gets_an_answer() {
+        let (mut server, output) = session(&["new", "sd 2", "usermove e2e4"]);
+        server.finish_search(false, false);
+
+        // The answer is played on the engine's board too
+        let moves = engine_moves(&output);
+        assert_eq!(moves.len(), 1);
+        assert_eq!(server.history.len(), 2);
+        let mut board = server.history[1].clone();
+        board.apply_move(board.parse_uci_move(&moves[0]).unwrap());
+        assert_eq!(server.board, board);
+        assert_eq!(server.board.side_to_move(), Color::White);
+
+        assert!(server.handle_command("usermove e2e5"));
+        assert_eq!(output.lines().last().unwrap(), "Illegal move: e2e5");
+    }
+
+    #[test]
+    fn force_and_go() {
+        // In force mode moves are only played
+        let (mut server, output) = session(&["new", "sd 2", "force", "usermove e4", "usermove e5"]);
+        assert!(engine_moves(&output).is_empty());
+        assert_eq!(server.history.len(), 2);
+
+        // `go` makes the engine play the side to move
+        assert!(server.handle_command("go"));
+        server.finish_search(false, false);
+        assert_eq!(engine_moves(&output).len(), 1);
+        assert_eq!(server.engine_color, Color::White);
+        assert_eq!(server.board.side_to_move(), Color::Black);
+    }
+
+    #[test]
+    fn mate_claims_the_result() {
+        let (mut server, output) = session(&[
+            "new",
+            "force",
+            "setboard k7/8/1K6/8/8/8/8/7Q w - - 0 1",
+            "sd 3",
+            "go",
+        ]);
+        server.finish_search(false, false);
+        assert_eq!(output.lines().last().unwrap(), "1-0 {White mates}");
+        assert!(server.board.is_checkmated(Color::Black));
+    }
+
+    #[test]
+    fn result_stops_thinking() {
+        let (mut server, output) = session(&["new", "st 30", "usermove e2e4"]);
+        thread::sleep(Duration::from_millis(100));
+        // The move of the interrupted search is dropped, and the engine
+        // waits in force mode
+        assert!(server.handle_command("result 1-0 {Black resigns}"));
+        assert!(server.search.is_none());
+        assert!(server.force);
+        assert!(server.handle_command("usermove e7e5"));
+        assert!(server.search.is_none());
+        assert!(engine_moves(&output).is_empty());
+        assert_eq!(server.history.len(), 2);
+    }
+
+    #[test]
+    fn move_now() {
+        let (mut server, output) = session(&["new", "st 30", "usermove d2d4"]);
+        thread::sleep(Duration::from_millis(100));
+        assert!(server.handle_command("?"));
+        assert_eq!(engine_moves(&output).len(), 1);
+        assert_eq!(server.history.len(), 2);
+    }
+
+    #[test]
+    fn levels() {
+        let mut clock = TimeControl::default();
+        assert!(clock.set_level(&["40", "5", "0"]).is_ok());
+        assert_eq!(clock.base, Duration::from_secs(300));
+        assert!(clock.set_level(&["0", "2:30", "1.5"]).is_ok());
+        assert_eq!(clock.base, Duration::from_secs(150));
+        assert_eq!(clock.increment, Duration::from_millis(1500));
+        assert!(clock.set_level(&["0", "2"]).is_err());
+
+        assert_eq!(xboard_score(Score::Mate(3)), 100003);
+        assert_eq!(xboard_score(Score::Mate(-2)), -100002);
+        assert_eq!(xboard_score(Score::Centipawns(-35)), -35);
+    }
+}
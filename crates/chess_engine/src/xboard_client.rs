@@ -0,0 +1,627 @@
+// Client side of the Chess Engine Communication Protocol (XBoard/WinBoard)
+//
+// CECP engines keep their own copy of the game and receive moves one by one.
+// The client remembers what it sent so a new position only costs the moves
+// played since, and keeps the engine in force mode unless it is thinking.
+
+use crate::external::{file_name, EngineProcess, HANDSHAKE_TIMEOUT};
+use crate::search::MAX_DEPTH;
+use crate::{
+    EngineProtocol, ExternalEngine, Score, SearchLimits, UciEvent, UciInfo, UciOption,
+    UciOptionKind,
+};
+use chess_core::{Board, Move, STARTING_FEN};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How long engines without `feature done=0` get to announce their features
+const FEATURE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Scores from this far out are mates, `100000 + N` for a mate in N moves
+const MATE_SCORE: i32 = 100000;
+
+/// Features this client understands, others are rejected
+const KNOWN_FEATURES: [&str; 18] = [
+    "ping",
+    "setboard",
+    "playother",
+    "san",
+    "usermove",
+    "time",
+    "draw",
+    "sigint",
+    "sigterm",
+    "reuse",
+    "analyze",
+    "myname",
+    "colors",
+    "name",
+    "debug",
+    "memory",
+    "option",
+    "done",
+];
+
+/// Split a `feature` line into its names and values, values may be quoted
+fn parse_features(line: &str) -> Vec<(String, String)> {
+    let mut features = Vec::new();
+    let Some(mut rest) = line.trim().strip_prefix("feature") else {
+        return features;
+    };
+
+    loop {
+        rest = rest.trim_start();
+        let Some(equals) = rest.find('=') else {
+            break;
+        };
+        let name = rest[..equals].trim().to_string();
+        rest = &rest[equals + 1..];
+
+        let value = if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            rest = quoted.get(end + 1..).unwrap_or("");
+            &quoted[..end]
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let value = &rest[..end];
+            rest = &rest[end..];
+            value
+        };
+        features.push((name, value.to_string()));
+    }
+
+    features
+}
+
+/// Read the value of `feature option="..."` like `Hash -spin 64 1 1024`
+fn parse_option_feature(text: &str) -> Option<UciOption> {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let kind_index = tokens.iter().position(|token| {
+        matches!(
+            *token,
+            "-button"
+                | "-save"
+                | "-reset"
+                | "-check"
+                | "-string"
+                | "-spin"
+                | "-slider"
+                | "-combo"
+                | "-file"
+                | "-path"
+        )
+    })?;
+    let name = tokens[..kind_index].join(" ");
+    let arguments = &tokens[kind_index + 1..];
+    let number = |index: usize| arguments.get(index).and_then(|value| value.parse().ok());
+
+    let kind = match tokens[kind_index] {
+        "-check" => UciOptionKind::Check {
+            default: arguments.first() == Some(&"1"),
+        },
+        "-spin" | "-slider" => UciOptionKind::Spin {
+            default: number(0).unwrap_or(0),
+            min: number(1).unwrap_or(i64::MIN),
+            max: number(2).unwrap_or(i64::MAX),
+        },
+        "-combo" => {
+            // Choices are separated by `///`, the default one starts with `*`
+            let joined = arguments.join(" ");
+            let mut default = String::new();
+            let choices = joined
+                .split("///")
+                .map(|choice| {
+                    let choice = choice.trim();
+                    match choice.strip_prefix('*') {
+                        Some(choice) => {
+                            default = choice.to_string();
+                            default.clone()
+                        }
+                        None => choice.to_string(),
+                    }
+                })
+                .collect::<Vec<_>>();
+            if default.is_empty() {
+                default = choices.first().cloned().unwrap_or_default();
+            }
+            UciOptionKind::Combo { default, choices }
+        }
+        "-string" | "-file" | "-path" => UciOptionKind::String {
+            default: arguments.join(" "),
+        },
+        _ => UciOptionKind::Button,
+    };
+
+    (!name.is_empty()).then_some(UciOption { name, kind })
+}
+
+/// An external engine speaking the XBoard protocol, version 2
+pub struct XboardEngine {
+    process: EngineProcess,
+    name: String,
+    options: Vec<UciOption>,
+    ping: bool,
+    setboard: bool,
+    san: bool,
+    usermove: bool,
+    next_ping: u32,
+    /// Set while an `sd` depth limit is in force, `new` lifts it
+    depth_limited: bool,
+    /// Set once the game's time control went out with `level`
+    level_sent: bool,
+    /// Position and moves the engine has been sent, or played itself
+    start: Board,
+    moves: Vec<Move>,
+    board: Board,
+}
+
+impl XboardEngine {
+    /// Start the engine at `path` and read the features it announces
+    pub fn start<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let path = path.as_ref();
+        let start = Board::new_starting_default();
+        let mut engine = Self {
+            process: EngineProcess::spawn(path)?,
+            name: file_name(path),
+            options: Vec::new(),
+            // Defaults of the protocol for features left out
+            ping: false,
+            setboard: false,
+            san: false,
+            usermove: false,
+            next_ping: 1,
+            depth_limited: false,
+            level_sent: false,
+            board: start.clone(),
+            start,
+            moves: Vec::new(),
+        };
+
+        engine.send("xboard")?;
+        engine.send("protover 2")?;
+
+        let mut deadline = Instant::now() + FEATURE_TIMEOUT;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            // Version 1 engines never send `done`
+            let Some(line) = engine.process.recv_line(timeout)? else {
+                break;
+            };
+            if !line.starts_with("feature") {
+                continue;
+            }
+
+            let mut done = false;
+            for (feature, value) in parse_features(&line) {
+                let enabled = value == "1";
+                match feature.as_str() {
+                    "ping" => engine.ping = enabled,
+                    "setboard" => engine.setboard = enabled,
+                    "san" => engine.san = enabled,
+                    "usermove" => engine.usermove = enabled,
+                    "myname" => engine.name = value.clone(),
+                    "option" => engine.options.extend(parse_option_feature(&value)),
+                    "done" if enabled => done = true,
+                    // The engine needs time, wait for `done=1` as long as for a UCI handshake
+                    "done" => deadline = Instant::now() + HANDSHAKE_TIMEOUT,
+                    _ => {}
+                }
+
+                let answer = if KNOWN_FEATURES.contains(&feature.as_str()) {
+                    "accepted"
+                } else {
+                    "rejected"
+                };
+                engine.send(&format!("{} {}", answer, feature))?;
+            }
+            if done {
+                break;
+            }
+        }
+
+        engine.send("new")?;
+        engine.send("force")?;
+        engine.send("post")?;
+        Ok(engine)
+    }
+
+    /// Send one command line
+    pub fn send(&mut self, command: &str) -> Result<(), &'static str> {
+        self.process.send(command)
+    }
+
+    /// Send a move in the notation the engine asked for
+    fn send_move(&mut self, mv: Move) -> Result<(), &'static str> {
+        let text = if self.san {
+            self.board.move_to_san(mv)
+        } else {
+            mv.to_uci()
+        };
+        if self.usermove {
+            self.send(&format!("usermove {}", text))?;
+        } else {
+            self.send(&text)?;
+        }
+
+        self.board.apply_move(mv);
+        self.moves.push(mv);
+        Ok(())
+    }
+
+    /// Turn thinking output `ply score time nodes pv` into search progress,
+    /// the variation translated to UCI moves as far as it reads as legal moves
+    fn parse_thinking(&self, line: &str) -> Option<UciInfo> {
+        let mut tokens = line.split_whitespace();
+        let depth = tokens.next()?.trim_end_matches(['.', '&']).parse().ok()?;
+        let score: i32 = tokens.next()?.parse().ok()?;
+        let time: u64 = tokens.next()?.parse().ok()?;
+        let nodes: u64 = tokens.next()?.parse().ok()?;
+
+        let score = if score >= MATE_SCORE {
+            Score::Mate(score - MATE_SCORE)
+        } else if score <= -MATE_SCORE {
+            Score::Mate(score + MATE_SCORE)
+        } else {
+            Score::Centipawns(score)
+        };
+
+        let mut board = self.board.clone();
+        let mut pv = Vec::new();
+        for token in tokens {
+            // Skip move numbers like `12.` and `12...`
+            if token.ends_with('.') && token.starts_with(|c: char| c.is_ascii_digit()) {
+                continue;
+            }
+            let Ok(mv) = board.parse_move(token) else {
+                break;
+            };
+            pv.push(mv.to_uci());
+            board.apply_move(mv);
+        }
+
+        Some(UciInfo {
+            depth: Some(depth),
+            score: Some(score),
+            nodes: Some(nodes),
+            time: Some(Duration::from_millis(time * 10)),
+            pv,
+            ..Default::default()
+        })
+    }
+
+    /// Read one output line, keeping track of the moves the engine plays
+    fn handle_line(&mut self, line: &str) -> Result<Option<UciEvent>, &'static str> {
+        let line = line.trim();
+        let mut tokens = line.split_whitespace();
+        let Some(first) = tokens.next() else {
+            return Ok(None);
+        };
+
+        if first == "move" {
+            let text = tokens.next().unwrap_or_default();
+            let mv = self
+                .board
+                .parse_move(text)
+                .map_err(|_| "Engine sent an illegal move\n")?;
+            self.board.apply_move(mv);
+            self.moves.push(mv);
+            return Ok(Some(UciEvent::BestMove(Some(mv.to_uci()))));
+        }
+
+        match first {
+            "resign" => Ok(Some(UciEvent::BestMove(None))),
+            _ if line.starts_with("Illegal move") => Err("Engine rejected a move\n"),
+            _ if first.starts_with(|c: char| c.is_ascii_digit()) => {
+                Ok(self.parse_thinking(line).map(UciEvent::Info))
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+impl ExternalEngine for XboardEngine {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn author(&self) -> &str {
+        ""
+    }
+
+    fn protocol(&self) -> EngineProtocol {
+        EngineProtocol::Xboard
+    }
+
+    fn options(&self) -> &[UciOption] {
+        &self.options
+    }
+
+    fn set_option(&mut self, name: &str, value: Option<&str>) -> Result<(), &'static str> {
+        let is_check = self.options.iter().any(|option| {
+            option.name == name && matches!(option.kind, UciOptionKind::Check { .. })
+        });
+        match value {
+            // Check boxes are `0` or `1` here
+            Some(value) if is_check => {
+                let value = if value == "true" { "1" } else { "0" };
+                self.send(&format!("option {}={}", name, value))
+            }
+            Some(value) => self.send(&format!("option {}={}", name, value)),
+            None => self.send(&format!("option {}", name)),
+        }
+    }
+
+    fn wait_ready(&mut self) -> Result<(), &'static str> {
+        if !self.ping {
+            return Ok(());
+        }
+
+        let pong = format!("pong {}", self.next_ping);
+        self.send(&format!("ping {}", self.next_ping))?;
+        self.next_ping += 1;
+
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let line = self.process.next_line(deadline)?;
+            if line.trim() == pong {
+                return Ok(());
+            }
+            // Moves still count, the engine played them
+            self.handle_line(&line)?;
+        }
+    }
+
+    fn new_game(&mut self) -> Result<(), &'static str> {
+        self.send("new")?;
+        self.send("force")?;
+        self.send("post")?;
+        self.depth_limited = false;
+        self.level_sent = false;
+        self.start = Board::new_starting_default();
+        self.board = self.start.clone();
+        self.moves.clear();
+        Ok(())
+    }
+
+    fn set_position(&mut self, start: &Board, moves: &[Move]) -> Result<(), &'static str> {
+        self.send("force")?;
+
+        let in_sync = self.start == *start && moves.starts_with(&self.moves);
+        if !in_sync {
+            self.new_game()?;
+            let fen = start.to_fen();
+            if fen != STARTING_FEN {
+                if !self.setboard {
+                    return Err("Engine cannot set up positions\n");
+                }
+                self.send(&format!("setboard {}", fen))?;
+                self.start = start.clone();
+                self.board = start.clone();
+            }
+        }
+
+        for &mv in &moves[self.moves.len()..] {
+            self.send_move(mv)?;
+        }
+        Ok(())
+    }
+
+    fn go(&mut self, limits: &SearchLimits) -> Result<(), &'static str> {
+        match limits.depth {
+            Some(depth) => {
+                self.send(&format!("sd {}", depth))?;
+                self.depth_limited = true;
+            }
+            // There is no command to lift `sd`, so allow the deepest search instead
+            None if std::mem::take(&mut self.depth_limited) => {
+                self.send(&format!("sd {}", MAX_DEPTH))?;
+            }
+            None => {}
+        }
+        if let Some(movetime) = limits.movetime {
+            // Whole seconds per move, rounded up
+            let seconds = movetime.as_millis().div_ceil(1000).max(1);
+            self.send(&format!("st {}", seconds))?;
+            self.level_sent = false;
+        } else if let Some(clock) = &limits.clock {
+            // The engine plays the side to move, `time` is always its own clock
+            let color = self.board.side_to_move();
+            let (time_left, increment) = clock.side(color);
+            let (opponent_time, _) = clock.side(color.opposite());
+
+            // Engines budget from `time` and `otim`, so the base time of
+            // `level` is only sent once per game
+            if !self.level_sent {
+                let seconds = time_left.as_secs();
+                self.send(&format!(
+                    "level {} {}:{:02} {}",
+                    clock.moves_to_go.unwrap_or(0),
+                    seconds / 60,
+                    seconds % 60,
+                    increment.as_secs_f64()
+                ))?;
+                self.level_sent = true;
+            }
+            self.send(&format!("time {}", time_left.as_millis() / 10))?;
+            self.send(&format!("otim {}", opponent_time.as_millis() / 10))?;
+        }
+        self.send("go")
+    }
+
+    fn stop(&mut self) -> Result<(), &'static str> {
+        self.send("?")
+    }
+
+    fn poll(&mut self) -> Result<Option<UciEvent>, &'static str> {
+        while let Some(line) = self.process.try_line()? {
+            if let Some(event) = self.handle_line(&line)? {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+
+    fn wait_event(&mut self, timeout: Duration) -> Result<Option<UciEvent>, &'static str> {
+        let deadline = Instant::now() + timeout;
+        while let Some(line) = self
+            .process
+            .recv_line(deadline.saturating_duration_since(Instant::now()))?
+        {
+            if let Some(event) = self.handle_line(&line)? {
+                return Ok(Some(event));
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ClockLimits;
+
+    /// Engine answering its searches with b8c6, then g8f6, then resigning,
+    /// writing the commands it reads to `commands.log` next to it
+    #[cfg(unix)]
+    const MOCK_ENGINE: &str = r#"#!/bin/sh
+set -- b8c6 g8f6
+while read -r line; do
+    echo "$line" >> commands.log
+    case "$line" in
+        "protover 2") echo 'feature myname="Mock Engine" usermove=1 done=1' ;;
+        go)
+            if [ $# -eq 0 ]; then
+                echo resign
+                continue
+            fi
+            echo "1 20 0 30 $1"
+            echo "move $1"
+            shift
+            ;;
+        quit|exit) exit 0 ;;
+    esac
+done
+"#;
+
+    #[cfg(unix)]
+    #[test]
+    fn mock_engine_session() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = std::env::temp_dir().join(format!("mock-xboard-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let path = directory.join("mock-engine");
+        std::fs::write(&path, MOCK_ENGINE).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut engine = XboardEngine::start(&path).unwrap();
+        assert_eq!(engine.name(), "Mock Engine");
+
+        let start = Board::new_starting_default();
+        let mut board = start.clone();
+        let mut moves = Vec::new();
+        let mut play = |board: &mut Board, text: &str| {
+            let mv = board.parse_uci_move(text).unwrap();
+            board.apply_move(mv);
+            moves.push(mv);
+            moves.clone()
+        };
+
+        engine
+            .set_position(&start, &play(&mut board, "e2e4"))
+            .unwrap();
+        let limits = SearchLimits {
+            depth: Some(5),
+            clock: Some(ClockLimits {
+                white_time: Duration::from_secs(60),
+                black_time: Duration::from_secs(90),
+                white_increment: Duration::from_secs(1),
+                black_increment: Duration::from_secs(2),
+                moves_to_go: None,
+            }),
+            ..Default::default()
+        };
+        engine.go(&limits).unwrap();
+
+        let Ok(Some(UciEvent::Info(info))) = engine.wait_event(HANDSHAKE_TIMEOUT) else {
+            panic!("expected search progress");
+        };
+        assert_eq!(info.score, Some(Score::Centipawns(20)));
+        assert_eq!(info.pv, ["b8c6"]);
+        assert_eq!(
+            engine.wait_event(HANDSHAKE_TIMEOUT),
+            Ok(Some(UciEvent::BestMove(Some("b8c6".to_string()))))
+        );
+
+        // Only the move played since goes out, the time control stays and
+        // the depth limit gets lifted
+        play(&mut board, "b8c6");
+        engine
+            .set_position(&start, &play(&mut board, "g1f3"))
+            .unwrap();
+        let limits = SearchLimits {
+            clock: Some(ClockLimits {
+                white_time: Duration::from_millis(59_500),
+                black_time: Duration::from_millis(88_250),
+                white_increment: Duration::from_secs(1),
+                black_increment: Duration::from_secs(2),
+                moves_to_go: None,
+            }),
+            ..Default::default()
+        };
+        engine.go(&limits).unwrap();
+        engine.wait_event(HANDSHAKE_TIMEOUT).unwrap();
+        assert_eq!(
+            engine.wait_event(HANDSHAKE_TIMEOUT),
+            Ok(Some(UciEvent::BestMove(Some("g8f6".to_string()))))
+        );
+
+        // A resignation is a search without a move
+        play(&mut board, "g8f6");
+        engine
+            .set_position(&start, &play(&mut board, "b1c3"))
+            .unwrap();
+        engine.go(&limits).unwrap();
+        assert_eq!(
+            engine.wait_event(HANDSHAKE_TIMEOUT),
+            Ok(Some(UciEvent::BestMove(None)))
+        );
+
+        engine.send("exit").unwrap();
+        drop(engine);
+
+        let commands = std::fs::read_to_string(directory.join("commands.log")).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(
+            commands.lines().collect::<Vec<_>>(),
+            [
+                "xboard",
+                "protover 2",
+                "accepted myname",
+                "accepted usermove",
+                "accepted done",
+                "new",
+                "force",
+                "post",
+                "force",
+                "usermove e2e4",
+                "sd 5",
+                "level 0 1:30 2",
+                "time 9000",
+                "otim 6000",
+                "go",
+                "force",
+                "usermove g1f3",
+                "sd 64",
+                "time 8825",
+                "otim 5950",
+                "go",
+                "force",
+                "usermove b1c3",
+                "time 8825",
+                "otim 5950",
+                "go",
+                "exit"
+            ]
+        );
+    }
+}
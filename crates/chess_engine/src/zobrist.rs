@@ -0,0 +1,114 @@
+use chess_core::{Board, Color, PieceType, Square};
+use std::sync::OnceLock;
+
+/// Random keys combined to identify positions
+struct Keys {
+    pieces: [[u64; 64]; 12],
+    black_to_move: u64,
+    castling: [u64; 4],
+    en_passant_file: [u64; 8],
+}
+
+fn keys() -> &'static Keys {
+    static KEYS: OnceLock<Keys> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        // Fixed seed so hashes are the same on every run
+        let mut rng = XorShift::new(0x9e37_79b9_7f4a_7c15);
+        let mut keys = Keys {
+            pieces: [[0; 64]; 12],
+            black_to_move: rng.next_u64(),
+            castling: [0; 4],
+            en_passant_file: [0; 8],
+        };
+        for piece_keys in keys.pieces.iter_mut() {
+            for key in piece_keys.iter_mut() {
+                *key = rng.next_u64();
+            }
+        }
+        for key in keys.castling.iter_mut() {
+            *key = rng.next_u64();
+        }
+        for key in keys.en_passant_file.iter_mut() {
+            *key = rng.next_u64();
+        }
+        keys
+    })
+}
+
+/// Small xorshift generator, enough for hash keys and move randomisation
+#[derive(Debug, Clone)]
+pub(crate) struct XorShift(u64);
+
+impl XorShift {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+/// Index of a square from 0 (a1) to 63 (h8)
+pub(crate) fn square_index(square: Square) -> usize {
+    square.rank() as usize * 8 + square.file() as usize
+}
+
+pub(crate) fn piece_index(color: Color, piece_type: PieceType) -> usize {
+    let type_index = match piece_type {
+        PieceType::Pawn => 0,
+        PieceType::Knight => 1,
+        PieceType::Bishop => 2,
+        PieceType::Rook => 3,
+        PieceType::Queen => 4,
+        PieceType::King => 5,
+    };
+    match color {
+        Color::White => type_index,
+        Color::Black => type_index + 6,
+    }
+}
+
+/// Hash of a position: pieces, side to move, castling rights and en passant file
+pub fn position_key(board: &Board) -> u64 {
+    let keys = keys();
+    let mut key = 0;
+
+    for rank in 0..8 {
+        for file in 0..8 {
+            let square = Square::new(rank, file).expect("always valid");
+            if let Some(piece) = board.get_piece(square) {
+                key ^=
+                    keys.pieces[piece_index(piece.color, piece.piece_type)][square_index(square)];
+            }
+        }
+    }
+
+    if board.side_to_move() == Color::Black {
+        key ^= keys.black_to_move;
+    }
+
+    let rights = board.castling_rights();
+    for (index, allowed) in [
+        rights.white_king_side,
+        rights.white_queen_side,
+        rights.black_king_side,
+        rights.black_queen_side,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if allowed {
+            key ^= keys.castling[index];
+        }
+    }
+
+    if let Some(square) = board.en_passant() {
+        key ^= keys.en_passant_file[square.file() as usize];
+    }
+
+    key
+}
@@ -0,0 +1,294 @@
+use chess_core::{Board, Color, Game, Move};
+use chess_engine::{white_score, Engine, Score, SearchLimits, SearchResult, StopHandle};
+use chess_render::Arrow;
+use egui::{Color32, FontId, Pos2, Rect, Ui};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+/// Most lines the panel can show
+pub const MAX_LINES: usize = 5;
+
+/// Progress sent by the analysis thread
+enum AnalysisUpdate {
+    /// Result of a completed iteration
+    Progress(SearchResult),
+    /// The search ended, giving the engine back
+    Finished(Engine),
+}
+
+/// Analyses the current position in the background and shows the best lines
+pub struct AnalysisPanel {
+    enabled: bool,
+    line_count: usize,
+    /// The engine while idle, it moves to the analysis thread while searching
+    engine: Option<Engine>,
+    stop_handle: StopHandle,
+    receiver: Option<Receiver<AnalysisUpdate>>,
+    /// Set once the running search was asked to stop, its results are stale
+    stopping: bool,
+    /// Position of the running or last search
+    analysed: Option<Board>,
+    result: Option<SearchResult>,
+}
+
+impl Default for AnalysisPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AnalysisPanel {
+    pub fn new() -> Self {
+        let engine = Engine::new();
+        Self {
+            enabled: false,
+            line_count: 3,
+            stop_handle: engine.stop_handle(),
+            engine: Some(engine),
+            receiver: None,
+            stopping: false,
+            analysed: None,
+            result: None,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Turn the analysis on or off
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.stop();
+            self.analysed = None;
+        }
+    }
+
+    fn stop(&mut self) {
+        if self.receiver.is_some() && !self.stopping {
+            self.stop_handle.stop();
+            self.stopping = true;
+        }
+        self.result = None;
+    }
+
+    /// Read the analysis progress and restart it when the displayed position changed
+    pub fn update(&mut self, ctx: &egui::Context, game: &Game) {
+        while let Some(update) = self
+            .receiver
+            .as_ref()
+            .and_then(|receiver| receiver.try_recv().ok())
+        {
+            match update {
+                AnalysisUpdate::Progress(result) => {
+                    if !self.stopping {
+                        self.result = Some(result);
+                    }
+                }
+                AnalysisUpdate::Finished(engine) => {
+                    self.engine = Some(engine);
+                    self.receiver = None;
+                    // The search may have finished before seeing the stop request
+                    if std::mem::take(&mut self.stopping) {
+                        self.stop_handle.clear();
+                    }
+                }
+            }
+        }
+
+        // A pawn waiting for its promotion piece is not a real position
+        if !self.enabled || game.get_promotion_square().is_some() {
+            return;
+        }
+        if self.analysed.as_ref() != Some(game.displayed_board()) {
+            self.stop();
+            self.start(ctx, game);
+        }
+    }
+
+    fn start(&mut self, ctx: &egui::Context, game: &Game) {
+        let Some(mut engine) = self.engine.take() else {
+            return;
+        };
+
+        let (sender, receiver) = channel();
+        // An earlier position being looked at is analysed instead of the current one
+        let board = game.displayed_board().clone();
+        let ply = game.viewed_ply().unwrap_or(game.positions().len());
+        let history = game.positions()[..ply].to_vec();
+        let ctx = ctx.clone();
+        engine.set_multipv(self.line_count);
+
+        self.analysed = Some(board.clone());
+        thread::spawn(move || {
+            engine.set_history(&history);
+            // Without limits the search runs until stopped
+            engine.search_with_info(&board, &SearchLimits::default(), |result| {
+                let _ = sender.send(AnalysisUpdate::Progress(result.clone()));
+                ctx.request_repaint();
+            });
+            let _ = sender.send(AnalysisUpdate::Finished(engine));
+            ctx.request_repaint();
+        });
+        self.receiver = Some(receiver);
+    }
+
+    /// Score of the current analysis from White's view
+    fn white_score(&self) -> Option<Score> {
+        let result = self.result.as_ref()?;
+        let side_to_move = self.analysed.as_ref()?.side_to_move();
+        Some(white_score(result.score, side_to_move))
+    }
+
+    /// Arrow of the best move found so far
+    pub fn best_move_arrow(&self) -> Option<Arrow> {
+        if !self.enabled {
+            return None;
+        }
+        let best_move = self.result.as_ref()?.best_move?;
+        Some(Arrow::new(best_move.from, best_move.to))
+    }
+
+    /// Draw the best lines with their scores and depth
+    pub fn draw(&mut self, ui: &mut Ui) {
+        ui.heading("Analysis");
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            ui.label("Lines");
+            if ui
+                .add(egui::DragValue::new(&mut self.line_count).range(1..=MAX_LINES))
+                .changed()
+            {
+                // Restart with the new number of lines
+                self.stop();
+                self.analysed = None;
+            }
+        });
+        ui.add_space(4.0);
+
+        let (Some(result), Some(board)) = (&self.result, &self.analysed) else {
+            ui.label(egui::RichText::new("Thinking...").weak());
+            return;
+        };
+
+        if result.lines.is_empty() {
+            let text = if board.is_king_in_check(board.side_to_move()) {
+                "Checkmate"
+            } else {
+                "Stalemate"
+            };
+            ui.label(egui::RichText::new(text).strong());
+            return;
+        }
+
+        ui.label(
+            egui::RichText::new(format!("Depth {}   {} nodes", result.depth, result.nodes)).weak(),
+        );
+        ui.add_space(4.0);
+
+        for line in &result.lines {
+            let score = white_score(line.score, board.side_to_move());
+            ui.horizontal_wrapped(|ui| {
+                ui.label(
+                    egui::RichText::new(score.to_string())
+                        .font(FontId::monospace(12.0))
+                        .strong(),
+                );
+                ui.label(egui::RichText::new(line_to_san(board, &line.pv)).size(12.0));
+            });
+            ui.add_space(2.0);
+        }
+    }
+
+    /// Draw a vertical bar showing who is better, White's share at the
+    /// bottom unless the board is flipped
+    pub fn draw_eval_bar(&self, ui: &Ui, rect: Rect, flipped: bool) {
+        if !self.enabled {
+            return;
+        }
+
+        let painter = ui.painter();
+        painter.rect_filled(rect, 2.0, Color32::from_gray(40));
+
+        let Some(score) = self.white_score() else {
+            return;
+        };
+        let fraction = match (score, &self.analysed) {
+            // Mated side to move, or no moves at all
+            (Score::Mate(0), Some(board)) => match board.is_king_in_check(board.side_to_move()) {
+                true if board.side_to_move() == Color::White => 0.0,
+                true => 1.0,
+                false => 0.5,
+            },
+            _ => white_share(score),
+        };
+
+        let white_height = rect.height() * fraction;
+        let white_rect = if flipped {
+            Rect::from_min_max(rect.min, Pos2::new(rect.max.x, rect.min.y + white_height))
+        } else {
+            Rect::from_min_max(Pos2::new(rect.min.x, rect.max.y - white_height), rect.max)
+        };
+        painter.rect_filled(white_rect, 2.0, Color32::from_gray(235));
+
+        // Score written on the side that is better
+        let white_better = fraction >= 0.5;
+        let text_color = if white_better {
+            Color32::from_gray(40)
+        } else {
+            Color32::from_gray(235)
+        };
+        let at_bottom = white_better != flipped;
+        let (position, align) = if at_bottom {
+            (
+                Pos2::new(rect.center().x, rect.max.y - 4.0),
+                egui::Align2::CENTER_BOTTOM,
+            )
+        } else {
+            (
+                Pos2::new(rect.center().x, rect.min.y + 4.0),
+                egui::Align2::CENTER_TOP,
+            )
+        };
+        let text = match score {
+            Score::Centipawns(centipawns) => format!("{:.1}", centipawns.abs() as f64 / 100.0),
+            Score::Mate(moves) => format!("M{}", moves.abs()),
+        };
+        painter.text(
+            position,
+            align,
+            text,
+            FontId::proportional(10.0),
+            text_color,
+        );
+    }
+}
+
+/// White's share of the bar: a sigmoid of the centipawns, full for a mate
+fn white_share(score: Score) -> f32 {
+    match score {
+        Score::Centipawns(centipawns) => 1.0 / (1.0 + 10f32.powf(-(centipawns as f32) / 400.0)),
+        Score::Mate(moves) if moves > 0 => 1.0,
+        Score::Mate(_) => 0.0,
+    }
+}
+
+/// Moves of a line in SAN with move numbers, like `12. Nf3 Nc6` or `12... Nc6 13. Bb5`
+pub fn line_to_san(board: &Board, moves: &[Move]) -> String {
+    let mut board = board.clone();
+    let mut text = String::new();
+    for (index, &mv) in moves.iter().enumerate() {
+        let number = board.fullmove_number();
+        match board.side_to_move() {
+            Color::White => text.push_str(&format!("{}. ", number)),
+            Color::Black if index == 0 => text.push_str(&format!("{}... ", number)),
+            Color::Black => {}
+        }
+        text.push_str(&board.move_to_san(mv));
+        text.push(' ');
+        board.apply_move(mv);
+    }
+    text.trim_end().to_string()
+}
@@ -1,8 +1,21 @@
+use crate::analysis_panel::AnalysisPanel;
 use crate::assets::PieceAssets;
-use crate::end_screen::{ChessEndScreen, GameResult};
+use crate::computer_player::ComputerPlayer;
+use crate::end_screen::{ChessEndScreen, EndScreenAction, GameResult};
+use crate::engine_dialog::{EngineDialog, EngineDialogAction};
+use crate::engine_player::{EngineConfig, EnginePlayer, PendingEngine};
+use crate::eval_graph::EvalGraph;
 use crate::game_view::ChessGameView;
-use chess_core::Game;
-use egui::{CentralPanel, Context, SidePanel};
+use crate::hint::Hint;
+use crate::mate_panel::MatePanel;
+use crate::new_game_dialog::{GameSettings, NewGameDialog, Opponent};
+use crate::puzzle_panel::PuzzlePanel;
+use crate::review::ReviewPanel;
+use crate::tablebase_panel::TablebasePanel;
+use chess_core::{read_pgn, Color, Game};
+use chess_render::RenderOptions;
+use egui::{CentralPanel, Context, SidePanel, TopBottomPanel};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 /// Main application that orchestrates the chess GUI
 pub struct ChessApp {
@@ -10,24 +23,293 @@ pub struct ChessApp {
     piece_assets: Option<PieceAssets>,
     game_view: ChessGameView,
     end_screen: ChessEndScreen,
+    tablebase_panel: TablebasePanel,
+    analysis_panel: AnalysisPanel,
+    hint: Hint,
+    review: ReviewPanel,
+    mate_panel: MatePanel,
+    puzzles: PuzzlePanel,
+    eval_graph: EvalGraph,
+    new_game_dialog: NewGameDialog,
+    computer: ComputerPlayer,
+    engine_dialog: EngineDialog,
+    /// External UCI or XBoard engine, when one is configured
+    engine_player: Option<EnginePlayer>,
+    /// Engine going through its handshake, replacing the current one once done
+    starting_engine: Option<PendingEngine>,
+    settings: GameSettings,
     game_over: Option<GameResult>,
+    /// Set once the end screen is closed, the game stays over
+    end_screen_dismissed: bool,
+    status_message: Option<String>,
+    /// PGN file to load, as typed under the move list
+    pgn_path: String,
 }
 
 impl ChessApp {
     pub fn new(egui_ctx: &egui::Context) -> Self {
+        let starting_engine =
+            EngineConfig::from_env().map(|config| PendingEngine::start(egui_ctx, config));
+
         Self {
             game: Game::new(),
             piece_assets: Some(PieceAssets::new(egui_ctx)),
             game_view: ChessGameView::new(),
             end_screen: ChessEndScreen::new(),
+            tablebase_panel: TablebasePanel::new(),
+            analysis_panel: AnalysisPanel::new(),
+            hint: Hint::new(),
+            review: ReviewPanel::new(),
+            mate_panel: MatePanel::new(),
+            puzzles: PuzzlePanel::new(),
+            eval_graph: EvalGraph::new(),
+            new_game_dialog: NewGameDialog::new(),
+            computer: ComputerPlayer::new(),
+            engine_dialog: EngineDialog::new(),
+            engine_player: None,
+            starting_engine,
+            settings: GameSettings::default(),
             game_over: None,
+            end_screen_dismissed: false,
+            status_message: None,
+            pgn_path: String::new(),
+        }
+    }
+
+    fn engine_name(&self) -> Option<&str> {
+        self.engine_player.as_ref().map(|player| player.name())
+    }
+
+    fn is_thinking(&self) -> bool {
+        self.computer.is_thinking()
+            || self
+                .engine_player
+                .as_ref()
+                .is_some_and(|player| player.is_thinking())
+    }
+
+    /// Replace or remove the external engine
+    fn apply_engine_action(&mut self, ctx: &Context, action: EngineDialogAction) {
+        match action {
+            EngineDialogAction::Apply(config) => {
+                self.status_message = Some(format!("Starting {}", config.path.display()));
+                self.starting_engine = Some(PendingEngine::start(ctx, config));
+            }
+            EngineDialogAction::Remove => {
+                self.engine_player = None;
+                self.starting_engine = None;
+                if self.settings.opponent == Opponent::Engine {
+                    self.settings.opponent = Opponent::Computer;
+                }
+            }
+        }
+    }
+
+    /// Take the started engine once its handshake is done
+    fn poll_starting_engine(&mut self) {
+        let Some(result) = self.starting_engine.as_ref().and_then(PendingEngine::poll) else {
+            return;
+        };
+        self.starting_engine = None;
+
+        // Dropping the old engine ends its search, the new one picks up the game
+        match result {
+            Ok(player) => {
+                self.status_message = Some(format!("Loaded {}", player.name()));
+                self.engine_player = Some(player);
+            }
+            Err(error) => self.status_message = Some(error.trim().to_string()),
+        }
+    }
+
+    /// Start a new game with the given settings
+    fn start_new_game(&mut self, mut settings: GameSettings) {
+        self.computer.cancel();
+        self.computer.new_game();
+        if let Some(player) = &mut self.engine_player {
+            player.cancel();
+            player.new_game();
+        } else if settings.opponent == Opponent::Engine {
+            settings.opponent = Opponent::Computer;
+        }
+        self.settings = settings;
+        self.puzzles.set_active(false, &mut self.game);
+        self.game.reset();
+        self.hint.clear();
+        self.review.clear();
+        self.game_over = None;
+        self.end_screen_dismissed = false;
+        self.game_view.reset();
+        self.game_view
+            .set_flipped(settings.player_color == Color::Black);
+        self.end_screen.reset();
+    }
+
+    /// Leave the game for puzzle mode, which sets up its own positions
+    fn start_puzzles(&mut self) {
+        self.computer.cancel();
+        if let Some(player) = &mut self.engine_player {
+            player.cancel();
+        }
+        self.hint.clear();
+        self.review.clear();
+        self.game_over = None;
+        self.end_screen_dismissed = false;
+        self.game_view.reset();
+        self.end_screen.reset();
+        self.puzzles.set_active(true, &mut self.game);
+    }
+
+    /// Let the computer move when it is its turn
+    fn update_computer(&mut self, ctx: &Context) {
+        // Both are polled so cancelled searches still hand back their engine
+        let computer_move = self.computer.poll();
+        let engine_move = match &mut self.engine_player {
+            Some(player) => {
+                let mv = player.poll(self.game.board());
+                if let Some(error) = player.take_error() {
+                    self.status_message = Some(error);
+                }
+                mv
+            }
+            None => None,
+        };
+        let chosen_move = match self.settings.opponent {
+            Opponent::Engine => engine_move,
+            _ => computer_move,
+        };
+
+        // The puzzle plays the opponent's moves itself
+        if self.puzzles.is_active() {
+            self.puzzles.update(ctx, &mut self.game);
+            if let Some(color) = self.puzzles.solver_color() {
+                self.game_view.set_flipped(color == Color::Black);
+            }
+            self.game_view
+                .set_interactive(self.puzzles.is_interactive(&self.game));
+            return;
         }
+
+        if let Some(mv) = chosen_move {
+            // The game may have moved on while the computer was thinking
+            if self.settings.is_computer(self.game.active_player()) && self.game_over.is_none() {
+                self.game.execute_move(mv.from, mv.to);
+                if let Some(promotion) = mv.promotion {
+                    self.game.promote_square(mv.to, promotion);
+                }
+            }
+        }
+
+        let computer_to_move = self.settings.is_computer(self.game.active_player())
+            && self.game_over.is_none()
+            && self.game.get_promotion_square().is_none();
+        if computer_to_move && !self.is_thinking() {
+            let limits = self.settings.difficulty.search_limits();
+            let skill = self.settings.difficulty.skill();
+            match (&mut self.engine_player, self.settings.opponent) {
+                (Some(player), Opponent::Engine) => {
+                    player.set_skill(skill);
+                    player.start_search(&self.game, &limits);
+                }
+                _ => self.computer.start(
+                    ctx,
+                    self.game.board(),
+                    self.game.positions(),
+                    limits,
+                    skill,
+                ),
+            }
+        }
+
+        // The external engine's output is read on the next frames
+        if self
+            .engine_player
+            .as_ref()
+            .is_some_and(|player| player.is_thinking())
+        {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+
+        // Block input for the computer's side
+        self.game_view
+            .set_interactive(!self.settings.is_computer(self.game.active_player()));
+    }
+
+    /// Replace the game with the first game of the PGN file, to be reviewed
+    /// or played on between two humans
+    fn load_pgn(&mut self) {
+        let result = std::fs::read_to_string(self.pgn_path.trim())
+            .map_err(|_| "Failed to read PGN file\n")
+            .and_then(|text| read_pgn(&text))
+            .and_then(|games| games.into_iter().next().ok_or("No game in PGN file\n"));
+
+        match result {
+            Ok(pgn) => {
+                self.start_new_game(GameSettings {
+                    opponent: Opponent::Human,
+                    player_color: Color::White,
+                    ..self.settings
+                });
+                self.game = Game::from_pgn(&pgn);
+                self.status_message = Some(format!("Loaded {} moves", pgn.moves.len()));
+            }
+            Err(error) => self.status_message = Some(error.trim().to_string()),
+        }
+    }
+
+    /// Save the position on screen as a PNG in the working directory, seen
+    /// from the same side as the board
+    fn export_position_image(&mut self) {
+        let orientation = if self.game_view.is_flipped() {
+            Color::Black
+        } else {
+            Color::White
+        };
+        let options = RenderOptions {
+            size: 800,
+            orientation,
+            last_move: self.game.displayed_last_move(),
+            ..Default::default()
+        };
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+        let file_name = format!("position_{}.png", timestamp);
+
+        let result =
+            chess_render::render_png(self.game.displayed_board(), &options).and_then(|png| {
+                std::fs::write(&file_name, png).map_err(|_| "Failed to write image file\n")
+            });
+
+        self.status_message = Some(match result {
+            Ok(()) => format!("Saved {}", file_name),
+            Err(error) => error.trim().to_string(),
+        });
     }
 }
 
 // main component for GUI
 impl eframe::App for ChessApp {
     fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        self.poll_starting_engine();
+        self.update_computer(ctx);
+        self.analysis_panel.update(ctx, &self.game);
+        self.hint.update(ctx, &self.game);
+        self.review.update(&mut self.game);
+        self.mate_panel.update();
+        let arrows = self
+            .analysis_panel
+            .best_move_arrow()
+            .into_iter()
+            .chain(self.hint.arrow())
+            .chain(self.review.best_move_arrow(&self.game))
+            .chain(self.mate_panel.key_arrows(&self.game))
+            .collect();
+        self.game_view.set_arrows(arrows);
+        self.game_view.set_hint_square(self.hint.piece_square());
+        self.game_view.set_move_glyphs(self.review.glyphs());
+
         // Calculate move panel width as a fixed size
         let move_panel_width = 150.0;
 
@@ -36,9 +318,66 @@ impl eframe::App for ChessApp {
             .exact_width(move_panel_width)
             .resizable(false)
             .show(ctx, |ui| {
-                self.game_view.draw_move_history(ui, &self.game);
+                // Tablebase results under the move list for covered endgames
+                if self.tablebase_panel.is_active(&self.game) {
+                    TopBottomPanel::bottom("tablebase_panel")
+                        .resizable(false)
+                        .show_inside(ui, |ui| {
+                            self.tablebase_panel.draw(ui, &self.game);
+                        });
+                }
+
+                // Evaluation graph once the game has evaluations
+                if self.eval_graph.is_active(&self.game) {
+                    TopBottomPanel::bottom("eval_graph")
+                        .resizable(false)
+                        .show_inside(ui, |ui| {
+                            self.eval_graph.draw(ui, &mut self.game);
+                        });
+                }
+
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.pgn_path)
+                            .hint_text("PGN file")
+                            .desired_width(90.0),
+                    );
+                    if ui.button("Load").clicked() {
+                        self.load_pgn();
+                    }
+                });
+
+                self.game_view.draw_move_history(ui, &mut self.game);
             });
 
+        // Puzzles, game review, analysis lines and mate search on the left while in use
+        if self.puzzles.is_active()
+            || self.review.is_active()
+            || self.analysis_panel.is_enabled()
+            || self.mate_panel.is_active()
+        {
+            SidePanel::left("analysis_panel")
+                .exact_width(220.0)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    if self.puzzles.is_active() {
+                        self.puzzles.draw(ui, &mut self.game);
+                        ui.add_space(10.0);
+                    }
+                    if self.review.is_active() {
+                        self.review.draw(ui, &mut self.game);
+                        ui.add_space(10.0);
+                    }
+                    if self.analysis_panel.is_enabled() {
+                        self.analysis_panel.draw(ui);
+                        ui.add_space(10.0);
+                    }
+                    if self.mate_panel.is_active() {
+                        self.mate_panel.draw(ui, &self.game);
+                    }
+                });
+        }
+
         // Main board area
         CentralPanel::default().show(ctx, |ui| {
             // Calculate board size based on central panel available space
@@ -55,7 +394,19 @@ impl eframe::App for ChessApp {
                     // Create a horizontal layout that spans the board width
                     ui.allocate_ui(egui::Vec2::new(board_size, 30.0), |ui| {
                         ui.horizontal(|ui| {
-                            ui.label(egui::RichText::new("Opponent").size(18.0));
+                            ui.label(
+                                egui::RichText::new(if self.puzzles.is_active() {
+                                    "Puzzle".to_string()
+                                } else {
+                                    self.settings.opponent_label(self.engine_name())
+                                })
+                                .size(18.0),
+                            );
+
+                            if self.is_thinking() {
+                                ui.add(egui::Spinner::new());
+                                ui.label(egui::RichText::new("Thinking...").size(12.0).weak());
+                            }
 
                             // Push button to the right within the board width
                             ui.with_layout(
@@ -65,9 +416,84 @@ impl eframe::App for ChessApp {
                                         .button(egui::RichText::new("New Game").size(16.0))
                                         .clicked()
                                     {
-                                        self.game.reset();
-                                        self.game_over = None;
-                                        self.game_view.reset();
+                                        self.new_game_dialog.open(self.settings);
+                                    }
+
+                                    if ui
+                                        .button(egui::RichText::new("Engine").size(16.0))
+                                        .on_hover_text(
+                                            "Choose a UCI or XBoard engine to play against",
+                                        )
+                                        .clicked()
+                                    {
+                                        self.engine_dialog.open(
+                                            self.engine_player
+                                                .as_ref()
+                                                .map(|player| player.config()),
+                                        );
+                                    }
+
+                                    let can_hint = self.game_over.is_none()
+                                        && !self.puzzles.is_active()
+                                        && self.game.get_promotion_square().is_none()
+                                        && self.game.viewed_ply().is_none()
+                                        && !self.settings.is_computer(self.game.active_player());
+                                    let hint_button =
+                                        egui::Button::new(egui::RichText::new("Hint").size(16.0));
+                                    if ui
+                                        .add_enabled(can_hint, hint_button)
+                                        .on_hover_text("Show a piece to move, then the move")
+                                        .clicked()
+                                    {
+                                        self.hint.request(&self.game);
+                                    }
+
+                                    let analysing = self.analysis_panel.is_enabled();
+                                    if ui
+                                        .selectable_label(
+                                            analysing,
+                                            egui::RichText::new("Analysis").size(16.0),
+                                        )
+                                        .on_hover_text("Show the best lines for the position")
+                                        .clicked()
+                                    {
+                                        self.analysis_panel.set_enabled(!analysing);
+                                    }
+
+                                    let solving_puzzles = self.puzzles.is_active();
+                                    if ui
+                                        .selectable_label(
+                                            solving_puzzles,
+                                            egui::RichText::new("Puzzles").size(16.0),
+                                        )
+                                        .on_hover_text("Solve puzzles from an imported puzzle file")
+                                        .clicked()
+                                    {
+                                        if solving_puzzles {
+                                            self.start_new_game(self.settings);
+                                        } else {
+                                            self.start_puzzles();
+                                        }
+                                    }
+
+                                    let finding_mate = self.mate_panel.is_active();
+                                    if ui
+                                        .selectable_label(
+                                            finding_mate,
+                                            egui::RichText::new("Find mate").size(16.0),
+                                        )
+                                        .on_hover_text("Solve the position as a mate in N problem")
+                                        .clicked()
+                                    {
+                                        self.mate_panel.set_active(!finding_mate);
+                                    }
+
+                                    if ui
+                                        .button(egui::RichText::new("Export Image").size(16.0))
+                                        .on_hover_text("Export position as image")
+                                        .clicked()
+                                    {
+                                        self.export_position_image();
                                     }
                                 },
                             );
@@ -85,8 +511,20 @@ impl eframe::App for ChessApp {
                     &self.piece_assets,
                 );
 
-                // Check for game over after drawing
-                if self.game_over.is_none() {
+                // Evaluation bar just left of the board
+                let board_rect = egui::Rect::from_center_size(
+                    self.game_view.board_center(),
+                    egui::Vec2::splat(board_size),
+                );
+                let bar_rect = egui::Rect::from_min_max(
+                    egui::Pos2::new(board_rect.min.x - 24.0, board_rect.min.y),
+                    egui::Pos2::new(board_rect.min.x - 8.0, board_rect.max.y),
+                );
+                self.analysis_panel
+                    .draw_eval_bar(ui, bar_rect, self.game_view.is_flipped());
+
+                // Check for game over after drawing, a puzzle ends with its solution
+                if self.game_over.is_none() && !self.puzzles.is_active() {
                     let current_player = self.game.active_player();
                     if self.game.is_checkmated(current_player) {
                         self.game_over = Some(GameResult::Checkmate(current_player.opposite()));
@@ -100,28 +538,52 @@ impl eframe::App for ChessApp {
                 ui.horizontal(|ui| {
                     ui.add_space(left_margin);
                     ui.label(egui::RichText::new("You").size(18.0));
+
+                    if let Some(text) = self.hint.text() {
+                        ui.add_space(10.0);
+                        ui.label(egui::RichText::new(text).size(12.0));
+                    }
+
+                    if let Some(message) = &self.status_message {
+                        ui.add_space(10.0);
+                        ui.label(egui::RichText::new(message).size(12.0).weak());
+                    }
                 });
             });
         });
 
         // Draw victory screen overlay if game is over
-        if let Some(result) = self.game_over {
-            let (should_close, new_game_requested) = self.end_screen.draw(
+        if let Some(result) = self.game_over.filter(|_| !self.end_screen_dismissed) {
+            let action = self.end_screen.draw(
                 ctx,
                 result,
                 self.game_view.board_center(),
                 &self.piece_assets,
             );
 
-            if new_game_requested {
-                self.game.reset();
-                self.game_over = None;
-                self.game_view.reset();
-                self.end_screen.reset();
-            } else if should_close {
-                self.game_over = None;
-                self.end_screen.reset();
+            match action {
+                Some(EndScreenAction::NewGame) => self.start_new_game(self.settings),
+                Some(EndScreenAction::Review) => {
+                    self.review.start(ctx, &self.game);
+                    self.end_screen_dismissed = true;
+                    self.end_screen.reset();
+                }
+                Some(EndScreenAction::Close) => {
+                    self.end_screen_dismissed = true;
+                    self.end_screen.reset();
+                }
+                None => {}
             }
         }
+
+        let board_center = self.game_view.board_center();
+        let engine_name = self.engine_player.as_ref().map(|player| player.name());
+        if let Some(settings) = self.new_game_dialog.draw(ctx, board_center, engine_name) {
+            self.start_new_game(settings);
+        }
+
+        if let Some(action) = self.engine_dialog.draw(ctx, board_center) {
+            self.apply_engine_action(ctx, action);
+        }
     }
 }
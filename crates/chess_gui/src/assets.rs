@@ -11,72 +11,21 @@ impl PieceAssets {
         let mut textures = HashMap::new();
 
         // Load all piece SVGs
-        let pieces = [
-            (
-                Color::White,
-                PieceType::King,
-                include_str!("../../assets/pieces/white_king.svg"),
-            ),
-            (
-                Color::White,
-                PieceType::Queen,
-                include_str!("../../assets/pieces/white_queen.svg"),
-            ),
-            (
-                Color::White,
-                PieceType::Rook,
-                include_str!("../../assets/pieces/white_rook.svg"),
-            ),
-            (
-                Color::White,
-                PieceType::Bishop,
-                include_str!("../../assets/pieces/white_bishop.svg"),
-            ),
-            (
-                Color::White,
-                PieceType::Knight,
-                include_str!("../../assets/pieces/white_knight.svg"),
-            ),
-            (
-                Color::White,
-                PieceType::Pawn,
-                include_str!("../../assets/pieces/white_pawn.svg"),
-            ),
-            (
-                Color::Black,
-                PieceType::King,
-                include_str!("../../assets/pieces/black_king.svg"),
-            ),
-            (
-                Color::Black,
-                PieceType::Queen,
-                include_str!("../../assets/pieces/black_queen.svg"),
-            ),
-            (
-                Color::Black,
-                PieceType::Rook,
-                include_str!("../../assets/pieces/black_rook.svg"),
-            ),
-            (
-                Color::Black,
-                PieceType::Bishop,
-                include_str!("../../assets/pieces/black_bishop.svg"),
-            ),
-            (
-                Color::Black,
-                PieceType::Knight,
-                include_str!("../../assets/pieces/black_knight.svg"),
-            ),
-            (
-                Color::Black,
-                PieceType::Pawn,
-                include_str!("../../assets/pieces/black_pawn.svg"),
-            ),
+        let piece_types = [
+            PieceType::King,
+            PieceType::Queen,
+            PieceType::Rook,
+            PieceType::Bishop,
+            PieceType::Knight,
+            PieceType::Pawn,
         ];
 
-        for (color, piece_type, svg_data) in pieces {
-            if let Some(texture) = Self::svg_to_texture(ctx, svg_data, 128, color, piece_type) {
-                textures.insert((color, piece_type), texture);
+        for color in [Color::White, Color::Black] {
+            for piece_type in piece_types {
+                let svg_data = chess_render::piece_svg(color, piece_type);
+                if let Some(texture) = Self::svg_to_texture(ctx, svg_data, 128, color, piece_type) {
+                    textures.insert((color, piece_type), texture);
+                }
             }
         }
 
@@ -94,20 +43,8 @@ impl PieceAssets {
         color: Color,
         piece_type: PieceType,
     ) -> Option<TextureHandle> {
-        let opt = usvg::Options::default();
-        let tree = usvg::Tree::from_str(svg_data, &opt).ok()?;
-
-        let mut pixmap = tiny_skia::Pixmap::new(size, size)?;
-
         // SVG -> pixmap
-        let tree_size = tree.size();
-        let scale_x = size as f32 / tree_size.width();
-        let scale_y = size as f32 / tree_size.height();
-        let scale = scale_x.min(scale_y);
-
-        let transform = tiny_skia::Transform::from_scale(scale, scale);
-
-        resvg::render(&tree, transform, &mut pixmap.as_mut());
+        let pixmap = chess_render::rasterize_svg(svg_data, size, size).ok()?;
 
         // convert to egui ColorImage
         let pixels = pixmap.data();
@@ -0,0 +1,108 @@
+use chess_core::{Board, Move};
+use chess_engine::{Engine, SearchLimits, SearchResult, Skill, StopHandle};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+/// Runs the built-in engine on a background thread so the UI stays responsive
+pub struct ComputerPlayer {
+    /// The engine while idle, it moves to the search thread while thinking
+    engine: Option<Engine>,
+    stop_handle: StopHandle,
+    receiver: Option<Receiver<(Engine, SearchResult)>>,
+    /// Set when the running search belongs to a game that no longer exists
+    discard_result: bool,
+    /// Set when a new game started while the engine was out searching
+    new_game_pending: bool,
+}
+
+impl Default for ComputerPlayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ComputerPlayer {
+    pub fn new() -> Self {
+        let engine = Engine::new();
+        Self {
+            stop_handle: engine.stop_handle(),
+            engine: Some(engine),
+            receiver: None,
+            discard_result: false,
+            new_game_pending: false,
+        }
+    }
+
+    pub fn is_thinking(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    /// Start searching a position at a skill level. `history` holds the earlier positions of the game
+    pub fn start(
+        &mut self,
+        ctx: &egui::Context,
+        board: &Board,
+        history: &[Board],
+        limits: SearchLimits,
+        skill: Skill,
+    ) {
+        let Some(mut engine) = self.engine.take() else {
+            return;
+        };
+
+        let (sender, receiver) = channel();
+        let board = board.clone();
+        let history = history.to_vec();
+        let ctx = ctx.clone();
+
+        thread::spawn(move || {
+            engine.set_history(&history);
+            engine.set_skill(skill);
+            let result = engine.search(&board, &limits);
+            // The receiver is gone if the app closed, nothing left to do then
+            let _ = sender.send((engine, result));
+            ctx.request_repaint();
+        });
+
+        self.receiver = Some(receiver);
+        self.discard_result = false;
+    }
+
+    /// Get the chosen move once the search is done
+    pub fn poll(&mut self) -> Option<Move> {
+        self.poll_result()?.best_move
+    }
+
+    /// Get the whole search result once the search is done
+    pub fn poll_result(&mut self) -> Option<SearchResult> {
+        let (mut engine, result) = self.receiver.as_ref()?.try_recv().ok()?;
+        if std::mem::take(&mut self.new_game_pending) {
+            engine.new_game();
+        }
+        self.engine = Some(engine);
+        self.receiver = None;
+
+        if std::mem::take(&mut self.discard_result) {
+            // The search may have finished before seeing the stop request
+            self.stop_handle.clear();
+            return None;
+        }
+        Some(result)
+    }
+
+    /// Stop thinking and drop the result, for example when a new game starts
+    pub fn cancel(&mut self) {
+        if self.is_thinking() {
+            self.stop_handle.stop();
+            self.discard_result = true;
+        }
+    }
+
+    /// Forget the previous game, now or once the engine is back from its search
+    pub fn new_game(&mut self) {
+        match &mut self.engine {
+            Some(engine) => engine.new_game(),
+            None => self.new_game_pending = true,
+        }
+    }
+}
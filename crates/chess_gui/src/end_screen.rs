@@ -10,6 +10,14 @@ pub enum GameResult {
     Draw,
 }
 
+/// What the player chose on the end screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndScreenAction {
+    NewGame,
+    Review,
+    Close,
+}
+
 /// Handles the end game victory screen
 pub struct ChessEndScreen {
     window_initialized: bool,
@@ -23,16 +31,15 @@ impl ChessEndScreen {
     }
 
     /// Draw the victory screen
-    /// Returns (should_close, new_game_requested)
+    /// Returns the button pressed, if any
     pub fn draw(
         &mut self,
         ctx: &Context,
         result: GameResult,
         board_center: Pos2,
         piece_assets: &Option<PieceAssets>,
-    ) -> (bool, bool) {
-        let mut should_close = false;
-        let mut new_game_requested = false;
+    ) -> Option<EndScreenAction> {
+        let mut action = None;
         let mut open = true;
 
         let window_size = Vec2::new(300.0, 200.0);
@@ -104,7 +111,7 @@ impl ChessEndScreen {
                 // Buttons - centered
                 ui.horizontal(|ui| {
                     let button_width = 80.0;
-                    let total_width = button_width * 2.0 + ui.spacing().item_spacing.x;
+                    let total_width = button_width * 3.0 + ui.spacing().item_spacing.x * 2.0;
                     let padding = (ui.available_width() - total_width) / 2.0;
                     ui.add_space(padding);
 
@@ -112,24 +119,32 @@ impl ChessEndScreen {
                         .add_sized([button_width, 24.0], egui::Button::new("New Game"))
                         .clicked()
                     {
-                        new_game_requested = true;
+                        action = Some(EndScreenAction::NewGame);
+                    }
+
+                    if ui
+                        .add_sized([button_width, 24.0], egui::Button::new("Review"))
+                        .on_hover_text("Analyse the game move by move")
+                        .clicked()
+                    {
+                        action = Some(EndScreenAction::Review);
                     }
 
                     if ui
                         .add_sized([button_width, 24.0], egui::Button::new("Close"))
                         .clicked()
                     {
-                        should_close = true;
+                        action = Some(EndScreenAction::Close);
                     }
                 });
             });
         });
 
         if !open {
-            should_close = true;
+            action = Some(EndScreenAction::Close);
         }
 
-        (should_close, new_game_requested)
+        action
     }
 
     pub fn reset(&mut self) {
@@ -0,0 +1,303 @@
+use crate::engine_player::EngineConfig;
+use chess_engine::{EngineProtocol, UciOption, UciOptionKind};
+use egui::{Context, Pos2, Vec2};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+/// Name, author and options an engine announces in its handshake
+type EngineDetails = (String, String, Vec<UciOption>);
+
+/// What the user decided in the engine dialog
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineDialogAction {
+    /// Run the engine with these settings
+    Apply(EngineConfig),
+    /// Stop using an external engine
+    Remove,
+}
+
+/// Window to choose a UCI or XBoard engine and edit its options
+pub struct EngineDialog {
+    open: bool,
+    path: String,
+    protocol: EngineProtocol,
+    /// Name, author and options read from the engine at `path`
+    loaded: Option<EngineDetails>,
+    /// Handshake running on a background thread
+    loading: Option<Receiver<Result<EngineDetails, &'static str>>>,
+    /// Set by `open` to load the engine on the next draw
+    load_requested: bool,
+    /// Option values to show once the engine is loaded
+    configured_values: Vec<(String, String)>,
+    /// Current value of each loaded option, empty for buttons
+    values: Vec<String>,
+    error: Option<String>,
+}
+
+impl Default for EngineDialog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EngineDialog {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            path: String::new(),
+            protocol: EngineProtocol::Uci,
+            loaded: None,
+            loading: None,
+            load_requested: false,
+            configured_values: Vec::new(),
+            values: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Show the dialog, starting from the config of the current engine
+    pub fn open(&mut self, config: Option<&EngineConfig>) {
+        self.open = true;
+        self.error = None;
+        self.configured_values.clear();
+        if let Some(config) = config {
+            self.path = config.path.display().to_string();
+            self.protocol = config.protocol;
+            self.load_requested = true;
+            self.configured_values = config.options.clone();
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Start the engine once on a background thread to read its name and options
+    fn load(&mut self, ctx: &Context) {
+        self.loaded = None;
+        self.values.clear();
+        self.error = None;
+
+        let (sender, receiver) = channel();
+        let protocol = self.protocol;
+        let path = self.path.trim().to_string();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            let details = protocol.start(path).map(|engine| {
+                (
+                    engine.name().to_string(),
+                    engine.author().to_string(),
+                    engine.options().to_vec(),
+                )
+            });
+            // The dialog moved on if the receiver is gone
+            let _ = sender.send(details);
+            ctx.request_repaint();
+        });
+        self.loading = Some(receiver);
+    }
+
+    /// Take the name and options once the handshake is done
+    fn poll_loading(&mut self) {
+        let Some(receiver) = &self.loading else {
+            return;
+        };
+        let details = match receiver.try_recv() {
+            Ok(details) => details,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("Failed to start engine\n"),
+        };
+        self.loading = None;
+
+        match details {
+            Ok((name, author, options)) => {
+                self.values = options
+                    .iter()
+                    .map(|option| option.default_value().unwrap_or_default())
+                    .collect();
+                self.loaded = Some((name, author, options));
+                for (name, value) in std::mem::take(&mut self.configured_values) {
+                    self.set_value(&name, value);
+                }
+            }
+            Err(error) => self.error = Some(error.trim().to_string()),
+        }
+    }
+
+    fn set_value(&mut self, name: &str, value: String) {
+        let Some((_, _, options)) = &self.loaded else {
+            return;
+        };
+        if let Some(index) = options.iter().position(|option| option.name == name) {
+            self.values[index] = value;
+        }
+    }
+
+    /// Settings to apply, keeping only options changed from their defaults
+    fn config(&self) -> EngineConfig {
+        let options = match &self.loaded {
+            Some((_, _, options)) => options
+                .iter()
+                .zip(&self.values)
+                .filter(|(option, value)| {
+                    option
+                        .default_value()
+                        .is_some_and(|default| &default != *value)
+                })
+                .map(|(option, value)| (option.name.clone(), value.clone()))
+                .collect(),
+            None => Vec::new(),
+        };
+
+        EngineConfig {
+            path: PathBuf::from(self.path.trim()),
+            protocol: self.protocol,
+            options,
+        }
+    }
+
+    /// Draw the dialog if open
+    /// Returns what to do with the engine once the user decided
+    pub fn draw(&mut self, ctx: &Context, board_center: Pos2) -> Option<EngineDialogAction> {
+        if !self.open {
+            return None;
+        }
+
+        self.poll_loading();
+        let mut action = None;
+        let mut load = std::mem::take(&mut self.load_requested);
+        let mut close = false;
+        let mut open = true;
+
+        let window_size = Vec2::new(360.0, 320.0);
+        egui::Window::new("Engine")
+            .collapsible(false)
+            .open(&mut open)
+            .default_pos(Pos2::new(
+                board_center.x - window_size.x / 2.0,
+                board_center.y - window_size.y / 2.0,
+            ))
+            .default_size(window_size)
+            .show(ctx, |ui| {
+                ui.label("Path of the engine");
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.path);
+                    if ui.button("Load").clicked() {
+                        load = true;
+                        self.configured_values.clear();
+                    }
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Protocol");
+                    for protocol in EngineProtocol::ALL {
+                        if ui
+                            .selectable_value(&mut self.protocol, protocol, protocol.name())
+                            .changed()
+                        {
+                            // Options of the other protocol do not apply
+                            self.loaded = None;
+                            self.loading = None;
+                            self.values.clear();
+                        }
+                    }
+                });
+
+                if let Some(error) = &self.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                if self.loading.is_some() {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label("Starting the engine");
+                    });
+                }
+
+                if let Some((name, author, options)) = &self.loaded {
+                    ui.add_space(8.0);
+                    ui.strong(name);
+                    if !author.is_empty() {
+                        ui.label(egui::RichText::new(format!("by {}", author)).weak());
+                    }
+                    ui.add_space(8.0);
+
+                    egui::ScrollArea::vertical()
+                        .max_height(220.0)
+                        .show(ui, |ui| {
+                            egui::Grid::new("engine_options")
+                                .num_columns(2)
+                                .spacing([12.0, 6.0])
+                                .show(ui, |ui| {
+                                    for (option, value) in options.iter().zip(&mut self.values) {
+                                        // Buttons act on a running engine, nothing to configure
+                                        if option.kind == UciOptionKind::Button {
+                                            continue;
+                                        }
+                                        ui.label(&option.name);
+                                        draw_option(ui, option, value);
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                }
+
+                ui.add_space(12.0);
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(self.loaded.is_some(), egui::Button::new("Apply"))
+                        .clicked()
+                    {
+                        action = Some(EngineDialogAction::Apply(self.config()));
+                    }
+                    if ui.button("Remove Engine").clicked() {
+                        action = Some(EngineDialogAction::Remove);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if load {
+            self.load(ctx);
+        }
+        if action.is_some() || close || !open {
+            self.open = false;
+        }
+        action
+    }
+}
+
+/// Widget editing one option value, kept as the text sent with `setoption`
+fn draw_option(ui: &mut egui::Ui, option: &UciOption, value: &mut String) {
+    match &option.kind {
+        UciOptionKind::Check { .. } => {
+            let mut checked = value == "true";
+            if ui.checkbox(&mut checked, "").changed() {
+                *value = checked.to_string();
+            }
+        }
+        UciOptionKind::Spin { min, max, .. } => {
+            let mut number: i64 = value.parse().unwrap_or(*min);
+            if ui
+                .add(egui::DragValue::new(&mut number).range(*min..=*max))
+                .changed()
+            {
+                *value = number.to_string();
+            }
+        }
+        UciOptionKind::Combo { choices, .. } => {
+            egui::ComboBox::from_id_salt(&option.name)
+                .selected_text(value.as_str())
+                .show_ui(ui, |ui| {
+                    for choice in choices {
+                        ui.selectable_value(value, choice.clone(), choice);
+                    }
+                });
+        }
+        UciOptionKind::String { .. } => {
+            ui.text_edit_singleline(value);
+        }
+        UciOptionKind::Button => {}
+    }
+}
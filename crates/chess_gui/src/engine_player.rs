@@ -0,0 +1,262 @@
+use chess_core::{Board, Game, Move};
+use chess_engine::{
+    EngineProtocol, ExternalEngine, SearchLimits, Skill, UciEvent, UciInfo, UciOptionKind,
+    MAX_SKILL_LEVEL,
+};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+/// Environment variable with the path of an engine to load at startup
+pub const ENGINE_PATH_VAR: &str = "CHESS_ENGINE_PATH";
+/// Environment variable with the protocol of that engine, `uci` or `xboard`
+pub const ENGINE_PROTOCOL_VAR: &str = "CHESS_ENGINE_PROTOCOL";
+
+/// Which engine to run and the options to set on it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EngineConfig {
+    pub path: PathBuf,
+    pub protocol: EngineProtocol,
+    /// Option names and values set after the handshake
+    pub options: Vec<(String, String)>,
+}
+
+impl EngineConfig {
+    /// Config for the engine in `CHESS_ENGINE_PATH`, if set
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var_os(ENGINE_PATH_VAR)?;
+        let protocol = std::env::var(ENGINE_PROTOCOL_VAR)
+            .ok()
+            .and_then(|name| EngineProtocol::from_name(&name))
+            .unwrap_or_default();
+        Some(Self {
+            path: PathBuf::from(path),
+            protocol,
+            options: Vec::new(),
+        })
+    }
+}
+
+/// An engine starting on a background thread, since the handshake can take
+/// seconds and would freeze the UI
+pub struct PendingEngine {
+    receiver: Receiver<Result<EnginePlayer, &'static str>>,
+}
+
+impl PendingEngine {
+    pub fn start(ctx: &egui::Context, config: EngineConfig) -> Self {
+        let (sender, receiver) = channel();
+        let ctx = ctx.clone();
+        thread::spawn(move || {
+            // Without a receiver nobody waits anymore, and dropping the engine quits it
+            let _ = sender.send(EnginePlayer::start(config));
+            ctx.request_repaint();
+        });
+        Self { receiver }
+    }
+
+    /// Get the started engine or the error once the handshake is done
+    pub fn poll(&self) -> Option<Result<EnginePlayer, &'static str>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err("Failed to start engine\n")),
+        }
+    }
+}
+
+/// Plays moves chosen by an external UCI or XBoard engine
+pub struct EnginePlayer {
+    engine: Box<dyn ExternalEngine>,
+    config: EngineConfig,
+    thinking: bool,
+    /// Set when the running search belongs to a position that no longer exists
+    discard_result: bool,
+    last_info: Option<UciInfo>,
+    error: Option<String>,
+    exited: bool,
+    /// Skill last sent to the engine
+    skill: Option<Skill>,
+}
+
+impl EnginePlayer {
+    /// Start the engine and apply the configured options
+    pub fn start(config: EngineConfig) -> Result<Self, &'static str> {
+        let mut engine = config.protocol.start(&config.path)?;
+        for (name, value) in &config.options {
+            engine.set_option(name, Some(value))?;
+        }
+        engine.wait_ready()?;
+
+        Ok(Self {
+            engine,
+            config,
+            thinking: false,
+            discard_result: false,
+            last_info: None,
+            error: None,
+            exited: false,
+            skill: None,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        self.engine.name()
+    }
+
+    pub fn config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    pub fn is_thinking(&self) -> bool {
+        self.thinking
+    }
+
+    /// Check whether the engine process is gone
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    /// Latest search progress of the running or last search
+    pub fn last_info(&self) -> Option<&UciInfo> {
+        self.last_info.as_ref()
+    }
+
+    /// Take the error that stopped the engine, if any
+    pub fn take_error(&mut self) -> Option<String> {
+        self.error.take()
+    }
+
+    fn fail(&mut self, error: &str) {
+        self.thinking = false;
+        self.error = Some(error.trim().to_string());
+    }
+
+    /// Make the engine play at a skill, through its `Skill Level` option or
+    /// else `UCI_LimitStrength` and `UCI_Elo`. Options set in the config win,
+    /// engines with neither option keep playing at full strength
+    pub fn set_skill(&mut self, skill: Skill) {
+        if self.thinking || self.exited || self.skill == Some(skill) {
+            return;
+        }
+        self.skill = Some(skill);
+
+        let mut settings = Vec::new();
+        let options = self.engine.options();
+        let find_spin = |name: &str| {
+            options.iter().find_map(|option| match option.kind {
+                UciOptionKind::Spin { min, max, .. } if option.name == name => Some((min, max)),
+                _ => None,
+            })
+        };
+        let has_limit_strength = options
+            .iter()
+            .any(|option| option.name == "UCI_LimitStrength");
+
+        if let Some((min, max)) = find_spin("Skill Level") {
+            let fraction = skill.level() / MAX_SKILL_LEVEL as f64;
+            let level = min + ((max - min) as f64 * fraction).round() as i64;
+            settings.push(("Skill Level", level.to_string()));
+        } else if let (true, Some((min, max))) = (has_limit_strength, find_spin("UCI_Elo")) {
+            match skill.elo() {
+                Some(elo) => {
+                    settings.push(("UCI_LimitStrength", "true".to_string()));
+                    settings.push(("UCI_Elo", (elo as i64).clamp(min, max).to_string()));
+                }
+                None => settings.push(("UCI_LimitStrength", "false".to_string())),
+            }
+        }
+
+        for (name, value) in settings {
+            if self
+                .config
+                .options
+                .iter()
+                .any(|(configured, _)| configured == name)
+            {
+                continue;
+            }
+            if let Err(error) = self.engine.set_option(name, Some(&value)) {
+                self.exited = true;
+                self.fail(error);
+                return;
+            }
+        }
+    }
+
+    /// Send the game and start searching its current position
+    pub fn start_search(&mut self, game: &Game, limits: &SearchLimits) {
+        if self.thinking || self.exited {
+            return;
+        }
+
+        let result = self
+            .engine
+            .set_game(game)
+            .and_then(|_| self.engine.go(limits));
+        match result {
+            Ok(()) => {
+                self.thinking = true;
+                self.discard_result = false;
+                self.last_info = None;
+            }
+            Err(error) => {
+                self.exited = true;
+                self.fail(error);
+            }
+        }
+    }
+
+    /// Read the engine output and get the chosen move once the search is done
+    pub fn poll(&mut self, board: &Board) -> Option<Move> {
+        loop {
+            match self.engine.poll() {
+                Ok(Some(UciEvent::Info(info))) => {
+                    // Keep the last line with a score, `info string` lines carry none
+                    if info.score.is_some() {
+                        self.last_info = Some(info);
+                    }
+                }
+                Ok(Some(UciEvent::BestMove(best_move))) => {
+                    self.thinking = false;
+                    if std::mem::take(&mut self.discard_result) {
+                        return None;
+                    }
+                    return match best_move.map(|text| board.parse_uci_move(&text)) {
+                        Some(Ok(mv)) => Some(mv),
+                        Some(Err(_)) => {
+                            self.fail("Engine sent an illegal move");
+                            None
+                        }
+                        None => None,
+                    };
+                }
+                Ok(None) => return None,
+                Err(error) => {
+                    if !std::mem::replace(&mut self.exited, true) {
+                        self.fail(error);
+                    }
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Stop thinking and drop the result
+    pub fn cancel(&mut self) {
+        if self.thinking && !self.discard_result {
+            self.discard_result = true;
+            if let Err(error) = self.engine.stop() {
+                self.fail(error);
+            }
+        }
+    }
+
+    /// Tell the engine a new game starts. This does not wait for the engine, so
+    /// the best move of a cancelled search still arrives and gets dropped
+    pub fn new_game(&mut self) {
+        if let Err(error) = self.engine.new_game() {
+            self.fail(error);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use crate::review::{win_percent_for, MoveClass};
+use chess_core::{Color, Game};
+use egui::{Color32, Pos2, Sense, Shape, Stroke, Ui, Vec2};
+
+/// Evaluations beyond this many centipawns, mates included, are drawn at the edge
+const GRAPH_LIMIT: i32 = 1000;
+
+/// Chart of the stored evaluations of a game against the ply, clicking a
+/// point shows the position after that move
+pub struct EvalGraph {
+    height: f32,
+}
+
+/// Known evaluations by ply as clamped centipawns, starting from the even
+/// initial position
+pub fn graph_points(game: &Game) -> Vec<(usize, i32)> {
+    std::iter::once((0, 0))
+        .chain(
+            game.evaluations()
+                .iter()
+                .enumerate()
+                .filter_map(|(index, evaluation)| {
+                    Some((index + 1, (*evaluation)?.clamped_centipawns(GRAPH_LIMIT)))
+                }),
+        )
+        .collect()
+}
+
+impl Default for EvalGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EvalGraph {
+    pub fn new() -> Self {
+        Self { height: 90.0 }
+    }
+
+    /// Whether the game has evaluations to draw
+    pub fn is_active(&self, game: &Game) -> bool {
+        game.evaluations().iter().any(Option::is_some)
+    }
+
+    pub fn draw(&self, ui: &mut Ui, game: &mut Game) {
+        ui.label(egui::RichText::new("Evaluation").strong());
+
+        let size = Vec2::new(ui.available_width(), self.height);
+        let (rect, response) = ui.allocate_exact_size(size, Sense::click());
+        let painter = ui.painter_at(rect);
+
+        let evaluations = game.evaluations();
+        let ply_count = evaluations.len().max(1);
+        let x = |ply: usize| rect.left() + rect.width() * ply as f32 / ply_count as f32;
+        let y = |centipawns: i32| {
+            rect.center().y - rect.height() / 2.0 * centipawns as f32 / GRAPH_LIMIT as f32
+        };
+
+        let points: Vec<(usize, Pos2)> = graph_points(game)
+            .into_iter()
+            .map(|(ply, centipawns)| (ply, Pos2::new(x(ply), y(centipawns))))
+            .collect();
+
+        // Black's side above the line, White's below
+        painter.rect_filled(rect, 2.0, Color32::from_gray(60));
+        for pair in points.windows(2) {
+            let (left, right) = (pair[0].1, pair[1].1);
+            painter.add(Shape::convex_polygon(
+                vec![
+                    left,
+                    right,
+                    Pos2::new(right.x, rect.bottom()),
+                    Pos2::new(left.x, rect.bottom()),
+                ],
+                Color32::from_gray(225),
+                Stroke::NONE,
+            ));
+        }
+        painter.hline(
+            rect.x_range(),
+            rect.center().y,
+            Stroke::new(1.0, Color32::from_gray(140)),
+        );
+        painter.add(Shape::line(
+            points.iter().map(|&(_, point)| point).collect(),
+            Stroke::new(1.5, Color32::from_rgb(90, 130, 200)),
+        ));
+
+        // Position being looked at
+        if let Some(ply) = game.viewed_ply() {
+            painter.vline(
+                x(ply),
+                rect.y_range(),
+                Stroke::new(1.0, Color32::from_rgb(230, 130, 40)),
+            );
+        }
+
+        // Weak moves judged from the evaluations before and after them
+        for pair in points.windows(2) {
+            let ((before_ply, _), (ply, point)) = (pair[0], pair[1]);
+            if before_ply + 1 != ply {
+                continue;
+            }
+            let mover = game.positions()[ply - 1].side_to_move();
+            let before = match ply {
+                1 => 50.0,
+                _ => evaluations[ply - 2]
+                    .map_or(50.0, |evaluation| win_percent_for(evaluation, mover)),
+            };
+            let Some(after) = evaluations[ply - 1] else {
+                continue;
+            };
+            let after = win_percent_for(after, mover);
+            let class = MoveClass::from_win_percents(before, after, false);
+            if class.is_critical() {
+                painter.circle_filled(point, 3.0, class.color());
+            }
+        }
+
+        // Move and evaluation under the pointer, a click jumps there
+        let Some(pointer) = response.hover_pos() else {
+            return;
+        };
+        let ply = (((pointer.x - rect.left()) / rect.width() * ply_count as f32).round() as usize)
+            .min(evaluations.len());
+        let text = match ply.checked_sub(1) {
+            Some(index) => {
+                let board = &game.positions()[index];
+                let dots = match board.side_to_move() {
+                    Color::White => ".",
+                    Color::Black => "...",
+                };
+                let evaluation =
+                    evaluations[index].map_or("?".to_string(), |value| value.to_string());
+                format!(
+                    "{}{} {}  {}",
+                    board.fullmove_number(),
+                    dots,
+                    game.san_history()[index],
+                    evaluation
+                )
+            }
+            None => "Start".to_string(),
+        };
+        painter.vline(
+            x(ply),
+            rect.y_range(),
+            Stroke::new(1.0, Color32::from_gray(160)),
+        );
+        let clicked = response.clicked();
+        response.on_hover_text_at_pointer(text);
+        if clicked {
+            game.set_viewed_ply(Some(ply));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess_core::read_pgn;
+
+    #[test]
+    fn imported_evaluations_are_drawn() {
+        let text = "1. e4 {[%eval 0.35]} f6 2. d4 g5 {[%eval #-3]} 3. Qh5# 1-0\n";
+        let games = read_pgn(text).unwrap();
+        let game = Game::from_pgn(&games[0]);
+        assert_eq!(game.moves().len(), 5);
+        assert!(game.is_checkmated(Color::Black));
+        assert_eq!(graph_points(&game), [(0, 0), (1, 35), (4, -GRAPH_LIMIT)]);
+    }
+}
@@ -1,11 +1,18 @@
 use crate::assets::PieceAssets;
-use chess_core::{Game, Piece, PieceType, Square};
+use chess_core::{Color, Game, Piece, PieceType, Square};
+use chess_render::Arrow;
 use egui::{Align2, Color32, FontId, Pos2, Rect, Sense, Ui, Vec2};
 
 /// Handles the main chess game board and move history display
 pub struct ChessGameView {
     cached_valid_moves: Vec<Square>,
     board_center: Pos2,
+    flipped: bool,
+    interactive: bool,
+    arrows: Vec<Arrow>,
+    /// Glyph and color shown after each move of the history, from a review
+    move_glyphs: Vec<Option<(&'static str, Color32)>>,
+    hint_square: Option<Square>,
 }
 
 impl ChessGameView {
@@ -13,6 +20,11 @@ impl ChessGameView {
         Self {
             cached_valid_moves: Vec::new(),
             board_center: Pos2::new(400.0, 400.0),
+            flipped: false,
+            interactive: true,
+            arrows: Vec::new(),
+            move_glyphs: Vec::new(),
+            hint_square: None,
         }
     }
 
@@ -20,6 +32,44 @@ impl ChessGameView {
         self.board_center
     }
 
+    /// Show the board from Black's side
+    pub fn set_flipped(&mut self, flipped: bool) {
+        self.flipped = flipped;
+    }
+
+    pub fn is_flipped(&self) -> bool {
+        self.flipped
+    }
+
+    /// Allow or block moving pieces, for example while the computer thinks
+    pub fn set_interactive(&mut self, interactive: bool) {
+        self.interactive = interactive;
+    }
+
+    /// Set the arrows drawn over the pieces, for example the best move of the analysis
+    pub fn set_arrows(&mut self, arrows: Vec<Arrow>) {
+        self.arrows = arrows;
+    }
+
+    /// Set the glyphs like `??` marking moves of the history, by move index
+    pub fn set_move_glyphs(&mut self, glyphs: Vec<Option<(&'static str, Color32)>>) {
+        self.move_glyphs = glyphs;
+    }
+
+    /// Highlight the square of a suggested piece
+    pub fn set_hint_square(&mut self, square: Option<Square>) {
+        self.hint_square = square;
+    }
+
+    /// Column and row where a square is drawn, counted from the top-left corner
+    fn display_position(&self, square: Square) -> (u8, u8) {
+        if self.flipped {
+            (7 - square.file(), square.rank())
+        } else {
+            (square.file(), 7 - square.rank())
+        }
+    }
+
     /// Draw the main game area including board and move history
     pub fn draw(
         &mut self,
@@ -50,29 +100,38 @@ impl ChessGameView {
         });
     }
 
-    /// Draw the move history panel
-    pub fn draw_move_history(&self, ui: &mut Ui, game: &Game) {
+    /// Draw the move history panel, clicking a move shows the position after it
+    pub fn draw_move_history(&self, ui: &mut Ui, game: &mut Game) {
         ui.heading("Move History");
         ui.separator();
 
         let scroll_area = egui::ScrollArea::vertical()
             .auto_shrink([false; 2])
             .stick_to_bottom(true);
+        let mut clicked_index = None;
         scroll_area.show(ui, |ui| {
-            let last_move_index = game.move_history().len().saturating_sub(1);
+            // The move leading to the displayed position is shown in bold
+            let last_move_index = game
+                .viewed_ply()
+                .unwrap_or(game.move_history().len())
+                .checked_sub(1);
             let moves = game.move_history();
 
-            // Group moves into pairs (white, black)
-            let mut move_pairs: Vec<(usize, Option<&String>, Option<&String>)> = Vec::new();
-            for i in (0..moves.len()).step_by(2) {
-                let move_number = (i / 2) + 1;
-                let white_move = moves.get(i);
-                let black_move = moves.get(i + 1);
-                move_pairs.push((move_number, white_move, black_move));
+            // Group move indexes into pairs (white, black). A game set up with
+            // Black to move starts with an empty White column
+            let first_position = game.positions().first().unwrap_or(game.board());
+            let offset = usize::from(first_position.side_to_move() == Color::Black);
+            let first_number = first_position.fullmove_number() as usize;
+            let mut move_pairs: Vec<(usize, Option<usize>, Option<usize>)> = Vec::new();
+            for slot in (0..moves.len() + offset).step_by(2) {
+                let move_number = first_number + slot / 2;
+                let white_index = slot.checked_sub(offset);
+                let black_index = Some(slot + 1 - offset).filter(|&index| index < moves.len());
+                move_pairs.push((move_number, white_index, black_index));
             }
 
             // Draw table with subtle styling
-            for (pair_index, (move_number, white_move, black_move)) in
+            for (pair_index, (move_number, white_index, black_index)) in
                 move_pairs.into_iter().enumerate()
             {
                 // Draw horizontal divider above each row except the first
@@ -94,15 +153,24 @@ impl ChessGameView {
 
                     // White move column with fixed width
                     ui.allocate_ui_with_layout(
-                        Vec2::new(40.0, 18.0),
+                        Vec2::new(48.0, 18.0),
                         egui::Layout::right_to_left(egui::Align::Center),
                         |ui| {
-                            if let Some(white_text) = white_move {
-                                let white_index = (move_number - 1) * 2;
-                                let is_last = white_index == last_move_index;
-
+                            if let Some(white_index) = white_index {
+                                let white_text = &moves[white_index];
+                                let is_last = Some(white_index) == last_move_index;
+
+                                let glyph = self.move_glyphs.get(white_index).copied().flatten();
+                                let label = format!(
+                                    "{}{}",
+                                    white_text,
+                                    glyph.map_or("", |(glyph, _)| glyph)
+                                );
                                 let mut text =
-                                    egui::RichText::new(white_text).font(FontId::monospace(12.0));
+                                    egui::RichText::new(label).font(FontId::monospace(12.0));
+                                if let Some((_, color)) = glyph {
+                                    text = text.color(color);
+                                }
                                 if is_last {
                                     text = text.strong();
                                 }
@@ -122,7 +190,7 @@ impl ChessGameView {
                                 }
 
                                 if response.clicked() {
-                                    println!("move {} clicked", white_index);
+                                    clicked_index = Some(white_index);
                                 }
                             }
                         },
@@ -133,15 +201,24 @@ impl ChessGameView {
 
                     // Black move column with fixed width
                     ui.allocate_ui_with_layout(
-                        Vec2::new(40.0, 18.0),
+                        Vec2::new(48.0, 18.0),
                         egui::Layout::left_to_right(egui::Align::Center),
                         |ui| {
-                            if let Some(black_text) = black_move {
-                                let black_index = (move_number - 1) * 2 + 1;
-                                let is_last = black_index == last_move_index;
-
+                            if let Some(black_index) = black_index {
+                                let black_text = &moves[black_index];
+                                let is_last = Some(black_index) == last_move_index;
+
+                                let glyph = self.move_glyphs.get(black_index).copied().flatten();
+                                let label = format!(
+                                    "{}{}",
+                                    black_text,
+                                    glyph.map_or("", |(glyph, _)| glyph)
+                                );
                                 let mut text =
-                                    egui::RichText::new(black_text).font(FontId::monospace(12.0));
+                                    egui::RichText::new(label).font(FontId::monospace(12.0));
+                                if let Some((_, color)) = glyph {
+                                    text = text.color(color);
+                                }
                                 if is_last {
                                     text = text.strong();
                                 }
@@ -161,7 +238,7 @@ impl ChessGameView {
                                 }
 
                                 if response.clicked() {
-                                    println!("move {} clicked", black_index);
+                                    clicked_index = Some(black_index);
                                 }
                             }
                         },
@@ -169,6 +246,10 @@ impl ChessGameView {
                 });
             }
         });
+
+        if let Some(index) = clicked_index {
+            game.set_viewed_ply(Some(index + 1));
+        }
     }
 
     fn draw_board(
@@ -185,15 +266,19 @@ impl ChessGameView {
 
         let board_rect = response.rect;
 
-        // Handle inputs
-        self.handle_input(&response, board_rect, square_size, game);
+        // Handle inputs, earlier positions can only be looked at
+        if self.interactive && game.viewed_ply().is_none() {
+            self.handle_input(&response, board_rect, square_size, game);
+        } else if game.selected_square().is_some() {
+            self.deselect_piece(game);
+        }
 
         // Draw squares
         for rank in 0..8 {
             for file in 0..8 {
                 let square = Square::new(rank, file).unwrap();
-                let display_rank = 7 - rank;
-                let x = board_rect.min.x + file as f32 * square_size;
+                let (display_file, display_rank) = self.display_position(square);
+                let x = board_rect.min.x + display_file as f32 * square_size;
                 let y = board_rect.min.y + display_rank as f32 * square_size;
 
                 let square_rect =
@@ -201,8 +286,9 @@ impl ChessGameView {
 
                 let is_light_square = (rank + file) % 2 == 0;
                 let is_selected = game.selected_square() == Some(square);
+                let is_hint = self.hint_square == Some(square);
                 let is_last_move = game
-                    .last_move()
+                    .displayed_last_move()
                     .map(|(from, to)| from == square || to == square)
                     .unwrap_or(false);
                 let is_valid_move = self.cached_valid_moves.contains(&square);
@@ -213,6 +299,12 @@ impl ChessGameView {
                     } else {
                         Color32::from_rgb(170, 162, 58)
                     }
+                } else if is_hint {
+                    if is_light_square {
+                        Color32::from_rgb(160, 200, 235)
+                    } else {
+                        Color32::from_rgb(95, 150, 200)
+                    }
                 } else if is_last_move {
                     if is_light_square {
                         Color32::from_rgb(205, 210, 106)
@@ -256,13 +348,13 @@ impl ChessGameView {
 
                 // Draw piece if present (and not being dragged)
                 if Some(square) != game.selected_square() || !response.dragged() {
-                    if let Some(piece) = game.get_piece(square) {
+                    if let Some(piece) = game.displayed_board().get_piece(square) {
                         self.draw_piece(&painter, square_rect, piece, piece_assets);
                     }
                 }
 
                 // Draw coordinates
-                if file == 0 {
+                if display_file == 0 {
                     painter.text(
                         Pos2::new(x + 4.0, y + 4.0),
                         Align2::LEFT_TOP,
@@ -276,7 +368,7 @@ impl ChessGameView {
                     );
                 }
 
-                if rank == 0 {
+                if display_rank == 7 {
                     let file_char = (b'a' + file as u8) as char;
                     painter.text(
                         Pos2::new(x + square_size - 4.0, y + square_size - 4.0),
@@ -293,6 +385,10 @@ impl ChessGameView {
             }
         }
 
+        for arrow in &self.arrows {
+            self.draw_arrow(&painter, board_rect, square_size, *arrow);
+        }
+
         // Draw dragged piece
         if let Some(selected) = game.selected_square() {
             if response.dragged() {
@@ -309,6 +405,46 @@ impl ChessGameView {
         board_rect
     }
 
+    /// Draw an arrow between the centers of two squares
+    fn draw_arrow(
+        &self,
+        painter: &egui::Painter,
+        board_rect: Rect,
+        square_size: f32,
+        arrow: Arrow,
+    ) {
+        let center = |square: Square| {
+            let (column, row) = self.display_position(square);
+            board_rect.min + Vec2::new(column as f32 + 0.5, row as f32 + 0.5) * square_size
+        };
+        let (from, to) = (center(arrow.from), center(arrow.to));
+        let length = (to - from).length();
+        if length == 0.0 {
+            return;
+        }
+
+        let color = Color32::from_rgba_unmultiplied(21, 120, 27, 204);
+        let direction = (to - from) / length;
+        let normal = direction.rot90();
+        let head_length = square_size * 0.45;
+        let head_width = square_size * 0.5;
+        let head_base = to - direction * head_length;
+
+        painter.line_segment(
+            [from, head_base],
+            egui::Stroke::new(square_size * 0.18, color),
+        );
+        painter.add(egui::Shape::convex_polygon(
+            vec![
+                to,
+                head_base + normal * head_width / 2.0,
+                head_base - normal * head_width / 2.0,
+            ],
+            color,
+            egui::Stroke::NONE,
+        ));
+    }
+
     fn draw_piece(
         &self,
         painter: &egui::Painter,
@@ -456,9 +592,13 @@ impl ChessGameView {
             return None;
         }
 
-        let file = ((pos.x - board_rect.min.x) / square_size) as u8;
+        let display_file = ((pos.x - board_rect.min.x) / square_size) as u8;
         let display_rank = ((pos.y - board_rect.min.y) / square_size) as u8;
-        let rank = 7 - display_rank;
+        let (rank, file) = if self.flipped {
+            (display_rank, 7u8.checked_sub(display_file)?)
+        } else {
+            (7u8.checked_sub(display_rank)?, display_file)
+        };
 
         Square::new(rank, file).ok()
     }
@@ -484,8 +624,9 @@ impl ChessGameView {
         };
 
         // Calculate position for the promotion UI
-        let file = promotion_square.file() as f32;
-        let display_rank = (7 - promotion_square.rank()) as f32;
+        let (display_file, display_rank) = self.display_position(promotion_square);
+        let file = display_file as f32;
+        let display_rank = display_rank as f32;
 
         // Make promotion squares 10% smaller
         let promo_square_size = square_size * 0.9;
@@ -493,11 +634,11 @@ impl ChessGameView {
 
         // Position the UI above or below the square depending on which side is promoting
         let x = board_rect.min.x + file * square_size + offset;
-        let y = if promotion_square.rank() == 7 {
-            // White promoting at top, show below
+        let y = if display_rank == 0.0 {
+            // Promoting at the top, show below
             board_rect.min.y + display_rank * square_size + square_size + offset
         } else {
-            // Black promoting at bottom, show above
+            // Promoting at the bottom, show above
             board_rect.min.y + display_rank * square_size - promo_square_size * 4.0 - offset
         };
 
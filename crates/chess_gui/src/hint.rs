@@ -0,0 +1,293 @@
+// Suggests a move to the player, shown in two steps: first the piece to move,
+// then where it goes with a one-line reason
+
+use crate::computer_player::ComputerPlayer;
+use chess_core::{type_index, Board, Color, Game, Move, PieceType, Square, SEE_VALUES};
+use chess_engine::{Score, SearchLimits, SearchResult, Skill};
+use chess_render::Arrow;
+use std::time::Duration;
+
+/// How long the engine looks for a hint
+const HINT_TIME: Duration = Duration::from_millis(500);
+/// Material a line has to win to be called winning material
+const WINNING_MARGIN: i32 = 100;
+
+/// How much of the hint is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HintStage {
+    Piece,
+    Destination,
+}
+
+/// A suggested move and why it is good
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    pub mv: Move,
+    pub san: String,
+    pub reason: String,
+}
+
+/// Hint for the position on the board, cleared once a move is made
+pub struct Hint {
+    computer: ComputerPlayer,
+    stage: HintStage,
+    /// Position the hint is for, set from the first request
+    position: Option<Board>,
+    suggestion: Option<Suggestion>,
+}
+
+impl Default for Hint {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hint {
+    pub fn new() -> Self {
+        Self {
+            computer: ComputerPlayer::new(),
+            stage: HintStage::Piece,
+            position: None,
+            suggestion: None,
+        }
+    }
+
+    /// Ask for a hint: the first request shows the piece, the next one the move
+    pub fn request(&mut self, game: &Game) {
+        if self.position.as_ref() != Some(game.board()) {
+            self.clear();
+            self.position = Some(game.board().clone());
+        } else if self.suggestion.is_some() {
+            self.stage = HintStage::Destination;
+        }
+    }
+
+    /// Forget the hint, stopping its search
+    pub fn clear(&mut self) {
+        self.computer.cancel();
+        self.stage = HintStage::Piece;
+        self.position = None;
+        self.suggestion = None;
+    }
+
+    /// Search for a requested hint and drop it once the position changed
+    pub fn update(&mut self, ctx: &egui::Context, game: &Game) {
+        if let Some(result) = self.computer.poll_result() {
+            if let Some(board) = &self.position {
+                self.suggestion = result.best_move.map(|mv| Suggestion {
+                    mv,
+                    san: board.move_to_san(mv),
+                    reason: explain_move(board, mv, &result),
+                });
+                // Nothing to suggest without legal moves
+                if self.suggestion.is_none() {
+                    self.position = None;
+                }
+            }
+        }
+
+        if self
+            .position
+            .as_ref()
+            .is_some_and(|board| board != game.board())
+        {
+            self.clear();
+        }
+
+        // A cancelled search hands its engine back before a new one can start
+        if self.is_searching() && !self.computer.is_thinking() {
+            let limits = SearchLimits {
+                movetime: Some(HINT_TIME),
+                ..Default::default()
+            };
+            self.computer
+                .start(ctx, game.board(), game.positions(), limits, Skill::full());
+        }
+    }
+
+    /// Whether a requested hint is still being searched
+    pub fn is_searching(&self) -> bool {
+        self.position.is_some() && self.suggestion.is_none()
+    }
+
+    /// Square of the piece to move
+    pub fn piece_square(&self) -> Option<Square> {
+        self.suggestion
+            .as_ref()
+            .map(|suggestion| suggestion.mv.from)
+    }
+
+    /// Arrow of the suggested move, once the destination is revealed
+    pub fn arrow(&self) -> Option<Arrow> {
+        let suggestion = self.suggestion.as_ref()?;
+        (self.stage == HintStage::Destination)
+            .then(|| Arrow::new(suggestion.mv.from, suggestion.mv.to))
+    }
+
+    /// One line describing the hint shown so far
+    pub fn text(&self) -> Option<String> {
+        if self.is_searching() {
+            return Some("Looking for a hint...".to_string());
+        }
+        let suggestion = self.suggestion.as_ref()?;
+        Some(match self.stage {
+            HintStage::Piece => {
+                let piece_type = self
+                    .position
+                    .as_ref()
+                    .and_then(|board| board.get_piece(suggestion.mv.from))
+                    .map_or(PieceType::Pawn, |piece| piece.piece_type);
+                format!(
+                    "Hint: move your {}, press Hint again to see where",
+                    piece_name(piece_type)
+                )
+            }
+            HintStage::Destination => format!("Hint: {} {}", suggestion.san, suggestion.reason),
+        })
+    }
+}
+
+fn piece_name(piece_type: PieceType) -> &'static str {
+    match piece_type {
+        PieceType::Pawn => "pawn",
+        PieceType::Knight => "knight",
+        PieceType::Bishop => "bishop",
+        PieceType::Rook => "rook",
+        PieceType::Queen => "queen",
+        PieceType::King => "king",
+    }
+}
+
+fn squares() -> impl Iterator<Item = Square> {
+    (0..8).flat_map(|rank| (0..8).map(move |file| Square::new(rank, file).expect("always valid")))
+}
+
+/// Material of `color` minus the other side's, in the exchange values of the
+/// core so that hints agree with its hanging pieces
+fn material_balance(board: &Board, color: Color) -> i32 {
+    squares()
+        .filter_map(|square| board.get_piece(square))
+        .map(|piece| {
+            let value = SEE_VALUES[type_index(piece.piece_type)];
+            if piece.color == color {
+                value
+            } else {
+                -value
+            }
+        })
+        .sum()
+}
+
+/// Whether `color`, to move again after its move on `board`, could mate at once
+fn threatens_mate(board: &Board, color: Color) -> bool {
+    if board.is_king_in_check(color.opposite()) {
+        return false;
+    }
+    let mut board = board.clone();
+    board.set_side_to_move(color);
+    board.get_all_valid_moves(color).into_iter().any(|mv| {
+        let mut child = board.clone();
+        child.apply_move(mv);
+        child.is_checkmated(color.opposite())
+    })
+}
+
+/// Material won by the line starting with the move, counted after the
+/// opponent's last reply so a capture that gets taken back wins nothing
+fn material_won(board: &Board, pv: &[Move], color: Color) -> i32 {
+    let length = match pv.len() {
+        0 | 1 => pv.len(),
+        length => length - length % 2,
+    };
+    let mut end = board.clone();
+    for &mv in &pv[..length] {
+        end.apply_move(mv);
+    }
+    material_balance(&end, color) - material_balance(board, color)
+}
+
+/// A short reason why a move is good, like "wins material" or "threatens mate"
+pub fn explain_move(board: &Board, mv: Move, result: &SearchResult) -> String {
+    let color = board.side_to_move();
+    let Some(piece) = board.get_piece(mv.from) else {
+        return "is the best move".to_string();
+    };
+    let captured = board.get_piece(mv.to);
+    let mut after = board.clone();
+    after.apply_move(mv);
+
+    if after.is_checkmated(color.opposite()) {
+        return "delivers checkmate".to_string();
+    }
+    if let Score::Mate(moves) = result.score {
+        if moves > 0 {
+            return format!("forces mate in {}", moves);
+        }
+    }
+
+    let pv = if result.pv.first() == Some(&mv) {
+        &result.pv[..]
+    } else {
+        std::slice::from_ref(&mv)
+    };
+    if material_won(board, pv, color) >= WINNING_MARGIN {
+        return "wins material".to_string();
+    }
+    if threatens_mate(&after, color) {
+        return "threatens mate".to_string();
+    }
+
+    // A piece that was hanging and no longer is
+    let still_hanging = after.hanging_pieces(color);
+    for square in board.hanging_pieces(color) {
+        let Some(hanging) = board.get_piece(square) else {
+            continue;
+        };
+        if square == mv.from {
+            if !still_hanging.contains(&mv.to) {
+                return format!("saves the hanging {}", piece_name(hanging.piece_type));
+            }
+        } else if !still_hanging.contains(&square) {
+            return format!("defends the hanging {}", piece_name(hanging.piece_type));
+        }
+    }
+
+    // An enemy piece the moved piece can now win
+    let attacked = after
+        .get_valid_moves(mv.to)
+        .into_iter()
+        .filter_map(|square| after.get_piece(square).map(|target| (square, target)))
+        .filter(|&(_, target)| target.piece_type != PieceType::King)
+        .map(|(square, target)| (after.static_exchange(Move::new(mv.to, square)), target))
+        .filter(|&(gain, _)| gain > 0)
+        .max_by_key(|&(gain, _)| gain);
+    if let Some((_, target)) = attacked {
+        return format!("attacks the {}", piece_name(target.piece_type));
+    }
+
+    if let Some(captured) = captured {
+        return format!("takes the {}", piece_name(captured.piece_type));
+    }
+    if mv.promotion.is_some() {
+        return "promotes the pawn".to_string();
+    }
+    if after.is_king_in_check(color.opposite()) {
+        return "gives check".to_string();
+    }
+    if piece.piece_type == PieceType::King && mv.from.file().abs_diff(mv.to.file()) == 2 {
+        return "castles the king to safety".to_string();
+    }
+    let home_rank = match color {
+        Color::White => 0,
+        Color::Black => 7,
+    };
+    if matches!(piece.piece_type, PieceType::Knight | PieceType::Bishop)
+        && mv.from.rank() == home_rank
+    {
+        return format!("develops the {}", piece_name(piece.piece_type));
+    }
+    if piece.piece_type == PieceType::Pawn && matches!(mv.from.file(), 3 | 4) {
+        return "fights for the center".to_string();
+    }
+    "improves the position".to_string()
+}
@@ -1,7 +1,18 @@
+pub mod analysis_panel;
 pub mod app;
 pub mod assets;
+pub mod computer_player;
 pub mod end_screen;
+pub mod engine_dialog;
+pub mod engine_player;
+pub mod eval_graph;
 pub mod game_view;
+pub mod hint;
+pub mod mate_panel;
+pub mod new_game_dialog;
+pub mod puzzle_panel;
+pub mod review;
+pub mod tablebase_panel;
 
 pub use app::ChessApp;
 pub use assets::PieceAssets;
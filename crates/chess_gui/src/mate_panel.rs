@@ -0,0 +1,208 @@
+// Solves the displayed position as a mate, helpmate, selfmate or reflexmate
+// problem, to check puzzles and compositions: the key moves, cooks and duals,
+// and the solution in problem notation
+
+use crate::analysis_panel::line_to_san;
+use chess_core::{Board, Game};
+use chess_engine::{MateSolution, MateSolver, Stipulation, StopHandle, MAX_MATE_MOVES};
+use chess_render::Arrow;
+use egui::{FontId, Ui};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+/// Most dual lines listed under the solution
+const MAX_DUALS_SHOWN: usize = 5;
+
+/// Panel with the "Find mate" command and its result
+pub struct MatePanel {
+    active: bool,
+    stipulation: Stipulation,
+    moves: u32,
+    stop_handle: Option<StopHandle>,
+    receiver: Option<Receiver<Option<MateSolution>>>,
+    /// Position of the running or last solve
+    position: Option<Board>,
+    solution: Option<MateSolution>,
+    /// Solution tree and dual lines in SAN, written once per solve
+    tree_text: String,
+    dual_lines: Vec<String>,
+}
+
+impl Default for MatePanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MatePanel {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            stipulation: Stipulation::Direct,
+            moves: 2,
+            stop_handle: None,
+            receiver: None,
+            position: None,
+            solution: None,
+            tree_text: String::new(),
+            dual_lines: Vec::new(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Show or hide the panel, hiding it stops a running solve
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+        if !active {
+            self.stop();
+        }
+    }
+
+    fn is_solving(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    fn stop(&mut self) {
+        if let Some(stop_handle) = self.stop_handle.take() {
+            stop_handle.stop();
+        }
+        self.receiver = None;
+    }
+
+    /// Start solving the displayed position
+    fn solve(&mut self, ctx: &egui::Context, game: &Game) {
+        self.stop();
+        let board = game.displayed_board().clone();
+        let stipulation = self.stipulation;
+        let moves = self.moves;
+        let mut solver = MateSolver::new();
+        let (sender, receiver) = channel();
+        let ctx = ctx.clone();
+        self.stop_handle = Some(solver.stop_handle());
+        self.position = Some(board.clone());
+        self.solution = None;
+        thread::spawn(move || {
+            let _ = sender.send(solver.solve(&board, stipulation, moves));
+            ctx.request_repaint();
+        });
+        self.receiver = Some(receiver);
+    }
+
+    /// Read the result of a finished solve
+    pub fn update(&mut self) {
+        let Some(result) = self
+            .receiver
+            .as_ref()
+            .and_then(|receiver| receiver.try_recv().ok())
+        else {
+            return;
+        };
+        if let (Some(solution), Some(board)) = (&result, &self.position) {
+            self.tree_text = solution.to_text(board);
+            self.dual_lines = solution
+                .duals()
+                .iter()
+                .map(|line| line_to_san(board, line))
+                .collect();
+        }
+        self.solution = result;
+        self.stop_handle = None;
+        self.receiver = None;
+    }
+
+    /// Arrows of the key moves while the solved position is shown
+    pub fn key_arrows(&self, game: &Game) -> Vec<Arrow> {
+        let (Some(solution), Some(position)) = (&self.solution, &self.position) else {
+            return Vec::new();
+        };
+        if !self.active || position != game.displayed_board() {
+            return Vec::new();
+        }
+        solution
+            .keys()
+            .into_iter()
+            .map(|mv| Arrow::new(mv.from, mv.to))
+            .collect()
+    }
+
+    pub fn draw(&mut self, ui: &mut Ui, game: &Game) {
+        ui.heading("Find mate");
+        ui.separator();
+
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_salt("stipulation")
+                .width(90.0)
+                .selected_text(self.stipulation.name())
+                .show_ui(ui, |ui| {
+                    for stipulation in Stipulation::ALL {
+                        ui.selectable_value(
+                            &mut self.stipulation,
+                            stipulation,
+                            format!("{} ({}N)", stipulation.name(), stipulation.symbol()),
+                        );
+                    }
+                });
+            ui.label("in");
+            ui.add(egui::DragValue::new(&mut self.moves).range(1..=MAX_MATE_MOVES));
+            if self.is_solving() {
+                if ui.button("Stop").clicked() {
+                    self.stop();
+                }
+            } else if ui
+                .button("Solve")
+                .on_hover_text("Search every line of the shown position for the stipulation")
+                .clicked()
+            {
+                self.solve(ui.ctx(), game);
+            }
+        });
+        ui.add_space(4.0);
+
+        if self.is_solving() {
+            ui.horizontal(|ui| {
+                ui.add(egui::Spinner::new());
+                ui.label(egui::RichText::new("Solving...").weak());
+            });
+            return;
+        }
+        let (Some(solution), Some(board)) = (&self.solution, &self.position) else {
+            return;
+        };
+        if board != game.displayed_board() {
+            ui.label(egui::RichText::new("Solved for another position").weak());
+        }
+
+        if !solution.is_mate() {
+            ui.label(egui::RichText::new(format!("No solution to {}", solution)).strong());
+            return;
+        }
+        let keys: Vec<String> = solution
+            .keys()
+            .into_iter()
+            .map(|mv| board.move_to_san(mv))
+            .collect();
+        ui.label(egui::RichText::new(format!("{}: {}", solution, keys.join(", "))).strong());
+        if solution.stipulation == Stipulation::Help {
+            let count = solution.solutions().len();
+            if count > 1 {
+                ui.label(format!("{} solutions", count));
+            }
+        } else if solution.is_cooked() {
+            ui.label(format!("Cooked: {} key moves", keys.len()));
+        }
+        if !self.dual_lines.is_empty() {
+            ui.label(format!("{} duals, after", self.dual_lines.len()));
+            for line in self.dual_lines.iter().take(MAX_DUALS_SHOWN) {
+                ui.label(egui::RichText::new(line).size(12.0));
+            }
+        }
+        ui.add_space(4.0);
+
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.label(egui::RichText::new(&self.tree_text).font(FontId::monospace(12.0)));
+        });
+    }
+}
@@ -0,0 +1,236 @@
+use chess_core::Color;
+use chess_engine::{SearchLimits, Skill};
+use egui::{Context, Pos2, Vec2};
+use std::time::Duration;
+
+/// Who plays against us
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opponent {
+    Human,
+    /// The built-in engine
+    Computer,
+    /// The external UCI engine chosen in the engine dialog
+    Engine,
+}
+
+/// Strength of the computer player
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Difficulty {
+    Beginner,
+    Easy,
+    Medium,
+    Hard,
+    /// Full strength
+    Master,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 5] = [
+        Difficulty::Beginner,
+        Difficulty::Easy,
+        Difficulty::Medium,
+        Difficulty::Hard,
+        Difficulty::Master,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Beginner => "Beginner",
+            Difficulty::Easy => "Easy",
+            Difficulty::Medium => "Medium",
+            Difficulty::Hard => "Hard",
+            Difficulty::Master => "Master",
+        }
+    }
+
+    /// Skill level the engine plays at
+    pub fn skill(&self) -> Skill {
+        match self {
+            Difficulty::Beginner => Skill::from_level(0),
+            Difficulty::Easy => Skill::from_level(5),
+            Difficulty::Medium => Skill::from_level(10),
+            Difficulty::Hard => Skill::from_level(15),
+            Difficulty::Master => Skill::full(),
+        }
+    }
+
+    /// How long the computer may think per move, weaker skills stop earlier
+    pub fn search_limits(&self) -> SearchLimits {
+        let seconds = match self {
+            Difficulty::Master => 3,
+            _ => 1,
+        };
+        SearchLimits {
+            movetime: Some(Duration::from_secs(seconds)),
+            ..Default::default()
+        }
+    }
+}
+
+/// Options chosen when starting a game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GameSettings {
+    pub opponent: Opponent,
+    /// Color we play, the one shown at the bottom of the board
+    pub player_color: Color,
+    pub difficulty: Difficulty,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            opponent: Opponent::Human,
+            player_color: Color::White,
+            difficulty: Difficulty::Medium,
+        }
+    }
+}
+
+impl GameSettings {
+    /// Check whether the built-in or an external engine plays a color
+    pub fn is_computer(&self, color: Color) -> bool {
+        self.opponent != Opponent::Human && color != self.player_color
+    }
+
+    /// Name shown for the player at the top of the board
+    pub fn opponent_label(&self, engine_name: Option<&str>) -> String {
+        match self.opponent {
+            Opponent::Human => "Opponent".to_string(),
+            Opponent::Computer => format!("Computer ({})", self.difficulty.name()),
+            Opponent::Engine => format!(
+                "{} ({})",
+                engine_name.unwrap_or("Engine"),
+                self.difficulty.name()
+            ),
+        }
+    }
+}
+
+/// Window to choose the opponent, our color and the difficulty of a new game
+pub struct NewGameDialog {
+    open: bool,
+    settings: GameSettings,
+}
+
+impl Default for NewGameDialog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NewGameDialog {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            settings: GameSettings::default(),
+        }
+    }
+
+    /// Show the dialog, starting from the settings of the current game
+    pub fn open(&mut self, settings: GameSettings) {
+        self.open = true;
+        self.settings = settings;
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Draw the dialog if open, offering the external engine when one is loaded
+    /// Returns the settings when a game should start
+    pub fn draw(
+        &mut self,
+        ctx: &Context,
+        board_center: Pos2,
+        engine_name: Option<&str>,
+    ) -> Option<GameSettings> {
+        if !self.open {
+            return None;
+        }
+
+        let mut start = false;
+        let mut cancel = false;
+        let mut open = true;
+
+        let window_size = Vec2::new(380.0, 180.0);
+        egui::Window::new("New Game")
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .default_pos(Pos2::new(
+                board_center.x - window_size.x / 2.0,
+                board_center.y - window_size.y / 2.0,
+            ))
+            .default_size(window_size)
+            .show(ctx, |ui| {
+                let settings = &mut self.settings;
+                if engine_name.is_none() && settings.opponent == Opponent::Engine {
+                    settings.opponent = Opponent::Computer;
+                }
+
+                egui::Grid::new("new_game_settings")
+                    .num_columns(2)
+                    .spacing([12.0, 8.0])
+                    .show(ui, |ui| {
+                        ui.label("Opponent");
+                        ui.horizontal(|ui| {
+                            ui.selectable_value(&mut settings.opponent, Opponent::Human, "Human");
+                            ui.selectable_value(
+                                &mut settings.opponent,
+                                Opponent::Computer,
+                                "Computer",
+                            );
+                            if let Some(engine_name) = engine_name {
+                                ui.selectable_value(
+                                    &mut settings.opponent,
+                                    Opponent::Engine,
+                                    engine_name,
+                                );
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Play as");
+                        ui.horizontal(|ui| {
+                            ui.selectable_value(&mut settings.player_color, Color::White, "White");
+                            ui.selectable_value(&mut settings.player_color, Color::Black, "Black");
+                        });
+                        ui.end_row();
+
+                        ui.label("Difficulty");
+                        ui.add_enabled_ui(settings.opponent != Opponent::Human, |ui| {
+                            ui.horizontal(|ui| {
+                                for difficulty in Difficulty::ALL {
+                                    let hover_text = match difficulty.skill().elo() {
+                                        Some(elo) => format!("Plays at roughly {} Elo", elo),
+                                        None => "Plays at full strength".to_string(),
+                                    };
+                                    ui.selectable_value(
+                                        &mut settings.difficulty,
+                                        difficulty,
+                                        difficulty.name(),
+                                    )
+                                    .on_hover_text(hover_text);
+                                }
+                            });
+                        });
+                        ui.end_row();
+                    });
+
+                ui.add_space(12.0);
+                ui.horizontal(|ui| {
+                    if ui.button("Start").clicked() {
+                        start = true;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+
+        if start || cancel || !open {
+            self.open = false;
+        }
+        start.then_some(self.settings)
+    }
+}
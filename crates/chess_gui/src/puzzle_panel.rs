@@ -0,0 +1,315 @@
+// Tactics training: puzzles imported from a Lichess puzzle CSV are played on
+// the board, the opponent's moves made automatically and each answer checked,
+// with a puzzle rating and solve history kept between sessions
+
+use chess_core::{Color, Game, Puzzle, PuzzleAnswer, PuzzleDatabase, PuzzleHistory};
+use egui::Ui;
+use std::time::{Duration, Instant};
+
+/// Environment variable naming a puzzle CSV file imported at start
+pub const PUZZLE_PATH_VAR: &str = "CHESS_PUZZLE_PATH";
+/// File in the working directory keeping the solve history
+const HISTORY_FILE: &str = "puzzle_history.csv";
+/// Pause before the opponent's moves are played
+const REPLY_DELAY: Duration = Duration::from_millis(500);
+/// Attempts listed under the puzzle
+const SHOWN_ATTEMPTS: usize = 8;
+
+/// Puzzle mode with the puzzle being played
+pub struct PuzzlePanel {
+    active: bool,
+    database: PuzzleDatabase,
+    history: PuzzleHistory,
+    /// CSV file to import, as typed in the panel
+    path: String,
+    message: Option<String>,
+    /// Index of the puzzle being played in the database
+    current: Option<usize>,
+    /// Moves of the puzzle played on the board so far
+    progress: usize,
+    /// Set after the first wrong answer, which counts as a failed attempt
+    failed: bool,
+    solved: bool,
+    /// When the opponent's next move is due
+    reply_at: Option<Instant>,
+    /// Rating change of the last recorded attempt
+    rating_change: Option<i32>,
+}
+
+impl Default for PuzzlePanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PuzzlePanel {
+    pub fn new() -> Self {
+        let loaded = PuzzleHistory::load(HISTORY_FILE);
+        let mut panel = Self {
+            active: false,
+            database: PuzzleDatabase::new(),
+            history: loaded.clone().unwrap_or_default(),
+            path: std::env::var(PUZZLE_PATH_VAR).unwrap_or_default(),
+            message: None,
+            current: None,
+            progress: 0,
+            failed: false,
+            solved: false,
+            reply_at: None,
+            rating_change: None,
+        };
+        if !panel.path.is_empty() {
+            panel.import();
+        }
+        // The history file is there but could not be read
+        if let Err(error) = loaded {
+            panel.message = Some(error.trim().to_string());
+        }
+        panel
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Enter or leave puzzle mode, entering starts the next puzzle
+    pub fn set_active(&mut self, active: bool, game: &mut Game) {
+        self.active = active;
+        self.current = None;
+        if active {
+            self.next_puzzle(game);
+        }
+    }
+
+    fn import(&mut self) {
+        self.message = Some(match self.database.import_file(self.path.trim()) {
+            Ok(0) => format!("{} puzzles", self.database.len()),
+            Ok(skipped) => format!(
+                "{} puzzles, {} invalid lines skipped",
+                self.database.len(),
+                skipped
+            ),
+            Err(error) => error.trim().to_string(),
+        });
+    }
+
+    fn puzzle(&self) -> Option<&Puzzle> {
+        self.database.puzzles().get(self.current?)
+    }
+
+    /// Color the player solves for, to turn the board
+    pub fn solver_color(&self) -> Option<Color> {
+        self.puzzle().map(Puzzle::solver_color)
+    }
+
+    /// Whether the player can move on the board
+    pub fn is_interactive(&self, game: &Game) -> bool {
+        self.puzzle()
+            .is_some_and(|puzzle| game.active_player() == puzzle.solver_color())
+            && !self.solved
+            && self.reply_at.is_none()
+    }
+
+    /// Set up the untried puzzle closest to the player's rating
+    fn next_puzzle(&mut self, game: &mut Game) {
+        let rating = self.history.rating();
+        let next = self
+            .database
+            .next_puzzle(rating, &self.history)
+            .map(|puzzle| puzzle.id.clone());
+        self.current = next.and_then(|id| {
+            self.database
+                .puzzles()
+                .iter()
+                .position(|puzzle| puzzle.id == id)
+        });
+        self.progress = 0;
+        self.failed = false;
+        self.solved = false;
+        self.rating_change = None;
+
+        match self.puzzle() {
+            Some(puzzle) => {
+                *game = Game::from_board(puzzle.board.clone());
+                self.reply_at = Some(Instant::now() + REPLY_DELAY);
+            }
+            None => {
+                self.reply_at = None;
+                if !self.database.is_empty() {
+                    self.message = Some("Every puzzle has been tried".to_string());
+                }
+            }
+        }
+    }
+
+    /// Record the attempt once, at the first wrong answer or the solution
+    fn record(&mut self, solved: bool) {
+        if self.rating_change.is_some() {
+            return;
+        }
+        let Some(puzzle) = self.puzzle().cloned() else {
+            return;
+        };
+        self.rating_change = Some(self.history.record(&puzzle, solved));
+        if let Err(error) = self.history.save(HISTORY_FILE) {
+            self.message = Some(error.trim().to_string());
+        }
+    }
+
+    /// Check the player's moves and play the opponent's replies
+    pub fn update(&mut self, ctx: &egui::Context, game: &mut Game) {
+        let Some(puzzle) = self.puzzle().cloned() else {
+            return;
+        };
+
+        // A move of the player, once its promotion piece is chosen
+        if game.moves().len() > self.progress && game.get_promotion_square().is_none() {
+            let mv = game.moves()[self.progress];
+            match puzzle.check_answer(self.progress, mv) {
+                PuzzleAnswer::Correct => {
+                    self.progress += 1;
+                    self.reply_at = Some(Instant::now() + REPLY_DELAY);
+                }
+                PuzzleAnswer::Solved => {
+                    self.progress += 1;
+                    self.solved = true;
+                    self.record(!self.failed);
+                }
+                PuzzleAnswer::Wrong => {
+                    self.failed = true;
+                    self.record(false);
+                    *game = replay(&puzzle, self.progress);
+                }
+            }
+        }
+
+        match self.reply_at {
+            Some(time) if Instant::now() >= time => {
+                self.reply_at = None;
+                if let Some(&mv) = puzzle.moves.get(self.progress) {
+                    game.execute_move(mv.from, mv.to);
+                    if let Some(promotion) = mv.promotion {
+                        game.promote_square(mv.to, promotion);
+                    }
+                    self.progress += 1;
+                }
+            }
+            Some(time) => ctx.request_repaint_after(time - Instant::now()),
+            None => {}
+        }
+    }
+
+    /// Play the rest of the solution, which fails the puzzle
+    fn show_solution(&mut self, game: &mut Game) {
+        let Some(puzzle) = self.puzzle().cloned() else {
+            return;
+        };
+        self.failed = true;
+        self.record(false);
+        *game = replay(&puzzle, puzzle.moves.len());
+        self.progress = puzzle.moves.len();
+        self.solved = true;
+        self.reply_at = None;
+    }
+
+    pub fn draw(&mut self, ui: &mut Ui, game: &mut Game) {
+        ui.heading("Puzzles");
+        ui.separator();
+
+        let change = self
+            .rating_change
+            .map_or(String::new(), |change| format!(" ({:+})", change));
+        ui.label(
+            egui::RichText::new(format!("Rating {}{}", self.history.rating(), change)).strong(),
+        );
+        ui.label(format!(
+            "{} solved of {} tried",
+            self.history.solved_count(),
+            self.history.attempts().len()
+        ));
+        ui.add_space(4.0);
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::TextEdit::singleline(&mut self.path)
+                    .hint_text("Lichess puzzle CSV")
+                    .desired_width(140.0),
+            );
+            if ui.button("Import").clicked() {
+                self.import();
+                if self.current.is_none() {
+                    self.next_puzzle(game);
+                }
+            }
+        });
+        if let Some(message) = &self.message {
+            ui.label(egui::RichText::new(message).size(12.0).weak());
+        }
+        ui.add_space(6.0);
+
+        if let Some(puzzle) = self.puzzle() {
+            let color = match puzzle.solver_color() {
+                Color::White => "White",
+                Color::Black => "Black",
+            };
+            let status = if self.solved && self.failed {
+                "Solution shown".to_string()
+            } else if self.solved {
+                "Solved!".to_string()
+            } else if self.failed {
+                "Not the best move, try again".to_string()
+            } else if self.progress > 1 {
+                "Correct, keep going".to_string()
+            } else {
+                format!("Find the best move for {}", color)
+            };
+            ui.label(egui::RichText::new(status).strong());
+
+            // Rating and themes would give the answer away before the end
+            if self.solved {
+                ui.label(format!("Puzzle {}, rated {}", puzzle.id, puzzle.rating));
+                if !puzzle.themes.is_empty() {
+                    ui.label(
+                        egui::RichText::new(puzzle.themes.join(", "))
+                            .size(12.0)
+                            .weak(),
+                    );
+                }
+            }
+
+            ui.horizontal(|ui| {
+                if ui.button("Next puzzle").clicked() {
+                    self.next_puzzle(game);
+                }
+                if !self.solved && ui.button("Show solution").clicked() {
+                    self.show_solution(game);
+                }
+            });
+        } else if self.database.is_empty() {
+            ui.label(egui::RichText::new("Import a puzzle file to start").weak());
+        }
+        ui.add_space(6.0);
+
+        egui::Grid::new("puzzle_history").show(ui, |ui| {
+            for attempt in self.history.attempts().iter().rev().take(SHOWN_ATTEMPTS) {
+                ui.label(if attempt.solved { "✔" } else { "✖" });
+                ui.label(&attempt.id);
+                ui.label(attempt.puzzle_rating.to_string());
+                ui.label(attempt.rating.to_string());
+                ui.end_row();
+            }
+        });
+    }
+}
+
+/// A game from the puzzle's position with its first `count` moves played
+fn replay(puzzle: &Puzzle, count: usize) -> Game {
+    let mut game = Game::from_board(puzzle.board.clone());
+    for &mv in puzzle.moves.iter().take(count) {
+        game.execute_move(mv.from, mv.to);
+        if let Some(promotion) = mv.promotion {
+            game.promote_square(mv.to, promotion);
+        }
+    }
+    game
+}
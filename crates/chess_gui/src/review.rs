@@ -0,0 +1,452 @@
+// Post-game review
+//
+// Every position of the finished game is searched in the background. A move
+// is judged by how much it lowered the mover's winning chances compared to
+// the position before it, with winning chances taken from the evaluation as
+// Lichess does: 50 + 50 * (2 / (1 + e^(-0.00368208 * centipawns)) - 1).
+
+use chess_core::{Board, Color, Game, GameEval, Move};
+use chess_engine::{white_score, Engine, Score, SearchLimits, SearchResult, StopHandle};
+use chess_render::Arrow;
+use egui::{Color32, Ui};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// Search of each position of the game
+const REVIEW_DEPTH: u32 = 12;
+const REVIEW_TIME: Duration = Duration::from_millis(300);
+
+/// How good a move was compared to the best one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveClass {
+    Best,
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+    /// A winning position let go
+    MissedWin,
+}
+
+impl MoveClass {
+    pub const ALL: [MoveClass; 6] = [
+        MoveClass::Best,
+        MoveClass::Good,
+        MoveClass::Inaccuracy,
+        MoveClass::Mistake,
+        MoveClass::Blunder,
+        MoveClass::MissedWin,
+    ];
+
+    /// Classify a move by the mover's winning chances in percent before and after it
+    pub fn from_win_percents(before: f64, after: f64, is_best: bool) -> Self {
+        let drop = before - after;
+        if is_best || drop < 2.0 {
+            MoveClass::Best
+        } else if before >= 80.0 && after < 60.0 {
+            MoveClass::MissedWin
+        } else if drop >= 30.0 {
+            MoveClass::Blunder
+        } else if drop >= 20.0 {
+            MoveClass::Mistake
+        } else if drop >= 10.0 {
+            MoveClass::Inaccuracy
+        } else {
+            MoveClass::Good
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MoveClass::Best => "Best",
+            MoveClass::Good => "Good",
+            MoveClass::Inaccuracy => "Inaccuracy",
+            MoveClass::Mistake => "Mistake",
+            MoveClass::Blunder => "Blunder",
+            MoveClass::MissedWin => "Missed win",
+        }
+    }
+
+    /// The class in a sentence, like "a blunder"
+    pub fn verdict(self) -> &'static str {
+        match self {
+            MoveClass::Best => "the best move",
+            MoveClass::Good => "a good move",
+            MoveClass::Inaccuracy => "an inaccuracy",
+            MoveClass::Mistake => "a mistake",
+            MoveClass::Blunder => "a blunder",
+            MoveClass::MissedWin => "a missed win",
+        }
+    }
+
+    /// Annotation glyph written after the move, empty for good moves
+    pub fn glyph(self) -> &'static str {
+        match self {
+            MoveClass::Best | MoveClass::Good => "",
+            MoveClass::Inaccuracy => "?!",
+            MoveClass::Mistake | MoveClass::MissedWin => "?",
+            MoveClass::Blunder => "??",
+        }
+    }
+
+    pub fn color(self) -> Color32 {
+        match self {
+            MoveClass::Best => Color32::from_rgb(92, 160, 70),
+            MoveClass::Good => Color32::from_rgb(140, 170, 120),
+            MoveClass::Inaccuracy => Color32::from_rgb(220, 180, 50),
+            MoveClass::Mistake => Color32::from_rgb(230, 130, 40),
+            MoveClass::Blunder => Color32::from_rgb(210, 60, 50),
+            MoveClass::MissedWin => Color32::from_rgb(150, 90, 200),
+        }
+    }
+
+    /// Whether the move is worth going back to
+    pub fn is_critical(self) -> bool {
+        matches!(
+            self,
+            MoveClass::Mistake | MoveClass::Blunder | MoveClass::MissedWin
+        )
+    }
+}
+
+/// Winning chances in percent of the side a score is for
+pub fn win_percent(score: Score) -> f64 {
+    match score {
+        Score::Centipawns(centipawns) => {
+            let centipawns = centipawns.clamp(-1000, 1000) as f64;
+            50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * centipawns).exp()) - 1.0)
+        }
+        Score::Mate(moves) if moves > 0 => 100.0,
+        // Includes being mated on the board
+        Score::Mate(_) => 0.0,
+    }
+}
+
+/// Evaluation for the game record of a score from the side to move's view
+pub fn game_eval(score: Score, side_to_move: Color) -> GameEval {
+    match white_score(score, side_to_move) {
+        Score::Centipawns(centipawns) => GameEval::Centipawns(centipawns),
+        // Only a checkmated side to move scores a mate in 0
+        Score::Mate(0) => GameEval::Checkmate(side_to_move.opposite()),
+        Score::Mate(moves) => GameEval::Mate(moves),
+    }
+}
+
+/// Winning chances in percent of `color` by an evaluation of the game record
+pub fn win_percent_for(evaluation: GameEval, color: Color) -> f64 {
+    let score = match evaluation {
+        GameEval::Centipawns(centipawns) => Score::Centipawns(centipawns),
+        GameEval::Mate(moves) => Score::Mate(moves),
+        GameEval::Checkmate(winner) if winner == color => return 100.0,
+        GameEval::Checkmate(_) => return 0.0,
+    };
+    match color {
+        Color::White => win_percent(score),
+        Color::Black => win_percent(score.flip()),
+    }
+}
+
+/// Accuracy in percent of a move that dropped the winning chances from `before` to `after`
+pub fn move_accuracy(before: f64, after: f64) -> f64 {
+    let drop = (before - after).max(0.0);
+    (103.1668 * (-0.04354 * drop).exp() - 3.1669).clamp(0.0, 100.0)
+}
+
+/// Review of one move of the game
+#[derive(Debug, Clone)]
+pub struct MoveReview {
+    pub class: MoveClass,
+    pub accuracy: f64,
+    /// Best move of the position the move was played in
+    pub best_move: Option<Move>,
+}
+
+/// Reviews a finished game move by move
+pub struct ReviewPanel {
+    active: bool,
+    receiver: Option<Receiver<SearchResult>>,
+    stop_handle: Option<StopHandle>,
+    /// Positions of the game, the final one included
+    positions: Vec<Board>,
+    moves: Vec<Move>,
+    sans: Vec<String>,
+    results: Vec<SearchResult>,
+    reviews: Vec<MoveReview>,
+}
+
+impl Default for ReviewPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReviewPanel {
+    pub fn new() -> Self {
+        Self {
+            active: false,
+            receiver: None,
+            stop_handle: None,
+            positions: Vec::new(),
+            moves: Vec::new(),
+            sans: Vec::new(),
+            results: Vec::new(),
+            reviews: Vec::new(),
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Whether positions are still being searched
+    pub fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    /// Start reviewing the moves of a game
+    pub fn start(&mut self, ctx: &egui::Context, game: &Game) {
+        self.clear();
+        self.active = true;
+        self.positions = game.positions().clone();
+        self.positions.push(game.board().clone());
+        self.moves = game.moves().clone();
+        self.sans = game.san_history().clone();
+
+        let mut engine = Engine::new();
+        let (sender, receiver) = channel();
+        let positions = self.positions.clone();
+        let ctx = ctx.clone();
+        self.stop_handle = Some(engine.stop_handle());
+        thread::spawn(move || {
+            let limits = SearchLimits {
+                depth: Some(REVIEW_DEPTH),
+                movetime: Some(REVIEW_TIME),
+                ..Default::default()
+            };
+            for (index, board) in positions.iter().enumerate() {
+                engine.set_history(&positions[..index]);
+                let result = engine.search(board, &limits);
+                // The review was closed when nobody listens anymore
+                if sender.send(result).is_err() {
+                    break;
+                }
+                ctx.request_repaint();
+            }
+        });
+        self.receiver = Some(receiver);
+    }
+
+    /// Stop and forget the review
+    pub fn clear(&mut self) {
+        if let Some(stop_handle) = self.stop_handle.take() {
+            stop_handle.stop();
+        }
+        *self = Self::new();
+    }
+
+    /// Read the searched positions, review the moves they complete and
+    /// store their evaluations in the game
+    pub fn update(&mut self, game: &mut Game) {
+        while let Some(result) = self
+            .receiver
+            .as_ref()
+            .and_then(|receiver| receiver.try_recv().ok())
+        {
+            self.results.push(result);
+            let Some(index) = self.results.len().checked_sub(2) else {
+                continue;
+            };
+            self.review_move(index);
+
+            let board = &self.positions[index + 1];
+            if game.moves() == &self.moves {
+                let score = self.results[index + 1].score;
+                game.set_evaluation(index, Some(game_eval(score, board.side_to_move())));
+            }
+        }
+        if self.results.len() == self.positions.len() {
+            self.receiver = None;
+            self.stop_handle = None;
+        }
+    }
+
+    fn review_move(&mut self, index: usize) {
+        let before = &self.results[index];
+        let after = &self.results[index + 1];
+
+        let win_before = win_percent(before.score);
+        // The score after the move is from the opponent's view
+        let win_after = 100.0 - win_percent(after.score);
+        let is_best = before.best_move == Some(self.moves[index]);
+
+        self.reviews.push(MoveReview {
+            class: MoveClass::from_win_percents(win_before, win_after, is_best),
+            accuracy: move_accuracy(win_before, win_after),
+            best_move: before.best_move,
+        });
+    }
+
+    /// Reviews of the moves analysed so far
+    pub fn reviews(&self) -> &[MoveReview] {
+        &self.reviews
+    }
+
+    /// Average accuracy of a player's moves in percent
+    pub fn accuracy(&self, color: Color) -> Option<f64> {
+        let accuracies: Vec<f64> = self
+            .reviews
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| self.positions[*index].side_to_move() == color)
+            .map(|(_, review)| review.accuracy)
+            .collect();
+        (!accuracies.is_empty()).then(|| accuracies.iter().sum::<f64>() / accuracies.len() as f64)
+    }
+
+    /// Glyph and color of each reviewed move, for the move history
+    pub fn glyphs(&self) -> Vec<Option<(&'static str, Color32)>> {
+        self.reviews
+            .iter()
+            .map(|review| {
+                let glyph = review.class.glyph();
+                (!glyph.is_empty()).then_some((glyph, review.class.color()))
+            })
+            .collect()
+    }
+
+    /// Arrow of the better move when looking at the position before a weak one
+    pub fn best_move_arrow(&self, game: &Game) -> Option<Arrow> {
+        let review = self.reviews.get(game.viewed_ply()?)?;
+        if matches!(review.class, MoveClass::Best | MoveClass::Good) {
+            return None;
+        }
+        let best_move = review.best_move?;
+        Some(Arrow::new(best_move.from, best_move.to))
+    }
+
+    /// Move like `12... Qxb2` of a move index
+    fn move_label(&self, index: usize) -> String {
+        let board = &self.positions[index];
+        let dots = match board.side_to_move() {
+            Color::White => ".",
+            Color::Black => "...",
+        };
+        format!(
+            "{}{} {}",
+            board.fullmove_number(),
+            dots,
+            self.sans.get(index).map_or("", String::as_str)
+        )
+    }
+
+    /// Draw accuracies, move counts and the critical moments to jump to
+    pub fn draw(&mut self, ui: &mut Ui, game: &mut Game) {
+        ui.horizontal(|ui| {
+            ui.heading("Game Review");
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui.small_button("Close").clicked() {
+                    self.clear();
+                    game.set_viewed_ply(None);
+                }
+            });
+        });
+        ui.separator();
+        if !self.active {
+            return;
+        }
+
+        if self.is_running() {
+            let progress = self.results.len() as f32 / self.positions.len().max(1) as f32;
+            ui.add(egui::ProgressBar::new(progress).text("Analysing moves..."));
+            ui.add_space(4.0);
+        }
+
+        // Accuracy and move counts for each player
+        egui::Grid::new("review_summary")
+            .num_columns(3)
+            .spacing([12.0, 2.0])
+            .show(ui, |ui| {
+                ui.label("");
+                ui.label(egui::RichText::new("White").strong());
+                ui.label(egui::RichText::new("Black").strong());
+                ui.end_row();
+
+                ui.label("Accuracy");
+                for color in [Color::White, Color::Black] {
+                    let text = self
+                        .accuracy(color)
+                        .map_or("-".to_string(), |accuracy| format!("{:.1}%", accuracy));
+                    ui.label(text);
+                }
+                ui.end_row();
+
+                for class in MoveClass::ALL {
+                    ui.label(egui::RichText::new(class.name()).color(class.color()));
+                    for color in [Color::White, Color::Black] {
+                        let count = self
+                            .reviews
+                            .iter()
+                            .enumerate()
+                            .filter(|(index, review)| {
+                                review.class == class
+                                    && self.positions[*index].side_to_move() == color
+                            })
+                            .count();
+                        ui.label(count.to_string());
+                    }
+                    ui.end_row();
+                }
+            });
+
+        ui.add_space(6.0);
+        ui.label(egui::RichText::new("Critical moments").strong());
+
+        let mut jump_to = None;
+        egui::ScrollArea::vertical()
+            .id_salt("critical_moments")
+            .max_height(200.0)
+            .show(ui, |ui| {
+                for (index, review) in self.reviews.iter().enumerate() {
+                    if !review.class.is_critical() {
+                        continue;
+                    }
+                    let text = egui::RichText::new(format!(
+                        "{}{}  {}",
+                        self.move_label(index),
+                        review.class.glyph(),
+                        review.class.name().to_lowercase()
+                    ))
+                    .color(review.class.color());
+                    let selected = game.viewed_ply() == Some(index);
+                    if ui.selectable_label(selected, text).clicked() {
+                        jump_to = Some(index);
+                    }
+                }
+            });
+        if let Some(index) = jump_to {
+            game.set_viewed_ply(Some(index));
+        }
+
+        // What should have been played in the position looked at
+        if let Some(index) = game.viewed_ply() {
+            if let Some(review) = self.reviews.get(index) {
+                ui.add_space(4.0);
+                let mut text = format!("{} was {}", self.move_label(index), review.class.verdict());
+                if let (Some(best_move), false) = (
+                    review.best_move,
+                    matches!(review.class, MoveClass::Best | MoveClass::Good),
+                ) {
+                    text.push_str(&format!(
+                        ", best was {}",
+                        self.positions[index].move_to_san(best_move)
+                    ));
+                }
+                ui.label(text);
+            }
+            if ui.button("Back to the game").clicked() {
+                game.set_viewed_ply(None);
+            }
+        }
+    }
+}
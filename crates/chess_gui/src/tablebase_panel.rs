@@ -0,0 +1,148 @@
+use chess_core::{Board, Color, Game, SyzygyTablebase, TablebaseMove, Wdl};
+use egui::{FontId, Ui};
+
+/// Environment variable listing the Syzygy directories, separated like `PATH`
+pub const SYZYGY_PATH_VAR: &str = "SYZYGY_PATH";
+
+/// Number of tablebase moves listed under the result
+const SHOWN_MOVES: usize = 5;
+
+/// Shows tablebase results for endgame positions
+pub struct TablebasePanel {
+    tablebase: Option<SyzygyTablebase>,
+    cached_position: Option<Board>,
+    cached_lines: Result<(String, Vec<String>), String>,
+    /// Directories of `SYZYGY_PATH` that could not be read
+    load_error: Option<String>,
+}
+
+impl Default for TablebasePanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TablebasePanel {
+    pub fn new() -> Self {
+        let (tablebase, load_error) = Self::load_tablebase();
+        Self {
+            tablebase,
+            cached_position: None,
+            cached_lines: Err(String::new()),
+            load_error,
+        }
+    }
+
+    /// Load the tables from the directories in `SYZYGY_PATH`, with the
+    /// directories that failed
+    fn load_tablebase() -> (Option<SyzygyTablebase>, Option<String>) {
+        let Some(paths) = std::env::var_os(SYZYGY_PATH_VAR) else {
+            return (None, None);
+        };
+        let mut tablebase = SyzygyTablebase::new();
+        let mut errors = Vec::new();
+        for path in std::env::split_paths(&paths) {
+            if let Err(error) = tablebase.add_directory(&path) {
+                errors.push(format!(
+                    "Failed to read tablebases in {}: {}",
+                    path.display(),
+                    error
+                ));
+            }
+        }
+        let load_error = (!errors.is_empty()).then(|| errors.join("\n"));
+        (
+            (tablebase.max_pieces() > 0).then_some(tablebase),
+            load_error,
+        )
+    }
+
+    /// Check whether the current position can be shown, or there is a
+    /// loading error to show
+    pub fn is_active(&self, game: &Game) -> bool {
+        self.load_error.is_some() || self.covers(game)
+    }
+
+    fn covers(&self, game: &Game) -> bool {
+        match &self.tablebase {
+            Some(tablebase) => {
+                game.get_promotion_square().is_none() && tablebase.covers(game.board())
+            }
+            None => false,
+        }
+    }
+
+    /// Draw the tablebase result and best moves of the current position
+    pub fn draw(&mut self, ui: &mut Ui, game: &Game) {
+        ui.heading("Tablebase");
+        ui.separator();
+
+        if let Some(error) = &self.load_error {
+            ui.colored_label(ui.visuals().error_fg_color, error);
+        }
+        if !self.covers(game) {
+            return;
+        }
+        let Some(tablebase) = &self.tablebase else {
+            return;
+        };
+
+        // Probing reads the table files, so only do it when the position changes
+        let board = game.board();
+        if self.cached_position.as_ref() != Some(board) {
+            self.cached_lines = Self::probe(tablebase, board);
+            self.cached_position = Some(board.clone());
+        }
+
+        match &self.cached_lines {
+            Ok((result, moves)) => {
+                ui.label(egui::RichText::new(result).strong());
+                for line in moves {
+                    ui.label(egui::RichText::new(line).font(FontId::monospace(11.0)));
+                }
+            }
+            Err(error) => {
+                ui.label(egui::RichText::new(error).weak());
+            }
+        }
+    }
+
+    fn probe(tablebase: &SyzygyTablebase, board: &Board) -> Result<(String, Vec<String>), String> {
+        let to_text = |error: &str| error.trim().to_string();
+
+        let wdl = tablebase.probe_wdl(board).map_err(to_text)?;
+        let dtz = tablebase.probe_dtz(board).map_err(to_text)?;
+        let side = match board.side_to_move() {
+            Color::White => "White",
+            Color::Black => "Black",
+        };
+        let result = format!("{}: {}", side, describe(wdl, dtz));
+
+        let moves = tablebase
+            .best_moves(board)
+            .map_err(to_text)?
+            .into_iter()
+            .take(SHOWN_MOVES)
+            .map(|tb_move: TablebaseMove| {
+                format!(
+                    "{:<7} {}",
+                    board.move_to_san(tb_move.mv),
+                    describe(tb_move.wdl, tb_move.dtz)
+                )
+            })
+            .collect();
+
+        Ok((result, moves))
+    }
+}
+
+/// Text for a result like "Win in 23 (DTZ)"
+fn describe(wdl: Wdl, dtz: i32) -> String {
+    match wdl {
+        Wdl::Win => format!("Win in {} (DTZ)", dtz.abs()),
+        Wdl::CursedWin => format!("Cursed win in {} (DTZ)", dtz.abs()),
+        Wdl::Draw => "Draw".to_string(),
+        Wdl::BlessedLoss => format!("Blessed loss in {} (DTZ)", dtz.abs()),
+        Wdl::Loss => format!("Loss in {} (DTZ)", dtz.abs()),
+    }
+}
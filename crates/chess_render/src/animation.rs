@@ -0,0 +1,200 @@
+use crate::options::RenderOptions;
+use crate::png::rasterize_svg;
+use crate::svg::{push_board, svg_header, FONT_FAMILY};
+use chess_core::{Board, Game};
+
+/// How to animate a game
+#[derive(Debug, Clone)]
+pub struct GifOptions {
+    /// Size, orientation, theme and annotations of every frame
+    pub board: RenderOptions,
+    /// Time each position is shown for, in milliseconds
+    pub frame_delay_ms: u32,
+    /// Time the final position is shown for before the animation loops, in milliseconds
+    pub final_delay_ms: u32,
+    /// Highlight the squares of the move leading to each position
+    pub highlight_last_move: bool,
+    /// Show the move leading to each position in SAN below the board
+    pub captions: bool,
+}
+
+impl Default for GifOptions {
+    fn default() -> Self {
+        Self {
+            board: RenderOptions::default(),
+            frame_delay_ms: 800,
+            final_delay_ms: 3000,
+            highlight_last_move: true,
+            captions: true,
+        }
+    }
+}
+
+/// Render a game as an animated GIF with one frame per ply, starting from the initial position
+pub fn render_gif(game: &Game, options: &GifOptions) -> Result<Vec<u8>, &'static str> {
+    let width = options.board.size;
+    let caption_height = if options.captions { width / 10 } else { 0 };
+    let height = width + caption_height;
+
+    if width > u16::MAX as u32 || height > u16::MAX as u32 {
+        return Err("Image too large for a GIF\n");
+    }
+
+    let mut bytes = Vec::new();
+    let mut encoder = gif::Encoder::new(&mut bytes, width as u16, height as u16, &[])
+        .map_err(|_| "Failed to create GIF encoder\n")?;
+    encoder
+        .set_repeat(gif::Repeat::Infinite)
+        .map_err(|_| "Failed to write GIF header\n")?;
+
+    // The positions before each move, then the current one
+    let boards: Vec<&Board> = game
+        .positions()
+        .iter()
+        .chain(std::iter::once(game.board()))
+        .collect();
+    let last_frame = boards.len() - 1;
+
+    for (ply, board) in boards.into_iter().enumerate() {
+        // Every frame but the first shows the move that was just made
+        let played = ply
+            .checked_sub(1)
+            .and_then(|index| Some((game.moves().get(index)?, game.san_history().get(index)?)));
+
+        let mut board_options = options.board.clone();
+        if options.highlight_last_move {
+            board_options.last_move = played.map(|(mv, _)| (mv.from, mv.to));
+        }
+
+        let mut svg = svg_header(width, height);
+        push_board(&mut svg, board, &board_options);
+        if options.captions {
+            let caption = match played {
+                Some((_, san)) => move_caption(ply - 1, san),
+                None => "Start".to_string(),
+            };
+            push_caption(&mut svg, &caption, width, caption_height);
+        }
+        svg.push_str("</svg>\n");
+
+        let mut pixels = rasterize_svg(&svg, width, height)?.take();
+        let mut frame = gif::Frame::from_rgba_speed(width as u16, height as u16, &mut pixels, 10);
+
+        // GIF delays are in hundredths of a second
+        let delay_ms = if ply == last_frame {
+            options.final_delay_ms
+        } else {
+            options.frame_delay_ms
+        };
+        frame.delay = (delay_ms / 10).min(u16::MAX as u32) as u16;
+
+        encoder
+            .write_frame(&frame)
+            .map_err(|_| "Failed to write GIF frame\n")?;
+    }
+
+    drop(encoder);
+    Ok(bytes)
+}
+
+/// Caption for the move at `ply` (0 based), e.g. `1. e4` or `1... e5`
+fn move_caption(ply: usize, san: &str) -> String {
+    let move_number = ply / 2 + 1;
+    if ply.is_multiple_of(2) {
+        format!("{}. {}", move_number, san)
+    } else {
+        format!("{}... {}", move_number, san)
+    }
+}
+
+fn push_caption(svg: &mut String, caption: &str, width: u32, caption_height: u32) {
+    let font_size = caption_height as f32 * 0.6;
+    svg.push_str(&format!(
+        "<rect x=\"0\" y=\"{width}\" width=\"{width}\" height=\"{caption_height}\" fill=\"#302e2b\"/>\n"
+    ));
+    svg.push_str(&format!(
+        "<text x=\"{}\" y=\"{}\" font-family=\"{FONT_FAMILY}\" font-size=\"{font_size}\" \
+         font-weight=\"bold\" text-anchor=\"middle\" fill=\"#ffffff\">{}</text>\n",
+        width as f32 / 2.0,
+        width as f32 + caption_height as f32 / 2.0 + font_size * 0.35,
+        caption
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chess_core::Square;
+
+    fn play(game: &mut Game, moves: &[(&str, &str)]) {
+        for (from, to) in moves {
+            game.execute_move(
+                Square::new_from_notation(from).unwrap(),
+                Square::new_from_notation(to).unwrap(),
+            );
+        }
+    }
+
+    /// Size and frame delays of a GIF
+    fn decode(bytes: &[u8]) -> ((u16, u16), Vec<u16>) {
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(bytes).unwrap();
+        let size = (decoder.width(), decoder.height());
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        (size, delays)
+    }
+
+    #[test]
+    fn frame_per_position() {
+        let mut game = Game::new();
+        play(&mut game, &[("e2", "e4"), ("e7", "e5"), ("g1", "f3")]);
+        let options = GifOptions {
+            board: RenderOptions {
+                size: 80,
+                ..RenderOptions::default()
+            },
+            frame_delay_ms: 500,
+            final_delay_ms: 2000,
+            ..GifOptions::default()
+        };
+
+        let (size, delays) = decode(&render_gif(&game, &options).unwrap());
+        // Captions add a tenth of the height below the board
+        assert_eq!(size, (80, 88));
+        assert_eq!(delays, [50, 50, 50, 200]);
+
+        let options = GifOptions {
+            captions: false,
+            ..options
+        };
+        let (size, delays) = decode(&render_gif(&Game::new(), &options).unwrap());
+        assert_eq!(size, (80, 80));
+        assert_eq!(delays, [200]);
+    }
+
+    #[test]
+    fn captions() {
+        assert_eq!(move_caption(0, "e4"), "1. e4");
+        assert_eq!(move_caption(1, "e5"), "1... e5");
+        assert_eq!(move_caption(4, "Nf3"), "3. Nf3");
+    }
+
+    #[test]
+    fn too_large() {
+        let options = GifOptions {
+            board: RenderOptions {
+                size: 70_000,
+                ..RenderOptions::default()
+            },
+            ..GifOptions::default()
+        };
+        assert_eq!(
+            render_gif(&Game::new(), &options),
+            Err("Image too large for a GIF\n")
+        );
+    }
+}
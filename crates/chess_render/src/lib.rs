@@ -0,0 +1,11 @@
+mod animation;
+mod options;
+mod pieces;
+mod png;
+mod svg;
+
+pub use animation::{render_gif, GifOptions};
+pub use options::{Arrow, RenderOptions, Rgb, Theme};
+pub use pieces::piece_svg;
+pub use png::{rasterize_svg, render_pixmap, render_png};
+pub use svg::render_svg;
@@ -0,0 +1,112 @@
+use chess_core::{Color, Square};
+
+/// An opaque RGB colour
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Format as an SVG/CSS hex colour, e.g. `#eeeed2`
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// Colours used to draw a board diagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub light_square: Rgb,
+    pub dark_square: Rgb,
+    pub highlight_light: Rgb,
+    pub highlight_dark: Rgb,
+    pub check: Rgb,
+    pub arrow: Rgb,
+    pub circle: Rgb,
+}
+
+impl Default for Theme {
+    /// Same colours as the board in the GUI
+    fn default() -> Self {
+        Self {
+            light_square: Rgb(238, 238, 210),
+            dark_square: Rgb(118, 150, 86),
+            highlight_light: Rgb(205, 210, 106),
+            highlight_dark: Rgb(170, 162, 58),
+            check: Rgb(230, 40, 40),
+            arrow: Rgb(21, 120, 27),
+            circle: Rgb(21, 120, 27),
+        }
+    }
+}
+
+/// An arrow drawn from the center of one square to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arrow {
+    pub from: Square,
+    pub to: Square,
+}
+
+impl Arrow {
+    pub fn new(from: Square, to: Square) -> Self {
+        Self { from, to }
+    }
+}
+
+/// What to draw on a board diagram and how
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Width and height of the diagram in pixels
+    pub size: u32,
+    /// Color shown at the bottom of the board
+    pub orientation: Color,
+    /// Draw rank and file labels along the board edges
+    pub coordinates: bool,
+    /// Squares of the last move, highlighted like in the GUI
+    pub last_move: Option<(Square, Square)>,
+    /// Highlight the square of any king that is in check
+    pub highlight_check: bool,
+    pub arrows: Vec<Arrow>,
+    pub circles: Vec<Square>,
+    pub theme: Theme,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            size: 400,
+            orientation: Color::White,
+            coordinates: true,
+            last_move: None,
+            highlight_check: true,
+            arrows: Vec::new(),
+            circles: Vec::new(),
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Top-left corner of a square in diagram coordinates
+    pub(crate) fn square_origin(&self, square: Square) -> (f32, f32) {
+        let square_size = self.square_size();
+        let (display_rank, display_file) = match self.orientation {
+            Color::White => (7 - square.rank(), square.file()),
+            Color::Black => (square.rank(), 7 - square.file()),
+        };
+
+        (
+            display_file as f32 * square_size,
+            display_rank as f32 * square_size,
+        )
+    }
+
+    /// Center of a square in diagram coordinates
+    pub(crate) fn square_center(&self, square: Square) -> (f32, f32) {
+        let (x, y) = self.square_origin(square);
+        let half = self.square_size() / 2.0;
+        (x + half, y + half)
+    }
+
+    pub(crate) fn square_size(&self) -> f32 {
+        self.size as f32 / 8.0
+    }
+}
@@ -0,0 +1,40 @@
+use chess_core::{Color, PieceType};
+
+/// Get the SVG source of the standard piece set for a piece
+pub fn piece_svg(color: Color, piece_type: PieceType) -> &'static str {
+    match color {
+        Color::White => match piece_type {
+            PieceType::King => include_str!("../../assets/pieces/white_king.svg"),
+            PieceType::Queen => include_str!("../../assets/pieces/white_queen.svg"),
+            PieceType::Rook => include_str!("../../assets/pieces/white_rook.svg"),
+            PieceType::Bishop => include_str!("../../assets/pieces/white_bishop.svg"),
+            PieceType::Knight => include_str!("../../assets/pieces/white_knight.svg"),
+            PieceType::Pawn => include_str!("../../assets/pieces/white_pawn.svg"),
+        },
+        Color::Black => match piece_type {
+            PieceType::King => include_str!("../../assets/pieces/black_king.svg"),
+            PieceType::Queen => include_str!("../../assets/pieces/black_queen.svg"),
+            PieceType::Rook => include_str!("../../assets/pieces/black_rook.svg"),
+            PieceType::Bishop => include_str!("../../assets/pieces/black_bishop.svg"),
+            PieceType::Knight => include_str!("../../assets/pieces/black_knight.svg"),
+            PieceType::Pawn => include_str!("../../assets/pieces/black_pawn.svg"),
+        },
+    }
+}
+
+/// Get the drawing content of a piece SVG without its outer `<svg>` element,
+/// so it can be embedded into a larger document
+pub(crate) fn piece_svg_body(color: Color, piece_type: PieceType) -> &'static str {
+    let svg = piece_svg(color, piece_type);
+
+    let start = svg
+        .find("<svg")
+        .and_then(|svg_start| svg[svg_start..].find('>').map(|end| svg_start + end + 1))
+        .unwrap_or(0);
+    let end = svg.rfind("</svg>").unwrap_or(svg.len());
+
+    svg[start..end].trim()
+}
+
+/// Size of the square viewport all piece SVGs are drawn in
+pub(crate) const PIECE_SVG_SIZE: f32 = 45.0;
@@ -0,0 +1,109 @@
+use crate::options::RenderOptions;
+use crate::svg::render_svg;
+use chess_core::Board;
+use std::sync::{Arc, OnceLock};
+use tiny_skia::Pixmap;
+use usvg::fontdb;
+
+/// Render a board as a PNG image
+pub fn render_png(board: &Board, options: &RenderOptions) -> Result<Vec<u8>, &'static str> {
+    render_pixmap(board, options)?
+        .encode_png()
+        .map_err(|_| "Failed to encode PNG image\n")
+}
+
+/// Render a board into an RGBA pixmap of `options.size` pixels
+pub fn render_pixmap(board: &Board, options: &RenderOptions) -> Result<Pixmap, &'static str> {
+    rasterize_svg(&render_svg(board, options), options.size, options.size)
+}
+
+/// Rasterise an SVG document, scaled to fit a `width` x `height` pixmap
+pub fn rasterize_svg(svg_data: &str, width: u32, height: u32) -> Result<Pixmap, &'static str> {
+    let opt = usvg::Options {
+        fontdb: system_fonts(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg_data, &opt).map_err(|_| "Failed to parse SVG\n")?;
+
+    let mut pixmap = Pixmap::new(width, height).ok_or("Invalid image size\n")?;
+
+    // SVG -> pixmap
+    let tree_size = tree.size();
+    let scale_x = width as f32 / tree_size.width();
+    let scale_y = height as f32 / tree_size.height();
+    let scale = scale_x.min(scale_y);
+
+    let transform = tiny_skia::Transform::from_scale(scale, scale);
+
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+    Ok(pixmap)
+}
+
+/// Fonts for text in diagrams, loaded once since scanning the system is slow
+fn system_fonts() -> Arc<fontdb::Database> {
+    static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+
+    FONTS
+        .get_or_init(|| {
+            let mut database = fontdb::Database::new();
+            database.load_system_fonts();
+            Arc::new(database)
+        })
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Theme;
+    use chess_core::{Color, Square};
+
+    #[test]
+    fn png_decodes_at_size() {
+        let options = RenderOptions {
+            size: 120,
+            ..RenderOptions::default()
+        };
+        let png = render_png(&Board::new_starting_default(), &options).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        let pixmap = Pixmap::decode_png(&png).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (120, 120));
+    }
+
+    #[test]
+    fn pixels_follow_orientation() {
+        let e2 = Square::new_from_notation("e2").unwrap();
+        let mut options = RenderOptions {
+            size: 80,
+            coordinates: false,
+            last_move: Some((e2, e2)),
+            ..RenderOptions::default()
+        };
+        let highlight = Theme::default().highlight_light;
+        let color_at = |pixmap: &Pixmap, x: u32, y: u32| {
+            let pixel = pixmap.pixel(x, y).unwrap();
+            (pixel.red(), pixel.green(), pixel.blue())
+        };
+        let expected = (highlight.0, highlight.1, highlight.2);
+
+        // e2 is the fifth column and seventh row seen from White
+        let pixmap = render_pixmap(&Board::new(), &options).unwrap();
+        assert_eq!(color_at(&pixmap, 45, 65), expected);
+        assert_ne!(color_at(&pixmap, 35, 15), expected);
+
+        // And the fourth column and second row seen from Black
+        options.orientation = Color::Black;
+        let pixmap = render_pixmap(&Board::new(), &options).unwrap();
+        assert_eq!(color_at(&pixmap, 35, 15), expected);
+        assert_ne!(color_at(&pixmap, 45, 65), expected);
+    }
+
+    #[test]
+    fn invalid_svg_is_an_error() {
+        assert_eq!(
+            rasterize_svg("<svg", 10, 10).err(),
+            Some("Failed to parse SVG\n")
+        );
+    }
+}
@@ -0,0 +1,293 @@
+use crate::options::{Arrow, RenderOptions};
+use crate::pieces::{piece_svg_body, PIECE_SVG_SIZE};
+use chess_core::{Board, Color, Square};
+
+/// Fonts tried in order for coordinate labels
+pub(crate) const FONT_FAMILY: &str = "Helvetica, Arial, DejaVu Sans, sans-serif";
+
+/// Render a board as a standalone SVG document
+pub fn render_svg(board: &Board, options: &RenderOptions) -> String {
+    let mut svg = svg_header(options.size, options.size);
+    push_board(&mut svg, board, options);
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Opening `<svg>` tag of a document, to be closed by the caller
+pub(crate) fn svg_header(width: u32, height: u32) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" \
+         width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+    )
+}
+
+/// Draw the board and its annotations in the top-left `options.size` square of a document
+pub(crate) fn push_board(svg: &mut String, board: &Board, options: &RenderOptions) {
+    let square_size = options.square_size();
+    let theme = &options.theme;
+
+    // Gradient used to mark a king in check
+    svg.push_str(&format!(
+        "<defs><radialGradient id=\"check\">\
+         <stop offset=\"0%\" stop-color=\"{check}\" stop-opacity=\"1\"/>\
+         <stop offset=\"50%\" stop-color=\"{check}\" stop-opacity=\"0.8\"/>\
+         <stop offset=\"100%\" stop-color=\"{check}\" stop-opacity=\"0\"/>\
+         </radialGradient></defs>\n",
+        check = theme.check.to_hex()
+    ));
+
+    let check_squares = if options.highlight_check {
+        checked_king_squares(board)
+    } else {
+        Vec::new()
+    };
+
+    // Squares and highlights
+    for rank in 0..8 {
+        for file in 0..8 {
+            let square = Square::new(rank, file).expect("always valid");
+            let (x, y) = options.square_origin(square);
+
+            let is_light_square = (rank + file) % 2 == 1;
+            let is_last_move = options
+                .last_move
+                .map(|(from, to)| from == square || to == square)
+                .unwrap_or(false);
+
+            let color = match (is_last_move, is_light_square) {
+                (true, true) => theme.highlight_light,
+                (true, false) => theme.highlight_dark,
+                (false, true) => theme.light_square,
+                (false, false) => theme.dark_square,
+            };
+
+            svg.push_str(&format!(
+                "<rect x=\"{x}\" y=\"{y}\" width=\"{square_size}\" height=\"{square_size}\" fill=\"{}\"/>\n",
+                color.to_hex()
+            ));
+
+            if check_squares.contains(&square) {
+                svg.push_str(&format!(
+                    "<rect x=\"{x}\" y=\"{y}\" width=\"{square_size}\" height=\"{square_size}\" fill=\"url(#check)\"/>\n"
+                ));
+            }
+        }
+    }
+
+    if options.coordinates {
+        push_coordinates(svg, options);
+    }
+
+    for rank in 0..8 {
+        for file in 0..8 {
+            let square = Square::new(rank, file).expect("always valid");
+            if let Some(piece) = board.get_piece(square) {
+                let (x, y) = options.square_origin(square);
+                let scale = square_size / PIECE_SVG_SIZE;
+                svg.push_str(&format!(
+                    "<g transform=\"translate({x} {y}) scale({scale})\">\n{}\n</g>\n",
+                    piece_svg_body(piece.color, piece.piece_type)
+                ));
+            }
+        }
+    }
+
+    // Annotations go on top of the pieces
+    for &square in &options.circles {
+        let (cx, cy) = options.square_center(square);
+        svg.push_str(&format!(
+            "<circle cx=\"{cx}\" cy=\"{cy}\" r=\"{}\" fill=\"none\" stroke=\"{}\" \
+             stroke-width=\"{}\" opacity=\"0.8\"/>\n",
+            square_size * 0.45,
+            theme.circle.to_hex(),
+            square_size * 0.07
+        ));
+    }
+
+    for arrow in &options.arrows {
+        push_arrow(svg, arrow, options);
+    }
+}
+
+fn checked_king_squares(board: &Board) -> Vec<Square> {
+    [Color::White, Color::Black]
+        .into_iter()
+        .filter_map(|color| {
+            let king_square = board.find_king_square(color)?;
+            board.is_king_in_check(color).then_some(king_square)
+        })
+        .collect()
+}
+
+fn push_coordinates(svg: &mut String, options: &RenderOptions) {
+    let square_size = options.square_size();
+    let font_size = square_size * 0.2;
+    let padding = square_size * 0.06;
+    let theme = &options.theme;
+
+    // Labels use the colour of the opposite square so they stay readable
+    let label_color = |square: Square| {
+        if (square.rank() + square.file()) % 2 == 1 {
+            theme.dark_square.to_hex()
+        } else {
+            theme.light_square.to_hex()
+        }
+    };
+
+    for index in 0..8u8 {
+        // Rank labels on the left edge, file labels on the bottom edge
+        let (rank_square, file_square) = match options.orientation {
+            Color::White => (
+                Square::new(index, 0).expect("always valid"),
+                Square::new(0, index).expect("always valid"),
+            ),
+            Color::Black => (
+                Square::new(index, 7).expect("always valid"),
+                Square::new(7, index).expect("always valid"),
+            ),
+        };
+
+        let (x, y) = options.square_origin(rank_square);
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"{FONT_FAMILY}\" font-size=\"{font_size}\" \
+             font-weight=\"bold\" text-anchor=\"start\" fill=\"{}\">{}</text>\n",
+            x + padding,
+            y + padding + font_size * 0.8,
+            label_color(rank_square),
+            rank_square.rank() + 1
+        ));
+
+        let (x, y) = options.square_origin(file_square);
+        svg.push_str(&format!(
+            "<text x=\"{}\" y=\"{}\" font-family=\"{FONT_FAMILY}\" font-size=\"{font_size}\" \
+             font-weight=\"bold\" text-anchor=\"end\" fill=\"{}\">{}</text>\n",
+            x + square_size - padding,
+            y + square_size - padding,
+            label_color(file_square),
+            (b'a' + file_square.file()) as char
+        ));
+    }
+}
+
+fn push_arrow(svg: &mut String, arrow: &Arrow, options: &RenderOptions) {
+    let square_size = options.square_size();
+    let (x1, y1) = options.square_center(arrow.from);
+    let (x2, y2) = options.square_center(arrow.to);
+
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length == 0.0 {
+        return;
+    }
+    let (ux, uy) = (dx / length, dy / length);
+
+    let head_length = square_size * 0.45;
+    let head_width = square_size * 0.5;
+    let shaft_width = square_size * 0.18;
+
+    // Shaft stops where the head begins so the tips don't overlap
+    let (shaft_x, shaft_y) = (x2 - ux * head_length, y2 - uy * head_length);
+    let (px, py) = (-uy * head_width / 2.0, ux * head_width / 2.0);
+    let color = options.theme.arrow.to_hex();
+
+    svg.push_str(&format!(
+        "<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{shaft_x}\" y2=\"{shaft_y}\" stroke=\"{color}\" \
+         stroke-width=\"{shaft_width}\" opacity=\"0.8\"/>\n"
+    ));
+    svg.push_str(&format!(
+        "<polygon points=\"{x2},{y2} {},{} {},{}\" fill=\"{color}\" opacity=\"0.8\"/>\n",
+        shaft_x + px,
+        shaft_y + py,
+        shaft_x - px,
+        shaft_y - py
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Theme;
+
+    fn square(notation: &str) -> Square {
+        Square::new_from_notation(notation).unwrap()
+    }
+
+    #[test]
+    fn document_structure() {
+        let svg = render_svg(&Board::new_starting_default(), &RenderOptions::default());
+        assert!(svg.starts_with(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" \
+             width=\"400\" height=\"400\" viewBox=\"0 0 400 400\">\n"
+        ));
+        assert!(svg.ends_with("</svg>\n"));
+        // A square per rect and a group per piece
+        assert_eq!(svg.matches("<rect ").count(), 64);
+        assert_eq!(svg.matches("<g transform=").count(), 32);
+        // Eight rank and eight file labels
+        assert_eq!(svg.matches("<text ").count(), 16);
+
+        let options = RenderOptions {
+            coordinates: false,
+            ..RenderOptions::default()
+        };
+        let svg = render_svg(&Board::new(), &options);
+        assert_eq!(svg.matches("<g transform=").count(), 0);
+        assert_eq!(svg.matches("<text ").count(), 0);
+    }
+
+    #[test]
+    fn orientation() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+        let white = render_svg(&board, &RenderOptions::default());
+        // e1 at the bottom and e8 at the top seen from White
+        assert!(white.contains("translate(200 350)"));
+        assert!(white.contains("translate(200 0)"));
+        assert!(white.contains(">a</text>"));
+
+        let flipped = RenderOptions {
+            orientation: Color::Black,
+            ..RenderOptions::default()
+        };
+        let black = render_svg(&board, &flipped);
+        // Mirrored on both axes seen from Black
+        assert!(black.contains("translate(150 0)"));
+        assert!(black.contains("translate(150 350)"));
+        assert_eq!(flipped.square_origin(square("h1")), (0.0, 0.0));
+        assert_eq!(flipped.square_origin(square("a8")), (350.0, 350.0));
+        assert_eq!(
+            RenderOptions::default().square_origin(square("a8")),
+            (0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn annotations() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/4K2R b - - 0 1").unwrap();
+        let options = RenderOptions {
+            last_move: Some((square("h1"), square("h8"))),
+            arrows: vec![Arrow::new(square("e2"), square("e4"))],
+            circles: vec![square("d5")],
+            ..RenderOptions::default()
+        };
+        let svg = render_svg(&board, &options);
+        assert_eq!(svg.matches("<circle ").count(), 1);
+        assert_eq!(svg.matches("<polygon ").count(), 1);
+        assert_eq!(svg.matches("fill=\"url(#check)\"").count(), 0);
+
+        let board = Board::from_fen("4k2R/8/8/8/8/8/8/4K3 b - - 0 1").unwrap();
+        let svg = render_svg(&board, &options);
+        // The king in check gets the gradient on e8
+        assert!(svg
+            .contains("<rect x=\"200\" y=\"0\" width=\"50\" height=\"50\" fill=\"url(#check)\"/>"));
+        // Last move squares use the highlight colours
+        let theme = Theme::default();
+        assert!(svg.contains(&format!(
+            "<rect x=\"350\" y=\"350\" width=\"50\" height=\"50\" fill=\"{}\"/>",
+            theme.highlight_light.to_hex()
+        )));
+        assert!(svg.contains(&format!(
+            "<rect x=\"350\" y=\"0\" width=\"50\" height=\"50\" fill=\"{}\"/>",
+            theme.highlight_dark.to_hex()
+        )));
+    }
+}
@@ -0,0 +1,235 @@
+// Plays two engines against each other to measure the strength of a change,
+// with options in the style of cutechess-cli:
+//
+// `chess-match -engine builtin name=base -engine cmd=./new proto=uci option.Hash=64
+//  -openings book.epd -games 200 -tc 10+0.1 -concurrency 2 -pgnout games.pgn
+//  -sprt elo0=0 elo1=5 alpha=0.05 beta=0.05 -resign movecount=3 score=600
+//  -draw movenumber=40 movecount=8 score=10 -maxmoves 200 -tb /path/to/syzygy`
+
+use chess_core::SyzygyTablebase;
+use chess_engine::engine_match::{
+    self, Adjudication, DrawRule, MatchConfig, PlayerConfig, ResignRule, TimeControl,
+};
+use chess_engine::{EngineProtocol, SearchLimits, Sprt};
+use std::time::Duration;
+
+/// Games of a match without `-games`
+const DEFAULT_GAMES: u32 = 100;
+/// Games of an SPRT without `-games`, which usually decides long before
+const DEFAULT_SPRT_GAMES: u32 = 1_000_000;
+
+const USAGE: &str =
+    "Usage: chess-match -engine builtin|cmd=<path> [proto=uci|xboard] [name=<name>] \
+[option.<name>=<value>]... -engine ... [-openings <file.epd|file.pgn>] [-games <n>] \
+[-tc [<moves>/]<seconds>[+<increment>] | -st <seconds> | -depth <n> | -nodes <n>] \
+[-concurrency <n>] [-pgnout <file>] [-sprt elo0=<elo> elo1=<elo> alpha=<a> beta=<b>] \
+[-resign movecount=<n> score=<cp>] [-draw movenumber=<n> movecount=<n> score=<cp>] \
+[-maxmoves <n>] [-tb <syzygy directory>]";
+
+fn main() {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let config = match parse_arguments(&arguments) {
+        Ok(config) => config,
+        Err(error) => {
+            eprint!("{}", error);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(error) = engine_match::run(&config, &mut std::io::stdout()) {
+        eprint!("{}", error);
+        std::process::exit(1);
+    }
+}
+
+/// A number from an option value
+fn number<T: std::str::FromStr>(value: &str) -> Result<T, &'static str> {
+    value.parse().map_err(|_| "Option values must be numbers\n")
+}
+
+/// The `key=value` pairs given to an option
+fn pairs(values: &[&str]) -> Result<Vec<(String, String)>, &'static str> {
+    values
+        .iter()
+        .map(|value| {
+            value
+                .split_once('=')
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .ok_or("Expected key=value\n")
+        })
+        .collect()
+}
+
+fn parse_engine(values: &[&str]) -> Result<PlayerConfig, &'static str> {
+    let mut player = None;
+    let mut protocol = EngineProtocol::Uci;
+    let mut name = None;
+    let mut options = Vec::new();
+    for &value in values {
+        if value == "builtin" {
+            player = Some(PlayerConfig::built_in());
+            continue;
+        }
+        let (key, value) = value.split_once('=').ok_or("Unknown engine setting\n")?;
+        match key {
+            "cmd" => player = Some(PlayerConfig::external(value, protocol)),
+            "proto" => {
+                protocol = EngineProtocol::from_name(value).ok_or("Unknown engine protocol\n")?
+            }
+            "name" => name = Some(value.to_string()),
+            _ => match key.strip_prefix("option.") {
+                Some(option) => options.push((option.to_string(), value.to_string())),
+                None => return Err("Unknown engine setting\n"),
+            },
+        }
+    }
+
+    let mut player = player.ok_or("Engines need builtin or cmd=<path>\n")?;
+    // The protocol may come after the command
+    if let Some((_, engine_protocol)) = &mut player.command {
+        *engine_protocol = protocol;
+    }
+    if let Some(name) = name {
+        player.name = name;
+    }
+    player.options = options;
+    Ok(player)
+}
+
+fn parse_arguments(arguments: &[String]) -> Result<MatchConfig, &'static str> {
+    let mut players = Vec::new();
+    let mut openings = Vec::new();
+    let mut games = None;
+    let mut time_control = TimeControl::parse("10+0.1")?;
+    let mut adjudication = Adjudication::default();
+    let mut concurrency = 1;
+    let mut sprt = None;
+    let mut pgn_path = None;
+
+    let mut index = 0;
+    while index < arguments.len() {
+        let option = arguments[index].as_str();
+        index += 1;
+        let mut values = Vec::new();
+        while index < arguments.len() && !arguments[index].starts_with('-') {
+            values.push(arguments[index].as_str());
+            index += 1;
+        }
+        let single = || match values[..] {
+            [value] => Ok(value),
+            _ => Err("Option needs one value\n"),
+        };
+
+        match option {
+            "-engine" => players.push(parse_engine(&values)?),
+            "-openings" => {
+                let path = single()?;
+                // cutechess-cli style `file=<path>` is accepted too
+                let path = path.strip_prefix("file=").unwrap_or(path);
+                openings = engine_match::load_openings(path)?;
+            }
+            "-games" => games = Some(number(single()?)?),
+            "-concurrency" => concurrency = number(single()?)?,
+            "-pgnout" => pgn_path = Some(single()?.into()),
+            "-tc" => time_control = TimeControl::parse(single()?)?,
+            "-st" => {
+                let seconds: f64 = number(single()?)?;
+                if !seconds.is_finite() || seconds <= 0.0 {
+                    return Err("Move time must be positive\n");
+                }
+                time_control = TimeControl::PerMove(SearchLimits {
+                    movetime: Some(Duration::from_secs_f64(seconds)),
+                    ..Default::default()
+                });
+            }
+            "-depth" => {
+                time_control = TimeControl::PerMove(SearchLimits {
+                    depth: Some(number(single()?)?),
+                    ..Default::default()
+                })
+            }
+            "-nodes" => {
+                time_control = TimeControl::PerMove(SearchLimits {
+                    nodes: Some(number(single()?)?),
+                    ..Default::default()
+                })
+            }
+            "-sprt" => {
+                let mut test = Sprt::default();
+                for (key, value) in pairs(&values)? {
+                    let value = number(&value)?;
+                    match key.as_str() {
+                        "elo0" => test.elo0 = value,
+                        "elo1" => test.elo1 = value,
+                        "alpha" => test.alpha = value,
+                        "beta" => test.beta = value,
+                        _ => return Err("Unknown SPRT setting\n"),
+                    }
+                }
+                sprt = Some(Sprt::new(test.elo0, test.elo1, test.alpha, test.beta)?);
+            }
+            "-resign" => {
+                let mut rule = ResignRule {
+                    moves: 3,
+                    score: 600,
+                };
+                for (key, value) in pairs(&values)? {
+                    match key.as_str() {
+                        "movecount" => rule.moves = number(&value)?,
+                        "score" => rule.score = number(&value)?,
+                        _ => return Err("Unknown resign setting\n"),
+                    }
+                }
+                adjudication.resign = Some(rule);
+            }
+            "-draw" => {
+                let mut rule = DrawRule {
+                    move_number: 40,
+                    moves: 8,
+                    score: 10,
+                };
+                for (key, value) in pairs(&values)? {
+                    match key.as_str() {
+                        "movenumber" => rule.move_number = number(&value)?,
+                        "movecount" => rule.moves = number(&value)?,
+                        "score" => rule.score = number(&value)?,
+                        _ => return Err("Unknown draw setting\n"),
+                    }
+                }
+                adjudication.draw = Some(rule);
+            }
+            "-maxmoves" => adjudication.max_moves = Some(number(single()?)?),
+            "-tb" => {
+                let mut tablebase = SyzygyTablebase::new();
+                let found = tablebase
+                    .add_directory(single()?)
+                    .map_err(|_| "Failed to read tablebase directory\n")?;
+                if found == 0 {
+                    return Err("No tablebase files in the directory\n");
+                }
+                adjudication.tablebase = Some(tablebase);
+            }
+            _ => return Err("Unknown option\n"),
+        }
+    }
+
+    let players: [PlayerConfig; 2] = players
+        .try_into()
+        .map_err(|_| "A match needs two engines\n")?;
+    let default_games = if sprt.is_some() {
+        DEFAULT_SPRT_GAMES
+    } else {
+        DEFAULT_GAMES
+    };
+    Ok(MatchConfig {
+        players,
+        openings,
+        games: games.unwrap_or(default_games),
+        time_control,
+        adjudication,
+        concurrency,
+        sprt,
+        pgn_path,
+    })
+}
@@ -0,0 +1,46 @@
+// Generates endgame tables with distance to mate by retrograde analysis and
+// saves them to a directory, together with the smaller tables they need.
+//
+// `chess-tablegen <directory> KQvK KRvKP ...`
+
+use chess_core::EndgameTables;
+use std::time::Instant;
+
+fn main() {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let Some((directory, names)) = arguments.split_first() else {
+        eprintln!("Usage: chess-tablegen <directory> <material>...");
+        std::process::exit(2);
+    };
+
+    let mut tables = EndgameTables::new();
+    // Tables saved earlier are reused instead of generated again
+    let _ = tables.add_directory(directory);
+
+    for name in names {
+        let start = Instant::now();
+        if let Err(error) = tables.generate(name) {
+            eprint!("{}: {}", name, error);
+            std::process::exit(1);
+        }
+        if let Some(summary) = tables.summary(name) {
+            println!(
+                "{}: {} wins, {} draws, {} losses, longest mate {} moves ({:.1}s)",
+                name,
+                summary.wins,
+                summary.draws,
+                summary.losses,
+                summary.longest_mate.div_ceil(2),
+                start.elapsed().as_secs_f64()
+            );
+        }
+    }
+
+    match tables.save_directory(directory) {
+        Ok(count) => println!("Saved {} tables to {}", count, directory),
+        Err(error) => {
+            eprintln!("Could not save the tables: {}", error);
+            std::process::exit(1);
+        }
+    }
+}
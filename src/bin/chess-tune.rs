@@ -0,0 +1,124 @@
+// Tunes the classical evaluation weights on positions labelled with the
+// results of their games, and writes them to a weights file for `EvalFile`:
+//
+// `chess-tune [-epochs 1000] [-rate 1.0] [-weights start.txt] [-skip 8]
+//  tuned.txt games.pgn positions.epd ...`
+
+use chess_core::{load_positions, ClassicalEvaluator, EvalWeights, Tuner, DEFAULT_SKIP_PLIES};
+use std::time::Instant;
+
+const DEFAULT_EPOCHS: u32 = 1000;
+/// Epochs between reports and saves of the weights so far
+const REPORT_INTERVAL: u32 = 50;
+
+const USAGE: &str = "Usage: chess-tune [-epochs <n>] [-rate <learning rate>] \
+[-weights <starting weights>] [-skip <plies>] <output> <file.pgn|file.epd>...";
+
+struct Options {
+    epochs: u32,
+    learning_rate: f64,
+    weights: EvalWeights,
+    skip_plies: usize,
+    output: String,
+    inputs: Vec<String>,
+}
+
+fn parse_arguments(arguments: &[String]) -> Result<Options, &'static str> {
+    let mut options = Options {
+        epochs: DEFAULT_EPOCHS,
+        learning_rate: 1.0,
+        weights: EvalWeights::default(),
+        skip_plies: DEFAULT_SKIP_PLIES,
+        output: String::new(),
+        inputs: Vec::new(),
+    };
+    let mut files = Vec::new();
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        if !argument.starts_with('-') {
+            files.push(argument.clone());
+            continue;
+        }
+        let value = arguments.next().ok_or("Option needs a value\n")?;
+        let number_error = "Option values must be numbers\n";
+        match argument.as_str() {
+            "-epochs" => options.epochs = value.parse().map_err(|_| number_error)?,
+            "-rate" => options.learning_rate = value.parse().map_err(|_| number_error)?,
+            "-weights" => options.weights = EvalWeights::load(value)?,
+            "-skip" => options.skip_plies = value.parse().map_err(|_| number_error)?,
+            _ => return Err("Unknown option\n"),
+        }
+    }
+
+    let (output, inputs) = files
+        .split_first()
+        .ok_or("Missing output and position files\n")?;
+    if inputs.is_empty() {
+        return Err("Missing position files\n");
+    }
+    options.output = output.clone();
+    options.inputs = inputs.to_vec();
+    Ok(options)
+}
+
+fn run(options: Options) -> Result<(), &'static str> {
+    let start = Instant::now();
+    let mut positions = Vec::new();
+    for input in &options.inputs {
+        positions.extend(load_positions(input, options.skip_plies)?);
+    }
+    if positions.is_empty() {
+        return Err("No labelled positions to tune on\n");
+    }
+
+    let evaluator = ClassicalEvaluator::with_weights(options.weights);
+    let mut tuner = Tuner::new(&evaluator, &positions);
+    drop(positions);
+    tuner.learning_rate = options.learning_rate;
+    let scaling = tuner.fit_scaling();
+    println!(
+        "{} quiet positions ({:.1}s), K = {:.4}, error {:.6}",
+        tuner.positions(),
+        start.elapsed().as_secs_f64(),
+        scaling,
+        tuner.error()
+    );
+
+    for epoch in 1..=options.epochs {
+        let error = tuner.step();
+        if epoch.is_multiple_of(REPORT_INTERVAL) {
+            println!(
+                "Epoch {}: error {:.6} ({:.1}s)",
+                epoch,
+                error,
+                start.elapsed().as_secs_f64()
+            );
+            tuner.weights().save(&options.output)?;
+        }
+    }
+    tuner.weights().save(&options.output)?;
+    println!(
+        "Final error {:.6}, K = {:.4}",
+        tuner.error(),
+        tuner.scaling()
+    );
+    println!("Saved the weights to {}", options.output);
+    Ok(())
+}
+
+fn main() {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse_arguments(&arguments) {
+        Ok(options) => options,
+        Err(error) => {
+            eprint!("{}", error);
+            eprintln!("{}", USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    if let Err(error) = run(options) {
+        eprint!("{}", error);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,42 @@
+// Runs the built-in engine for other chess GUIs. It speaks UCI, or the XBoard
+// protocol when the first command is `xboard`.
+//
+// `chess-uci bench [depth] [threads]` instead measures time to depth on the
+// bench positions with 1, 2, 4... up to `threads` threads.
+
+use chess_engine::bench;
+use std::io::{BufRead, Cursor, Read};
+use std::thread;
+
+fn main() {
+    let arguments: Vec<String> = std::env::args().skip(1).collect();
+    if arguments.first().map(String::as_str) == Some("bench") {
+        let depth = arguments
+            .get(1)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(bench::DEFAULT_BENCH_DEPTH);
+        let max_threads = arguments
+            .get(2)
+            .and_then(|value| value.parse().ok())
+            .or_else(|| {
+                thread::available_parallelism()
+                    .ok()
+                    .map(|count| count.get())
+            })
+            .unwrap_or(1);
+        let _ = bench::scaling(depth, max_threads, &mut std::io::stdout());
+        return;
+    }
+
+    let mut input = std::io::stdin().lock();
+    let mut first_line = String::new();
+    if input.read_line(&mut first_line).is_err() {
+        return;
+    }
+
+    if first_line.trim() == "xboard" {
+        chess_engine::xboard::run(input, std::io::stdout());
+    } else {
+        chess_engine::uci::run(Cursor::new(first_line).chain(input), std::io::stdout());
+    }
+}